@@ -0,0 +1,135 @@
+//! Credits hammering a single account, once with every write taking the
+//! account's lock and once through its actor queue (`BANK_ACTOR_ACCOUNTS=*`).
+//!
+//!     cargo bench --bench hot_account
+//!
+//! `BENCH_SECONDS` (default 5) and `BENCH_CONNECTIONS` (default 64) tune the
+//! run. The server inherits the environment, so e.g. `BANK_WAL_FILE` puts the
+//! WAL append under the lock, which is where batching pays off most.
+
+use std::{
+    env,
+    net::{SocketAddr, TcpListener},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+const BODY: &str = r#"{"valor":1,"tipo":"C","descricao":"bench"}"#;
+
+fn setting(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn spawn(addr: SocketAddr, actors: bool) -> Server {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rinha2024"));
+    command
+        .env("BANK_BIND", addr.to_string())
+        .env_remove("BANK_ACTOR_ACCOUNTS")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if actors {
+        command.env("BANK_ACTOR_ACCOUNTS", "*");
+    }
+    Server(command.spawn().expect("failed to start the server"))
+}
+
+/// One keep-alive connection posting credits until `deadline`.
+async fn client(addr: SocketAddr, deadline: Instant, accepted: Arc<AtomicU64>) {
+    let stream = TcpStream::connect(addr).await.expect("connect");
+    let mut stream = BufReader::new(stream);
+    let request = format!(
+        "POST /clientes/1/transacoes HTTP/1.1\r\nHost: bench\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{BODY}",
+        BODY.len()
+    );
+    let mut line = String::new();
+    while Instant::now() < deadline {
+        stream
+            .get_mut()
+            .write_all(request.as_bytes())
+            .await
+            .expect("write");
+        let mut ok = false;
+        let mut length = 0;
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await.expect("read");
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(status) = header.strip_prefix("HTTP/1.1 ") {
+                ok = status.starts_with("200");
+            } else if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().expect("content-length");
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.expect("body");
+        if ok {
+            accepted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn run(actors: bool, connections: u64, duration: Duration) -> f64 {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port");
+    let _server = spawn(addr, actors);
+    let started = Instant::now();
+    while TcpStream::connect(addr).await.is_err() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "server did not start"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let accepted = Arc::new(AtomicU64::new(0));
+    let deadline = Instant::now() + duration;
+    let clients: Vec<_> = (0..connections)
+        .map(|_| tokio::spawn(client(addr, deadline, accepted.clone())))
+        .collect();
+    for client in clients {
+        client.await.expect("client panicked");
+    }
+    accepted.load(Ordering::Relaxed) as f64 / duration.as_secs_f64()
+}
+
+#[tokio::main]
+async fn main() {
+    let duration = Duration::from_secs(setting("BENCH_SECONDS", 5));
+    let connections = setting("BENCH_CONNECTIONS", 64);
+
+    let lock = run(false, connections, duration).await;
+    println!("write lock:  {lock:>10.0} credits/s");
+    let actor = run(true, connections, duration).await;
+    println!(
+        "actor queue: {actor:>10.0} credits/s ({:+.0}%)",
+        (actor / lock - 1.0) * 100.0
+    );
+}
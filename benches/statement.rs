@@ -0,0 +1,70 @@
+//! `GET /clientes/:id/extrato` through the router, in-process, for an
+//! account with ten transactions: routing, the read lock, rendering and
+//! serialization, without sockets in the way.
+//!
+//!     cargo bench --bench statement
+//!
+//! `BANK_*` settings apply as for the server, e.g.
+//! `BANK_STATEMENT_CACHE=true` or `BANK_AMOUNT_FORMAT=decimal`; criterion's
+//! own flags go after `--`, e.g. `-- --save-baseline before`.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rinha2024::{config::Config, service::BankService};
+use tower::ServiceExt;
+
+async fn send(app: &Router, request: Request<Body>) -> usize {
+    let response = app.clone().oneshot(request).await.expect("router");
+    assert_eq!(response.status(), StatusCode::OK);
+    to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body")
+        .len()
+}
+
+fn extrato() -> Request<Body> {
+    Request::get("/clientes/1/extrato")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Ten, the default `BANK_HISTORY_CAP`: a full `ultimas_transacoes`.
+async fn seed(app: &Router) {
+    for index in 0..10 {
+        let body = format!(
+            r#"{{"valor":{},"tipo":"C","descricao":"bench"}}"#,
+            index + 1
+        );
+        let request = Request::post("/clientes/1/transacoes")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        send(app, request).await;
+    }
+}
+
+fn statement(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the runtime");
+    let config = Config::load().expect("invalid configuration");
+    let service = BankService::in_memory(config).expect("failed to build the service");
+    let app = service.router();
+    runtime.block_on(seed(&app));
+    let size = runtime.block_on(send(&app, extrato()));
+
+    let mut group = c.benchmark_group("extrato");
+    group.throughput(Throughput::Bytes(size as u64));
+    group.bench_function("router", |b| {
+        b.to_async(&runtime).iter(|| send(&app, extrato()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, statement);
+criterion_main!(benches);
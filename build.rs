@@ -0,0 +1,5 @@
+fn main() {
+    // Only the `grpc` feature has a contract to generate code for.
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/bank.proto").expect("proto/bank.proto does not compile");
+}
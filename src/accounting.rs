@@ -0,0 +1,262 @@
+use std::{borrow::Cow, collections::HashSet, fmt::Write};
+
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, OffsetDateTime, UtcOffset,
+};
+
+use crate::{
+    error::AppError,
+    registry::{AccountId, Entry},
+    AppState, Transaction, TransactionType,
+};
+
+pub const UNKNOWN: &str = "formato de extrato desconhecido";
+pub const UNREADABLE: &str = "historico arquivado ilegivel";
+
+const CSV_HEADER: &str = "id,realizada_em,tipo,valor,moeda,descricao\r\n";
+
+#[derive(Default, Deserialize)]
+pub struct FormatQuery {
+    formato: Option<String>,
+}
+
+/// Statement layouts for accounting tools, besides the JSON one.
+#[derive(Clone, Copy)]
+pub enum Format {
+    Csv,
+    Ofx,
+}
+
+impl Format {
+    /// `?formato=csv|ofx|json` first, then `Accept`; `None` means the JSON
+    /// statement.
+    pub fn requested(query: &FormatQuery, headers: &HeaderMap) -> Result<Option<Self>, AppError> {
+        match query.formato.as_deref() {
+            Some("csv") => return Ok(Some(Format::Csv)),
+            Some("ofx") => return Ok(Some(Format::Ofx)),
+            Some("json") => return Ok(None),
+            Some(_) => return Err(AppError::Invalid(UNKNOWN)),
+            None => {}
+        }
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        Ok(if accept.contains("text/csv") {
+            Some(Format::Csv)
+        } else if accept.contains("application/x-ofx") || accept.contains("application/ofx") {
+            Some(Format::Ofx)
+        } else {
+            None
+        })
+    }
+}
+
+/// The account's history oldest first: everything in `BANK_HISTORY_FILE`
+/// when there is one, plus whatever the statement ring holds that it does
+/// not (say, from before the archive was turned on). Without the archive
+/// only the ring is there to export, which `x-historico-parcial` says.
+/// Amounts are decimal in major units, whatever `BANK_AMOUNT_FORMAT` says:
+/// that is what these tools import.
+pub async fn export(
+    state: &AppState,
+    account_id: AccountId,
+    entry: &Entry,
+    format: Format,
+) -> Result<Response, AppError> {
+    let mut transactions = match state.history.clone() {
+        Some(archive) => tokio::task::spawn_blocking(move || archive.read(account_id))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .ok_or(AppError::Status(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                UNREADABLE,
+            ))?,
+        None => Vec::new(),
+    };
+    let account = entry.account.read().await;
+    // Coalesced credits: the archive has each one, the ring only their sum
+    // under the newest id, so the archived entries win.
+    let archived: HashSet<_> = transactions
+        .iter()
+        .map(|transaction| transaction.id)
+        .collect();
+    transactions.extend(
+        account
+            .transactions
+            .iter()
+            .filter(|transaction| !archived.contains(&transaction.id))
+            .cloned(),
+    );
+    transactions.sort_by_key(|transaction| (transaction.create_at, transaction.seq));
+
+    let now = state.clock.now();
+    let (content_type, extension, body) = match format {
+        Format::Csv => ("text/csv; charset=utf-8", "csv", csv(&transactions, state)),
+        Format::Ofx => (
+            "application/x-ofx",
+            "ofx",
+            ofx(account_id, &transactions, account.balance, now, state),
+        ),
+    };
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"extrato-{account_id}.{extension}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response();
+    if state.history.is_none() {
+        response.headers_mut().insert(
+            "x-historico-parcial",
+            header::HeaderValue::from_static("true"),
+        );
+    }
+    Ok(response)
+}
+
+/// RFC 4180, one row per transaction, `valor` signed (debits negative).
+fn csv(transactions: &[Transaction], state: &AppState) -> String {
+    let mut body = String::from(CSV_HEADER);
+    for transaction in transactions {
+        let currency = transaction.currency.unwrap_or(state.config.currency);
+        let _ = write!(
+            body,
+            "{},{},{},{},{},{}\r\n",
+            transaction.id,
+            transaction.create_at.format(&Rfc3339).unwrap(),
+            kind(transaction.kind),
+            currency.format(signed(transaction)),
+            currency.code(),
+            csv_field(&transaction.description.0),
+        );
+    }
+    body
+}
+
+/// Quoted when it holds a comma, quote or line break, with quotes doubled.
+/// A leading `=`, `+`, `-` or `@` gets a `'` first, so a spreadsheet opening
+/// the file shows the description instead of evaluating it.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    let formula = value.starts_with(['=', '+', '-', '@']);
+    let quoted = value.contains([',', '"', '\r', '\n']);
+    if !formula && !quoted {
+        return Cow::Borrowed(value);
+    }
+    let value = match formula {
+        true => format!("'{value}"),
+        false => value.to_string(),
+    };
+    match quoted {
+        true => Cow::Owned(format!("\"{}\"", value.replace('"', "\"\""))),
+        false => Cow::Owned(value),
+    }
+}
+
+/// OFX 2.2 bank statement in the configured currency. Transactions in other
+/// currencies are left out: a statement carries one `CURDEF`, and their
+/// amounts are not in it.
+fn ofx(
+    account_id: AccountId,
+    transactions: &[Transaction],
+    balance: i64,
+    now: OffsetDateTime,
+    state: &AppState,
+) -> String {
+    let currency = state.config.currency;
+    let transactions: Vec<_> = transactions
+        .iter()
+        .filter(|transaction| transaction.currency.is_none())
+        .collect();
+    let start = transactions.first().map_or(now, |first| first.create_at);
+
+    let mut body = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" ",
+        "OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n",
+    ));
+    let _ = write!(
+        body,
+        "<OFX><SIGNONMSGSRSV1><SONRS>\
+         <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\
+         <DTSERVER>{now}</DTSERVER><LANGUAGE>POR</LANGUAGE>\
+         </SONRS></SIGNONMSGSRSV1>\n\
+         <BANKMSGSRSV1><STMTTRNRS><TRNUID>0</TRNUID>\
+         <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\
+         <STMTRS><CURDEF>{code}</CURDEF>\
+         <BANKACCTFROM><BANKID>0</BANKID><ACCTID>{account_id}</ACCTID>\
+         <ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n\
+         <BANKTRANLIST><DTSTART>{start}</DTSTART><DTEND>{now}</DTEND>\n",
+        now = ofx_time(now),
+        code = currency.code(),
+        start = ofx_time(start),
+    );
+    for transaction in transactions {
+        let _ = writeln!(
+            body,
+            "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED>\
+             <TRNAMT>{}</TRNAMT><FITID>{}</FITID><MEMO>{}</MEMO></STMTTRN>",
+            match transaction.kind {
+                TransactionType::Credit => "CREDIT",
+                TransactionType::Debit => "DEBIT",
+            },
+            ofx_time(transaction.create_at),
+            currency.format(signed(transaction)),
+            transaction.id,
+            xml_text(&transaction.description.0),
+        );
+    }
+    let _ = writeln!(
+        body,
+        "</BANKTRANLIST>\
+         <LEDGERBAL><BALAMT>{}</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL>\
+         </STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>",
+        currency.format(balance),
+        ofx_time(now),
+    );
+    body
+}
+
+/// OFX's own `YYYYMMDDHHMMSS.XXX[0:GMT]`, always in UTC.
+fn ofx_time(at: OffsetDateTime) -> String {
+    let format =
+        format_description!("[year][month][day][hour][minute][second].[subsecond digits:3]");
+    let utc = at.to_offset(UtcOffset::UTC).format(format).unwrap();
+    format!("{utc}[0:GMT]")
+}
+
+fn xml_text(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+    )
+}
+
+fn kind(kind: TransactionType) -> &'static str {
+    match kind {
+        TransactionType::Credit => "C",
+        TransactionType::Debit => "D",
+    }
+}
+
+pub(crate) fn signed(transaction: &Transaction) -> i64 {
+    match transaction.kind {
+        TransactionType::Credit => transaction.value,
+        TransactionType::Debit => -transaction.value,
+    }
+}
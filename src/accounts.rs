@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    actor,
+    error::{self, AppError, JsonBody},
+    open_account, policy,
+    registry::AccountId,
+    transact::TransactError,
+    AppState,
+};
+
+pub const EXISTS: &str = "conta ja existe";
+pub const NO_FREE_ID: &str = "nenhum id de conta livre";
+/// The balance is already further negative than the limit asked for.
+pub const BALANCE_BEYOND_LIMIT: &str = "saldo alem do novo limite";
+pub const NOTHING_TO_UPDATE: &str = "informe limite ou tipo";
+
+#[derive(Deserialize)]
+pub struct CreateRequest {
+    id: Option<AccountId>,
+    #[serde(rename = "limite")]
+    limit: i64,
+    /// One of `BANK_ACCOUNT_TYPES`; `BANK_DEFAULT_ACCOUNT_TYPE` when absent.
+    #[serde(default, rename = "tipo")]
+    account_type: Option<String>,
+}
+
+/// Opens an account with the per-account settings the seeds get. Without an
+/// `id` the smallest free one is taken. The new account is logged before it
+/// is added, so WAL replay opens it again whether or not a snapshot has it.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<CreateRequest>,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    let id = match request.id {
+        Some(id) => id,
+        None => state
+            .accounts
+            .free_id()
+            .ok_or(AppError::Conflict(NO_FREE_ID))?,
+    };
+    let mut account = open_account(id, request.limit, &state.config).map_err(AppError::Invalid)?;
+    if let Some(name) = &request.account_type {
+        account.account_type = state
+            .config
+            .account_type(name)
+            .ok_or(AppError::Invalid(policy::UNKNOWN_TYPE))?;
+    }
+    let limit = account.limit;
+    let account_type = account.account_type.clone();
+    let Some(entry) = state
+        .accounts
+        .insert_logged(id, account, |account| state.persist(id, account))?
+    else {
+        return Err(AppError::Conflict(EXISTS));
+    };
+    if state.config.is_actor(id) {
+        actor::start(&state, id, &entry);
+    }
+    state.changed(id);
+
+    let now = state.clock.now();
+    state.audit.record(json!({
+        "evento": "conta_criada",
+        "account": id,
+        "limite": limit,
+        "tipo": account_type.name,
+        "em": state.config.timestamp(now),
+    }));
+    let formatter = state.config.formatter();
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "id": id,
+            "limite": formatter.amount(limit),
+            "saldo": formatter.amount(0),
+            "tipo": account_type.name,
+            "politica": policy::render(account_type.policy, &state.config),
+        })),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRequest {
+    #[serde(default, rename = "limite")]
+    limit: Option<i64>,
+    #[serde(default, rename = "tipo")]
+    account_type: Option<String>,
+}
+
+/// Sets the limit and/or the type outright. Refused when the current
+/// balance already sits beyond what the new limit under the new type's
+/// policy allows, same as a limit transfer out of the account.
+pub async fn update(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<UpdateRequest>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    if request.limit.is_none() && request.account_type.is_none() {
+        return Err(AppError::Invalid(NOTHING_TO_UPDATE));
+    }
+    if request.limit.is_some_and(|limit| limit < 0) {
+        return Err(AppError::Invalid(error::INVALID_LIMIT));
+    }
+    if request
+        .limit
+        .is_some_and(|limit| limit > state.config.max_limit)
+    {
+        return Err(AppError::Invalid(error::LIMIT_ABOVE_MAX));
+    }
+    let account_type = match &request.account_type {
+        Some(name) => Some(
+            state
+                .config
+                .account_type(name)
+                .ok_or(AppError::Invalid(policy::UNKNOWN_TYPE))?,
+        ),
+        None => None,
+    };
+
+    let mut account = entry.account.write().await;
+    if account.closed {
+        return Err(AppError::Invalid(TransactError::AccountClosed.reason()));
+    }
+    let limit = request.limit.unwrap_or(account.limit);
+    let policy = account_type
+        .as_ref()
+        .map_or(account.account_type.policy, |account_type| {
+            account_type.policy
+        });
+    if account.balance < policy.floor(limit) {
+        return Err(AppError::Invalid(BALANCE_BEYOND_LIMIT));
+    }
+    let now = state.clock.now();
+    let before = (*account).clone();
+    if let Some(limit) = request.limit {
+        account.limit = limit;
+    }
+    if let Some(account_type) = account_type {
+        account.account_type = account_type;
+    }
+    account.touch(now);
+    if let Err(error) = state.persist(account_id, &account) {
+        *account = before;
+        return Err(error);
+    }
+    state.changed(account_id);
+
+    if request.limit.is_some() {
+        state.audit.record(json!({
+            "evento": "limite_alterado",
+            "account": account_id,
+            "de": before.limit,
+            "para": account.limit,
+            "em": state.config.timestamp(now),
+        }));
+    }
+    if request.account_type.is_some() {
+        state.audit.record(json!({
+            "evento": "tipo_alterado",
+            "account": account_id,
+            "de": before.account_type.name,
+            "para": account.account_type.name,
+            "em": state.config.timestamp(now),
+        }));
+    }
+
+    let formatter = state.config.formatter();
+    Ok(Json(json!({
+        "id": account_id,
+        "limite": formatter.amount(account.limit),
+        "saldo": formatter.amount(account.balance),
+        "tipo": account.account_type.name,
+        "politica": policy::render(account.account_type.policy, &state.config),
+    })))
+}
+
+/// Marks the account closed. It keeps its balance, history and id, and the
+/// extrato stays readable; transactions, holds and due schedules are refused
+/// from here on. Closing twice is a no-op.
+pub async fn close(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let mut account = entry.account.write().await;
+    if account.closed {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+    let before = (*account).clone();
+    account.closed = true;
+    let now = state.clock.now();
+    account.touch(now);
+    if let Err(error) = state.persist(account_id, &account) {
+        *account = before;
+        return Err(error);
+    }
+    state.changed(account_id);
+    state.audit.record(json!({
+        "evento": "conta_encerrada",
+        "account": account_id,
+        "saldo": account.balance,
+        "em": state.config.timestamp(now),
+    }));
+    Ok(StatusCode::NO_CONTENT)
+}
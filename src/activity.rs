@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use time::{format_description::FormatItem, macros::format_description, UtcOffset};
+
+use crate::{error::AppError, registry::AccountId, AppState};
+
+pub const INVALID_TZ: &str = "tz invalido";
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    /// Fixed UTC offset such as `-03:00`; defaults to UTC.
+    tz: Option<String>,
+}
+
+const OFFSET: &[FormatItem<'static>] =
+    format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+
+pub fn parse_offset(tz: &str) -> Option<UtcOffset> {
+    if tz == "Z" || tz == "UTC" {
+        return Some(UtcOffset::UTC);
+    }
+    UtcOffset::parse(tz, OFFSET).ok()
+}
+
+/// Transactions per hour of day over the retained window.
+pub async fn heatmap(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<ActivityQuery>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let offset = match query.tz.as_deref().map(parse_offset) {
+        Some(Some(offset)) => offset,
+        Some(None) => return Err(AppError::BadRequest(INVALID_TZ)),
+        None => UtcOffset::UTC,
+    };
+
+    match state.accounts.get(&account_id) {
+        Some(entry) => {
+            let account = entry.account.read().await;
+            let mut hours = [0u32; 24];
+            for transaction in account.transactions.iter() {
+                hours[transaction.create_at.to_offset(offset).hour() as usize] += 1;
+            }
+            Ok(Json(json!({
+                "account": account_id,
+                "tz": offset.format(OFFSET).unwrap(),
+                "horas": hours,
+            })))
+        }
+        None => Err(AppError::UnknownAccount),
+    }
+}
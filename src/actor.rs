@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use tokio::sync::{mpsc, oneshot};
+use tracing::Span;
+
+use crate::{
+    correlation,
+    deadline::{self, Deadline},
+    error::AppError,
+    persistence,
+    registry::{AccountId, Entry},
+    transact::{TransactError, TransactResult},
+    AppState, Transaction,
+};
+
+/// How many queued transactions are applied under a single write lock.
+const BATCH: usize = 64;
+
+/// The outer error, as in `BankService::apply`, is for a transaction never
+/// tried.
+type Outcome = Result<Result<TransactResult, TransactError>, AppError>;
+type Reply = oneshot::Sender<Outcome>;
+/// The flag selects `Account::transact_partial`, the version is `If-Match`'s;
+/// the id and the span are the submitting request's, so the actor's work is
+/// logged as part of it.
+type Command = (
+    Transaction,
+    bool,
+    Option<u64>,
+    Option<Deadline>,
+    Option<Arc<str>>,
+    Span,
+    Reply,
+);
+
+pub type Queue = mpsc::UnboundedSender<Command>;
+type Inbox = mpsc::UnboundedReceiver<Command>;
+
+/// Makes `account_id` an actor account: gives its entry a queue and spawns
+/// the task draining it. Idempotent.
+pub fn start(state: &Arc<AppState>, account_id: AccountId, entry: &Arc<Entry>) {
+    let (queue, inbox) = mpsc::unbounded_channel();
+    if entry.queue.set(queue).is_ok() {
+        tokio::spawn(run(account_id, entry.clone(), inbox, state.clone()));
+    }
+}
+
+/// `deadline` is checked here and again when the actor takes the command:
+/// one that runs out in the queue is a `408` and never applied.
+pub async fn submit(
+    queue: &Queue,
+    transaction: Transaction,
+    partial: bool,
+    expected: Option<u64>,
+    deadline: Option<Deadline>,
+) -> Outcome {
+    if expired(deadline) {
+        return Err(AppError::Status(
+            StatusCode::REQUEST_TIMEOUT,
+            deadline::EXPIRED,
+        ));
+    }
+    let (reply, outcome) = oneshot::channel();
+    let command = (
+        transaction,
+        partial,
+        expected,
+        deadline,
+        correlation::current(),
+        Span::current(),
+        reply,
+    );
+    if queue.send(command).is_err() {
+        return Ok(Err(TransactError::QueueClosed));
+    }
+    outcome.await.unwrap_or(Ok(Err(TransactError::QueueClosed)))
+}
+
+fn expired(deadline: Option<Deadline>) -> bool {
+    deadline.is_some_and(|deadline| deadline.remaining().is_zero())
+}
+
+/// Sole writer for a designated hot account: handlers enqueue instead of
+/// competing for `.write()`, and queued work is drained in batches. A batch
+/// is also one WAL group commit: its transactions go out in one write and,
+/// under `strict` durability, one `fsync`, before any of them is answered.
+/// Should that fail, what the batch accepted is rolled back and answered
+/// with the failure instead; its refusals stand.
+async fn run(account_id: AccountId, entry: Arc<Entry>, mut inbox: Inbox, state: Arc<AppState>) {
+    let mut batch = Vec::with_capacity(BATCH);
+    let mut replies: Vec<(Reply, Option<Arc<str>>, Outcome)> = Vec::with_capacity(BATCH);
+    while inbox.recv_many(&mut batch, BATCH).await > 0 {
+        let mut account = entry.account.write().await;
+        let savepoint = state.wal.is_some().then(|| account.savepoint(batch.len()));
+        for (transaction, partial, expected, deadline, id, parent, reply) in batch.drain(..) {
+            if expired(deadline) {
+                let status = StatusCode::REQUEST_TIMEOUT;
+                replies.push((reply, id, Err(AppError::Status(status, deadline::EXPIRED))));
+                continue;
+            }
+            let span = tracing::debug_span!(parent: &parent, "transact", account = account_id.0, actor = true);
+            let _entered = span.enter();
+            let now = state.clock.now();
+            let outcome = account.check_version(expected).and_then(|()| {
+                if partial {
+                    account.transact_partial(transaction, now)
+                } else {
+                    account.transact(transaction, now)
+                }
+            });
+            replies.push((reply, id, Ok(outcome)));
+        }
+
+        let recorded = replies
+            .iter()
+            .filter_map(|(_, _, outcome)| match outcome {
+                Ok(Ok(result)) => Some(result),
+                _ => None,
+            })
+            .flat_map(TransactResult::recorded)
+            .map(|transaction| (account_id, transaction));
+        match state.log(recorded) {
+            Ok(()) => {
+                for (_, id, outcome) in &replies {
+                    if let Ok(Ok(result)) = outcome {
+                        correlation::within(id.clone(), || {
+                            state.accepted_result(account_id, result)
+                        });
+                    }
+                }
+            }
+            Err(_) => {
+                if let Some(savepoint) = savepoint {
+                    account.roll_back(savepoint);
+                }
+                for (_, _, outcome) in &mut replies {
+                    if let Ok(Ok(_)) = outcome {
+                        *outcome = Err(persistence::failed());
+                    }
+                }
+            }
+        }
+        drop(account);
+        for (reply, _, outcome) in replies.drain(..) {
+            let _ = reply.send(outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use axum::{body::Body, http::Request};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        persistence::{Wal, WalDurability, WAL_FAILED},
+        rotation::Rotation,
+        testing::{bank, get, post, send},
+    };
+
+    fn actor_bank() -> Arc<AppState> {
+        bank(Config {
+            actor_accounts: vec![AccountId(1)],
+            ..Config::default()
+        })
+        .0
+    }
+
+    #[tokio::test]
+    async fn an_expired_deadline_is_refused_before_the_queue() {
+        let state = actor_bank();
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let transaction: Transaction =
+            serde_json::from_value(json!({"valor": 10, "tipo": "C", "descricao": "tarde"}))
+                .unwrap();
+        let outcome = submit(
+            entry.queue.get().unwrap(),
+            transaction,
+            false,
+            None,
+            Some(Deadline(Instant::now())),
+        )
+        .await;
+        assert!(matches!(
+            outcome,
+            Err(AppError::Status(StatusCode::REQUEST_TIMEOUT, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_deadline_that_runs_out_in_the_queue_is_not_applied() {
+        let state = actor_bank();
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        // The actor takes the command but cannot apply it until this goes.
+        let held = entry.account.write().await;
+        let request = Request::post("/clientes/1/transacoes")
+            .header("content-type", "application/json")
+            .header("x-deadline", "50")
+            .body(Body::from(
+                json!({"valor": 10, "tipo": "C", "descricao": "fila"}).to_string(),
+            ))
+            .unwrap();
+        let pending = tokio::spawn({
+            let state = state.clone();
+            async move { send(&state, request).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(held);
+
+        let (status, body) = pending.await.unwrap();
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT, "{body}");
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn a_group_commit_the_wal_refuses_fails_every_transaction_in_it() {
+        let (mut state, _) = bank(Config::default());
+        let wal = Wal::open(
+            Path::new("/dev/full"),
+            WalDurability::Strict,
+            Rotation::default(),
+        );
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.wal = Some(wal.unwrap());
+        state_mut.config.actor_accounts = vec![AccountId(1)];
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        assert!(state.config.is_actor(AccountId(1)));
+        start(&state, AccountId(1), &entry);
+
+        // Queued behind the lock, so the actor finds them waiting together.
+        let held = entry.account.write().await;
+        let pending: Vec<_> = [(300, "C"), (200, "C"), (100, "D"), (200_000, "D")]
+            .into_iter()
+            .map(|(valor, tipo)| {
+                let state = state.clone();
+                let transaction = json!({"valor": valor, "tipo": tipo, "descricao": "fila"});
+                tokio::spawn(async move {
+                    send(&state, post("/clientes/1/transacoes", transaction)).await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(held);
+
+        let mut outcomes = Vec::new();
+        for pending in pending {
+            outcomes.push(pending.await.unwrap());
+        }
+        for (status, body) in &outcomes[..3] {
+            assert_eq!(*status, StatusCode::SERVICE_UNAVAILABLE, "{body}");
+            assert_eq!(body["message"], WAL_FAILED);
+        }
+        // A refusal is still the refusal: it had nothing to log.
+        assert_eq!(outcomes[3].0, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::{macros::format_description, Date, Month, OffsetDateTime, Time};
+
+use crate::{
+    accounting::{self, signed},
+    error::AppError,
+    http::render_transaction,
+    registry::AccountId,
+    AppState,
+};
+
+pub const DISABLED: &str = "arquivamento desativado";
+pub const INVALID_PERIOD: &str = "periodo invalido";
+
+/// Every `BANK_ARCHIVE_INTERVAL_MS`, moves history transactions older than
+/// `BANK_ARCHIVE_AFTER_MS` into a gzip segment beside `BANK_HISTORY_FILE`.
+/// Each segment records, per account, how many transactions and how much
+/// net it holds, so balances around an archived range are worked out
+/// without decompressing what lies outside it.
+pub async fn run(state: Arc<AppState>) {
+    let (Some(after), Some(archive)) = (state.config.archive_after, state.history.clone()) else {
+        return;
+    };
+    let mut interval = tokio::time::interval(state.config.archive_interval);
+    loop {
+        interval.tick().await;
+        if state.maintenance.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        let now = state.clock.now();
+        let cutoff = now - after;
+        let archive = archive.clone();
+        match tokio::task::spawn_blocking(move || archive.compact(cutoff)).await {
+            Ok(Ok(None)) => {}
+            Ok(Ok(Some(segment))) => {
+                let transactions: u64 = segment
+                    .accounts
+                    .values()
+                    .map(|aggregate| aggregate.count)
+                    .sum();
+                tracing::info!(
+                    transactions,
+                    cutoff = %state.config.timestamp(cutoff),
+                    file = %segment.file,
+                    "archival: old transactions moved to a segment"
+                );
+                state.audit.record(json!({
+                    "evento": "historico_arquivado",
+                    "arquivo": segment.file,
+                    "corte": state.config.timestamp(cutoff),
+                    "transacoes": transactions,
+                    "contas": segment.accounts.len(),
+                    "em": state.config.timestamp(now),
+                }));
+            }
+            Ok(Err(err)) => tracing::error!(error = %err, "archival failed"),
+            Err(err) => tracing::error!(error = %err, "archival failed"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PeriodQuery {
+    periodo: String,
+}
+
+/// `AAAA-MM`, `AAAA-MM-DD` or `AAAA-MM-DD/AAAA-MM-DD`, whole UTC days, the
+/// last one included. As `[from, to)`.
+fn period(text: &str) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let day = format_description!("[year]-[month]-[day]");
+    let midnight = |date: Date| date.with_time(Time::MIDNIGHT).assume_utc();
+    if let Some((from, to)) = text.split_once('/') {
+        let (from, to) = (Date::parse(from, &day).ok()?, Date::parse(to, &day).ok()?);
+        return (from <= to).then_some((midnight(from), midnight(to.next_day()?)));
+    }
+    if let Ok(date) = Date::parse(text, &day) {
+        return Some((midnight(date), midnight(date.next_day()?)));
+    }
+    month(text)
+}
+
+/// `AAAA-MM`, the whole UTC month, as `[from, to)`.
+pub(crate) fn month(text: &str) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let midnight = |date: Date| date.with_time(Time::MIDNIGHT).assume_utc();
+    let first = Date::parse(
+        &format!("{text}-01"),
+        format_description!("[year]-[month]-[day]"),
+    )
+    .ok()?;
+    let next = match first.month() {
+        Month::December => Date::from_calendar_date(first.year() + 1, Month::January, 1),
+        other => Date::from_calendar_date(first.year(), other.next(), 1),
+    };
+    Some((midnight(first), midnight(next.ok()?)))
+}
+
+/// The account's transactions stamped within `periodo`, oldest first,
+/// whether compaction has moved them out of the live history yet or not,
+/// with the balance just before and just after. Those are taken back from
+/// the current balance through the segment checkpoints, so only segments
+/// overlapping the period are read. Once the account's history has been
+/// purged the files no longer add up to its balance: both are `null` then.
+pub async fn archived(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<PeriodQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let archive = state
+        .config
+        .archive_after
+        .and(state.history.clone())
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let (from, to) = period(&query.periodo).ok_or(AppError::Invalid(INVALID_PERIOD))?;
+
+    let period = tokio::task::spawn_blocking(move || archive.period(account_id, from, to))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .ok_or(AppError::Status(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            accounting::UNREADABLE,
+        ))?;
+    let account = entry.account.read().await;
+    // Accepted after the files were read: not in `net_after` yet.
+    let since: i64 = account
+        .transactions
+        .iter()
+        .filter(|transaction| Some(transaction.id) > period.newest)
+        .filter(|transaction| transaction.currency.is_none())
+        .map(signed)
+        .sum();
+    let net: i64 = period
+        .transactions
+        .iter()
+        .filter(|transaction| transaction.currency.is_none())
+        .map(signed)
+        .sum();
+    let closing = (!period.purged).then(|| account.balance - since - period.net_after);
+    let formatter = state.config.formatter();
+
+    Ok(Json(json!({
+        "account": account_id,
+        "periodo": {
+            "de": state.config.timestamp(from),
+            "ate": state.config.timestamp(to),
+        },
+        "saldo_inicial": closing.map(|closing| formatter.amount(closing - net)),
+        "saldo_final": closing.map(|closing| formatter.amount(closing)),
+        "transacoes": period
+            .transactions
+            .iter()
+            .map(|transaction| render_transaction(transaction, &state.config))
+            .collect::<Vec<_>>(),
+        "segmentos": period.read,
+        "arquivado_ate": period.archived_until.map(|until| state.config.timestamp(until)),
+    })))
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    currency::{self, Currency},
+    domain::TransactionType,
+    error::AppError,
+    ledger,
+    registry::AccountId,
+    transact::TransactError,
+    AppState,
+};
+
+pub const BEYOND_HISTORY: &str = "historico retido nao alcanca o instante pedido";
+
+#[derive(Deserialize)]
+pub struct AsOfQuery {
+    /// Defaults to now.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    em: Option<OffsetDateTime>,
+    /// Defaults to the configured currency.
+    moeda: Option<String>,
+}
+
+/// The balance as it stood at `em`, counting every transaction stamped at or
+/// before it. With `BANK_LEDGER_FILE` the account's events are folded from
+/// the first; they are never dropped, so any instant can be answered.
+/// Without it the retained statement history is unwound from the current
+/// balance instead, which only works while that history reaches back to
+/// `em`: a `422` otherwise. Either way only `realizada_em` decides what
+/// counts, so out-of-order timestamps give the same answer however they were
+/// applied. Coalesced credits carry the first one's time, so under
+/// `BANK_COALESCE_CREDITS_MS` the answer is only that precise.
+pub async fn balance(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<AsOfQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let at = query.em.unwrap_or_else(|| state.clock.now());
+    let currency = match query.moeda.as_deref() {
+        None => None,
+        Some(code) => match Currency::from_code(&code.to_ascii_uppercase()) {
+            None => return Err(AppError::Invalid(currency::UNKNOWN)),
+            Some(currency) if currency == state.config.currency => None,
+            Some(currency) => Some(currency),
+        },
+    };
+
+    let (balance, counted, source) = match state.ledger.clone() {
+        Some(ledger) => {
+            let (balance, counted) =
+                tokio::task::spawn_blocking(move || ledger.balance_at(account_id, currency, at))
+                    .await
+                    .ok()
+                    .and_then(Result::ok)
+                    .ok_or(AppError::Status(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        ledger::UNREADABLE,
+                    ))?;
+            (balance, counted, "ledger")
+        }
+        None => {
+            let account = entry.account.read().await;
+            let current = match currency {
+                None => account.balance,
+                Some(currency) => *account.balances.get(&currency).ok_or(AppError::Transact(
+                    TransactError::CurrencyNotHeld { currency },
+                    state.config.formatter(),
+                ))?,
+            };
+            let in_currency: Vec<_> = account
+                .transactions
+                .iter()
+                .filter(|transaction| transaction.currency == currency)
+                .collect();
+            // Newest first, and a ring only ever loses its oldest: once the
+            // oldest kept is at or before `em`, everything after it is here.
+            // Nothing after it to unwind either if the account has not
+            // changed since.
+            let reaches = account
+                .transactions
+                .iter()
+                .last()
+                .is_some_and(|oldest| oldest.create_at <= at);
+            if !reaches && at < account.modified_at {
+                return Err(AppError::Invalid(BEYOND_HISTORY));
+            }
+            let later: Vec<_> = in_currency
+                .iter()
+                .filter(|transaction| transaction.create_at > at)
+                .collect();
+            let unwound: i64 = later
+                .iter()
+                .map(|transaction| match transaction.kind {
+                    TransactionType::Credit => transaction.value,
+                    TransactionType::Debit => -transaction.value,
+                })
+                .sum();
+            let counted = in_currency.len() - later.len();
+            (current - unwound, counted, "historico")
+        }
+    };
+
+    let currency = currency.unwrap_or(state.config.currency);
+    Ok(Json(json!({
+        "account": account_id,
+        "em": state.config.timestamp(at),
+        "moeda": currency.code(),
+        "saldo": state.config.formatter().in_currency(currency).amount(balance),
+        "transacoes": counted,
+        "fonte": source,
+    })))
+}
@@ -0,0 +1,40 @@
+use std::{io, path::Path};
+
+use serde_json::Value;
+
+use crate::{
+    correlation,
+    rotation::{RotatingFile, Rotation},
+};
+
+/// Append-only JSON-lines record of administrative actions. Goes to stderr
+/// when no file is configured.
+pub struct AuditLog {
+    file: Option<RotatingFile>,
+}
+
+impl AuditLog {
+    pub fn open(path: Option<&Path>, rotation: Rotation) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(RotatingFile::open(path, rotation)?),
+            None => None,
+        };
+        Ok(Self { file })
+    }
+
+    /// Entries made while serving a request carry its `correlacao`.
+    pub fn record(&self, mut entry: Value) {
+        if let (Some(fields), Some(id)) = (entry.as_object_mut(), correlation::current()) {
+            fields.insert("correlacao".into(), id.as_ref().into());
+        }
+        let line = format!("{entry}\n");
+        match &self.file {
+            Some(file) => {
+                if let Err(err) = file.append(line.as_bytes(), false) {
+                    tracing::error!(error = %err, line = line.trim_end(), "audit write failed");
+                }
+            }
+            None => eprint!("audit: {line}"),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{error::AppError, registry::AccountId, trace, AppState};
+
+pub const MISSING: &str = "chave de API ausente ou invalida";
+pub const FORBIDDEN: &str = "chave de API sem acesso a esta conta";
+
+/// Served to anyone: they describe the API, not an account. The probes too,
+/// since the kubelet has no key.
+const PUBLIC: &[&str] = &[
+    "/",
+    "/capabilities",
+    "/openapi.json",
+    "/docs",
+    "/healthz",
+    "/readyz",
+    // Checked against `BANK_REPLICATION_TOKEN` instead.
+    "/interno/replicacao",
+];
+
+/// What an API key may reach.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// Everything, the admin routes included (as with `X-Admin-Token`).
+    Admin,
+    /// Only `/clientes/:id/...` for these ids. A transfer out of one of them
+    /// may still credit any account.
+    Accounts(Vec<AccountId>),
+}
+
+impl Scope {
+    fn allows(&self, account: Option<AccountId>) -> bool {
+        match (self, account) {
+            (Scope::Admin, _) => true,
+            (Scope::Accounts(ids), Some(id)) => ids.contains(&id),
+            (Scope::Accounts(_), None) => false,
+        }
+    }
+}
+
+/// `BANK_API_KEYS`: `chave:escopo` pairs separated by `;`, the scope being
+/// `admin` or comma-separated account ids, e.g. `k-2:2;k-ops:1,3;k-root:admin`.
+pub fn parse_keys(value: &str) -> Option<HashMap<String, Scope>> {
+    value
+        .split(';')
+        .map(|pair| {
+            let (key, scope) = pair.trim().rsplit_once(':')?;
+            let scope = match scope.trim() {
+                "admin" => Scope::Admin,
+                ids => Scope::Accounts(
+                    ids.split(',')
+                        .map(|id| id.trim().parse().ok())
+                        .collect::<Option<_>>()?,
+                ),
+            };
+            (!key.is_empty()).then(|| (key.to_owned(), scope))
+        })
+        .collect()
+}
+
+/// With `BANK_API_KEYS` set, every route but `PUBLIC` needs
+/// `Authorization: Bearer <chave>`: `401` without a known key, `403` when
+/// its scope does not cover the path's account or the route has none. The
+/// scope rides along as an extension for `require_admin`. Without keys
+/// configured the API stays open, as it always was.
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if state.config.api_keys.is_empty() || PUBLIC.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let scope = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|key| state.config.api_keys.get(key.trim()));
+    let Some(scope) = scope.cloned() else {
+        let mut response = AppError::Status(StatusCode::UNAUTHORIZED, MISSING).into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    if !scope.allows(trace::account_of(request.uri().path())) {
+        return AppError::Status(StatusCode::FORBIDDEN, FORBIDDEN).into_response();
+    }
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
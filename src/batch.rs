@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    deadline::Deadline,
+    error::{AppError, JsonBody},
+    precondition,
+    registry::AccountId,
+    schedule, strict, AppState, Transaction, TransactionRequest,
+};
+
+pub const EMPTY: &str = "lote vazio";
+pub const TOO_LARGE: &str = "lote com transacoes demais";
+
+/// Entries one batch may carry; the account stays locked while it runs.
+const MAX_ENTRIES: usize = 1000;
+
+/// `POST /clientes/:id/transacoes/lote`: the body is an array of ordinary
+/// transaction requests, each checked like the body of
+/// `POST /clientes/:id/transacoes` and applied in order as one step. One
+/// with a future `realizada_em` is scheduled for then instead. A refusal
+/// rolls back what the batch had applied and is recorded on the account
+/// like any other; its error body carries the failing entry's `indice`.
+/// Honors `If-Match` for the batch as a whole.
+pub async fn create(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    headers: HeaderMap,
+    JsonBody(entries): JsonBody<Vec<Value>>,
+) -> Result<Json<Value>, AppError> {
+    match entries.len() {
+        0 => return Err(AppError::Invalid(EMPTY)),
+        len if len > MAX_ENTRIES => return Err(AppError::Invalid(TOO_LARGE)),
+        _ => {}
+    }
+    let expected = precondition::expected_version(&headers).map_err(AppError::BadRequest)?;
+    let received = state.clock.now();
+    let transactions = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let at = |error| AppError::Entry {
+                index,
+                error: Box::new(error),
+            };
+            let request: TransactionRequest =
+                strict::from_value(entry, &state.config).map_err(at)?;
+            let run_at = request.run_at(received);
+            let transaction = request
+                .validate(&state.config, received)
+                .map_err(|errors| at(AppError::Fields(errors)))?;
+            Ok((run_at, transaction))
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let mut account = state
+        .write_account(&entry, deadline.map(|Extension(deadline)| deadline))
+        .await?;
+    let now = state.clock.now();
+    let refused = |error| AppError::Transact(error, state.config.formatter());
+    account.check_version(expected).map_err(refused)?;
+
+    let savepoint = account.savepoint(transactions.len());
+    let mut applied = Vec::with_capacity(transactions.len());
+    let mut scheduled = Vec::new();
+    for (index, (run_at, transaction)) in transactions.into_iter().enumerate() {
+        if let Some(run_at) = run_at {
+            scheduled.push(Transaction {
+                create_at: run_at,
+                ..transaction
+            });
+            continue;
+        }
+        let submitted = transaction.clone();
+        match account.transact(transaction, now) {
+            Ok(result) => applied.extend(result.recorded().cloned()),
+            Err(error) => {
+                account.roll_back(savepoint);
+                state.rejected(account_id, &submitted, error);
+                return Err(AppError::Entry {
+                    index,
+                    error: Box::new(refused(error)),
+                });
+            }
+        }
+    }
+
+    // Scheduling is not a transaction: with any, the account is logged
+    // whole, immediate entries included, in one WAL record.
+    let schedule = (!scheduled.is_empty()).then(|| account.schedule.clone());
+    let scheduled: Vec<Value> = scheduled
+        .into_iter()
+        .map(|transaction| {
+            let run_at = transaction.create_at;
+            schedule::render(account.schedule.add(run_at, transaction), &state.config)
+        })
+        .collect();
+    let logged = match schedule {
+        Some(_) => {
+            account.touch(now);
+            state.persist(account_id, &account)
+        }
+        None => state.log(applied.iter().map(|transaction| (account_id, transaction))),
+    };
+    if let Err(error) = logged {
+        account.roll_back(savepoint);
+        if let Some(schedule) = schedule {
+            account.schedule = schedule;
+        }
+        return Err(error);
+    }
+
+    for transaction in &applied {
+        state.accepted(account_id, transaction);
+    }
+    if !scheduled.is_empty() {
+        state.changed(account_id);
+    }
+    state.audit.record(json!({
+        "evento": "lote",
+        "account": account_id,
+        "transacoes": applied.len(),
+        "agendadas": scheduled.len(),
+        "em": state.config.timestamp(now),
+    }));
+
+    let formatter = state.config.formatter();
+    let mut body = json!({
+        "account": account_id,
+        "transacoes": applied
+            .iter()
+            .map(|transaction| json!({ "id": transaction.id, "seq": state.config.seq(transaction.seq) }))
+            .collect::<Vec<_>>(),
+        "limite": formatter.amount(account.limit),
+        "saldo": formatter.amount(account.balance),
+        "versao": account.version,
+    });
+    if !scheduled.is_empty() {
+        body["agendamentos"] = scheduled.into();
+    }
+    Ok(Json(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use time::Duration;
+
+    use crate::{
+        config::Config,
+        testing::{bank, get, post, send, timestamp, START},
+    };
+
+    const PATH: &str = "/clientes/1/transacoes/lote";
+
+    #[tokio::test]
+    async fn entries_are_checked_field_by_field_and_strictly() {
+        let (state, _) = bank(Config {
+            strict_json: true,
+            ..Config::default()
+        });
+        let batch = json!([
+            {"valor": 10, "tipo": "C", "descricao": "ok"},
+            {"valor": "dez", "tipo": "X", "descricao": "ruim"},
+        ]);
+        let (status, body) = send(&state, post(PATH, batch)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+        assert_eq!(body["indice"], 1);
+        let fields: Vec<_> = body["campos"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["campo"].clone())
+            .collect();
+        assert_eq!(fields, [json!("valor"), json!("tipo")]);
+
+        let batch = json!([{"valor": 10, "tipo": "C", "descricao": "ok", "extra": 1}]);
+        let (status, body) = send(&state, post(PATH, batch)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["indice"], 0);
+        assert_eq!(body["campo"], "extra");
+    }
+
+    #[tokio::test]
+    async fn a_future_entry_is_scheduled_rather_than_applied() {
+        let (state, _) = bank(Config::default());
+        let later = timestamp(START + Duration::hours(1));
+        let batch = json!([
+            {"valor": 10, "tipo": "C", "descricao": "agora"},
+            {"valor": 20, "tipo": "C", "descricao": "depois", "realizada_em": later},
+        ]);
+        let (status, body) = send(&state, post(PATH, batch)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["transacoes"].as_array().unwrap().len(), 1);
+        assert_eq!(body["saldo"], 10);
+        assert_eq!(body["agendamentos"][0]["executar_em"], later);
+
+        let (_, body) = send(&state, get("/clientes/1/agendamentos")).await;
+        assert_eq!(body["agendamentos"][0]["transacao"]["valor"], 20);
+    }
+
+    #[tokio::test]
+    async fn a_refusal_is_recorded_and_nothing_applied() {
+        let (state, _) = bank(Config::default());
+        let batch = json!([
+            {"valor": 10, "tipo": "C", "descricao": "credito"},
+            {"valor": 100_011, "tipo": "D", "descricao": "saque"},
+        ]);
+        let (status, body) = send(&state, post(PATH, batch)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["indice"], 1);
+
+        let (_, body) = send(&state, get("/clientes/1/rejeicoes")).await;
+        assert_eq!(body["rejeicoes"][0]["transacao"]["valor"], 100_011);
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(body["saldo"]["total"], 0);
+        assert_eq!(body["ultimas_transacoes"], json!([]));
+    }
+}
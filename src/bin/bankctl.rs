@@ -0,0 +1,314 @@
+//! Operations against a running server, or offline against its snapshot.
+//!
+//!     bankctl accounts
+//!     bankctl statement 1 [--format json|csv|ofx]
+//!     bankctl set-limit 1 150000
+//!     bankctl set-type 1 corporate
+//!     bankctl credit 1 500 [description]
+//!     bankctl debit 1 500 [description]
+//!     bankctl --snapshot /var/lib/bank/snapshot.json accounts
+//!
+//! `--url` (or `BANKCTL_URL`, default `http://localhost:3000`) picks the
+//! server; `--token` (or `BANK_ADMIN_TOKEN`) is sent as `X-Admin-Token` and
+//! `--api-key` (or `BANKCTL_API_KEY`) as a bearer key. With `--snapshot`
+//! only `accounts` and `statement` work, amounts are in minor units and what
+//! the WAL holds past the snapshot is not included.
+
+use std::{
+    env, fs,
+    io::{Read, Write},
+    net::TcpStream,
+    process::ExitCode,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+const USAGE: &str = "usage: bankctl [--url URL] [--token TOKEN] [--api-key KEY] [--snapshot FILE] \
+    <accounts | statement ID [--format json|csv|ofx] | set-limit ID LIMIT | set-type ID TYPE | credit ID AMOUNT [DESCRIPTION] \
+    | debit ID AMOUNT [DESCRIPTION]>";
+
+/// The server caps `descricao` at 10 characters by default.
+const DEFAULT_DESCRIPTION: &str = "ajuste";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Options {
+    url: String,
+    token: Option<String>,
+    api_key: Option<String>,
+    snapshot: Option<String>,
+    format: Option<String>,
+    command: Vec<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            url: env::var("BANKCTL_URL").unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            token: env::var("BANK_ADMIN_TOKEN").ok(),
+            api_key: env::var("BANKCTL_API_KEY").ok(),
+            snapshot: None,
+            format: None,
+            command: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} requires a value"));
+            match arg.as_str() {
+                "--url" => options.url = value()?,
+                "--token" => options.token = Some(value()?),
+                "--api-key" => options.api_key = Some(value()?),
+                "--snapshot" => options.snapshot = Some(value()?),
+                "--format" => options.format = Some(value()?),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+                _ => options.command.push(arg),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> ExitCode {
+    let result = Options::parse(env::args().skip(1)).and_then(|options| match &options.snapshot {
+        Some(path) => offline(&options, path),
+        None => online(&options),
+    });
+    match result {
+        Ok(output) => {
+            // Piped into `head`, stdout may close early; that is not a failure.
+            let _ = writeln!(std::io::stdout(), "{}", output.trim_end());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("bankctl: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn online(options: &Options) -> Result<String, String> {
+    let server = Server::new(options)?;
+    let command: Vec<&str> = options.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["accounts"] => {
+            let csv = server.request("GET", "/relatorio.csv", None)?;
+            Ok(table(&csv))
+        }
+        ["statement", id] => {
+            let path = match options.format.as_deref() {
+                None | Some("json") => format!("/clientes/{}/extrato", account(id)?),
+                Some(format @ ("csv" | "ofx")) => {
+                    format!("/clientes/{}/extrato?formato={format}", account(id)?)
+                }
+                Some(format) => return Err(format!("unknown format {format}")),
+            };
+            pretty(server.request("GET", &path, None)?)
+        }
+        ["set-limit", id, limit] => {
+            let body = json!({ "limite": amount(limit)? });
+            let path = format!("/clientes/{}", account(id)?);
+            pretty(server.request("PATCH", &path, Some(body))?)
+        }
+        ["set-type", id, account_type] => {
+            let body = json!({ "tipo": account_type });
+            let path = format!("/clientes/{}", account(id)?);
+            pretty(server.request("PATCH", &path, Some(body))?)
+        }
+        [kind @ ("credit" | "debit"), id, value, description @ ..] => {
+            let body = json!({
+                "valor": amount(value)?,
+                "tipo": if *kind == "credit" { "C" } else { "D" },
+                "descricao": match description {
+                    [] => DEFAULT_DESCRIPTION.to_string(),
+                    words => words.join(" "),
+                },
+            });
+            let path = format!("/clientes/{}/transacoes", account(id)?);
+            pretty(server.request("POST", &path, Some(body))?)
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Straight from `BANK_SNAPSHOT_FILE`, for when the server is down.
+fn offline(options: &Options, path: &str) -> Result<String, String> {
+    let snapshot = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+    let accounts: Vec<Value> =
+        serde_json::from_str(&snapshot).map_err(|err| format!("{path}: {err}"))?;
+    let command: Vec<&str> = options.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["accounts"] => {
+            let mut csv = String::from("id,tipo,limite,saldo,encerrada\n");
+            for account in &accounts {
+                csv.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    account["id"],
+                    account["tipo"].as_str().unwrap_or("-"),
+                    account["limite"],
+                    account["saldo"],
+                    account["encerrada"].as_bool().unwrap_or(false),
+                ));
+            }
+            Ok(table(&csv))
+        }
+        ["statement", id] => {
+            let id = account(id)?;
+            let account = accounts
+                .iter()
+                .find(|account| account["id"] == id)
+                .ok_or(format!("account {id} not in the snapshot"))?;
+            serde_json::to_string_pretty(account).map_err(|err| err.to_string())
+        }
+        _ => Err(format!("with --snapshot: {USAGE}")),
+    }
+}
+
+fn account(id: &str) -> Result<u64, String> {
+    id.parse().map_err(|_| format!("invalid account id {id}"))
+}
+
+/// Passed through as the number it is, so `12.50` works against a server
+/// with `BANK_AMOUNT_FORMAT=decimal`.
+fn amount(value: &str) -> Result<serde_json::Number, String> {
+    value.parse().map_err(|_| format!("invalid amount {value}"))
+}
+
+/// JSON bodies indented; anything else (CSV, OFX) as it came.
+fn pretty(body: String) -> Result<String, String> {
+    Ok(match serde_json::from_str::<Value>(&body) {
+        Ok(value) => serde_json::to_string_pretty(&value).unwrap_or(body),
+        Err(_) => body,
+    })
+}
+
+/// Columns padded to their widest cell.
+fn table(csv: &str) -> String {
+    let rows: Vec<Vec<&str>> = csv
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split(',').collect())
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:>width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Just enough HTTP/1.1 for the admin API: one request per connection,
+/// `Connection: close`, chunked bodies decoded. Plain `http://` only.
+struct Server<'a> {
+    host: String,
+    prefix: String,
+    options: &'a Options,
+}
+
+impl<'a> Server<'a> {
+    fn new(options: &'a Options) -> Result<Self, String> {
+        let rest = options
+            .url
+            .strip_prefix("http://")
+            .ok_or(format!("only http:// URLs are supported: {}", options.url))?;
+        let (host, prefix) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], rest[slash..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        Ok(Self {
+            host: host.to_string(),
+            prefix: prefix.to_string(),
+            options,
+        })
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> Result<String, String> {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut request = format!(
+            "{method} {}{path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\
+             Accept: */*\r\nContent-Length: {}\r\n",
+            self.prefix,
+            self.host,
+            body.len()
+        );
+        if !body.is_empty() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        if let Some(token) = &self.options.token {
+            request.push_str(&format!("X-Admin-Token: {token}\r\n"));
+        }
+        if let Some(key) = &self.options.api_key {
+            request.push_str(&format!("Authorization: Bearer {key}\r\n"));
+        }
+        request.push_str("\r\n");
+        request.push_str(&body);
+
+        let connect = |err: std::io::Error| format!("{}: {err}", self.host);
+        let mut stream = TcpStream::connect(&self.host).map_err(connect)?;
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(connect)?;
+        stream.write_all(request.as_bytes()).map_err(connect)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map_err(connect)?;
+
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or("malformed response from the server")?;
+        let head = String::from_utf8_lossy(&response[..split]);
+        let body = &response[split + 4..];
+        let status: u16 = head
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or("malformed status line from the server")?;
+        let chunked = head.lines().any(|line| {
+            line.to_ascii_lowercase()
+                .starts_with("transfer-encoding: chunked")
+        });
+        let body = match chunked {
+            true => dechunk(body)?,
+            false => body.to_vec(),
+        };
+        let body = String::from_utf8_lossy(&body);
+        match status {
+            200..=299 if body.is_empty() => Ok(format!("{status}")),
+            200..=299 => Ok(body.into_owned()),
+            _ => Err(format!("{method} {path}: {status} {body}")
+                .trim_end()
+                .to_string()),
+        }
+    }
+}
+
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    loop {
+        let line = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or("truncated chunked body")?;
+        let size = String::from_utf8_lossy(&body[..line]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16)
+            .map_err(|_| "malformed chunk size")?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let rest = &body[line + 2..];
+        decoded.extend_from_slice(rest.get(..size).ok_or("truncated chunk")?);
+        body = rest.get(size + 2..).ok_or("truncated chunk")?;
+    }
+}
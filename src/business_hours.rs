@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use time::{
+    format_description::FormatItem, macros::format_description, OffsetDateTime, Time, UtcOffset,
+};
+
+use crate::{error::AppError, AppState};
+
+pub const CLOSED: &str = "fora do horario de funcionamento";
+
+const CLOCK: &[FormatItem<'static>] = format_description!("[hour]:[minute]");
+
+/// When writes are accepted, from `BANK_BUSINESS_HOURS`, `BANK_BUSINESS_DAYS`
+/// and `BANK_BUSINESS_TZ`.
+#[derive(Clone, Debug)]
+pub struct BusinessHours {
+    /// Indexed by `Weekday::number_days_from_monday`.
+    pub days: [bool; 7],
+    /// Start inclusive, end exclusive, within one day.
+    pub ranges: Vec<(Time, Time)>,
+    pub offset: UtcOffset,
+}
+
+impl BusinessHours {
+    pub fn is_open(&self, at: OffsetDateTime) -> bool {
+        let local = at.to_offset(self.offset);
+        let time = local.time();
+        self.days[local.weekday().number_days_from_monday() as usize]
+            && self
+                .ranges
+                .iter()
+                .any(|(start, end)| *start <= time && time < *end)
+    }
+}
+
+/// ISO weekday numbers, Monday `1` to Sunday `7`, as a list and/or ranges:
+/// `1-5`, `1,3,5`, `1-3,6`.
+pub fn parse_days(days: &str) -> Option<[bool; 7]> {
+    let mut open = [false; 7];
+    for part in days.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let (first, last): (usize, usize) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+        if !(1..=7).contains(&first) || !(first..=7).contains(&last) {
+            return None;
+        }
+        open[first - 1..last].fill(true);
+    }
+    Some(open)
+}
+
+/// `HH:MM-HH:MM` ranges separated by commas, e.g. `09:00-12:00,13:00-18:00`.
+/// A range may not wrap past midnight; `24:00` ends one at the end of the day.
+pub fn parse_ranges(ranges: &str) -> Option<Vec<(Time, Time)>> {
+    ranges
+        .split(',')
+        .map(|range| {
+            let (start, end) = range.split_once('-')?;
+            let start = Time::parse(start.trim(), CLOCK).ok()?;
+            let end = match end.trim() {
+                "24:00" => Time::from_hms_nano(23, 59, 59, 999_999_999).ok()?,
+                end => Time::parse(end, CLOCK).ok()?,
+            };
+            (start < end).then_some((start, end))
+        })
+        .collect()
+}
+
+/// Layered on the write routes, next to maintenance mode: outside business
+/// hours they answer `423` while reads carry on.
+pub async fn enforce(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if let Some(hours) = &state.config.business_hours {
+        if !hours.is_open(state.clock.now()) {
+            return AppError::Status(StatusCode::LOCKED, CLOSED).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use time::{macros::offset, Duration};
+
+    use super::{parse_days, parse_ranges, BusinessHours, CLOSED};
+    use crate::{
+        testing::{bank, get, post, send, START},
+        Config,
+    };
+
+    /// Weekdays 09:00 to 18:00 at `-03:00`; `START` is a Friday at 09:00
+    /// there.
+    fn office() -> Config {
+        Config {
+            business_hours: Some(BusinessHours {
+                days: parse_days("1-5").unwrap(),
+                ranges: parse_ranges("09:00-18:00").unwrap(),
+                offset: offset!(-3),
+            }),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_are_refused_outside_the_window_and_reads_carry_on() {
+        let (state, clock) = bank(office());
+        let credit = || {
+            post(
+                "/clientes/1/transacoes",
+                json!({"valor": 100, "tipo": "C", "descricao": "x"}),
+            )
+        };
+        let (status, _) = send(&state, credit()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        clock.set(START - Duration::minutes(1));
+        let (status, body) = send(&state, credit()).await;
+        assert_eq!(status, StatusCode::LOCKED);
+        assert_eq!(body["message"], CLOSED);
+
+        // 18:00 ends the range; Saturday is closed all day.
+        for at in [START + Duration::hours(9), START + Duration::days(1)] {
+            clock.set(at);
+            let (status, _) = send(&state, credit()).await;
+            assert_eq!(status, StatusCode::LOCKED);
+        }
+        let (status, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(statement["saldo"]["total"], 100);
+
+        clock.set(START + Duration::days(3));
+        let (status, _) = send(&state, credit()).await;
+        assert_eq!(status, StatusCode::OK, "Monday morning");
+    }
+
+    #[test]
+    fn schedules_parse_and_refuse_what_they_cannot_mean() {
+        assert_eq!(
+            parse_days("1-3,6"),
+            Some([true, true, true, false, false, true, false])
+        );
+        assert_eq!(parse_days("0-5"), None);
+        assert_eq!(parse_days("5-2"), None);
+        assert_eq!(parse_ranges("09:00-12:00,13:00-18:00").unwrap().len(), 2);
+        assert_eq!(parse_ranges("22:00-02:00"), None);
+        assert!(parse_ranges("18:00-24:00").is_some());
+    }
+}
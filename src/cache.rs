@@ -0,0 +1,237 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime, UtcOffset};
+
+use crate::{registry::AccountId, statement, Account, AppState};
+
+const HTTP_DATE: &[FormatItem<'static>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+struct CachedStatement {
+    version: u64,
+    built_at: OffsetDateTime,
+    etag: String,
+    /// Everything but `data_extrato` and `disponivel`, which are filled in on
+    /// every response: holds lapse without moving the account's version.
+    body: Value,
+}
+
+/// An account's rendered statement, reused until the account's `version`
+/// moves or `BANK_STATEMENT_CACHE_MAX_AGE_MS` passes. `data_extrato` is
+/// always the serving time and, like `disponivel`, is left out of the ETag.
+#[derive(Default)]
+pub struct CacheSlot(Mutex<Option<CachedStatement>>);
+
+impl CacheSlot {
+    /// Call with the account's read lock held so the version and the body agree.
+    pub fn serve(
+        &self,
+        account_id: AccountId,
+        account: &Account,
+        state: &AppState,
+        headers: &HeaderMap,
+    ) -> Response {
+        let now = state.clock.now();
+        let max_age = state.config.statement_cache_max_age;
+        let mut slot = self.0.lock().unwrap();
+        let cached = match slot.take() {
+            Some(cached)
+                if cached.version == account.version
+                    && max_age.is_none_or(|age| now - cached.built_at < age) =>
+            {
+                cached
+            }
+            _ => {
+                let mut body = statement(account_id, account, state);
+                body["saldo"]["data_extrato"] = Value::Null;
+                body["saldo"]["disponivel"] = Value::Null;
+                let mut hasher = DefaultHasher::new();
+                body.to_string().hash(&mut hasher);
+                CachedStatement {
+                    version: account.version,
+                    built_at: now,
+                    etag: format!("\"{:016x}\"", hasher.finish()),
+                    body,
+                }
+            }
+        };
+
+        let last_modified = account
+            .modified_at
+            .to_offset(UtcOffset::UTC)
+            .format(HTTP_DATE)
+            .unwrap();
+        let not_modified = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|tags| {
+                tags.split(',')
+                    .any(|tag| tag.trim() == cached.etag || tag.trim() == "*")
+            });
+
+        let response = if not_modified {
+            (
+                StatusCode::NOT_MODIFIED,
+                [
+                    (header::ETAG, cached.etag.clone()),
+                    (header::LAST_MODIFIED, last_modified),
+                ],
+            )
+                .into_response()
+        } else {
+            let mut body = cached.body.clone();
+            body["saldo"]["data_extrato"] = state.config.coarse_timestamp(now).into();
+            body["saldo"]["disponivel"] = state.config.amount(account.available(now));
+            (
+                [
+                    (header::CONTENT_TYPE, "application/json".to_string()),
+                    (header::ETAG, cached.etag.clone()),
+                    (header::LAST_MODIFIED, last_modified),
+                ],
+                body.to_string(),
+            )
+                .into_response()
+        };
+        *slot = Some(cached);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use serde_json::json;
+    use time::Duration;
+    use tower::ServiceExt;
+
+    use crate::{
+        config::Config,
+        http,
+        registry::AccountId,
+        testing::{bank, get, post, send, START},
+    };
+
+    fn cached() -> Config {
+        Config {
+            statement_cache: true,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_between_writes_reuse_the_cached_body() {
+        let (state, clock) = bank(cached());
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let built_at = || {
+            entry
+                .statement
+                .0
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|c| c.built_at)
+        };
+        let etag = || async {
+            let response = http::router(state.clone())
+                .oneshot(get("/clientes/1/extrato"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.headers()[header::ETAG]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let first = etag().await;
+        assert_eq!(built_at(), Some(START));
+        clock.advance(Duration::seconds(1));
+        let second = etag().await;
+        assert_eq!(first, second);
+        assert_eq!(built_at(), Some(START), "second read rebuilt the body");
+
+        let credit = json!({"valor": 10, "tipo": "C", "descricao": "cache"});
+        send(&state, post("/clientes/1/transacoes", credit)).await;
+        let third = etag().await;
+        assert_ne!(third, first);
+        assert_eq!(built_at(), Some(START + Duration::seconds(1)));
+    }
+
+    #[tokio::test]
+    async fn matching_etag_is_not_modified_until_a_write() {
+        let (state, _) = bank(cached());
+        let conditional = |etag: &str| {
+            Request::get("/clientes/1/extrato")
+                .header(header::IF_NONE_MATCH, etag)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = http::router(state.clone())
+            .oneshot(get("/clientes/1/extrato"))
+            .await
+            .unwrap();
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+
+        let (status, _) = send(&state, conditional(&etag)).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        let credit = json!({"valor": 10, "tipo": "C", "descricao": "cache"});
+        send(&state, post("/clientes/1/transacoes", credit)).await;
+        let (status, body) = send(&state, conditional(&etag)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["saldo"]["total"], 10);
+    }
+
+    #[tokio::test]
+    async fn serving_time_is_current_and_max_age_forces_a_rebuild() {
+        let (state, clock) = bank(Config {
+            statement_cache_max_age: Some(std::time::Duration::from_secs(5)),
+            ..cached()
+        });
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let built_at = || {
+            entry
+                .statement
+                .0
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|c| c.built_at)
+        };
+        let served_at = || async {
+            let (status, body) = send(&state, get("/clientes/1/extrato")).await;
+            assert_eq!(status, StatusCode::OK);
+            body["saldo"]["data_extrato"].clone()
+        };
+
+        assert_eq!(served_at().await, state.config.coarse_timestamp(START));
+        clock.advance(Duration::seconds(3));
+        // From the cache, stamped with the time it is served at.
+        assert_eq!(
+            served_at().await,
+            state.config.coarse_timestamp(START + Duration::seconds(3))
+        );
+        assert_eq!(built_at(), Some(START));
+
+        clock.advance(Duration::seconds(3));
+        let now = START + Duration::seconds(6);
+        assert_eq!(served_at().await, state.config.coarse_timestamp(now));
+        assert_eq!(built_at(), Some(now), "idle past max age, still cached");
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde_json::{json, Value};
+
+use crate::{
+    config::{Config, LengthUnit},
+    currency::{AmountFormat, Currency},
+    policy, AppState,
+};
+
+/// What this build and configuration support, so clients can adapt instead
+/// of probing. Flags only: no paths, URLs or secrets.
+pub async fn describe(State(state): State<Arc<AppState>>) -> Json<Value> {
+    Json(document(&state.config))
+}
+
+fn document(config: &Config) -> Value {
+    let millis = |duration: Option<std::time::Duration>| duration.map(|d| d.as_millis() as u64);
+    json!({
+        "versao": env!("CARGO_PKG_VERSION"),
+        "persistencia": {
+            "snapshot": config.snapshot_file.is_some(),
+            "wal": config.wal_file.is_some(),
+            "postgres": config.database_url.is_some(),
+            "durabilidade": config.wal_file.as_ref().map(|_| config.wal_durability.name()),
+            "rotacao": config.log_rotation.max_bytes.is_some()
+                || config.log_rotation.max_age.is_some(),
+        },
+        "autenticacao": {
+            "admin": config.admin_token.is_some(),
+            "chaves_api": !config.api_keys.is_empty(),
+            "erros_precisos": config.precise_auth_errors,
+            "digito_verificador": config.account_checksum,
+        },
+        "limite_de_requisicoes": {
+            "por_ip": config.rate_limit_ip.map(|rate| json!({ "por_segundo": rate.rate, "rajada": rate.burst })),
+            "por_conta": config.rate_limit_account.map(|rate| json!({ "por_segundo": rate.rate, "rajada": rate.burst })),
+            "x_forwarded_for": config.rate_limit_forwarded,
+        },
+        "webhooks": config.webhook.is_some(),
+        "tls": config.tls.is_some(),
+        "http2": true,
+        "replicacao": config.replication.as_ref().map(|replication| replication.role.name()),
+        "auditoria": config.audit_log.is_some(),
+        "log_estruturado": config.log_level.map(|level| level.as_str()),
+        "moeda": config.currency.code(),
+        "moedas": Currency::ALL.map(Currency::code),
+        "formato_valor": match config.amount_format {
+            AmountFormat::Minor => "minor",
+            AmountFormat::Decimal => "decimal",
+        },
+        "msgpack": cfg!(feature = "msgpack"),
+        "historico": {
+            "capacidade": config.history_cap,
+            "retencao_ms": millis(config.history_retention),
+            "agrupa_creditos_ms": millis(config.coalesce_credits),
+            "arquivo": config.history_file.is_some(),
+            "arquivamento_ms": millis(config.archive_after),
+        },
+        "exportacao": ["csv", "ofx"],
+        "categorias": config.categories,
+        "ledger": config.ledger_file.is_some(),
+        "descricao": {
+            "tamanho_maximo": config.description.max_len,
+            "unidade": match config.description.unit {
+                LengthUnit::Chars => "chars",
+                LengthUnit::Bytes => "bytes",
+            },
+            "lista_bloqueio": !config.description.blocklist.is_empty(),
+        },
+        "limites": {
+            "diario": !config.daily_debit_limits.is_empty(),
+            "maximo_por_transacao": !config.max_debit_values.is_empty(),
+            "janela_movel_ms": (!config.rolling_debit_limits.is_empty())
+                .then_some(config.rolling_debit_window.as_millis() as u64),
+            "dinamico": config.dynamic_limit.is_some(),
+            "intervalo_minimo_ms": millis(config.min_transaction_interval),
+            "deduplicacao_ms": millis(config.dedup_window),
+            "valor_maximo": config.amount(config.max_transaction_value),
+        },
+        "tipos_de_conta": config
+            .account_types
+            .iter()
+            .map(|account_type| json!({
+                "tipo": account_type.name,
+                "politica": policy::render(account_type.policy, config),
+                "padrao": account_type.name == config.default_account_type,
+            }))
+            .collect::<Vec<_>>(),
+        "idempotencia_ms": config.idempotency_ttl.as_millis() as u64,
+        "cache_extrato": config.statement_cache,
+        "json_estrito": config.strict_json,
+        "envelope": config.response_envelope,
+        "horario_funcionamento": config.business_hours.is_some(),
+        "debug": config.debug_endpoints,
+    })
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    accounting, archival,
+    currency::{self, Currency},
+    domain::TransactionType,
+    error::AppError,
+    registry::AccountId,
+    AppState, Config,
+};
+
+pub const TYPE: &str = "categoria deve ser texto";
+pub const UNKNOWN: &str = "categoria desconhecida";
+pub const INVALID_MONTH: &str = "mes invalido";
+
+/// Where transactions without a `categoria` are totalled.
+const UNCATEGORIZED: &str = "sem_categoria";
+
+pub fn defaults() -> Vec<String> {
+    [
+        "alimentacao",
+        "transporte",
+        "moradia",
+        "saude",
+        "educacao",
+        "lazer",
+        "outros",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// `BANK_CATEGORIES`: names separated by `,`, each lowercase ASCII letters,
+/// digits and `_`.
+pub fn parse(value: &str) -> Option<Vec<String>> {
+    let names: Vec<String> = value
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .collect();
+    let valid = |name: &String| {
+        !name.is_empty()
+            && name != UNCATEGORIZED
+            && name
+                .bytes()
+                .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+    };
+    names.iter().all(valid).then_some(names)
+}
+
+/// `categoria` as configured, whatever the case it was sent in.
+pub fn resolve(name: &str, config: &Config) -> Result<String, &'static str> {
+    let name = name.trim().to_ascii_lowercase();
+    config
+        .categories
+        .iter()
+        .find(|category| **category == name)
+        .cloned()
+        .ok_or(UNKNOWN)
+}
+
+#[derive(Deserialize)]
+pub struct SummaryQuery {
+    mes: String,
+    /// Defaults to the configured currency.
+    moeda: Option<String>,
+}
+
+#[derive(Default)]
+struct Totals {
+    credits: i64,
+    debits: i64,
+    count: u64,
+}
+
+impl Totals {
+    fn add(&mut self, kind: TransactionType, value: i64) {
+        match kind {
+            TransactionType::Credit => self.credits += value,
+            TransactionType::Debit => self.debits += value,
+        }
+        self.count += 1;
+    }
+}
+
+/// What went in and out of the account in `mes` (`AAAA-MM`, UTC), per
+/// `categoria` and per `tipo`, in one currency. With `BANK_HISTORY_FILE`
+/// the month is read from the history, archived segments included, plus
+/// whatever the statement ring holds that it does not, like `export`;
+/// otherwise only the ring is there, which `fonte` says.
+pub async fn summary(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<SummaryQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let (from, to) = archival::month(&query.mes).ok_or(AppError::Invalid(INVALID_MONTH))?;
+    let currency = match query.moeda.as_deref() {
+        None => None,
+        Some(code) => match Currency::from_code(&code.to_ascii_uppercase()) {
+            None => return Err(AppError::Invalid(currency::UNKNOWN)),
+            Some(currency) if currency == state.config.currency => None,
+            Some(currency) => Some(currency),
+        },
+    };
+
+    let (mut transactions, source) = match state.history.clone() {
+        Some(archive) => {
+            let period = tokio::task::spawn_blocking(move || archive.period(account_id, from, to))
+                .await
+                .ok()
+                .and_then(Result::ok)
+                .ok_or(AppError::Status(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    accounting::UNREADABLE,
+                ))?;
+            (period.transactions, "arquivo")
+        }
+        None => (Vec::new(), "historico"),
+    };
+    let account = entry.account.read().await;
+    // Coalesced credits: the history has each one, the ring only their sum
+    // under the newest id, so the history wins.
+    let stored: HashSet<_> = transactions
+        .iter()
+        .map(|transaction| transaction.id)
+        .collect();
+    transactions.extend(
+        account
+            .transactions
+            .iter()
+            .filter(|transaction| (from..to).contains(&transaction.create_at))
+            .filter(|transaction| !stored.contains(&transaction.id))
+            .cloned(),
+    );
+    drop(account);
+
+    let mut categories: BTreeMap<&str, Totals> = BTreeMap::new();
+    let (mut credits, mut debits) = (Totals::default(), Totals::default());
+    for transaction in transactions
+        .iter()
+        .filter(|transaction| transaction.currency == currency)
+    {
+        categories
+            .entry(transaction.category.as_deref().unwrap_or(UNCATEGORIZED))
+            .or_default()
+            .add(transaction.kind, transaction.value);
+        match transaction.kind {
+            TransactionType::Credit => &mut credits,
+            TransactionType::Debit => &mut debits,
+        }
+        .add(transaction.kind, transaction.value);
+    }
+
+    let currency = currency.unwrap_or(state.config.currency);
+    let formatter = state.config.formatter().in_currency(currency);
+    let by_category: Map<String, Value> = categories
+        .into_iter()
+        .map(|(name, totals)| {
+            let body = json!({
+                "creditos": formatter.amount(totals.credits),
+                "debitos": formatter.amount(totals.debits),
+                "liquido": formatter.amount(totals.credits - totals.debits),
+                "transacoes": totals.count,
+            });
+            (name.to_string(), body)
+        })
+        .collect();
+    Ok(Json(json!({
+        "account": account_id,
+        "mes": query.mes,
+        "moeda": currency.code(),
+        "por_categoria": by_category,
+        "por_tipo": {
+            "C": { "total": formatter.amount(credits.credits), "transacoes": credits.count },
+            "D": { "total": formatter.amount(debits.debits), "transacoes": debits.count },
+        },
+        "fonte": source,
+    })))
+}
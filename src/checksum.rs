@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::Uri,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{error::AppError, AppState};
+
+pub const INVALID: &str = "numero de conta invalido: digito verificador nao confere";
+
+/// With `BANK_ACCOUNT_CHECKSUM`, account numbers in `/clientes/:id/...` carry
+/// a trailing Luhn check digit (account 1 is `18`). Runs before routing: a
+/// bad digit is a `400`, a good one is stripped so handlers keep seeing the
+/// plain id.
+pub async fn verify(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.account_checksum {
+        return next.run(request).await;
+    }
+    let path = request.uri().path();
+    let Some(rest) = path.strip_prefix("/clientes/") else {
+        return next.run(request).await;
+    };
+    let (number, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return next.run(request).await;
+    }
+    if number.len() < 2 || !luhn_valid(number) {
+        return AppError::BadRequest(INVALID).into_response();
+    }
+
+    let rewritten = match request.uri().query() {
+        Some(query) => format!("/clientes/{}{tail}?{query}", &number[..number.len() - 1]),
+        None => format!("/clientes/{}{tail}", &number[..number.len() - 1]),
+    };
+    match rewritten.parse::<Uri>() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(_) => return AppError::BadRequest(INVALID).into_response(),
+    }
+    next.run(request).await
+}
+
+/// Standard Luhn over every digit, the check digit included.
+fn luhn_valid(number: &str) -> bool {
+    let sum: u32 = number
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(index, byte)| {
+            let digit = u32::from(byte - b'0');
+            match index % 2 {
+                0 => digit,
+                _ if digit * 2 > 9 => digit * 2 - 9,
+                _ => digit * 2,
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::Duration;
+
+use crate::{
+    error::{self, AppError},
+    registry::AccountId,
+    statement, AppState, Transaction, TransactionType,
+};
+
+/// How far apart the two legs of a movement may be recorded.
+const LINK_WINDOW: Duration = Duration::seconds(1);
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    a: AccountId,
+    b: AccountId,
+}
+
+/// Both statements side by side for support staff, plus the pairs of
+/// entries that look like two legs of one movement between the accounts.
+pub async fn compare(
+    Query(query): Query<CompareQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    if query.a == query.b {
+        return Err(AppError::Invalid(error::SAME_ACCOUNT));
+    }
+    let (Some(a), Some(b)) = (state.accounts.get(&query.a), state.accounts.get(&query.b)) else {
+        return Err(AppError::UnknownAccount);
+    };
+    let (a, b) = if query.a < query.b {
+        let a = a.account.read().await;
+        (a, b.account.read().await)
+    } else {
+        let b = b.account.read().await;
+        (a.account.read().await, b)
+    };
+
+    let mut links = Vec::new();
+    for left in a.transactions.iter() {
+        if let Some(right) = b.transactions.iter().find(|right| linked(left, right)) {
+            let mut link = json!({ "a": left.id, "b": right.id });
+            if let Some(transfer) = left.transfer {
+                link["transferencia"] = json!(transfer.id);
+            }
+            links.push(link);
+        }
+    }
+
+    Ok(Json(json!({
+        "a": statement(query.a, &a, &state),
+        "b": statement(query.b, &b, &state),
+        "vinculadas": links,
+    })))
+}
+
+/// The two halves of a transfer share its id. Other entries carry no
+/// counterpart, so they are matched on what a movement between two accounts
+/// leaves behind: opposite kinds, the same value and description, recorded
+/// within `LINK_WINDOW` of each other.
+fn linked(left: &Transaction, right: &Transaction) -> bool {
+    match (left.transfer, right.transfer) {
+        (Some(left), Some(right)) => return left.id == right.id,
+        (None, None) => {}
+        _ => return false,
+    }
+    let opposite = matches!(
+        (&left.kind, &right.kind),
+        (TransactionType::Credit, TransactionType::Debit)
+            | (TransactionType::Debit, TransactionType::Credit)
+    );
+    opposite
+        && left.value == right.value
+        && left.description.0 == right.description.0
+        && (left.create_at - right.create_at).abs() <= LINK_WINDOW
+}
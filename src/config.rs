@@ -0,0 +1,1110 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    env,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use serde::{Serialize, Serializer};
+use time::{
+    format_description::{self, well_known::Rfc3339, OwnedFormatItem},
+    OffsetDateTime, UtcOffset,
+};
+
+use crate::{
+    activity, auth,
+    business_hours::{self, BusinessHours},
+    categories,
+    currency::{AmountFormat, Currency, Formatter, Rounding},
+    dynamic_limit::DynamicLimit,
+    persistence::WalDurability,
+    policy::{self, AccountType},
+    rate_limit::Rate,
+    registry::AccountId,
+    replication::{ReplicationConfig, Role},
+    rotation::Rotation,
+    tls::TlsConfig,
+    webhook::WebhookConfig,
+};
+
+/// How `Description` length is measured against `max_len`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LengthUnit {
+    /// Unicode scalar values, so "açaí" counts as 4.
+    #[default]
+    Chars,
+    /// UTF-8 bytes, for fixed-width downstream systems ("açaí" counts as 6).
+    Bytes,
+}
+
+impl LengthUnit {
+    pub fn measure(self, value: &str) -> usize {
+        match self {
+            LengthUnit::Chars => value.chars().count(),
+            LengthUnit::Bytes => value.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DescriptionPolicy {
+    pub max_len: usize,
+    pub unit: LengthUnit,
+    /// Strip surrounding whitespace before measuring; off for strict compatibility.
+    pub trim: bool,
+    /// Forbidden substrings, already folded with `fold`; empty disables the check.
+    pub blocklist: Vec<String>,
+}
+
+impl Default for DescriptionPolicy {
+    fn default() -> Self {
+        Self {
+            max_len: 10,
+            unit: LengthUnit::default(),
+            trim: false,
+            blocklist: Vec::new(),
+        }
+    }
+}
+
+impl DescriptionPolicy {
+    pub fn is_blocked(&self, description: &str) -> bool {
+        if self.blocklist.is_empty() {
+            return false;
+        }
+        let folded = fold(description);
+        self.blocklist
+            .iter()
+            .any(|term| folded.contains(term.as_str()))
+    }
+}
+
+/// Lowercases and strips the accents used in Portuguese (plus a few common
+/// neighbours) so "Açaí" and "acai" match the same blocklist term or search.
+pub fn fold(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'õ' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            other => other,
+        })
+        .collect()
+}
+
+/// `(id, limit)` of the accounts an instance starts with when
+/// `BANK_ACCOUNTS` is unset.
+const DEFAULT_ACCOUNTS: [(AccountId, i64); 5] = [
+    (AccountId(1), 100_000),
+    (AccountId(2), 80_000),
+    (AccountId(3), 1_000_000),
+    (AccountId(4), 10_000_000),
+    (AccountId(5), 500_000),
+];
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// HTTP/1.1 and, by prior knowledge, cleartext HTTP/2.
+    pub bind: SocketAddr,
+    /// Served over TLS at `bind` instead, HTTP/2 negotiated by ALPN.
+    pub tls: Option<TlsConfig>,
+    /// `BANK_GRPC_BIND`: the gRPC listener of `proto/bank.proto`, with the
+    /// `grpc` feature.
+    pub grpc_bind: Option<SocketAddr>,
+    /// Tokio worker threads; one per core when unset.
+    pub workers: Option<usize>,
+    /// `(id, limit)` seeded at startup, before any snapshot or WAL replay.
+    pub accounts: Vec<(AccountId, i64)>,
+    /// Structured JSON logs on stderr from this level up; warnings and errors
+    /// only when unset.
+    pub log_level: Option<tracing::Level>,
+    pub description: DescriptionPolicy,
+    /// Every account's base currency: the one its limit, holds and debit
+    /// rules apply to, and what `moeda` defaults to.
+    pub currency: Currency,
+    /// Further currencies an account holds, each a balance of its own that
+    /// may not go negative.
+    pub account_currencies: HashMap<AccountId, Vec<Currency>>,
+    /// Refuse transactions without `moeda` instead of assuming `currency`.
+    pub require_currency: bool,
+    pub amount_format: AmountFormat,
+    /// How often the scheduler looks for due `agendamentos`.
+    pub schedule_interval: Duration,
+    /// Fixed-precision timestamp format; `None` keeps `Rfc3339`'s variable
+    /// sub-second digits.
+    pub timestamp_format: Option<OwnedFormatItem>,
+    /// `data_extrato` is truncated to this and formatted once per step per
+    /// worker thread instead of on every statement; zero formats every one.
+    pub statement_time_granularity: Duration,
+    /// Decimal places of the statement's `utilizacao` percentage.
+    pub utilization_precision: u32,
+    /// Token expected in `X-Admin-Token`; admin routes are refused when unset.
+    pub admin_token: Option<String>,
+    /// `Authorization: Bearer` keys and what each may reach; empty leaves
+    /// the API unauthenticated.
+    pub api_keys: HashMap<String, auth::Scope>,
+    /// Requests per second per client address; unlimited when `None`.
+    pub rate_limit_ip: Option<Rate>,
+    /// Requests per second per `/clientes/:id` account, across clients.
+    pub rate_limit_account: Option<Rate>,
+    /// Take the client address from `X-Forwarded-For`, for use behind a
+    /// proxy that sets it; otherwise clients could pick their own bucket.
+    pub rate_limit_forwarded: bool,
+    pub audit_log: Option<PathBuf>,
+    /// Archive of every accepted transaction, for statement exports; see
+    /// `history::Archive`.
+    pub history_file: Option<PathBuf>,
+    /// Event stream balances are derived from on startup; see `ledger::Ledger`.
+    pub ledger_file: Option<PathBuf>,
+    /// Hot accounts whose writes go through a dedicated single-writer task.
+    pub actor_accounts: Vec<AccountId>,
+    /// `BANK_ACTOR_ACCOUNTS=*`: every account, created ones included.
+    pub all_actor_accounts: bool,
+    /// Accounts whose balance may not go negative even within the limit.
+    pub no_overdraft_accounts: Vec<AccountId>,
+    /// The `tipo`s an account may have, each with its limit policy.
+    pub account_types: Vec<AccountType>,
+    /// What a transaction's `categoria` may be.
+    pub categories: Vec<String>,
+    /// Given to seeded accounts and to those created without a `tipo`.
+    pub default_account_type: String,
+    /// Transactions retained per account for `ultimas_transacoes`.
+    pub history_cap: usize,
+    /// Largest credit limit an account may have, in minor units; keeps
+    /// `limit + balance` far from overflowing.
+    pub max_limit: i64,
+    /// Largest `valor` a single transaction may carry, in minor units.
+    pub max_transaction_value: i64,
+    /// Enabled by `BANK_WEBHOOK_URL`, or by `BANK_WEBHOOKS` to start with no
+    /// subscription and take them from `POST /webhooks`.
+    pub webhook: Option<WebhookConfig>,
+    /// `BANK_REPLICATION_ROLE`: this instance as the leader or the follower
+    /// of a pair behind one load balancer.
+    pub replication: Option<ReplicationConfig>,
+    /// Give up waiting for an account's write lock after this long (503).
+    pub lock_timeout: Option<Duration>,
+    /// Exposes `/debug/*`; meant for development and incident diagnosis only.
+    pub debug_endpoints: bool,
+    /// Render transaction `seq` numbers as strings for clients that parse
+    /// JSON numbers as doubles and lose precision above 2^53.
+    pub ids_as_strings: bool,
+    /// Balances and history written on shutdown and loaded on startup.
+    pub snapshot_file: Option<PathBuf>,
+    /// Also snapshot this often while running, truncating the WAL behind it.
+    pub snapshot_interval: Option<Duration>,
+    /// Accepted transactions, appended as they happen; replayed on startup on
+    /// top of the snapshot.
+    pub wal_file: Option<PathBuf>,
+    /// Postgres connection string; needs the `postgres` feature.
+    pub database_url: Option<String>,
+    pub wal_durability: WalDurability,
+    /// Maximum sum of debits per calendar day, in minor units, by account.
+    pub daily_debit_limits: HashMap<AccountId, i64>,
+    /// Where the calendar day of `daily_debit_limits` starts and ends.
+    pub daily_debit_timezone: UtcOffset,
+    /// Largest single debit, in minor units, by account.
+    pub max_debit_values: HashMap<AccountId, i64>,
+    /// Maximum sum of debits within any trailing `rolling_debit_window`, by
+    /// account. Unlike `daily_debit_limits` it never resets at midnight.
+    pub rolling_debit_limits: HashMap<AccountId, i64>,
+    pub rolling_debit_window: Duration,
+    /// Window for folding bursts of credits into one history entry.
+    pub coalesce_credits: Option<Duration>,
+    /// Reuse serialized statements between writes and answer `If-None-Match`.
+    pub statement_cache: bool,
+    /// Rebuild cached statements this old even if the account did not change.
+    pub statement_cache_max_age: Option<Duration>,
+    /// Per-account minimum gap between consecutive transactions.
+    pub min_transaction_interval: Option<Duration>,
+    /// Refuse a transaction identical to the account's previous one within this window.
+    pub dedup_window: Option<Duration>,
+    /// How long an `Idempotency-Key` keeps answering with its first response.
+    pub idempotency_ttl: Duration,
+    /// Wrap JSON responses in `{"data"|"error", "meta"}`.
+    pub response_envelope: bool,
+    /// How long a `reserva` holds funds before it lapses on its own.
+    pub hold_ttl: Duration,
+    /// Account numbers in `/clientes/:id` end in a Luhn check digit.
+    pub account_checksum: bool,
+    /// Transactions slower than this are logged with a `WARN` line.
+    pub slow_transaction: Option<Duration>,
+    /// Reject transaction bodies carrying fields the API does not define.
+    pub strict_json: bool,
+    /// Rounding of derived amounts such as the utilization percentage.
+    pub rounding: Rounding,
+    /// Statement entries older than this are pruned in the background.
+    pub history_retention: Option<Duration>,
+    /// How often the retention task runs.
+    pub retention_interval: Duration,
+    /// History transactions older than this are compacted into gzip
+    /// segments; see `archival`.
+    pub archive_after: Option<Duration>,
+    /// How often the compaction task runs.
+    pub archive_interval: Duration,
+    /// Allocate each account's full history ring at startup.
+    pub preallocate_history: bool,
+    /// Answer unauthorized requests with `403` instead of a uniform `404`.
+    pub precise_auth_errors: bool,
+    /// Limit derived from recent average daily debit instead of the fixed one.
+    pub dynamic_limit: Option<DynamicLimit>,
+    /// Accounts the dynamic limit applies to; all of them when `None`.
+    pub dynamic_limit_accounts: Option<Vec<AccountId>>,
+    /// The shutdown report is also written here, besides stderr.
+    pub shutdown_report: Option<PathBuf>,
+    /// How long in-flight requests get to finish after SIGTERM or Ctrl-C;
+    /// open SSE streams never do, so they are cut when it runs out.
+    pub shutdown_grace: Duration,
+    /// Size and age limits for the audit log and the WAL.
+    pub log_rotation: Rotation,
+    /// Writes are refused with `423` outside these hours.
+    pub business_hours: Option<BusinessHours>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            tls: None,
+            grpc_bind: None,
+            workers: None,
+            accounts: DEFAULT_ACCOUNTS.to_vec(),
+            log_level: None,
+            description: DescriptionPolicy::default(),
+            currency: Currency::default(),
+            account_currencies: HashMap::new(),
+            require_currency: false,
+            amount_format: AmountFormat::default(),
+            schedule_interval: Duration::from_secs(1),
+            timestamp_format: None,
+            statement_time_granularity: Duration::from_millis(1),
+            utilization_precision: 2,
+            admin_token: None,
+            api_keys: HashMap::new(),
+            rate_limit_ip: None,
+            rate_limit_account: None,
+            rate_limit_forwarded: false,
+            audit_log: None,
+            history_file: None,
+            ledger_file: None,
+            actor_accounts: Vec::new(),
+            all_actor_accounts: false,
+            no_overdraft_accounts: Vec::new(),
+            account_types: policy::defaults(),
+            categories: categories::defaults(),
+            default_account_type: AccountType::default().name,
+            history_cap: 10,
+            max_limit: 1_000_000_000_000,
+            max_transaction_value: 1_000_000_000_000,
+            webhook: None,
+            replication: None,
+            lock_timeout: None,
+            debug_endpoints: false,
+            ids_as_strings: false,
+            snapshot_file: None,
+            snapshot_interval: None,
+            wal_file: None,
+            database_url: None,
+            wal_durability: WalDurability::default(),
+            daily_debit_limits: HashMap::new(),
+            daily_debit_timezone: UtcOffset::UTC,
+            max_debit_values: HashMap::new(),
+            rolling_debit_limits: HashMap::new(),
+            rolling_debit_window: Duration::from_secs(24 * 60 * 60),
+            coalesce_credits: None,
+            statement_cache: false,
+            statement_cache_max_age: None,
+            min_transaction_interval: None,
+            dedup_window: None,
+            idempotency_ttl: Duration::from_secs(24 * 60 * 60),
+            response_envelope: false,
+            hold_ttl: Duration::from_secs(7 * 24 * 60 * 60),
+            account_checksum: false,
+            slow_transaction: None,
+            strict_json: false,
+            rounding: Rounding::default(),
+            history_retention: None,
+            retention_interval: Duration::from_secs(60),
+            archive_after: None,
+            archive_interval: Duration::from_secs(60 * 60),
+            preallocate_history: true,
+            precise_auth_errors: false,
+            dynamic_limit: None,
+            dynamic_limit_accounts: None,
+            shutdown_report: None,
+            shutdown_grace: Duration::from_secs(10),
+            log_rotation: Rotation::default(),
+            business_hours: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads `BANK_*` settings from the environment and, with
+    /// `BANK_CONFIG_FILE`, from that file; the environment wins where both
+    /// set a key, and defaults fill in the rest.
+    pub fn load() -> Result<Self, String> {
+        if let Some(path) = env::var("BANK_CONFIG_FILE")
+            .ok()
+            .filter(|path| !path.is_empty())
+        {
+            let values = read_file(&path)
+                .map_err(|reason| format!("BANK_CONFIG_FILE invalido: {path}: {reason}"))?;
+            let _ = FILE.set(File {
+                values,
+                read: Mutex::default(),
+            });
+        }
+        Self::from_env()
+    }
+
+    /// Warns about `BANK_CONFIG_FILE` keys `load` never read. Call once a
+    /// subscriber is installed, which `load` comes before.
+    pub fn warn_unused_keys() {
+        let Some(file) = FILE.get() else {
+            return;
+        };
+        let read = file.read.lock().unwrap();
+        let mut unused: Vec<&str> = file
+            .values
+            .keys()
+            .filter(|key| !read.contains(*key))
+            .map(String::as_str)
+            .collect();
+        if !unused.is_empty() {
+            unused.sort_unstable();
+            tracing::warn!(
+                keys = %unused.join(", "),
+                "config: keys unknown or without effect"
+            );
+        }
+    }
+
+    fn from_env() -> Result<Self, String> {
+        let mut config = Config::default();
+
+        if let Some(bind) = var("BANK_BIND") {
+            config.bind = bind
+                .parse()
+                .map_err(|_| format!("BANK_BIND invalido: {bind}"))?;
+        }
+
+        match (var("BANK_TLS_CERT"), var("BANK_TLS_KEY")) {
+            (None, None) => {
+                if var("BANK_TLS_REDIRECT_BIND").is_some() {
+                    return Err("BANK_TLS_REDIRECT_BIND requer BANK_TLS_CERT".to_string());
+                }
+            }
+            (Some(cert), Some(key)) => {
+                if !cfg!(feature = "tls") {
+                    return Err("BANK_TLS_CERT requer o recurso tls".to_string());
+                }
+                let redirect = match var("BANK_TLS_REDIRECT_BIND") {
+                    None => None,
+                    Some(redirect) => Some(
+                        redirect
+                            .parse()
+                            .map_err(|_| format!("BANK_TLS_REDIRECT_BIND invalido: {redirect}"))?,
+                    ),
+                };
+                config.tls = Some(TlsConfig {
+                    cert: PathBuf::from(cert),
+                    key: PathBuf::from(key),
+                    redirect,
+                });
+            }
+            (Some(_), None) => return Err("BANK_TLS_CERT requer BANK_TLS_KEY".to_string()),
+            (None, Some(_)) => return Err("BANK_TLS_KEY requer BANK_TLS_CERT".to_string()),
+        }
+
+        if let Some(bind) = var("BANK_GRPC_BIND") {
+            if !cfg!(feature = "grpc") {
+                return Err("BANK_GRPC_BIND requer o recurso grpc".to_string());
+            }
+            config.grpc_bind = Some(
+                bind.parse()
+                    .map_err(|_| format!("BANK_GRPC_BIND invalido: {bind}"))?,
+            );
+        }
+
+        if let Some(workers) = var("BANK_WORKERS") {
+            config.workers = match workers.parse() {
+                Ok(workers) if workers >= 1 => Some(workers),
+                _ => return Err(format!("BANK_WORKERS invalido: {workers}")),
+            };
+        }
+
+        config.log_level = parse("BANK_LOG_LEVEL")?;
+
+        // `1:100000,2:80000`; ids and limits are checked when seeding.
+        if let Some(accounts) = var("BANK_ACCOUNTS") {
+            config.accounts = accounts
+                .split(',')
+                .map(|pair| {
+                    let (id, limit) = pair.split_once(':')?;
+                    Some((id.trim().parse().ok()?, limit.trim().parse().ok()?))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("BANK_ACCOUNTS invalido: {accounts}"))?;
+        }
+
+        if let Some(unit) = var("BANK_DESCRIPTION_UNIT") {
+            config.description.unit = match unit.as_str() {
+                "chars" => LengthUnit::Chars,
+                "bytes" => LengthUnit::Bytes,
+                other => return Err(format!("BANK_DESCRIPTION_UNIT invalido: {other}")),
+            };
+        }
+
+        if let Some(trim) = parse("BANK_DESCRIPTION_TRIM")? {
+            config.description.trim = trim;
+        }
+
+        // One term per line; blank lines and `#` comments are skipped.
+        if let Some(path) = var("BANK_DESCRIPTION_BLOCKLIST") {
+            let terms = std::fs::read_to_string(&path)
+                .map_err(|err| format!("BANK_DESCRIPTION_BLOCKLIST invalido: {path}: {err}"))?;
+            config.description.blocklist = terms
+                .lines()
+                .map(str::trim)
+                .filter(|term| !term.is_empty() && !term.starts_with('#'))
+                .map(fold)
+                .collect();
+        }
+
+        if let Some(code) = var("BANK_CURRENCY") {
+            config.currency = Currency::from_code(&code)
+                .ok_or_else(|| format!("BANK_CURRENCY invalido: {code}"))?;
+        }
+        if let Some(value) = var("BANK_ACCOUNT_CURRENCIES") {
+            config.account_currencies = account_currencies(&value, config.currency)
+                .ok_or_else(|| format!("BANK_ACCOUNT_CURRENCIES invalido: {value}"))?;
+        }
+        config.require_currency = parse("BANK_REQUIRE_CURRENCY")?.unwrap_or(false);
+
+        if let Some(format) = var("BANK_AMOUNT_FORMAT") {
+            config.amount_format = match format.as_str() {
+                "minor" => AmountFormat::Minor,
+                "decimal" => AmountFormat::Decimal,
+                other => return Err(format!("BANK_AMOUNT_FORMAT invalido: {other}")),
+            };
+        }
+
+        if let Some(name) = var("BANK_ROUNDING") {
+            config.rounding = Rounding::from_name(&name)
+                .ok_or_else(|| format!("BANK_ROUNDING invalido: {name}"))?;
+        }
+
+        if let Some(millis) = var("BANK_SCHEDULE_INTERVAL_MS") {
+            config.schedule_interval = match millis.parse() {
+                Ok(millis) if millis > 0 => Duration::from_millis(millis),
+                _ => return Err(format!("BANK_SCHEDULE_INTERVAL_MS invalido: {millis}")),
+            };
+        }
+
+        if let Some(digits) = var("BANK_TIMESTAMP_PRECISION") {
+            config.timestamp_format = match digits.parse::<u8>() {
+                Ok(digits) if digits <= 9 => Some(fixed_precision_format(digits)),
+                _ => return Err(format!("BANK_TIMESTAMP_PRECISION invalido: {digits}")),
+            };
+        }
+
+        if let Some(millis) = parse::<u64>("BANK_STATEMENT_TIME_GRANULARITY_MS")? {
+            config.statement_time_granularity = Duration::from_millis(millis);
+        }
+
+        if let Some(digits) = var("BANK_UTILIZATION_PRECISION") {
+            config.utilization_precision = match digits.parse() {
+                Ok(digits) if digits <= 6 => digits,
+                _ => return Err(format!("BANK_UTILIZATION_PRECISION invalido: {digits}")),
+            };
+        }
+
+        config.admin_token = var("BANK_ADMIN_TOKEN");
+        if let Some(keys) = var("BANK_API_KEYS") {
+            // Secrets: the error names the variable, not the value.
+            config.api_keys =
+                auth::parse_keys(&keys).ok_or_else(|| "BANK_API_KEYS invalido".to_string())?;
+        }
+        config.rate_limit_ip = rate("BANK_RATE_LIMIT_IP")?;
+        config.rate_limit_account = rate("BANK_RATE_LIMIT_ACCOUNT")?;
+        config.rate_limit_forwarded = parse("BANK_RATE_LIMIT_FORWARDED")?.unwrap_or(false);
+        config.audit_log = var("BANK_AUDIT_LOG").map(PathBuf::from);
+        config.history_file = var("BANK_HISTORY_FILE").map(PathBuf::from);
+        config.ledger_file = var("BANK_LEDGER_FILE").map(PathBuf::from);
+
+        if var("BANK_ACTOR_ACCOUNTS").as_deref() == Some("*") {
+            config.all_actor_accounts = true;
+        } else if let Some(ids) = account_list("BANK_ACTOR_ACCOUNTS")? {
+            config.actor_accounts = ids;
+        }
+
+        if let Some(ids) = account_list("BANK_NO_OVERDRAFT_ACCOUNTS")? {
+            config.no_overdraft_accounts = ids;
+        }
+        if let Some(value) = var("BANK_ACCOUNT_TYPES") {
+            config.account_types = policy::parse_types(&value)
+                .ok_or_else(|| format!("BANK_ACCOUNT_TYPES invalido: {value}"))?;
+        }
+        if let Some(value) = var("BANK_CATEGORIES") {
+            config.categories = categories::parse(&value)
+                .ok_or_else(|| format!("BANK_CATEGORIES invalido: {value}"))?;
+        }
+        if let Some(name) = var("BANK_DEFAULT_ACCOUNT_TYPE") {
+            config.default_account_type = name;
+        }
+        if config.account_type(&config.default_account_type).is_none() {
+            return Err(format!(
+                "BANK_DEFAULT_ACCOUNT_TYPE invalido: {}",
+                config.default_account_type
+            ));
+        }
+
+        if let Some(cap) = var("BANK_HISTORY_CAP") {
+            config.history_cap = match cap.parse() {
+                Ok(cap) if cap >= 1 => cap,
+                _ => return Err(format!("BANK_HISTORY_CAP invalido: {cap}")),
+            };
+        }
+
+        if let Some(max) = var("BANK_MAX_LIMIT") {
+            config.max_limit = match max.parse() {
+                Ok(max) if max >= 0 => max,
+                _ => return Err(format!("BANK_MAX_LIMIT invalido: {max}")),
+            };
+        }
+        if let Some(max) = var("BANK_MAX_TRANSACTION_VALUE") {
+            config.max_transaction_value = match max.parse() {
+                Ok(max) if max > 0 => max,
+                _ => return Err(format!("BANK_MAX_TRANSACTION_VALUE invalido: {max}")),
+            };
+        }
+
+        let url = var("BANK_WEBHOOK_URL");
+        if url.is_some() || parse("BANK_WEBHOOKS")?.unwrap_or(false) {
+            config.webhook = Some(WebhookConfig {
+                url,
+                batch_size: parse("BANK_WEBHOOK_BATCH_SIZE")?.unwrap_or(20).max(1),
+                batch_window: Duration::from_millis(
+                    parse("BANK_WEBHOOK_BATCH_WINDOW_MS")?.unwrap_or(200),
+                ),
+                max_retries: parse("BANK_WEBHOOK_MAX_RETRIES")?.unwrap_or(3),
+                dlq_file: var("BANK_WEBHOOK_DLQ_FILE").map(PathBuf::from),
+            });
+        }
+
+        if let Some(role) = var("BANK_REPLICATION_ROLE") {
+            let role = match role.as_str() {
+                "leader" => Role::Leader,
+                "follower" => Role::Follower,
+                _ => return Err(format!("BANK_REPLICATION_ROLE invalido: {role}")),
+            };
+            let peer = var("BANK_REPLICATION_PEER").unwrap_or_default();
+            if !peer.starts_with("http://") {
+                return Err(format!("BANK_REPLICATION_PEER invalido: {peer}"));
+            }
+            // The endpoint overwrites accounts: it is never left open.
+            let token = var("BANK_REPLICATION_TOKEN")
+                .ok_or("BANK_REPLICATION_ROLE requer BANK_REPLICATION_TOKEN")?;
+            config.replication = Some(ReplicationConfig {
+                role,
+                peer,
+                token,
+                wait: Duration::from_millis(parse("BANK_REPLICATION_WAIT_MS")?.unwrap_or(100)),
+            });
+        }
+
+        config.lock_timeout = parse("BANK_LOCK_TIMEOUT_MS")?.map(Duration::from_millis);
+        config.debug_endpoints = parse("BANK_DEBUG_ENDPOINTS")?.unwrap_or(false);
+        config.ids_as_strings = parse("BANK_IDS_AS_STRINGS")?.unwrap_or(false);
+
+        config.log_rotation = Rotation {
+            max_bytes: parse("BANK_LOG_MAX_BYTES")?.filter(|bytes| *bytes > 0),
+            max_age: parse("BANK_LOG_MAX_AGE_MS")?
+                .filter(|millis| *millis > 0)
+                .map(Duration::from_millis),
+        };
+        config.shutdown_report = var("BANK_SHUTDOWN_REPORT").map(PathBuf::from);
+        if let Some(millis) = parse::<u64>("BANK_SHUTDOWN_GRACE_MS")? {
+            config.shutdown_grace = Duration::from_millis(millis);
+        }
+        config.snapshot_file = var("BANK_SNAPSHOT_FILE").map(PathBuf::from);
+        config.snapshot_interval = match parse("BANK_SNAPSHOT_INTERVAL_MS")? {
+            Some(0) | None => None,
+            Some(_) if config.snapshot_file.is_none() => {
+                return Err("BANK_SNAPSHOT_INTERVAL_MS requer BANK_SNAPSHOT_FILE".to_string())
+            }
+            Some(ms) => Some(Duration::from_millis(ms)),
+        };
+        config.wal_file = var("BANK_WAL_FILE").map(PathBuf::from);
+        config.database_url = var("BANK_DATABASE_URL");
+        if let Some(level) = var("BANK_WAL_DURABILITY") {
+            config.wal_durability = match level.as_str() {
+                "none" => WalDurability::None,
+                "strict" => WalDurability::Strict,
+                "batch" => WalDurability::Batch {
+                    interval: Duration::from_millis(
+                        parse("BANK_WAL_FSYNC_INTERVAL_MS")?.unwrap_or(100).max(1),
+                    ),
+                    entries: parse("BANK_WAL_FSYNC_ENTRIES")?.unwrap_or(100).max(1),
+                },
+                other => return Err(format!("BANK_WAL_DURABILITY invalido: {other}")),
+            };
+        }
+
+        if let Some(limits) = amounts("BANK_DAILY_DEBIT_LIMITS")? {
+            config.daily_debit_limits = limits;
+        }
+        if let Some(tz) = var("BANK_DAILY_DEBIT_TZ") {
+            config.daily_debit_timezone = activity::parse_offset(&tz)
+                .ok_or_else(|| format!("BANK_DAILY_DEBIT_TZ invalido: {tz}"))?;
+        }
+        if let Some(values) = amounts("BANK_MAX_DEBIT_VALUES")? {
+            config.max_debit_values = values;
+        }
+        if let Some(limits) = amounts("BANK_ROLLING_DEBIT_LIMITS")? {
+            config.rolling_debit_limits = limits;
+        }
+        if let Some(millis) = parse::<u64>("BANK_ROLLING_DEBIT_WINDOW_MS")? {
+            config.rolling_debit_window = Duration::from_millis(millis.max(1));
+        }
+
+        config.coalesce_credits = match parse("BANK_COALESCE_CREDITS_MS")? {
+            Some(0) | None => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+        };
+
+        config.statement_cache = parse("BANK_STATEMENT_CACHE")?.unwrap_or(false);
+        config.statement_cache_max_age =
+            parse("BANK_STATEMENT_CACHE_MAX_AGE_MS")?.map(Duration::from_millis);
+
+        config.min_transaction_interval =
+            parse("BANK_MIN_TRANSACTION_INTERVAL_MS")?.map(Duration::from_millis);
+
+        config.dedup_window = match parse("BANK_DEDUP_WINDOW_MS")? {
+            Some(0) | None => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+        };
+
+        if let Some(ttl) = parse::<u64>("BANK_IDEMPOTENCY_TTL_MS")? {
+            if ttl == 0 {
+                return Err(format!("BANK_IDEMPOTENCY_TTL_MS invalido: {ttl}"));
+            }
+            config.idempotency_ttl = Duration::from_millis(ttl);
+        }
+
+        config.response_envelope = parse("BANK_RESPONSE_ENVELOPE")?.unwrap_or(false);
+
+        if let Some(ttl) = parse::<u64>("BANK_HOLD_TTL_MS")? {
+            if ttl == 0 {
+                return Err(format!("BANK_HOLD_TTL_MS invalido: {ttl}"));
+            }
+            config.hold_ttl = Duration::from_millis(ttl);
+        }
+
+        config.account_checksum = parse("BANK_ACCOUNT_CHECKSUM")?.unwrap_or(false);
+
+        config.slow_transaction = parse("BANK_SLOW_TRANSACTION_MS")?.map(Duration::from_millis);
+
+        config.strict_json = parse("BANK_STRICT_JSON")?.unwrap_or(false);
+
+        config.history_retention = parse("BANK_HISTORY_RETENTION_MS")?.map(Duration::from_millis);
+        if let Some(millis) = parse::<u64>("BANK_RETENTION_INTERVAL_MS")? {
+            if millis == 0 {
+                return Err(format!("BANK_RETENTION_INTERVAL_MS invalido: {millis}"));
+            }
+            config.retention_interval = Duration::from_millis(millis);
+        }
+
+        config.archive_after = parse("BANK_ARCHIVE_AFTER_MS")?.map(Duration::from_millis);
+        if config.archive_after.is_some() && config.history_file.is_none() {
+            return Err("BANK_ARCHIVE_AFTER_MS requer BANK_HISTORY_FILE".to_string());
+        }
+        if config.archive_after.is_some() && !cfg!(feature = "archive") {
+            return Err("BANK_ARCHIVE_AFTER_MS requer o recurso archive".to_string());
+        }
+        if let Some(millis) = parse::<u64>("BANK_ARCHIVE_INTERVAL_MS")? {
+            if millis == 0 {
+                return Err(format!("BANK_ARCHIVE_INTERVAL_MS invalido: {millis}"));
+            }
+            config.archive_interval = Duration::from_millis(millis);
+        }
+
+        config.preallocate_history = parse("BANK_PREALLOCATE_HISTORY")?.unwrap_or(true);
+
+        config.precise_auth_errors = parse("BANK_PRECISE_AUTH_ERRORS")?.unwrap_or(false);
+
+        if let Some(factor) = var("BANK_DYNAMIC_LIMIT_FACTOR") {
+            let hundredths = match factor.parse::<f64>() {
+                Ok(value) if value > 0.0 && value <= 1000.0 => (value * 100.0).round() as i64,
+                _ => return Err(format!("BANK_DYNAMIC_LIMIT_FACTOR invalido: {factor}")),
+            };
+            config.dynamic_limit = Some(DynamicLimit {
+                factor_hundredths: hundredths,
+                min_debits: parse("BANK_DYNAMIC_LIMIT_MIN_DEBITS")?.unwrap_or(5),
+                rounding: config.rounding,
+            });
+            config.dynamic_limit_accounts = account_list("BANK_DYNAMIC_LIMIT_ACCOUNTS")?;
+        }
+
+        // `09:00-12:00,13:00-18:00` on `1-5` (Monday to Friday), every day
+        // when `BANK_BUSINESS_DAYS` is unset.
+        if let Some(ranges) = var("BANK_BUSINESS_HOURS") {
+            let days = var("BANK_BUSINESS_DAYS").unwrap_or_else(|| "1-7".to_string());
+            let offset = match var("BANK_BUSINESS_TZ") {
+                Some(tz) => activity::parse_offset(&tz)
+                    .ok_or_else(|| format!("BANK_BUSINESS_TZ invalido: {tz}"))?,
+                None => UtcOffset::UTC,
+            };
+            config.business_hours = Some(BusinessHours {
+                days: business_hours::parse_days(&days)
+                    .ok_or_else(|| format!("BANK_BUSINESS_DAYS invalido: {days}"))?,
+                ranges: business_hours::parse_ranges(&ranges)
+                    .ok_or_else(|| format!("BANK_BUSINESS_HOURS invalido: {ranges}"))?,
+                offset,
+            });
+        }
+
+        Ok(config)
+    }
+
+    pub fn timestamp(&self, at: OffsetDateTime) -> String {
+        match &self.timestamp_format {
+            Some(format) => at.to_offset(UtcOffset::UTC).format(format).unwrap(),
+            None => at.format(&Rfc3339).unwrap(),
+        }
+    }
+
+    /// `timestamp` for typed response bodies, formatted straight into the
+    /// output rather than into a `String` first.
+    pub fn typed_timestamp(&self, at: OffsetDateTime) -> Timestamp<'_> {
+        Timestamp(self, at)
+    }
+
+    /// `timestamp` of `now` truncated to `statement_time_granularity`, the
+    /// last one this thread formatted while still in the same step.
+    pub fn coarse_timestamp(&self, now: OffsetDateTime) -> String {
+        let step = self.statement_time_granularity.as_nanos() as i128;
+        if step == 0 {
+            return self.timestamp(now);
+        }
+        let bucket = now.unix_timestamp_nanos().div_euclid(step);
+        let key = (self as *const Config as usize, step, bucket);
+        COARSE_TIMESTAMP.with(|cached| {
+            let mut cached = cached.borrow_mut();
+            if cached.0 != Some(key) {
+                let at = OffsetDateTime::from_unix_timestamp_nanos(bucket * step)
+                    .map_or(now, |at| at.to_offset(now.offset()));
+                *cached = (Some(key), self.timestamp(at));
+            }
+            cached.1.clone()
+        })
+    }
+
+    /// Whether the account's writes go through an actor queue.
+    pub fn is_actor(&self, id: AccountId) -> bool {
+        self.all_actor_accounts || self.actor_accounts.contains(&id)
+    }
+
+    /// Writes go to the leader; this instance only applies what it ships.
+    pub fn is_follower(&self) -> bool {
+        self.replication
+            .as_ref()
+            .is_some_and(|replication| replication.role == Role::Follower)
+    }
+
+    pub fn account_type(&self, name: &str) -> Option<AccountType> {
+        self.account_types
+            .iter()
+            .find(|account_type| account_type.name == name)
+            .cloned()
+    }
+
+    /// `default_account_type`, which `from_env` has checked is configured.
+    pub fn default_account_type(&self) -> AccountType {
+        self.account_type(&self.default_account_type)
+            .unwrap_or_default()
+    }
+
+    pub fn formatter(&self) -> Formatter {
+        Formatter {
+            currency: self.currency,
+            amount_format: self.amount_format,
+            utilization_precision: self.utilization_precision,
+            rounding: self.rounding,
+        }
+    }
+
+    pub fn amount(&self, minor: i64) -> serde_json::Value {
+        self.formatter().amount(minor)
+    }
+
+    /// A transaction `seq` as a number, or a string with `ids_as_strings`.
+    pub fn seq(&self, seq: u64) -> Seq {
+        Seq(seq, self.ids_as_strings)
+    }
+}
+
+/// RFC3339 in UTC with exactly `digits` fractional second digits (none for 0).
+fn fixed_precision_format(digits: u8) -> OwnedFormatItem {
+    let fraction = match digits {
+        0 => String::new(),
+        digits => format!(".[subsecond digits:{digits}]"),
+    };
+    format_description::parse_owned::<2>(&format!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second]{fraction}Z"
+    ))
+    .expect("static timestamp format is valid")
+}
+
+/// `BANK_CONFIG_FILE` contents, plus which keys the config asked for.
+struct File {
+    values: HashMap<String, String>,
+    read: Mutex<HashSet<String>>,
+}
+
+static FILE: OnceLock<File> = OnceLock::new();
+
+fn var(name: &str) -> Option<String> {
+    let file = FILE.get().and_then(|file| {
+        file.read.lock().unwrap().insert(name.to_string());
+        file.values.get(name).cloned()
+    });
+    env::var(name)
+        .ok()
+        .or(file)
+        .filter(|value| !value.is_empty())
+}
+
+/// The file is TOML restricted to one flat table of the environment's own
+/// names, so any setting can move between the two unchanged:
+///
+/// ```toml
+/// BANK_BIND = "0.0.0.0:3000"
+/// BANK_ACCOUNTS = "1:100000,2:80000"
+/// BANK_HISTORY_CAP = 50
+/// BANK_STRICT_JSON = true
+/// ```
+///
+/// Values are basic strings, integers, floats or booleans; tables and arrays
+/// are refused rather than half-read.
+fn read_file(path: &str) -> Result<HashMap<String, String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut values = HashMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = index + 1;
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("linha {number}: esperado CHAVE = valor"))?;
+        let key = key.trim();
+        if !key.starts_with("BANK_")
+            || !key
+                .bytes()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || byte == b'_')
+        {
+            return Err(format!("linha {number}: chave invalida: {key}"));
+        }
+        let value = toml_value(value.trim())
+            .ok_or_else(|| format!("linha {number}: valor invalido para {key}"))?;
+        if values.insert(key.to_string(), value).is_some() {
+            return Err(format!("linha {number}: chave repetida: {key}"));
+        }
+    }
+    Ok(values)
+}
+
+/// A string's contents, or a bare scalar as written. Trailing `#` comments
+/// are dropped.
+fn toml_value(raw: &str) -> Option<String> {
+    let Some(quoted) = raw.strip_prefix('"') else {
+        let bare = raw.split('#').next()?.trim();
+        let scalar =
+            bare == "true" || bare == "false" || bare.replace('_', "").parse::<f64>().is_ok();
+        return scalar.then(|| bare.replace('_', ""));
+    };
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => value.push(match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                _ => return None,
+            }),
+            other => value.push(other),
+        }
+    }
+    let rest = chars.as_str().trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+/// `conta:moedas` pairs separated by `;`, e.g. `3:USD,EUR;4:EUR`. Naming
+/// the base currency again is pointless, so it is refused.
+fn account_currencies(value: &str, base: Currency) -> Option<HashMap<AccountId, Vec<Currency>>> {
+    value
+        .split(';')
+        .map(|pair| {
+            let (id, codes) = pair.split_once(':')?;
+            let currencies = codes
+                .split(',')
+                .map(|code| Currency::from_code(code.trim()).filter(|currency| *currency != base))
+                .collect::<Option<_>>()?;
+            Some((id.trim().parse().ok()?, currencies))
+        })
+        .collect()
+}
+
+/// Which config, step and step number `COARSE_TIMESTAMP` was formatted for.
+type CoarseKey = (usize, i128, i128);
+
+thread_local! {
+    /// Per thread, so statements on different workers never contend. Keyed
+    /// by the config too, for processes (tests) that run several.
+    static COARSE_TIMESTAMP: RefCell<(Option<CoarseKey>, String)> =
+        const { RefCell::new((None, String::new())) };
+}
+
+/// See `Config::typed_timestamp`.
+pub struct Timestamp<'a>(&'a Config, OffsetDateTime);
+
+impl Serialize for Timestamp<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // RFC 3339 with nanoseconds and an offset fits in well under 64 bytes.
+        let mut buffer = [0u8; 64];
+        let mut out = &mut buffer[..];
+        let written = match &self.0.timestamp_format {
+            Some(format) => self
+                .1
+                .to_offset(UtcOffset::UTC)
+                .format_into(&mut out, format),
+            None => self.1.format_into(&mut out, &Rfc3339),
+        };
+        // What is left of `out` says how much was written; the count
+        // `format_into` returns has not always included the subsecond digits.
+        let length = 64 - out.len();
+        match written {
+            Ok(_) => serializer.serialize_str(
+                std::str::from_utf8(&buffer[..length]).map_err(serde::ser::Error::custom)?,
+            ),
+            Err(_) => serializer.serialize_str(&self.0.timestamp(self.1)),
+        }
+    }
+}
+
+/// See `Config::seq`.
+#[derive(Clone, Copy)]
+pub struct Seq(u64, bool);
+
+impl Serialize for Seq {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Seq(seq, true) => serializer.collect_str(seq),
+            Seq(seq, false) => serializer.serialize_u64(*seq),
+        }
+    }
+}
+
+/// Comma-separated account ids, e.g. `1,3`.
+fn account_list(name: &str) -> Result<Option<Vec<AccountId>>, String> {
+    var(name)
+        .map(|ids| {
+            ids.split(',')
+                .map(|id| id.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("{name} invalido: {ids}"))
+        })
+        .transpose()
+}
+
+/// Non-negative amounts by account id, e.g. `1:50000,3:200000`.
+fn amounts(name: &str) -> Result<Option<HashMap<AccountId, i64>>, String> {
+    var(name)
+        .map(|pairs| {
+            pairs
+                .split(',')
+                .map(|pair| {
+                    let (id, amount) = pair.split_once(':')?;
+                    let amount = amount.trim().parse().ok().filter(|amount| *amount >= 0)?;
+                    Some((id.trim().parse().ok()?, amount))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| format!("{name} invalido: {pairs}"))
+        })
+        .transpose()
+}
+
+/// `{name}` requests per second, with `{name}_BURST` at once (default: one
+/// second's worth, at least one request).
+fn rate(name: &str) -> Result<Option<Rate>, String> {
+    let Some(rate) = parse::<f64>(name)? else {
+        return Ok(None);
+    };
+    if !(rate > 0.0 && rate.is_finite()) {
+        return Err(format!("{name} invalido: {rate}"));
+    }
+    let burst_name = format!("{name}_BURST");
+    let burst = match parse::<f64>(&burst_name)? {
+        Some(burst) if burst >= 1.0 && burst.is_finite() => burst,
+        Some(burst) => return Err(format!("{burst_name} invalido: {burst}")),
+        None => rate.max(1.0),
+    };
+    Ok(Some(Rate { rate, burst }))
+}
+
+fn parse<T: FromStr>(name: &str) -> Result<Option<T>, String> {
+    var(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("{name} invalido: {value}"))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn fraction(config: &Config, at: OffsetDateTime) -> String {
+        let stamp = config.timestamp(at);
+        let seconds = stamp.strip_suffix('Z').unwrap().rsplit(':').next().unwrap();
+        seconds
+            .split_once('.')
+            .map_or(String::new(), |(_, fraction)| fraction.to_string())
+    }
+
+    #[test]
+    fn fixed_precision_has_exactly_that_many_digits() {
+        // Trailing zeros included: a variable format would drop them.
+        let at = datetime!(2024-03-01 12:00:00.120 UTC);
+        for digits in [0, 3, 6, 9] {
+            let config = Config {
+                timestamp_format: Some(fixed_precision_format(digits)),
+                ..Config::default()
+            };
+            assert_eq!(fraction(&config, at).len(), usize::from(digits));
+        }
+        let config = Config {
+            timestamp_format: Some(fixed_precision_format(3)),
+            ..Config::default()
+        };
+        assert_eq!(config.timestamp(at), "2024-03-01T12:00:00.120Z");
+    }
+}
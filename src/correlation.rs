@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{ulid::Ulid, AppState};
+
+pub const HEADER: &str = "x-correlation-id";
+
+/// Longest client-supplied id kept; anything else gets a fresh one.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: Arc<str>;
+}
+
+/// Outermost layer: takes the caller's `X-Correlation-Id` or mints a ULID,
+/// makes it `current()` for everything the request does on its task (logs,
+/// audit entries, webhook events) and echoes it on the response.
+pub async fn propagate(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let id: Arc<str> = match request
+        .headers()
+        .get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| valid(id))
+    {
+        Some(id) => id.into(),
+        None => Ulid::next_after(Ulid::NIL, state.clock.now())
+            .to_string()
+            .into(),
+    };
+    let mut response = CURRENT.scope(id.clone(), next.run(request)).await;
+    // `valid` only lets through visible ASCII, so this cannot fail.
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(HEADER, value);
+    }
+    response
+}
+
+fn valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// The id of the request being served on this task, if any.
+pub fn current() -> Option<Arc<str>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Runs `f` as part of request `id`, for work handed to another task such
+/// as an account actor.
+pub fn within<R>(id: Option<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    match id {
+        Some(id) => CURRENT.sync_scope(id, f),
+        None => f(),
+    }
+}
@@ -0,0 +1,448 @@
+use std::fmt;
+
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Currency {
+    #[default]
+    Brl,
+    Usd,
+    Eur,
+    Jpy,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 4] = [Currency::Brl, Currency::Usd, Currency::Eur, Currency::Jpy];
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "BRL" => Some(Currency::Brl),
+            "USD" => Some(Currency::Usd),
+            "EUR" => Some(Currency::Eur),
+            "JPY" => Some(Currency::Jpy),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Currency::Brl => "BRL",
+            Currency::Usd => "USD",
+            Currency::Eur => "EUR",
+            Currency::Jpy => "JPY",
+        }
+    }
+
+    /// Number of decimal places between the major unit and the stored minor unit.
+    pub fn minor_units(self) -> u32 {
+        match self {
+            Currency::Jpy => 0,
+            Currency::Brl | Currency::Usd | Currency::Eur => 2,
+        }
+    }
+
+    /// Renders minor units as a decimal string, e.g. `95000` BRL as `"950.00"`
+    /// and `-5` as `"-0.05"`. Only amounts below zero carry a sign.
+    pub fn format(self, minor: i64) -> String {
+        let exponent = self.minor_units();
+        if exponent == 0 {
+            return minor.to_string();
+        }
+
+        let scale = 10u64.pow(exponent);
+        let sign = if minor < 0 { "-" } else { "" };
+        let abs = minor.unsigned_abs();
+        format!(
+            "{sign}{}.{:0width$}",
+            abs / scale,
+            abs % scale,
+            width = exponent as usize
+        )
+    }
+
+    /// Parses a decimal string in major units into minor units, rejecting more
+    /// fractional digits than the currency has (any fraction at all for JPY).
+    pub fn parse(self, input: &str) -> Result<i64, &'static str> {
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let (whole, fraction) = match input.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (input, None),
+        };
+        if !digits(whole) || fraction.is_some_and(|f| !digits(f)) {
+            return Err("valor invalido");
+        }
+
+        let fraction = fraction.unwrap_or("");
+        let exponent = self.minor_units();
+        if fraction.len() > exponent as usize {
+            return Err("valor com casas decimais invalidas para a moeda");
+        }
+
+        let padded = format!("{whole}{fraction:0<width$}", width = exponent as usize);
+        padded.parse().map_err(|_| "valor invalido")
+    }
+}
+
+/// By ISO code, as in the WAL, snapshots and `moeda`.
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Currency::from_code(&code)
+            .ok_or_else(|| de::Error::custom(format!("unknown currency {code}")))
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum AmountFormat {
+    /// Integer minor units (centavos), the historical wire format.
+    #[default]
+    Minor,
+    /// Decimal strings in major units, using the currency's minor-unit exponent.
+    Decimal,
+}
+
+/// How a derived amount with a fractional part is brought back to an integer.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    HalfUp,
+    /// Banker's rounding: ties go to the even neighbour.
+    #[default]
+    HalfEven,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "half_up" => Some(Rounding::HalfUp),
+            "half_even" => Some(Rounding::HalfEven),
+            "floor" => Some(Rounding::Floor),
+            "ceil" => Some(Rounding::Ceil),
+            _ => None,
+        }
+    }
+
+    /// `numerator / denominator` rounded by this policy. Every derived amount
+    /// goes through here so the direction is the same everywhere.
+    pub fn divide(self, numerator: i128, denominator: i128) -> i128 {
+        let (numerator, denominator) = if denominator < 0 {
+            (-numerator, -denominator)
+        } else {
+            (numerator, denominator)
+        };
+        let floor = numerator.div_euclid(denominator);
+        let remainder = numerator.rem_euclid(denominator);
+        if remainder == 0 {
+            return floor;
+        }
+        let round_up = match self {
+            Rounding::Floor => false,
+            Rounding::Ceil => true,
+            Rounding::HalfUp => remainder * 2 >= denominator,
+            Rounding::HalfEven => match (remainder * 2).cmp(&denominator) {
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Equal => floor % 2 != 0,
+            },
+        };
+        floor + i128::from(round_up)
+    }
+}
+
+/// The one place amounts and percentages get their wire representation.
+/// Handlers take it from `Config::formatter` instead of combining currency,
+/// amount format and precision themselves.
+#[derive(Clone, Copy, Debug)]
+pub struct Formatter {
+    pub currency: Currency,
+    pub amount_format: AmountFormat,
+    pub utilization_precision: u32,
+    pub rounding: Rounding,
+}
+
+impl Formatter {
+    /// The same formatting for amounts held in `currency`.
+    pub fn in_currency(self, currency: Currency) -> Self {
+        Self { currency, ..self }
+    }
+
+    /// `minor` in the configured `AmountFormat`. Every amount in a response
+    /// goes through here, with one sign convention: a negative `saldo` is
+    /// owed to the bank, positive is the client's money. Amounts are integer
+    /// minor units end to end, so a balance that nets to zero is `0` (or
+    /// `"0.00"`), never `-0`.
+    pub fn amount(self, minor: i64) -> Value {
+        match self.amount_format {
+            AmountFormat::Minor => Value::from(minor),
+            AmountFormat::Decimal => Value::from(self.currency.format(minor)),
+        }
+    }
+
+    /// `amount` for typed response bodies: serialized as it is written, with
+    /// no `Value` built in between.
+    pub fn typed(self, minor: i64) -> Amount {
+        Amount(self, minor)
+    }
+
+    /// Major units for people to read, whatever the wire format is.
+    pub fn display(self, minor: i64) -> String {
+        self.currency.format(minor)
+    }
+
+    /// How much of `limit` a negative `balance` uses, as a percentage string.
+    pub fn utilization(self, balance: i64, limit: i64) -> Option<String> {
+        percentage(
+            balance.min(0).unsigned_abs(),
+            limit,
+            self.utilization_precision,
+            self.rounding,
+        )
+    }
+}
+
+/// See `Formatter::typed`.
+#[derive(Clone, Copy)]
+pub struct Amount(Formatter, i64);
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Amount(formatter, minor) = *self;
+        match formatter.amount_format {
+            AmountFormat::Minor => serializer.serialize_i64(minor),
+            AmountFormat::Decimal => serializer.serialize_str(&formatter.currency.format(minor)),
+        }
+    }
+}
+
+/// `part / whole` as a percentage string with exactly `precision` decimals,
+/// rounded by `rounding` with integer math. `None` when `whole` is not positive.
+fn percentage(part: u64, whole: i64, precision: u32, rounding: Rounding) -> Option<String> {
+    if whole <= 0 {
+        return None;
+    }
+
+    let scale = 10i128.pow(precision);
+    let scaled = rounding.divide(i128::from(part) * 100 * scale, i128::from(whole));
+    if precision == 0 {
+        return Some(scaled.to_string());
+    }
+    Some(format!(
+        "{}.{:0width$}",
+        scaled / scale,
+        scaled % scale,
+        width = precision as usize
+    ))
+}
+
+pub const OUT_OF_RANGE: &str =
+    "valor fora do intervalo suportado (-9223372036854775808 a 9223372036854775807)";
+pub const NOT_INTEGER: &str = "valor deve ser um inteiro em centavos";
+pub const UNKNOWN: &str = "moeda desconhecida";
+pub const REQUIRED: &str = "moeda obrigatoria";
+
+/// `valor` as sent by clients: integer minor units or a decimal string in major units.
+/// JSON numbers that are not an `i64` are kept as `Invalid` instead of failing
+/// the whole body, so the handler can answer with the reason.
+pub enum AmountInput {
+    Minor(i64),
+    Decimal(String),
+    Invalid(&'static str),
+}
+
+impl<'de> Deserialize<'de> for AmountInput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AmountVisitor;
+
+        impl Visitor<'_> for AmountVisitor {
+            type Value = AmountInput;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an integer amount in minor units or a decimal string")
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<AmountInput, E> {
+                Ok(AmountInput::Minor(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<AmountInput, E> {
+                Ok(i64::try_from(value)
+                    .map(AmountInput::Minor)
+                    .unwrap_or(AmountInput::Invalid(OUT_OF_RANGE)))
+            }
+
+            /// Integers too large even for `u64` arrive here as well.
+            fn visit_f64<E>(self, value: f64) -> Result<AmountInput, E> {
+                Ok(AmountInput::Invalid(
+                    if value.fract() == 0.0 || !value.is_finite() {
+                        OUT_OF_RANGE
+                    } else {
+                        NOT_INTEGER
+                    },
+                ))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<AmountInput, E> {
+                Ok(AmountInput::Decimal(value.to_string()))
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<AmountInput, E> {
+                Ok(AmountInput::Decimal(value))
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+impl AmountInput {
+    pub fn resolve(self, currency: Currency) -> Result<i64, &'static str> {
+        match self {
+            AmountInput::Minor(value) => Ok(value),
+            AmountInput::Decimal(value) => currency.parse(&value),
+            AmountInput::Invalid(reason) => Err(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        testing::{bank, get, post, send},
+        Config,
+    };
+
+    #[test]
+    fn brl_has_two_decimals() {
+        assert_eq!(Currency::Brl.format(95_000), "950.00");
+        assert_eq!(Currency::Brl.format(-5), "-0.05");
+        assert_eq!(Currency::Brl.parse("950.5"), Ok(95_050));
+        assert_eq!(Currency::Brl.parse("950"), Ok(95_000));
+        assert!(Currency::Brl.parse("0.001").is_err());
+    }
+
+    #[test]
+    fn jpy_has_no_decimals() {
+        assert_eq!(Currency::Jpy.format(1_500), "1500");
+        assert_eq!(Currency::Jpy.format(-7), "-7");
+        assert_eq!(Currency::Jpy.parse("1500"), Ok(1_500));
+        assert!(Currency::Jpy.parse("1500.5").is_err());
+        assert!(Currency::Jpy.parse("1500.0").is_err());
+    }
+
+    fn formatter(amount_format: AmountFormat, precision: u32, rounding: Rounding) -> Formatter {
+        Formatter {
+            currency: Currency::Brl,
+            amount_format,
+            utilization_precision: precision,
+            rounding,
+        }
+    }
+
+    #[test]
+    fn amounts_follow_the_format_and_currency() {
+        let minor = formatter(AmountFormat::Minor, 2, Rounding::HalfEven);
+        assert_eq!(minor.amount(-95_050), Value::from(-95_050));
+        let decimal = formatter(AmountFormat::Decimal, 2, Rounding::HalfEven);
+        assert_eq!(decimal.amount(-95_050), Value::from("-950.50"));
+        assert_eq!(
+            decimal.in_currency(Currency::Jpy).amount(1_500),
+            Value::from("1500")
+        );
+        // Minor units on the wire, major units for people.
+        assert_eq!(minor.display(95_050), "950.50");
+
+        for formatter in [minor, decimal, decimal.in_currency(Currency::Jpy)] {
+            for value in [0, 7, -95_050] {
+                assert_eq!(
+                    serde_json::to_value(formatter.typed(value)).unwrap(),
+                    formatter.amount(value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn utilization_has_the_configured_precision_and_rounding() {
+        let two = formatter(AmountFormat::Minor, 2, Rounding::HalfEven);
+        assert_eq!(two.utilization(-1, 3).as_deref(), Some("33.33"));
+        assert_eq!(two.utilization(-2, 3).as_deref(), Some("66.67"));
+        assert_eq!(two.utilization(0, 3).as_deref(), Some("0.00"));
+        // Money of one's own uses none of the limit.
+        assert_eq!(two.utilization(500, 3).as_deref(), Some("0.00"));
+        assert_eq!(two.utilization(-1, 0), None);
+
+        let floor = formatter(AmountFormat::Minor, 2, Rounding::Floor);
+        assert_eq!(floor.utilization(-2, 3).as_deref(), Some("66.66"));
+        let whole = formatter(AmountFormat::Minor, 0, Rounding::HalfEven);
+        assert_eq!(whole.utilization(-1, 3).as_deref(), Some("33"));
+        // 12.5 and 37.5: ties.
+        assert_eq!(whole.utilization(-1, 8).as_deref(), Some("12"));
+        assert_eq!(whole.utilization(-3, 8).as_deref(), Some("38"));
+        let half_up = formatter(AmountFormat::Minor, 0, Rounding::HalfUp);
+        assert_eq!(half_up.utilization(-1, 8).as_deref(), Some("13"));
+    }
+
+    #[test]
+    fn a_fee_of_two_and_a_half_centavos_under_each_policy() {
+        // 250 basis points of 100 centavos.
+        let fee = |rounding: Rounding, amount: i128| rounding.divide(amount * 250, 10_000);
+        assert_eq!(fee(Rounding::HalfUp, 100), 3);
+        assert_eq!(fee(Rounding::HalfEven, 100), 2);
+        assert_eq!(fee(Rounding::Floor, 100), 2);
+        assert_eq!(fee(Rounding::Ceil, 100), 3);
+        // 3.5: the even neighbour is now above.
+        assert_eq!(fee(Rounding::HalfEven, 140), 4);
+        // -2.5, for amounts going the other way.
+        assert_eq!(fee(Rounding::HalfUp, -100), -2);
+        assert_eq!(fee(Rounding::HalfEven, -100), -2);
+        assert_eq!(fee(Rounding::Floor, -100), -3);
+        assert_eq!(fee(Rounding::Ceil, -100), -2);
+        // Exact results are left alone, whatever the sign of the divisor.
+        assert_eq!(Rounding::Ceil.divide(300, 100), 3);
+        assert_eq!(Rounding::Floor.divide(5, -2), -3);
+
+        assert_eq!(Rounding::default(), Rounding::HalfEven);
+        assert_eq!(Rounding::from_name("ceil"), Some(Rounding::Ceil));
+        assert_eq!(Rounding::from_name("up"), None);
+    }
+
+    #[tokio::test]
+    async fn a_sequence_netting_to_zero_renders_zero() {
+        for (amount_format, zero) in [
+            (AmountFormat::Minor, json!(0)),
+            (AmountFormat::Decimal, json!("0.00")),
+        ] {
+            let (state, _) = bank(Config {
+                amount_format,
+                ..Config::default()
+            });
+            // Into the negative and back out, by amounts of both parities.
+            for (valor, tipo) in [(700, "D"), (1_201, "C"), (500, "D"), (1, "D")] {
+                let body = json!({"valor": valor, "tipo": tipo, "descricao": "zero"});
+                let (status, _) = send(&state, post("/clientes/1/transacoes", body)).await;
+                assert_eq!(status, StatusCode::OK);
+            }
+            let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+            assert_eq!(statement["saldo"]["total"], zero);
+            assert_eq!(statement["saldos"]["BRL"], zero);
+            assert_eq!(statement["saldo"]["utilizacao"], "0.00");
+        }
+        assert_eq!(Currency::Brl.format(0), "0.00");
+        assert_eq!(Currency::Jpy.format(-0), "0");
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{error::AppError, AppState};
+
+pub const INVALID: &str = "X-Deadline invalido";
+pub const EXPIRED: &str = "prazo da requisicao expirado";
+
+/// When the client stops waiting, taken from `X-Deadline` on arrival.
+#[derive(Clone, Copy)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    pub fn remaining(self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
+
+/// `X-Deadline` is either an RFC 3339 instant or a budget in milliseconds
+/// counted from arrival. A deadline already behind us is a `408` without
+/// touching any account; otherwise it rides along as a `Deadline` extension
+/// for the lock wait to honour.
+pub async fn enforce(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(value) = request.headers().get("x-deadline") else {
+        return next.run(request).await;
+    };
+    let Some(remaining) = value
+        .to_str()
+        .ok()
+        .and_then(|value| remaining(value.trim(), state.clock.now()))
+    else {
+        return AppError::BadRequest(INVALID).into_response();
+    };
+    if remaining.is_zero() {
+        return AppError::Status(StatusCode::REQUEST_TIMEOUT, EXPIRED).into_response();
+    }
+    request
+        .extensions_mut()
+        .insert(Deadline(Instant::now() + remaining));
+    next.run(request).await
+}
+
+/// Time left before `value`, zero when it has passed; `None` if unparseable.
+fn remaining(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    if let Ok(millis) = value.parse::<u64>() {
+        return Some(Duration::from_millis(millis));
+    }
+    let at = OffsetDateTime::parse(value, &Rfc3339).ok()?;
+    Some((at - now).try_into().unwrap_or(Duration::ZERO))
+}
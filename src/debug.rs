@@ -0,0 +1,139 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::AppState;
+
+/// Diagnostics are hidden entirely unless `BANK_DEBUG_ENDPOINTS` is set.
+pub async fn require_debug(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.debug_endpoints {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    next.run(request).await
+}
+
+/// Probes each account with `try_write`, so a busy account never blocks the report.
+pub async fn locks(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let accounts: Vec<_> = state
+        .accounts
+        .entries()
+        .into_iter()
+        .map(|(id, entry)| {
+            json!({
+                "account": id,
+                "escrita_bloqueada": entry.account.try_write().is_err(),
+                "timeouts": entry.lock_timeouts.load(Ordering::Relaxed),
+            })
+        })
+        .collect();
+    Json(json!({ "contas": accounts }))
+}
+
+/// Open SSE streams per account, as live receivers on its broadcast channel.
+pub async fn subscribers(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let accounts: Vec<_> = state
+        .accounts
+        .entries()
+        .into_iter()
+        .map(|(id, entry)| {
+            json!({
+                "account": id,
+                "assinantes": entry.events.receiver_count(),
+            })
+        })
+        .collect();
+    Json(json!({ "contas": accounts }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+
+    use tower::ServiceExt;
+
+    use crate::{
+        config::Config,
+        http,
+        registry::AccountId,
+        testing::{bank, get, post, send},
+    };
+
+    fn report(body: &Value, account: u64) -> &Value {
+        body["contas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|report| report["account"] == account)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn locks_reflect_a_held_lock_and_its_timeouts() {
+        let (state, _) = bank(Config {
+            debug_endpoints: true,
+            lock_timeout: Some(Duration::from_millis(10)),
+            ..Config::default()
+        });
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let held = entry.account.write().await;
+
+        let credit = json!({"valor": 1, "tipo": "C", "descricao": "bloqueio"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (status, body) = send(&state, get("/debug/locks")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report(&body, 1)["escrita_bloqueada"], true);
+        assert_eq!(report(&body, 1)["timeouts"], 1);
+        assert_eq!(report(&body, 2)["escrita_bloqueada"], false);
+        assert_eq!(report(&body, 2)["timeouts"], 0);
+
+        drop(held);
+        let (_, body) = send(&state, get("/debug/locks")).await;
+        assert_eq!(report(&body, 1)["escrita_bloqueada"], false);
+    }
+
+    #[tokio::test]
+    async fn subscribers_rise_and_fall_with_open_streams() {
+        let (state, _) = bank(Config {
+            debug_endpoints: true,
+            ..Config::default()
+        });
+        let count = |body: &Value| report(body, 1)["assinantes"].clone();
+        let (_, body) = send(&state, get("/debug/subscribers")).await;
+        assert_eq!(count(&body), 0);
+
+        let open = || http::router(state.clone()).oneshot(get("/clientes/1/sse"));
+        let (first, second) = (open().await.unwrap(), open().await.unwrap());
+        let (_, body) = send(&state, get("/debug/subscribers")).await;
+        assert_eq!(count(&body), 2);
+        assert_eq!(report(&body, 2)["assinantes"], 0);
+
+        drop(first);
+        let (_, body) = send(&state, get("/debug/subscribers")).await;
+        assert_eq!(count(&body), 1);
+        drop(second);
+        let (_, body) = send(&state, get("/debug/subscribers")).await;
+        assert_eq!(count(&body), 0);
+    }
+
+    #[tokio::test]
+    async fn hidden_unless_enabled() {
+        let (state, _) = bank(Config::default());
+        let (status, _) = send(&state, get("/debug/locks")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
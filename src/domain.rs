@@ -0,0 +1,1196 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, Date, OffsetDateTime, UtcOffset};
+use tokio::sync::RwLock;
+
+use crate::{categories, currency, error, holds, rejections, velocity};
+use crate::{
+    config::{Config, DescriptionPolicy},
+    currency::{AmountInput, Currency},
+    dynamic_limit::DynamicLimit,
+    policy::AccountType,
+    registry::AccountId,
+    rejections::Rejection,
+    schedule::Schedule,
+    strict::KnownFields,
+    transact::{TransactError, TransactResult},
+    transfer::TransferLink,
+    ulid::Ulid,
+    validation::{self, FieldError},
+};
+
+#[derive(Clone)]
+pub struct Account {
+    /// In `config.currency`; the limit and everything built on it apply here.
+    pub(crate) balance: i64,
+    /// One per further currency the account holds, none of them overdrawable.
+    pub(crate) balances: BTreeMap<Currency, i64>,
+    pub(crate) limit: i64,
+    /// When false the balance may never go below zero, whatever the limit.
+    /// Only `BANK_NO_OVERDRAFT_ACCOUNTS`; see `overdraft` for the type's say.
+    pub(crate) allow_overdraft: bool,
+    /// Its `tipo`, and with it how the limit is enforced.
+    pub(crate) account_type: AccountType,
+    /// Id of the last accepted transaction; each new id sorts after it.
+    pub(crate) last_id: Ulid,
+    pub(crate) transactions: RingBuffer<Transaction>,
+    pub(crate) schedule: Schedule,
+    /// Caps the sum of debits per calendar day in `day_offset`.
+    pub(crate) daily_debit_limit: Option<i64>,
+    pub(crate) day_offset: UtcOffset,
+    /// Running total for the day it belongs to; in memory only, so it starts
+    /// over on restart.
+    pub(crate) daily_debits: (Date, i64),
+    /// Per-transaction and rolling-window caps on debits.
+    pub(crate) velocity: velocity::Rules,
+    pub(crate) rejections: RingBuffer<Rejection>,
+    /// Credits landing this soon after a credit at the head of the history
+    /// are folded into it rather than taking a slot of their own.
+    pub(crate) coalesce_window: Option<Duration>,
+    pub(crate) holds: holds::Holds,
+    /// Smallest gap allowed between consecutive transactions.
+    pub(crate) min_interval: Option<Duration>,
+    /// A repeat of the newest transaction this soon after it is refused.
+    pub(crate) dedup_window: Option<Duration>,
+    pub(crate) dynamic_limit: Option<DynamicLimit>,
+    /// Set by `DELETE /clientes/:id`; the statement stays readable but every
+    /// new transaction and hold is refused.
+    pub(crate) closed: bool,
+    /// Bumped by every change visible in the statement; keys the statement cache.
+    pub(crate) version: u64,
+    pub(crate) modified_at: OffsetDateTime,
+}
+
+#[derive(Clone)]
+pub struct RingBuffer<Transaction> {
+    pub(crate) items: VecDeque<Transaction>,
+    pub(crate) capacity: usize,
+}
+
+impl<Transaction> RingBuffer<Transaction> {
+    /// Allocates the full capacity up front, so filling the ring never
+    /// reallocates on the request path.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    /// Grows on demand instead, for `BANK_PREALLOCATE_HISTORY=false`.
+    pub(crate) fn unallocated(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            capacity,
+        }
+    }
+    pub(crate) fn push(&mut self, item: Transaction) {
+        if self.items.len() == self.capacity {
+            self.items.pop_back();
+            self.items.push_front(item)
+        } else {
+            self.items.push_front(item)
+        }
+    }
+    /// Newest first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.items.iter()
+    }
+    pub(crate) fn latest(&self) -> Option<&Transaction> {
+        self.items.front()
+    }
+    pub(crate) fn latest_mut(&mut self) -> Option<&mut Transaction> {
+        self.items.front_mut()
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub(crate) fn clear(&mut self) {
+        self.items.clear()
+    }
+    /// Drops items from the oldest end while `expired` holds; returns how many.
+    pub(crate) fn prune_oldest(&mut self, mut expired: impl FnMut(&Transaction) -> bool) -> usize {
+        let before = self.items.len();
+        while self.items.back().is_some_and(&mut expired) {
+            self.items.pop_back();
+        }
+        before - self.items.len()
+    }
+}
+
+/// Always a plain JSON array, newest first (`[]` when empty), never the
+/// struct's fields.
+impl<Transaction: Serialize> Serialize for RingBuffer<Transaction> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Reads the array `Serialize` writes. Capacity is just the item count;
+/// restoring into an account re-pushes into a ring of the configured size.
+impl<'de, Transaction: Deserialize<'de>> Deserialize<'de> for RingBuffer<Transaction> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = VecDeque::<Transaction>::deserialize(deserializer)?;
+        Ok(Self {
+            capacity: items.len().max(1),
+            items,
+        })
+    }
+}
+
+/// What `Account::check` found a transaction would leave.
+pub(crate) struct Admitted {
+    /// In the transaction's currency.
+    balance: i64,
+    /// The day's debits with it, for a debit in the base currency.
+    debited: Option<i64>,
+}
+
+impl Account {
+    pub fn new(limit: i64, history_cap: usize) -> Self {
+        Account {
+            balance: 0,
+            balances: BTreeMap::new(),
+            limit,
+            allow_overdraft: true,
+            account_type: AccountType::default(),
+            last_id: Ulid::NIL,
+            transactions: RingBuffer::new(history_cap),
+            schedule: Schedule::default(),
+            daily_debit_limit: None,
+            day_offset: UtcOffset::UTC,
+            daily_debits: (Date::MIN, 0),
+            velocity: velocity::Rules::default(),
+            rejections: RingBuffer::new(rejections::KEPT),
+            coalesce_window: None,
+            holds: holds::Holds::default(),
+            min_interval: None,
+            dedup_window: None,
+            dynamic_limit: None,
+            closed: false,
+            version: 0,
+            modified_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Marks a change visible in the statement.
+    pub(crate) fn touch(&mut self, now: OffsetDateTime) {
+        self.version += 1;
+        self.modified_at = now;
+    }
+
+    /// `new` with the limit checked against the configured maximum.
+    pub fn with_limit(limit: i64, config: &Config) -> Result<Self, &'static str> {
+        if limit < 0 {
+            return Err(error::INVALID_LIMIT);
+        }
+        if limit > config.max_limit {
+            return Err(error::LIMIT_ABOVE_MAX);
+        }
+        let mut account = Self::new(limit, config.history_cap);
+        if !config.preallocate_history {
+            account.transactions = RingBuffer::unallocated(config.history_cap);
+        }
+        Ok(account)
+    }
+
+    /// Debits already accepted on the calendar day containing `now`.
+    pub(crate) fn debited_today(&self, now: OffsetDateTime) -> i64 {
+        match self.daily_debits {
+            (day, total) if day == now.to_offset(self.day_offset).date() => total,
+            _ => 0,
+        }
+    }
+
+    /// Largest debit `check_debit` would accept at `now`.
+    pub(crate) fn affordable(&self, now: OffsetDateTime) -> i64 {
+        let mut max = self.available(now);
+        if let Some(limit) = self.daily_debit_limit {
+            max = max.min(limit - self.debited_today(now));
+        }
+        max.min(self.velocity.affordable(now)).max(0)
+    }
+
+    /// `transact` for `?parcial=true`: a debit that does not fit is shrunk to
+    /// the affordable amount instead of rejected, unless nothing fits at all.
+    pub fn transact_partial(
+        &mut self,
+        mut transaction: Transaction,
+        now: OffsetDateTime,
+    ) -> Result<TransactResult, TransactError> {
+        if let (TransactionType::Debit, Some(currency)) = (transaction.kind, transaction.currency) {
+            let held = self.balances.get(&currency).copied().unwrap_or(0);
+            if held > 0 {
+                transaction.value = transaction.value.min(held);
+            }
+        } else if let TransactionType::Debit = transaction.kind {
+            let affordable = self.affordable(now);
+            if affordable > 0 {
+                transaction.value = transaction.value.min(affordable);
+            }
+        }
+        self.transact(transaction, now)
+    }
+
+    /// Returns the day's debit total including `value` when the debit fits.
+    /// Outstanding holds count as already spent.
+    pub(crate) fn check_debit(
+        &self,
+        value: i64,
+        now: OffsetDateTime,
+    ) -> Result<i64, TransactError> {
+        let spendable = self.balance.saturating_sub(self.holds.outstanding(now));
+        if !self.overdraft() && spendable < value {
+            return Err(TransactError::InsufficientBalance {
+                needed: value,
+                available: spendable,
+            });
+        }
+        let available = self
+            .available(now)
+            .saturating_add(self.account_type.policy.tolerance());
+        if available < value {
+            return Err(TransactError::InsufficientLimit {
+                needed: value,
+                available,
+            });
+        }
+        let debited = self.debited_today(now).saturating_add(value);
+        if let Some(limit) = self.daily_debit_limit.filter(|limit| debited > *limit) {
+            return Err(TransactError::DailyLimitExceeded {
+                limit,
+                debited: debited - value,
+            });
+        }
+        self.velocity.check(value, now)?;
+        Ok(debited)
+    }
+
+    /// Assigns `id` and stamps `create_at` while the caller holds the
+    /// account's write lock, so statement order always matches application
+    /// order. Returns the recorded transaction with the balance it left;
+    /// rejected ones go to the account's rejection ring instead.
+    pub fn transact(
+        &mut self,
+        mut transaction: Transaction,
+        now: OffsetDateTime,
+    ) -> Result<TransactResult, TransactError> {
+        let admitted = match self.check(&transaction, now) {
+            Ok(admitted) => admitted,
+            Err(error) => return Err(self.reject(transaction, error, now)),
+        };
+        match transaction.currency {
+            Some(currency) => drop(self.balances.insert(currency, admitted.balance)),
+            None => self.balance = admitted.balance,
+        }
+        if let Some(debited) = admitted.debited {
+            self.daily_debits = (now.to_offset(self.day_offset).date(), debited);
+            self.velocity.record(transaction.value, now);
+        }
+
+        self.last_id = Ulid::next_after(self.last_id, now);
+        transaction.id = self.last_id;
+        transaction.seq = next_seq();
+        transaction.create_at = match self.transactions.latest() {
+            Some(last) => now.max(last.create_at),
+            None => now,
+        };
+        tracing::debug!(id = %transaction.id, balance = self.balance, "transaction accepted");
+        self.record(transaction.clone());
+        let fee = self.charge_overdraft_fee(&transaction, now);
+        self.touch(now);
+        let balance = match transaction.currency {
+            Some(currency) => self.balances[&currency],
+            None => self.balance,
+        };
+        Ok(TransactResult {
+            transaction,
+            balance,
+            limit: self.limit,
+            version: self.version,
+            fee,
+        })
+    }
+
+    /// Whether `transact` would accept `transaction` at `now`, and what it
+    /// would leave, without changing anything; a refusal is not recorded
+    /// either. For a change spanning accounts, checking every side before
+    /// applying any. In a currency other than the base one there is no
+    /// limit, holds or debit rules: the balance just may not go below zero.
+    pub(crate) fn check(
+        &self,
+        transaction: &Transaction,
+        now: OffsetDateTime,
+    ) -> Result<Admitted, TransactError> {
+        self.check_open()?;
+        self.check_interval(now)?;
+        self.check_duplicate(transaction, now)?;
+        let (kind, value) = (transaction.kind, transaction.value);
+        match transaction.currency {
+            Some(currency) => {
+                let held = *self
+                    .balances
+                    .get(&currency)
+                    .ok_or(TransactError::CurrencyNotHeld { currency })?;
+                if kind == TransactionType::Debit && held < value {
+                    return Err(TransactError::InsufficientCurrencyBalance {
+                        currency,
+                        needed: value,
+                        available: held,
+                    });
+                }
+                Ok(Admitted {
+                    balance: kind.apply(held, value)?,
+                    debited: None,
+                })
+            }
+            None => {
+                let debited = match kind {
+                    TransactionType::Credit => None,
+                    TransactionType::Debit => Some(self.check_debit(value, now)?),
+                };
+                Ok(Admitted {
+                    balance: kind.apply(self.balance, value)?,
+                    debited,
+                })
+            }
+        }
+    }
+
+    /// For `If-Match`: call under the same lock as the write it guards.
+    /// Not a refusal of the transaction, so nothing goes to the rejection ring.
+    pub fn check_version(&self, expected: Option<u64>) -> Result<(), TransactError> {
+        match expected {
+            Some(expected) if expected != self.version => Err(TransactError::VersionMismatch {
+                current: self.version,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn reject(
+        &mut self,
+        transaction: Transaction,
+        error: TransactError,
+        now: OffsetDateTime,
+    ) -> TransactError {
+        tracing::debug!(reason = error.reason(), "transaction refused");
+        self.rejections.push(Rejection {
+            transaction,
+            error,
+            at: now,
+        });
+        error
+    }
+
+    pub(crate) fn check_open(&self) -> Result<(), TransactError> {
+        match self.closed {
+            true => Err(TransactError::AccountClosed),
+            false => Ok(()),
+        }
+    }
+
+    /// Enforces `min_interval` against the newest recorded transaction.
+    pub(crate) fn check_interval(&self, now: OffsetDateTime) -> Result<(), TransactError> {
+        match (self.min_interval, self.transactions.latest()) {
+            (Some(gap), Some(last)) if now - last.create_at < gap => Err(TransactError::TooSoon),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enforces `dedup_window`: same kind, value and description as the
+    /// newest recorded transaction means a double submit, not a new one.
+    pub(crate) fn check_duplicate(
+        &self,
+        transaction: &Transaction,
+        now: OffsetDateTime,
+    ) -> Result<(), TransactError> {
+        match (self.dedup_window, self.transactions.latest()) {
+            (Some(window), Some(last))
+                if now - last.create_at <= window
+                    && last.kind == transaction.kind
+                    && last.value == transaction.value
+                    && last.currency == transaction.currency
+                    && last.description.0 == transaction.description.0 =>
+            {
+                Err(TransactError::Duplicate { previous: last.id })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds an accepted transaction to the history. With a coalescing window,
+    /// a credit within it of the head credit is summed into that entry, which
+    /// keeps the first description and time and takes the newest id; the
+    /// balance never waits on this. Debits, transfers and reversals are never
+    /// coalesced.
+    pub(crate) fn record(&mut self, transaction: Transaction) {
+        if let (TransactionType::Credit, Some(window)) = (&transaction.kind, self.coalesce_window) {
+            if let Some(head) = self.transactions.latest_mut() {
+                if matches!(head.kind, TransactionType::Credit)
+                    && head.transfer.is_none()
+                    && transaction.transfer.is_none()
+                    && head.reversal.is_none()
+                    && transaction.reversal.is_none()
+                    && head.currency == transaction.currency
+                    && transaction.create_at - head.create_at <= window
+                {
+                    head.value += transaction.value;
+                    head.id = transaction.id;
+                    head.seq = transaction.seq;
+                    return;
+                }
+            }
+        }
+        self.transactions.push(transaction);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionType {
+    #[serde(rename = "C")]
+    Credit,
+
+    #[serde(rename = "D")]
+    Debit,
+}
+
+impl TransactionType {
+    /// `balance` once `value` has moved it this way, unless that leaves the
+    /// range of `i64`.
+    pub(crate) fn apply(self, balance: i64, value: i64) -> Result<i64, TransactError> {
+        match self {
+            TransactionType::Credit => balance.checked_add(value),
+            TransactionType::Debit => balance.checked_sub(value),
+        }
+        .ok_or(TransactError::OutOfRange)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "StoredTransaction")]
+pub struct Transaction {
+    /// `Ulid::NIL` until `Account::transact` accepts it.
+    pub(crate) id: Ulid,
+
+    #[serde(rename = "valor")]
+    pub(crate) value: i64,
+
+    #[serde(rename = "tipo")]
+    pub(crate) kind: TransactionType,
+
+    #[serde(rename = "descricao")]
+    pub(crate) description: Description,
+
+    #[serde(rename = "realizada_em", with = "time::serde::rfc3339")]
+    pub(crate) create_at: OffsetDateTime,
+
+    /// False only when `realizada_em` came from outside (an import) rather
+    /// than being stamped here.
+    pub(crate) server_assigned_time: bool,
+
+    /// Position in the order accepted across all accounts; 0 until accepted.
+    pub(crate) seq: u64,
+
+    /// Set on both halves of a transfer between accounts.
+    #[serde(rename = "transferencia", skip_serializing_if = "Option::is_none")]
+    pub(crate) transfer: Option<TransferLink>,
+
+    /// On a reversal, the id of the transaction it undoes.
+    #[serde(rename = "estorno", skip_serializing_if = "Option::is_none")]
+    pub(crate) reversal: Option<Ulid>,
+
+    /// Set when not the configured currency, whose balance carries the limit;
+    /// see `Account::balances`.
+    #[serde(rename = "moeda", skip_serializing_if = "Option::is_none")]
+    pub(crate) currency: Option<Currency>,
+
+    /// One of `BANK_CATEGORIES`, as sent with the transaction.
+    #[serde(rename = "categoria", skip_serializing_if = "Option::is_none")]
+    pub(crate) category: Option<String>,
+}
+
+/// Source of `Transaction::seq`. Bumped under the account's write lock, so
+/// within an account `seq` follows statement order as well. Numbers taken by
+/// rolled-back settlements, simulations and projections leave gaps; only
+/// order is promised.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn next_seq() -> u64 {
+    SEQUENCE.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Keeps the sequence ahead of numbers restored from a snapshot or the WAL.
+pub(crate) fn observe_seq(seq: u64) {
+    SEQUENCE.fetch_max(seq, Ordering::SeqCst);
+}
+
+/// Deserialization shape of `Transaction`: a missing `realizada_em` is not
+/// an error, but it is recorded as server-assigned instead of passing off
+/// "now" as the original time.
+#[derive(Deserialize)]
+pub struct StoredTransaction {
+    #[serde(default)]
+    pub(crate) id: Ulid,
+    #[serde(rename = "valor")]
+    pub(crate) value: i64,
+    #[serde(rename = "tipo")]
+    pub(crate) kind: TransactionType,
+    #[serde(rename = "descricao")]
+    pub(crate) description: Description,
+    #[serde(
+        rename = "realizada_em",
+        with = "time::serde::rfc3339::option",
+        default
+    )]
+    pub(crate) create_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    pub(crate) server_assigned_time: bool,
+    #[serde(default)]
+    pub(crate) seq: u64,
+    #[serde(default, rename = "transferencia")]
+    pub(crate) transfer: Option<TransferLink>,
+    #[serde(default, rename = "estorno")]
+    pub(crate) reversal: Option<Ulid>,
+    #[serde(default, rename = "moeda")]
+    pub(crate) currency: Option<Currency>,
+    #[serde(default, rename = "categoria")]
+    pub(crate) category: Option<String>,
+}
+
+impl From<StoredTransaction> for Transaction {
+    fn from(stored: StoredTransaction) -> Self {
+        Transaction {
+            id: stored.id,
+            value: stored.value,
+            kind: stored.kind,
+            description: stored.description,
+            server_assigned_time: stored.server_assigned_time || stored.create_at.is_none(),
+            create_at: stored.create_at.unwrap_or_else(OffsetDateTime::now_utc),
+            seq: stored.seq,
+            transfer: stored.transfer,
+            reversal: stored.reversal,
+            currency: stored.currency,
+            category: stored.category,
+        }
+    }
+}
+
+/// Incoming payload; `valor` and `descricao` are only checked against the
+/// configured currency and description policy in `into_transaction`.
+#[derive(Deserialize)]
+pub struct TransactionRequest {
+    #[serde(rename = "valor")]
+    pub(crate) value: AmountInput,
+
+    #[serde(rename = "tipo")]
+    pub(crate) kind: TransactionType,
+
+    #[serde(rename = "descricao")]
+    pub(crate) description: Description,
+
+    /// ISO code; the configured currency when absent, unless
+    /// `BANK_REQUIRE_CURRENCY`. Kept as text so an unknown code is refused
+    /// like a bad `valor` rather than as malformed JSON.
+    #[serde(default, rename = "moeda")]
+    pub(crate) currency: Option<String>,
+
+    /// Only a future time means anything: `POST /clientes/:id/transacoes`
+    /// then schedules the transaction for it instead of applying it.
+    #[serde(
+        default,
+        rename = "realizada_em",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub(crate) scheduled_for: Option<OffsetDateTime>,
+
+    /// Checked against `BANK_CATEGORIES`; kept as text for the same reason
+    /// as `moeda`.
+    #[serde(default, rename = "categoria")]
+    pub(crate) category: Option<String>,
+}
+
+impl KnownFields for TransactionRequest {
+    const FIELDS: &'static [&'static str] = &[
+        "valor",
+        "tipo",
+        "descricao",
+        "moeda",
+        "realizada_em",
+        "categoria",
+    ];
+
+    /// Each field on its own, shape first and then what `validate` checks,
+    /// for a body that did not deserialize as a whole.
+    fn field_errors(body: &Value, config: &Config) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let value = validation::field::<AmountInput>(
+            body,
+            "valor",
+            true,
+            validation::AMOUNT_TYPE,
+            &mut errors,
+        );
+        validation::field::<TransactionType>(body, "tipo", true, validation::KIND, &mut errors);
+        let description = validation::field::<Description>(
+            body,
+            "descricao",
+            true,
+            validation::DESCRIPTION_TYPE,
+            &mut errors,
+        );
+        let currency = validation::field::<String>(
+            body,
+            "moeda",
+            false,
+            validation::CURRENCY_TYPE,
+            &mut errors,
+        );
+        let category =
+            validation::field::<String>(body, "categoria", false, categories::TYPE, &mut errors);
+        if body
+            .get("realizada_em")
+            .filter(|at| !at.is_null())
+            .is_some_and(|at| {
+                at.as_str()
+                    .and_then(|at| OffsetDateTime::parse(at, &Rfc3339).ok())
+                    .is_none()
+            })
+        {
+            errors.push(FieldError {
+                field: "realizada_em",
+                reason: validation::TIMESTAMP,
+            });
+        }
+
+        let currency = currency.map(|code| check(&mut errors, "moeda", currency_of(code, config)));
+        let currency = currency.flatten().unwrap_or(config.currency);
+        if let Some(Some(value)) = value {
+            check(&mut errors, "valor", amount_of(value, currency, config));
+        }
+        if let Some(Some(description)) = description {
+            check(
+                &mut errors,
+                "descricao",
+                description.normalize(&config.description),
+            );
+        }
+        if let Some(Some(category)) = category {
+            check(
+                &mut errors,
+                "categoria",
+                categories::resolve(&category, config),
+            );
+        }
+        errors.sort_by_key(|error| Self::FIELDS.iter().position(|field| *field == error.field));
+        errors
+    }
+}
+
+/// `result`'s value, or `None` with its reason pushed as `field`'s error.
+fn check<T>(
+    errors: &mut Vec<FieldError>,
+    field: &'static str,
+    result: Result<T, &'static str>,
+) -> Option<T> {
+    result
+        .map_err(|reason| errors.push(FieldError { field, reason }))
+        .ok()
+}
+
+fn currency_of(code: Option<String>, config: &Config) -> Result<Currency, &'static str> {
+    match code {
+        Some(code) => Currency::from_code(&code).ok_or(currency::UNKNOWN),
+        None if config.require_currency => Err(currency::REQUIRED),
+        None => Ok(config.currency),
+    }
+}
+
+/// Decimal input is read with the minor units of its own currency.
+fn amount_of(value: AmountInput, currency: Currency, config: &Config) -> Result<i64, &'static str> {
+    match value.resolve(currency)? {
+        value if value <= 0 => Err(validation::NOT_POSITIVE),
+        value if value > config.max_transaction_value => Err(validation::ABOVE_MAX),
+        value => Ok(value),
+    }
+}
+
+impl TransactionRequest {
+    /// `realizada_em` when it lies after `now`.
+    pub(crate) fn run_at(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.scheduled_for.filter(|at| *at > now)
+    }
+
+    /// `received_at` is provisional: accepted transactions are re-stamped by
+    /// `Account::transact`, and a client-sent `realizada_em` never is the
+    /// recorded time.
+    pub(crate) fn into_transaction(
+        self,
+        config: &Config,
+        received_at: OffsetDateTime,
+    ) -> Result<Transaction, &'static str> {
+        self.validate(config, received_at)
+            .map_err(|errors| errors[0].reason)
+    }
+
+    /// `into_transaction` with every refused field reported, not just the
+    /// first: `valor` must be positive and at most
+    /// `BANK_MAX_TRANSACTION_VALUE`, `descricao` must pass the description
+    /// policy, and `moeda` and `categoria` must be known.
+    pub(crate) fn validate(
+        self,
+        config: &Config,
+        received_at: OffsetDateTime,
+    ) -> Result<Transaction, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let currency = check(&mut errors, "moeda", currency_of(self.currency, config));
+        let value = check(
+            &mut errors,
+            "valor",
+            amount_of(self.value, currency.unwrap_or(config.currency), config),
+        );
+        let description = check(
+            &mut errors,
+            "descricao",
+            self.description.normalize(&config.description),
+        );
+        let category = match self.category {
+            None => Some(None),
+            Some(category) => check(
+                &mut errors,
+                "categoria",
+                categories::resolve(&category, config),
+            )
+            .map(Some),
+        };
+        let (Some(currency), Some(value), Some(description), Some(category)) =
+            (currency, value, description, category)
+        else {
+            return Err(errors);
+        };
+        Ok(Transaction {
+            id: Ulid::NIL,
+            value,
+            kind: self.kind,
+            description,
+            create_at: received_at,
+            server_assigned_time: true,
+            seq: 0,
+            transfer: None,
+            reversal: None,
+            currency: (currency != config.currency).then_some(currency),
+            category,
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Description(pub(crate) String);
+
+impl Description {
+    /// Validation depends on the configured policy, so it runs in the handler
+    /// rather than during deserialization.
+    pub(crate) fn normalize(self, policy: &DescriptionPolicy) -> Result<Self, &'static str> {
+        let value = if policy.trim && self.0.trim() != self.0 {
+            self.0.trim().to_string()
+        } else {
+            self.0
+        };
+
+        if value.is_empty() || policy.unit.measure(&value) > policy.max_len {
+            Err("Descrição invalida")
+        } else if policy.is_blocked(&value) {
+            Err("descricao contem termo proibido")
+        } else {
+            Ok(Self(value))
+        }
+    }
+}
+
+/// Builds the account map, refusing duplicate ids: collecting into a map
+/// would otherwise keep the last one and silently drop the others.
+pub(crate) fn seed_accounts(
+    seeds: &[(AccountId, i64)],
+    config: &Config,
+) -> Result<HashMap<AccountId, RwLock<Account>>, String> {
+    let mut duplicates: Vec<AccountId> = seeds
+        .iter()
+        .enumerate()
+        .filter(|(index, (id, _))| seeds[..*index].iter().any(|(seen, _)| seen == id))
+        .map(|(_, (id, _))| *id)
+        .collect();
+    if !duplicates.is_empty() {
+        duplicates.sort_unstable();
+        duplicates.dedup();
+        let ids: Vec<String> = duplicates.iter().map(AccountId::to_string).collect();
+        return Err(format!("ids de conta duplicados: {}", ids.join(", ")));
+    }
+
+    seeds
+        .iter()
+        .map(|&(id, limit)| {
+            let account = open_account(id, limit, config)
+                .map_err(|reason| format!("account {id}: {reason}"))?;
+            Ok((id, RwLock::new(account)))
+        })
+        .collect()
+}
+
+/// A fresh account `id` with every per-account setting from `config`
+/// applied, for the seeds and for `POST /clientes` alike.
+pub(crate) fn open_account(
+    id: AccountId,
+    limit: i64,
+    config: &Config,
+) -> Result<Account, &'static str> {
+    let mut account = Account::with_limit(limit, config)?;
+    account.allow_overdraft = !config.no_overdraft_accounts.contains(&id);
+    account.account_type = config.default_account_type();
+    account.daily_debit_limit = config.daily_debit_limits.get(&id).copied();
+    account.day_offset = config.daily_debit_timezone;
+    let max_debit = config.max_debit_values.get(&id).copied();
+    let rolling = config
+        .rolling_debit_limits
+        .get(&id)
+        .map(|&limit| velocity::Rule::Rolling {
+            window: config.rolling_debit_window,
+            limit,
+        });
+    account.velocity = velocity::Rules::new(
+        max_debit
+            .map(velocity::Rule::MaxDebit)
+            .into_iter()
+            .chain(rolling)
+            .collect(),
+    );
+    if let Some(currencies) = config.account_currencies.get(&id) {
+        account.balances = currencies.iter().map(|currency| (*currency, 0)).collect();
+    }
+    account.coalesce_window = config.coalesce_credits;
+    account.min_interval = config.min_transaction_interval;
+    account.dedup_window = config.dedup_window;
+    account.dynamic_limit = config.dynamic_limit.filter(|_| {
+        config
+            .dynamic_limit_accounts
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&id))
+    });
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::{seed_accounts, Account, RingBuffer, Transaction, TransactionRequest};
+    use crate::{
+        config::LengthUnit, currency::Currency, error, registry::AccountId,
+        transact::TransactError, ulid::Ulid, Config,
+    };
+
+    #[test]
+    fn imported_timestamps_are_marked_as_supplied() {
+        let supplied: Transaction = serde_json::from_value(json!({
+            "valor": 100, "tipo": "C", "descricao": "importada",
+            "realizada_em": "2024-01-15T10:30:00Z",
+        }))
+        .unwrap();
+        assert!(!supplied.server_assigned_time);
+        assert_eq!(supplied.create_at, datetime!(2024-01-15 10:30 UTC));
+        let rendered = serde_json::to_value(&supplied).unwrap();
+        assert_eq!(rendered["server_assigned_time"], false);
+
+        let defaulted: Transaction = serde_json::from_value(json!({
+            "valor": 100, "tipo": "C", "descricao": "sem data",
+        }))
+        .unwrap();
+        assert!(defaulted.server_assigned_time);
+        // As stored: it was stamped here, whatever the time it carries.
+        let restored: Transaction = serde_json::from_value(rendered).unwrap();
+        assert!(!restored.server_assigned_time);
+        let stamped: Transaction =
+            serde_json::from_value(serde_json::to_value(&defaulted).unwrap()).unwrap();
+        assert!(stamped.server_assigned_time);
+    }
+
+    #[test]
+    fn ring_serializes_as_a_plain_array() {
+        let mut ring = RingBuffer::new(2);
+        assert_eq!(serde_json::to_string(&ring).unwrap(), "[]");
+        for item in 1..=3 {
+            ring.push(item);
+        }
+        // Newest first, the oldest evicted.
+        assert_eq!(serde_json::to_string(&ring).unwrap(), "[3,2]");
+        let back: RingBuffer<i32> = serde_json::from_str("[3,2]").unwrap();
+        assert_eq!(back.iter().copied().collect::<Vec<_>>(), [3, 2]);
+    }
+
+    fn capped(max_limit: i64) -> Config {
+        Config {
+            max_limit,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn limits_up_to_the_maximum_are_accepted() {
+        let config = capped(1_000_000);
+        assert_eq!(
+            Account::with_limit(500_000, &config).unwrap().limit,
+            500_000
+        );
+        assert_eq!(
+            Account::with_limit(1_000_000, &config).unwrap().limit,
+            1_000_000
+        );
+        assert_eq!(
+            Account::with_limit(1_000_001, &config).err(),
+            Some(error::LIMIT_ABOVE_MAX)
+        );
+        assert_eq!(
+            Account::with_limit(i64::MAX, &config).err(),
+            Some(error::LIMIT_ABOVE_MAX)
+        );
+        assert_eq!(
+            Account::with_limit(-1, &config).err(),
+            Some(error::INVALID_LIMIT)
+        );
+    }
+
+    #[test]
+    fn seeds_above_the_maximum_fail_startup() {
+        let config = capped(1_000_000);
+        let seeds = [(AccountId(1), 1_000_000), (AccountId(2), 2_000_000)];
+        let err = seed_accounts(&seeds, &config).err().unwrap();
+        assert_eq!(err, format!("account 2: {}", error::LIMIT_ABOVE_MAX));
+        assert!(seed_accounts(&seeds[..1], &config).is_ok());
+    }
+
+    #[test]
+    fn duplicate_seed_ids_fail_startup() {
+        let config = Config::default();
+        let seeds = [
+            (AccountId(3), 100),
+            (AccountId(1), 200),
+            (AccountId(3), 300),
+            (AccountId(1), 400),
+            (AccountId(3), 500),
+        ];
+        let err = seed_accounts(&seeds, &config).err().unwrap();
+        assert_eq!(err, "ids de conta duplicados: 1, 3");
+        let accounts = seed_accounts(&seeds[..2], &config).unwrap();
+        assert_eq!(accounts.len(), 2);
+    }
+
+    fn transaction(value: i64, kind: &str, currency: Option<&str>) -> Transaction {
+        let mut body = json!({"valor": value, "tipo": kind, "descricao": "extremo"});
+        if let Some(currency) = currency {
+            body["moeda"] = currency.into();
+        }
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn balances_refuse_to_leave_the_i64_range() {
+        let now = datetime!(2024-03-01 12:00 UTC);
+        let mut account = Account::new(0, 10);
+        account.balance = i64::MAX - 5;
+        let error = account.transact(transaction(6, "C", None), now).err();
+        assert_eq!(error, Some(TransactError::OutOfRange));
+        assert_eq!(error.unwrap().reason(), "valor fora do intervalo suportado");
+        assert_eq!(account.balance, i64::MAX - 5);
+        assert_eq!(account.rejections.iter().count(), 1);
+        assert!(account.transact(transaction(5, "C", None), now).is_ok());
+        assert_eq!(account.balance, i64::MAX);
+
+        account.balances.insert(Currency::Usd, i64::MAX);
+        let error = account
+            .transact(transaction(1, "C", Some("USD")), now)
+            .err();
+        assert_eq!(error, Some(TransactError::OutOfRange));
+        assert_eq!(account.balances[&Currency::Usd], i64::MAX);
+
+        let mut replayed = Account::new(0, 10);
+        replayed.balance = i64::MIN + 1;
+        let mut debit = transaction(2, "D", None);
+        debit.id = Ulid::next_after(Ulid::NIL, now);
+        assert_eq!(replayed.replay(debit), Err(TransactError::OutOfRange));
+        assert_eq!(replayed.balance, i64::MIN + 1);
+        assert_eq!(replayed.last_id, Ulid::NIL);
+    }
+
+    #[test]
+    fn refusals_carry_their_variant_and_acceptances_what_was_recorded() {
+        let now = datetime!(2024-03-01 12:00 UTC);
+        let mut account = Account::new(1_000, 10);
+        let result = account.transact(transaction(300, "D", None), now).unwrap();
+        assert_eq!(result.balance, -300);
+        assert_eq!(result.limit, 1_000);
+        assert_eq!(result.version, account.version);
+        assert_eq!(result.transaction.id, account.last_id);
+        assert_eq!(result.transaction.create_at, now);
+        assert!(result.transaction.seq > 0);
+        assert!(result.fee.is_none());
+
+        assert_eq!(
+            account.transact(transaction(701, "D", None), now).err(),
+            Some(TransactError::InsufficientLimit {
+                needed: 701,
+                available: 700,
+            })
+        );
+        account.daily_debit_limit = Some(500);
+        assert_eq!(
+            account.transact(transaction(201, "D", None), now).err(),
+            Some(TransactError::DailyLimitExceeded {
+                limit: 500,
+                debited: 300,
+            })
+        );
+        assert_eq!(
+            account
+                .transact(transaction(1, "C", Some("USD")), now)
+                .err(),
+            Some(TransactError::CurrencyNotHeld {
+                currency: Currency::Usd,
+            })
+        );
+        account.min_interval = Some(std::time::Duration::from_secs(1));
+        assert_eq!(
+            account.transact(transaction(1, "C", None), now).err(),
+            Some(TransactError::TooSoon)
+        );
+        account.closed = true;
+        assert_eq!(
+            account.transact(transaction(1, "C", None), now).err(),
+            Some(TransactError::AccountClosed)
+        );
+
+        let mut cash = Account::new(1_000, 10);
+        cash.allow_overdraft = false;
+        assert_eq!(
+            cash.transact(transaction(1, "D", None), now).err(),
+            Some(TransactError::InsufficientBalance {
+                needed: 1,
+                available: 0,
+            })
+        );
+        // None of them left a trace but in the rejection ring.
+        assert_eq!(account.balance, -300);
+        assert_eq!(account.rejections.iter().count(), 5);
+    }
+
+    #[test]
+    fn transact_moves_the_balance_down_to_the_limit_and_no_further() {
+        let now = datetime!(2024-03-01 12:00 UTC);
+        let mut account = Account::new(1_000, 10);
+        let credited = account.transact(transaction(500, "C", None), now).unwrap();
+        assert_eq!(credited.balance, 500);
+        // Exactly the limit is still within it.
+        let debited = account
+            .transact(transaction(1_500, "D", None), now)
+            .unwrap();
+        assert_eq!(debited.balance, -1_000);
+        assert_eq!(debited.version, credited.version + 1);
+        assert!(debited.transaction.id > credited.transaction.id);
+        assert_eq!(
+            account.transact(transaction(1, "D", None), now).err(),
+            Some(TransactError::InsufficientLimit {
+                needed: 1,
+                available: 0,
+            })
+        );
+        // A credit always goes through, and makes room again.
+        account.transact(transaction(1, "C", None), now).unwrap();
+        assert!(account.transact(transaction(1, "D", None), now).is_ok());
+
+        assert_eq!(account.balance, -1_000);
+        let history: Vec<_> = account
+            .transactions
+            .iter()
+            .map(|transaction| transaction.value)
+            .collect();
+        assert_eq!(history, [1, 1, 1_500, 500]);
+    }
+
+    fn described(description: &str) -> TransactionRequest {
+        serde_json::from_value(json!({"valor": 1, "tipo": "C", "descricao": description})).unwrap()
+    }
+
+    fn description(request: TransactionRequest, config: &Config) -> Result<String, &'static str> {
+        let now = datetime!(2024-03-01 12:00 UTC);
+        request
+            .into_transaction(config, now)
+            .map(|transaction| transaction.description.0)
+    }
+
+    #[test]
+    fn descriptions_follow_the_configured_policy() {
+        let config = Config::default();
+        assert_eq!(description(described("pix"), &config).unwrap(), "pix");
+        assert_eq!(
+            description(described("0123456789"), &config).unwrap(),
+            "0123456789"
+        );
+        assert_eq!(
+            description(described("0123456789a"), &config),
+            Err("Descrição invalida")
+        );
+        assert_eq!(
+            description(described(""), &config),
+            Err("Descrição invalida")
+        );
+        // Characters by default; bytes when configured.
+        assert!(description(described("açaí açaí"), &config).is_ok());
+        let mut bytes = Config::default();
+        bytes.description.unit = LengthUnit::Bytes;
+        assert_eq!(
+            description(described("açaí açaí"), &bytes),
+            Err("Descrição invalida")
+        );
+
+        // Kept as sent unless trimming is on, and then nothing is left.
+        assert_eq!(description(described(" pix "), &config).unwrap(), " pix ");
+        let mut trimmed = Config::default();
+        trimmed.description.trim = true;
+        assert_eq!(description(described(" pix "), &trimmed).unwrap(), "pix");
+        assert_eq!(
+            description(described("   "), &trimmed),
+            Err("Descrição invalida")
+        );
+
+        let mut blocked = Config::default();
+        blocked.description.blocklist = vec!["acai".to_string()];
+        assert_eq!(
+            description(described("Açaí"), &blocked),
+            Err("descricao contem termo proibido")
+        );
+    }
+
+    #[test]
+    fn history_is_preallocated_unless_disabled() {
+        let config = Config {
+            history_cap: 500,
+            ..Config::default()
+        };
+        let account = Account::with_limit(1_000, &config).unwrap();
+        assert!(account.transactions.items.capacity() >= 500);
+        assert_eq!(account.transactions.capacity, 500);
+
+        let lazy = Config {
+            preallocate_history: false,
+            ..config
+        };
+        let account = Account::with_limit(1_000, &lazy).unwrap();
+        assert_eq!(account.transactions.items.capacity(), 0);
+        assert_eq!(account.transactions.capacity, 500);
+    }
+}
@@ -0,0 +1,108 @@
+use time::OffsetDateTime;
+
+use crate::{currency::Rounding, Account, TransactionType};
+
+/// Limit as a multiple of the account's recent average daily debit, for
+/// accounts in `BANK_DYNAMIC_LIMIT_ACCOUNTS`.
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicLimit {
+    /// `BANK_DYNAMIC_LIMIT_FACTOR` in hundredths, so `1.5` is `150`.
+    pub factor_hundredths: i64,
+    /// Fewer retained debits than this and the fixed limit applies.
+    pub min_debits: usize,
+    pub rounding: Rounding,
+}
+
+impl Account {
+    /// The limit `available` works with: the dynamic one when configured and
+    /// there is enough history, the fixed `limit` otherwise. Recomputed on
+    /// every call from the retained statement, oldest debit to `now`.
+    pub fn effective_limit(&self, now: OffsetDateTime) -> i64 {
+        let Some(dynamic) = self.dynamic_limit else {
+            return self.limit;
+        };
+        let debits = || {
+            self.transactions.iter().filter(|transaction| {
+                transaction.currency.is_none() && matches!(transaction.kind, TransactionType::Debit)
+            })
+        };
+        if debits().count() < dynamic.min_debits {
+            return self.limit;
+        }
+        let Some(oldest) = debits().map(|transaction| transaction.create_at).min() else {
+            return self.limit;
+        };
+
+        let today = now.to_offset(self.day_offset).date();
+        let first = oldest.to_offset(self.day_offset).date();
+        let days = i128::from((today - first).whole_days().max(0) + 1);
+        let total: i128 = debits()
+            .map(|transaction| i128::from(transaction.value))
+            .sum();
+        let limit = dynamic
+            .rounding
+            .divide(total * i128::from(dynamic.factor_hundredths), days * 100);
+        i64::try_from(limit).unwrap_or(i64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use super::DynamicLimit;
+    use crate::{
+        currency::Rounding,
+        testing::{bank, post, send},
+        Config,
+    };
+
+    /// Twice the average daily debit, from the third debit on.
+    fn dynamic() -> Config {
+        Config {
+            dynamic_limit: Some(DynamicLimit {
+                factor_hundredths: 200,
+                min_debits: 3,
+                rounding: Rounding::HalfEven,
+            }),
+            ..Config::default()
+        }
+    }
+
+    /// Whether account 1 takes each debit in turn.
+    async fn debits(config: Config, values: &[i64]) -> Vec<StatusCode> {
+        let (state, _) = bank(config);
+        let mut statuses = Vec::new();
+        for &value in values {
+            let debit = json!({"valor": value, "tipo": "D", "descricao": "compra"});
+            let (status, _) = send(&state, post("/clientes/1/transacoes", debit)).await;
+            statuses.push(status);
+        }
+        statuses
+    }
+
+    #[tokio::test]
+    async fn the_same_debit_passes_the_fixed_limit_and_not_the_dynamic_one() {
+        // Three debits of 1_000 in a day: the dynamic limit is 6_000, of
+        // which 3_000 is already used, against a fixed 100_000.
+        let values = [1_000, 1_000, 1_000, 10_000];
+        let ok = StatusCode::OK;
+        assert_eq!(debits(Config::default(), &values).await, [ok; 4]);
+        assert_eq!(
+            debits(dynamic(), &values).await,
+            [ok, ok, ok, StatusCode::UNPROCESSABLE_ENTITY]
+        );
+        assert_eq!(
+            debits(dynamic(), &[1_000, 1_000, 1_000, 3_000]).await,
+            [ok; 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn too_little_history_falls_back_to_the_fixed_limit() {
+        // With two debits retained the average does not count yet.
+        let statuses = debits(dynamic(), &[1_000, 1_000, 50_000]).await;
+        assert_eq!(statuses, [StatusCode::OK; 3]);
+    }
+}
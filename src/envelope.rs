@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::{ulid::Ulid, AppState};
+
+/// Bodies larger than this are passed through unwrapped.
+const MAX_BODY: usize = 1 << 20;
+
+/// With `BANK_RESPONSE_ENVELOPE`, wraps JSON responses as
+/// `{"data": ..., "meta": {...}}`, or `{"error": ..., "meta": {...}}` for
+/// error statuses. Non-JSON bodies (SSE, MessagePack, `/metrics`) and empty
+/// successes such as `204`/`304` are left alone.
+pub async fn wrap(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if !state.config.response_envelope {
+        return next.run(request).await;
+    }
+
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Ulid::next_after(Ulid::NIL, state.clock.now()).to_string());
+
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_error = status.is_client_error() || status.is_server_error();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let is_json = content_type.is_some_and(|kind| kind.starts_with("application/json"));
+    // Bare status codes and plain-text errors still get an `error` object.
+    let wrappable = match content_type {
+        _ if is_json => true,
+        Some(kind) => is_error && kind.starts_with("text/plain"),
+        None => is_error,
+    };
+    if !wrappable {
+        return with_request_id(response, &request_id);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY).await else {
+        return with_request_id(Response::from_parts(parts, Body::empty()), &request_id);
+    };
+    let payload = if is_json {
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+    } else {
+        Value::from(String::from_utf8_lossy(&bytes).into_owned())
+    };
+
+    let meta = json!({
+        "request_id": request_id,
+        "timestamp": state.config.timestamp(state.clock.now()),
+    });
+    let wrapped = if is_error {
+        let error = match payload {
+            Value::Object(_) => payload,
+            Value::String(message) if !message.is_empty() => {
+                json!({ "status": status.as_u16(), "message": message })
+            }
+            _ => json!({
+                "status": status.as_u16(),
+                "message": status.canonical_reason().unwrap_or_default(),
+            }),
+        };
+        json!({ "error": error, "meta": meta })
+    } else {
+        json!({ "data": payload, "meta": meta })
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    with_request_id((parts, Json(wrapped)).into_response(), &request_id)
+}
+
+fn with_request_id(mut response: Response, request_id: &str) -> Response {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use serde_json::json;
+
+    use crate::{
+        config::Config,
+        testing::{bank, get, post, send, timestamp, START},
+    };
+
+    fn enveloped() -> Config {
+        Config {
+            response_envelope: true,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn successes_carry_data_and_the_echoed_request_id() {
+        let (state, _) = bank(enveloped());
+        let mut request = post(
+            "/clientes/1/transacoes",
+            json!({"valor": 100, "tipo": "C", "descricao": "envelope"}),
+        );
+        request
+            .headers_mut()
+            .insert("x-request-id", "pedido-1".parse().unwrap());
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["saldo"], 100);
+        assert_eq!(body["meta"]["request_id"], "pedido-1");
+        assert_eq!(body["meta"]["timestamp"], timestamp(START));
+        assert!(body.get("error").is_none());
+
+        // Without one, an id is generated.
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(body["data"]["saldo"]["total"], 100);
+        assert_eq!(body["meta"]["request_id"].as_str().unwrap().len(), 26);
+    }
+
+    #[tokio::test]
+    async fn errors_carry_error_instead_of_data() {
+        let (state, _) = bank(enveloped());
+        let (status, body) = send(&state, get("/clientes/99/extrato")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["code"], "conta_nao_encontrada");
+        assert!(body.get("data").is_none());
+        assert!(body["meta"]["request_id"].is_string());
+
+        // A plain-text rejection from axum itself is wrapped as well.
+        let request = Request::post("/clientes/1/transacoes")
+            .header("content-type", "application/json")
+            .body(Body::from("{"))
+            .unwrap();
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["message"].is_string());
+    }
+
+    #[tokio::test]
+    async fn flat_by_default() {
+        let (state, _) = bank(Config::default());
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert!(body.get("data").is_none());
+        assert_eq!(body["saldo"]["total"], 0);
+    }
+}
@@ -0,0 +1,160 @@
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::{
+    currency::Formatter,
+    i18n::{self, Language},
+    transact::TransactError,
+    validation::{self, FieldError},
+};
+
+pub const UNKNOWN_ACCOUNT: &str = "conta nao encontrada";
+pub const MALFORMED_JSON: &str = "corpo JSON invalido";
+pub const UNKNOWN_FIELD: &str = "campo desconhecido";
+pub const SAME_ACCOUNT: &str = "as contas precisam ser distintas";
+pub const INVALID_LIMIT: &str = "limite invalido";
+pub const LIMIT_ABOVE_MAX: &str = "limite acima do maximo";
+
+/// Why a handler refused a request. Rendered as `{code, message}` like every
+/// other error body, in the language `i18n::negotiate` picked for the
+/// request, with whatever figures explain it merged in.
+#[derive(Debug)]
+pub enum AppError {
+    /// No account with the path's (or the body's) id.
+    UnknownAccount,
+    /// Something else the path names (a transaction, hold, schedule) is not
+    /// there; the reason says what.
+    NotFound(&'static str),
+    /// Not JSON, or not the shape the route takes. `detail` is serde's text.
+    MalformedJson {
+        status: StatusCode,
+        detail: String,
+    },
+    /// `BANK_STRICT_JSON` and a top-level field the route does not know.
+    UnknownField(String),
+    /// One or more fields refused; the first one's reason is the body's
+    /// `code`, and `campos` lists them all. Never empty.
+    Fields(Vec<FieldError>),
+    /// Well-formed but not accepted as sent.
+    BadRequest(&'static str),
+    Invalid(&'static str),
+    Conflict(&'static str),
+    /// Any other refusal with its own status, e.g. outside business hours.
+    Status(StatusCode, &'static str),
+    /// `Account::transact` said no.
+    Transact(TransactError, Formatter),
+    /// Entry `index` of a batch body was refused; its `indice` joins the body.
+    Entry {
+        index: usize,
+        error: Box<AppError>,
+    },
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::UnknownAccount | Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::MalformedJson { status, .. } => *status,
+            Self::UnknownField(_) | Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Status(status, _) => *status,
+            Self::Fields(errors) => validation::status(errors),
+            Self::Transact(error, _) => error.status(),
+            Self::Entry { error, .. } => error.status(),
+        }
+    }
+
+    /// The pt-BR reason `i18n` looks the code and translation up by.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::UnknownAccount => UNKNOWN_ACCOUNT,
+            Self::MalformedJson { .. } => MALFORMED_JSON,
+            Self::UnknownField(_) => UNKNOWN_FIELD,
+            Self::Fields(errors) => errors.first().map_or(MALFORMED_JSON, |error| error.reason),
+            Self::NotFound(reason)
+            | Self::BadRequest(reason)
+            | Self::Invalid(reason)
+            | Self::Conflict(reason)
+            | Self::Status(_, reason) => reason,
+            Self::Transact(error, _) => error.reason(),
+            Self::Entry { error, .. } => error.reason(),
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            Self::MalformedJson { detail, .. } => json!({ "detalhe": detail }),
+            Self::UnknownField(field) => json!({ "campo": field }),
+            Self::Fields(errors) => json!({
+                "campo": errors.first().map(|error| error.field),
+                "campos": validation::render(errors, Language::current()),
+            }),
+            Self::Transact(error, formatter) => error.details(*formatter),
+            Self::Entry { index, error } => {
+                let mut details = error.details();
+                details["indice"] = json!(index);
+                details
+            }
+            _ => json!({}),
+        }
+    }
+
+    /// The response body: code and message in the request's language, plus
+    /// whatever details the error carries.
+    pub(crate) fn body(&self) -> Value {
+        let mut body = i18n::body(self.reason(), Language::current());
+        if let (Some(body), Value::Object(details)) = (body.as_object_mut(), self.details()) {
+            body.extend(details);
+        }
+        body
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
+
+/// So handlers answering `Result<_, Response>` can still use `?` on these.
+impl From<AppError> for Response {
+    fn from(error: AppError) -> Self {
+        error.into_response()
+    }
+}
+
+/// In place of axum's plain-text answer for a bad body.
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::MalformedJson {
+            status: rejection.status(),
+            detail: rejection.body_text(),
+        }
+    }
+}
+
+/// `Json<T>` refusing a bad body with an `AppError`. See `strict::CheckedJson`
+/// for the transaction body.
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, AppError> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        Ok(Self(value))
+    }
+}
@@ -0,0 +1,308 @@
+//! `BANK_GRPC_BIND`: the `bank.v1.Bank` service of `proto/bank.proto`, for
+//! internal callers, next to the HTTP listener and over the same
+//! `BankService`. Needs the `grpc` feature.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::http::StatusCode;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{Code, Request, Response, Status};
+
+use crate::{
+    config::Config,
+    domain::{TransactionRequest, TransactionType},
+    error::AppError,
+    http::MAINTENANCE,
+    registry::AccountId,
+    replication::Role,
+    service::{BankService, Options},
+    storage, strict, AppState, Transaction,
+};
+
+#[allow(clippy::all)]
+mod proto {
+    tonic::include_proto!("bank.v1");
+}
+
+use proto::{
+    bank_server::{Bank, BankServer},
+    CreateTransactionRequest, CreateTransactionResponse, GetStatementRequest, Kind, Statement,
+    StreamTransactionsRequest,
+};
+
+/// Writes go to the leader, as `replication::forward` sends HTTP ones; this
+/// listener does not proxy.
+pub const FOLLOWER: &str = "replica: escritas somente no lider";
+
+/// Serves until the same shutdown signal as the HTTP listener.
+pub async fn serve(state: Arc<AppState>, bind: SocketAddr) {
+    let bank = BankServer::new(Grpc(BankService::new(state)));
+    let served = tonic::transport::Server::builder()
+        .add_service(bank)
+        .serve_with_shutdown(bind, crate::shutdown_signal())
+        .await;
+    if let Err(err) = served {
+        tracing::error!(error = %err, %bind, "grpc listener failed");
+    }
+}
+
+struct Grpc(BankService);
+
+impl Grpc {
+    /// What `reject_during_maintenance` and `reject_without_storage` refuse
+    /// on the HTTP side, and any write on a follower.
+    fn writable(&self) -> Result<(), Status> {
+        let state = self.0.state();
+        if state.maintenance.load(std::sync::atomic::Ordering::SeqCst) {
+            return Err(status(AppError::Status(
+                StatusCode::SERVICE_UNAVAILABLE,
+                MAINTENANCE,
+            )));
+        }
+        if state
+            .storage
+            .as_ref()
+            .is_some_and(|storage| !storage.is_available())
+        {
+            return Err(status(AppError::Status(
+                StatusCode::SERVICE_UNAVAILABLE,
+                storage::UNAVAILABLE,
+            )));
+        }
+        if state
+            .replication
+            .as_ref()
+            .is_some_and(|replication| replication.role() == Role::Follower)
+        {
+            return Err(Status::failed_precondition(FOLLOWER));
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Bank for Grpc {
+    /// Checked like the JSON body of `POST /clientes/:id/transacoes`, by
+    /// building that body; a future `realizada_em` cannot be sent here.
+    async fn create_transaction(
+        &self,
+        request: Request<CreateTransactionRequest>,
+    ) -> Result<Response<CreateTransactionResponse>, Status> {
+        self.writable()?;
+        let request = request.into_inner();
+        let state = self.0.state();
+        let kind = match Kind::try_from(request.tipo) {
+            Ok(Kind::Credit) => json!("C"),
+            Ok(Kind::Debit) => json!("D"),
+            _ => json!(null),
+        };
+        let body = json!({
+            "valor": request.valor,
+            "tipo": kind,
+            "descricao": request.descricao,
+            "moeda": Some(request.moeda).filter(|code| !code.is_empty()),
+        });
+        let transaction =
+            strict::from_value::<TransactionRequest>(&body, &state.config).map_err(status)?;
+        let options = Options {
+            expected_version: request.versao,
+            ..Options::default()
+        };
+        let result = self
+            .0
+            .transact(AccountId(request.account), transaction, options)
+            .await
+            .map_err(status)?
+            .map_err(|error| status(AppError::Transact(error, state.config.formatter())))?;
+        Ok(Response::new(CreateTransactionResponse {
+            id: result.transaction.id.to_string(),
+            seq: result.transaction.seq,
+            limite: result.limit,
+            saldo: result.balance,
+            versao: result.version,
+        }))
+    }
+
+    async fn get_statement(
+        &self,
+        request: Request<GetStatementRequest>,
+    ) -> Result<Response<Statement>, Status> {
+        let account_id = AccountId(request.into_inner().account);
+        let state = self.0.state();
+        let entry = state
+            .accounts
+            .get(&account_id)
+            .ok_or_else(|| status(AppError::UnknownAccount))?;
+        let account = entry.account.read().await;
+        let now = state.clock.now();
+        Ok(Response::new(Statement {
+            account: account_id.0,
+            total: account.balance,
+            limite: account.limit,
+            disponivel: account.available(now),
+            versao: account.version,
+            data_extrato: rfc3339(now),
+            ultimas_transacoes: account
+                .transactions
+                .iter()
+                .map(|transaction| message(transaction, &state.config))
+                .collect(),
+        }))
+    }
+
+    type StreamTransactionsStream = BoxStream<'static, Result<proto::Transaction, Status>>;
+
+    async fn stream_transactions(
+        &self,
+        request: Request<StreamTransactionsRequest>,
+    ) -> Result<Response<Self::StreamTransactionsStream>, Status> {
+        let account_id = AccountId(request.into_inner().account);
+        let state = self.0.state().clone();
+        let entry = state
+            .accounts
+            .get(&account_id)
+            .ok_or_else(|| status(AppError::UnknownAccount))?;
+        let receiver = entry.events.subscribe();
+        drop(entry);
+
+        let transactions = stream::unfold(Some(receiver), move |receiver| {
+            let state = state.clone();
+            async move {
+                let mut receiver = receiver?;
+                match receiver.recv().await {
+                    Ok(transaction) => {
+                        Some((Ok(message(&transaction, &state.config)), Some(receiver)))
+                    }
+                    Err(RecvError::Lagged(missed)) => Some((
+                        Err(Status::data_loss(format!(
+                            "{missed} transacoes perdidas; assine de novo"
+                        ))),
+                        None,
+                    )),
+                    Err(RecvError::Closed) => None,
+                }
+            }
+        });
+        Ok(Response::new(transactions.boxed()))
+    }
+}
+
+/// Amounts in minor units whatever `BANK_AMOUNT_FORMAT` says, and the
+/// currency always named.
+fn message(transaction: &Transaction, config: &Config) -> proto::Transaction {
+    let kind = match transaction.kind {
+        TransactionType::Credit => Kind::Credit,
+        TransactionType::Debit => Kind::Debit,
+    };
+    proto::Transaction {
+        id: transaction.id.to_string(),
+        seq: transaction.seq,
+        valor: transaction.value,
+        tipo: kind.into(),
+        descricao: transaction.description.0.clone(),
+        moeda: transaction
+            .currency
+            .unwrap_or(config.currency)
+            .code()
+            .to_string(),
+        realizada_em: rfc3339(transaction.create_at),
+    }
+}
+
+fn rfc3339(at: time::OffsetDateTime) -> String {
+    at.format(&Rfc3339).unwrap_or_default()
+}
+
+/// The HTTP status mapped to its gRPC code, with the JSON error body the
+/// HTTP API would send as the message.
+fn status(error: AppError) -> Status {
+    Status::new(code(error.status()), error.body().to_string())
+}
+
+fn code(status: StatusCode) -> Code {
+    match status {
+        StatusCode::BAD_REQUEST => Code::InvalidArgument,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        StatusCode::CONFLICT => Code::Aborted,
+        StatusCode::UNPROCESSABLE_ENTITY => Code::FailedPrecondition,
+        StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+        StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+        StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => Code::DeadlineExceeded,
+        _ => Code::Internal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::bank, transact::TransactError};
+
+    #[tokio::test]
+    async fn a_refused_debit_carries_the_http_code() {
+        let (state, _) = bank(Config::default());
+        let grpc = Grpc(BankService::new(state));
+
+        let request = CreateTransactionRequest {
+            account: 1,
+            valor: 1_000_000_000,
+            tipo: Kind::Debit.into(),
+            descricao: "grpc".to_string(),
+            ..Default::default()
+        };
+        let refused = grpc
+            .create_transaction(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+        let body: serde_json::Value = serde_json::from_str(refused.message()).unwrap();
+        let expected = AppError::Transact(
+            TransactError::InsufficientLimit {
+                needed: 0,
+                available: 0,
+            },
+            Config::default().formatter(),
+        );
+        assert_eq!(body["code"], expected.body()["code"]);
+
+        let request = CreateTransactionRequest {
+            account: 1,
+            valor: 100,
+            tipo: Kind::Credit.into(),
+            descricao: "grpc".to_string(),
+            ..Default::default()
+        };
+        let created = grpc
+            .create_transaction(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        let statement = grpc
+            .get_statement(Request::new(GetStatementRequest { account: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(statement.total, created.saldo);
+        assert_eq!(statement.ultimas_transacoes[0].id, created.id);
+    }
+
+    #[tokio::test]
+    async fn an_unspecified_kind_is_a_field_error() {
+        let (state, _) = bank(Config::default());
+        let grpc = Grpc(BankService::new(state));
+        let request = CreateTransactionRequest {
+            account: 1,
+            valor: 100,
+            descricao: "grpc".to_string(),
+            ..Default::default()
+        };
+        let refused = grpc
+            .create_transaction(Request::new(request))
+            .await
+            .unwrap_err();
+        let body: serde_json::Value = serde_json::from_str(refused.message()).unwrap();
+        assert_eq!(body["campo"], "tipo");
+    }
+}
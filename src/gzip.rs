@@ -0,0 +1,144 @@
+//! gzip members for archived history segments, through `flate2`, so
+//! `zcat` reads what this writes and this reads what `gzip -9` writes.
+//! Without the `archive` feature `BANK_ARCHIVE_AFTER_MS` is refused, and
+//! segments an earlier build wrote cannot be read back.
+
+use std::io;
+
+#[cfg(feature = "archive")]
+pub fn encode(data: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// An error when `data` is not one well-formed gzip member.
+#[cfg(feature = "archive")]
+pub fn decode(data: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut text = Vec::new();
+    flate2::read::GzDecoder::new(data).read_to_end(&mut text)?;
+    Ok(text)
+}
+
+#[cfg(not(feature = "archive"))]
+pub fn encode(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "archive"))]
+pub fn decode(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "archive"))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "gzip requer o recurso archive")
+}
+
+#[cfg(all(test, feature = "archive"))]
+mod tests {
+    use super::{decode, encode};
+
+    /// `zlib.compressobj(0, DEFLATED, 31)` of `{"valor":1}\n`: one stored
+    /// block.
+    const STORED: [u8; 35] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x0c, 0x00, 0xf3, 0xff,
+        0x7b, 0x22, 0x76, 0x61, 0x6c, 0x6f, 0x72, 0x22, 0x3a, 0x31, 0x7d, 0x0a, 0x2a, 0x95, 0x98,
+        0x87, 0x0c, 0x00, 0x00, 0x00,
+    ];
+    /// The same with `Z_FIXED`, of two lines sharing most of their bytes.
+    const FIXED: [u8; 51] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x56, 0x2a, 0x4b, 0xcc,
+        0xc9, 0x2f, 0x52, 0xb2, 0x32, 0x34, 0x30, 0xd0, 0x51, 0x2a, 0xc9, 0x2c, 0xc8, 0x57, 0xb2,
+        0x52, 0x72, 0x56, 0xaa, 0xe5, 0xaa, 0xc6, 0x26, 0xe1, 0x02, 0x94, 0x00, 0x00, 0x77, 0x15,
+        0x78, 0x95, 0x32, 0x00, 0x00, 0x00,
+    ];
+    /// Level 9 of `lines()`, which zlib codes with a dynamic block.
+    const DYNAMIC: [u8; 172] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x85, 0xd2, 0xbb, 0x0a, 0x83,
+        0x40, 0x10, 0x85, 0xe1, 0x3e, 0x4f, 0x11, 0xa6, 0xb6, 0xd8, 0xd9, 0xfb, 0xda, 0x26, 0x2f,
+        0x22, 0x6a, 0xb1, 0x90, 0x60, 0x30, 0x92, 0x46, 0x7c, 0xf7, 0xa0, 0xc5, 0x99, 0x14, 0x61,
+        0xec, 0xb4, 0xf8, 0xf8, 0xe1, 0xec, 0xac, 0x54, 0x07, 0x6a, 0x4d, 0x43, 0x9f, 0xee, 0x31,
+        0xcd, 0xc7, 0xd7, 0x52, 0x5f, 0x13, 0xb5, 0x74, 0xa3, 0x86, 0x86, 0xf1, 0xdd, 0xcf, 0xb5,
+        0xef, 0xf6, 0xff, 0xba, 0x8c, 0xcf, 0xab, 0xa1, 0xed, 0xb2, 0x1e, 0x84, 0x41, 0x5c, 0x82,
+        0xb9, 0xff, 0x33, 0x0c, 0x63, 0x61, 0x92, 0xd7, 0x3b, 0x16, 0xc6, 0xc1, 0x30, 0xb3, 0x1e,
+        0x72, 0x40, 0x5e, 0x90, 0xcf, 0x7a, 0xc9, 0x03, 0x05, 0x41, 0x39, 0xe8, 0xa5, 0x00, 0x14,
+        0x81, 0xac, 0xb5, 0x7a, 0x29, 0x02, 0x25, 0x41, 0xa1, 0xe8, 0x25, 0x19, 0x3c, 0x0b, 0x2a,
+        0x51, 0x2f, 0xc9, 0xe2, 0x45, 0x5e, 0xc9, 0x39, 0xbd, 0x24, 0x93, 0xb3, 0xf9, 0x79, 0xdb,
+        0x93, 0x83, 0x90, 0xcd, 0x59, 0x2e, 0xc2, 0x9b, 0x93, 0x93, 0xd8, 0x47, 0xff, 0x02, 0x1d,
+        0xe3, 0xfc, 0x4f, 0x7a, 0x02, 0x00, 0x00,
+    ];
+    /// `gzip.GzipFile(filename="segmento.jsonl")` of `{"valor":1}\n`: the
+    /// name is skipped.
+    const NAMED: [u8; 47] = [
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x73, 0x65, 0x67, 0x6d, 0x65,
+        0x6e, 0x74, 0x6f, 0x2e, 0x6a, 0x73, 0x6f, 0x6e, 0x6c, 0x00, 0xab, 0x56, 0x2a, 0x4b, 0xcc,
+        0xc9, 0x2f, 0x52, 0xb2, 0x32, 0xac, 0xe5, 0x02, 0x00, 0x2a, 0x95, 0x98, 0x87, 0x0c, 0x00,
+        0x00, 0x00,
+    ];
+
+    /// Twelve history-like lines.
+    fn lines() -> Vec<u8> {
+        (0..12)
+            .map(|id| {
+                let kind = ["C", "D"][id % 2];
+                let (value, item) = (id * 37, id % 7);
+                format!(
+                    "{{\"id\":{id},\"valor\":{value},\"tipo\":\"{kind}\",\"descricao\":\"item {item}\"}}\n"
+                )
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn members_zlib_wrote_decode() {
+        assert_eq!(decode(&STORED).unwrap(), b"{\"valor\":1}\n");
+        assert_eq!(
+            decode(&FIXED).unwrap(),
+            b"{\"valor\":100,\"tipo\":\"C\"}\n{\"valor\":100,\"tipo\":\"D\"}\n"
+        );
+        assert_eq!(decode(&DYNAMIC).unwrap(), lines());
+        assert_eq!(decode(&NAMED).unwrap(), b"{\"valor\":1}\n");
+    }
+
+    #[test]
+    fn what_is_encoded_decodes_back() {
+        // Long runs, for matches of the longest length and farthest distance.
+        let repeated = lines().repeat(100);
+        // Little to match, every byte value.
+        let mut seed = 1u32;
+        let noise: Vec<u8> = (0..70_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        for data in [Vec::new(), b"a".to_vec(), lines(), repeated.clone(), noise] {
+            assert_eq!(
+                decode(&encode(&data).unwrap()).unwrap(),
+                data,
+                "{} bytes",
+                data.len()
+            );
+        }
+        assert!(encode(&repeated).unwrap().len() < repeated.len() / 20);
+    }
+
+    #[test]
+    fn damaged_members_are_refused() {
+        let mut crc = DYNAMIC;
+        crc[DYNAMIC.len() - 8] ^= 1;
+        assert!(decode(&crc).is_err());
+        let mut size = STORED;
+        size[STORED.len() - 4] ^= 1;
+        assert!(decode(&size).is_err());
+        assert!(decode(&DYNAMIC[..DYNAMIC.len() - 1]).is_err());
+        assert!(decode(&FIXED[..30]).is_err());
+        assert!(decode(b"{\"valor\":1}\n").is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Map, Value};
+
+use crate::AppState;
+
+/// Longest a backend round trip may take before it counts as down.
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Liveness: the process answers. Nothing else is checked, so a database
+/// outage never gets the pod restarted, only taken out of rotation.
+pub async fn live() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: `503` while any configured dependency is unreachable, with
+/// each one's status so the failing one is obvious. Only what is configured
+/// is listed; without any the answer is `200` with nothing under
+/// `dependencias`. The listener is bound only after the WAL, snapshot and
+/// database are replayed, so a starting instance is never ready early.
+pub async fn ready(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let mut dependencies = Map::new();
+    if let Some(wal) = &state.wal {
+        dependencies.insert(
+            "wal".into(),
+            status(wal.check().map_err(|err| err.to_string())),
+        );
+    }
+    if let Some(history) = &state.history {
+        dependencies.insert(
+            "historico".into(),
+            status(history.check().map_err(|err| err.to_string())),
+        );
+    }
+    if let Some(ledger) = &state.ledger {
+        dependencies.insert(
+            "ledger".into(),
+            status(ledger.check().map_err(|err| err.to_string())),
+        );
+    }
+    if let Some(storage) = &state.storage {
+        let reached = tokio::time::timeout(PING_TIMEOUT, storage.ping())
+            .await
+            .unwrap_or_else(|_| Err(format!("sem resposta em {}ms", PING_TIMEOUT.as_millis())));
+        let mut report = status(reached);
+        report["pendentes"] = storage.pending().into();
+        dependencies.insert("armazenamento".into(), report);
+    }
+
+    let ready = dependencies
+        .values()
+        .all(|dependency| dependency["status"] == "ok");
+    let code = match ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (
+        code,
+        Json(json!({
+            "status": if ready { "ok" } else { "indisponivel" },
+            "dependencias": dependencies,
+        })),
+    )
+}
+
+fn status(reached: Result<(), String>) -> Value {
+    match reached {
+        Ok(()) => json!({ "status": "ok" }),
+        Err(err) => json!({ "status": "falha", "erro": err }),
+    }
+}
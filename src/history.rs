@@ -0,0 +1,375 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    accounting::signed,
+    gzip,
+    observer::{AccountObserver, Outcome},
+    registry::AccountId,
+    rotation::{self, RotatingFile, Rotation},
+    ulid::Ulid,
+    Transaction,
+};
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    account: AccountId,
+    /// `None` marks a purge: what came before it for the account is gone.
+    #[serde(rename = "transacao", default, skip_serializing_if = "Option::is_none")]
+    transaction: Option<Transaction>,
+}
+
+/// One account's share of an archived segment: the checkpoint that lets
+/// balances be worked out without decompressing it.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Aggregate {
+    #[serde(rename = "transacoes")]
+    pub count: u64,
+    /// Credits minus debits, base currency only.
+    #[serde(rename = "saldo")]
+    pub net: i64,
+    /// The segment holds a purge of the account.
+    #[serde(rename = "apagada", default)]
+    pub purged: bool,
+}
+
+/// A compressed file of transactions moved out of the live history, with
+/// what each account has in it. Listed in the order they were written.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Segment {
+    #[serde(rename = "arquivo")]
+    pub file: String,
+    /// Everything in the segment is stamped before this.
+    #[serde(rename = "corte", with = "time::serde::rfc3339")]
+    pub cutoff: OffsetDateTime,
+    #[serde(rename = "de", with = "time::serde::rfc3339::option")]
+    pub first: Option<OffsetDateTime>,
+    #[serde(rename = "ate", with = "time::serde::rfc3339::option")]
+    pub last: Option<OffsetDateTime>,
+    #[serde(rename = "contas")]
+    pub accounts: BTreeMap<AccountId, Aggregate>,
+}
+
+/// An account's transactions stamped within `[from, to)`, archived or not,
+/// and what it takes to put balances around them.
+pub struct Period {
+    pub transactions: Vec<Transaction>,
+    /// Base-currency net of every transaction on file stamped at or after
+    /// `to`, archived or still live.
+    pub net_after: i64,
+    /// The newest id on file for the account; anything newer landed after
+    /// the files were read.
+    pub newest: Option<Ulid>,
+    /// The account was purged at some point, so what is on file no longer
+    /// adds up to its balance.
+    pub purged: bool,
+    /// The segments that had to be decompressed.
+    pub read: Vec<String>,
+    pub archived_until: Option<OffsetDateTime>,
+}
+
+/// `BANK_HISTORY_FILE`: every accepted transaction, kept for good. Unlike
+/// the WAL, checkpoints never drop its segments, and unlike the statement
+/// ring it is not capped, so exports can cover an account's whole life.
+/// With `BANK_ARCHIVE_AFTER_MS` the older part moves into gzip segments
+/// listed in `<file>.arquivo.json`, still read back by everything here.
+pub struct Archive {
+    path: PathBuf,
+    file: RotatingFile,
+    segments: Mutex<Vec<Segment>>,
+    /// Held for writing while `compact` swaps files, so a read never finds
+    /// one half moved.
+    moving: RwLock<()>,
+}
+
+impl AccountObserver for Archive {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        if outcome.is_ok() {
+            self.write(account_id, Some(transaction.clone()));
+        }
+    }
+}
+
+impl Archive {
+    pub fn open(path: &Path, rotation: Rotation) -> io::Result<Self> {
+        let index = index_path(path);
+        let segments = match index.exists() {
+            true => serde_json::from_reader(File::open(&index)?)?,
+            false => Vec::new(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: RotatingFile::open(path, rotation)?,
+            segments: Mutex::new(segments),
+            moving: RwLock::new(()),
+        })
+    }
+
+    /// Follows `DELETE /clientes/:id/transacoes`: the archive is not a way
+    /// around a privacy request.
+    pub fn purge(&self, account_id: AccountId) {
+        self.write(account_id, None);
+    }
+
+    /// For `/readyz`: appends would still land.
+    pub fn check(&self) -> io::Result<()> {
+        self.file.check()
+    }
+
+    fn write(&self, account_id: AccountId, transaction: Option<Transaction>) {
+        let line = Line {
+            account: account_id,
+            transaction,
+        };
+        let line = format!("{}\n", serde_json::to_string(&line).unwrap());
+        if let Err(err) = self.file.append(line.as_bytes(), false) {
+            tracing::error!(error = %err, line = line.trim_end(), "history write failed");
+        }
+    }
+
+    /// The account's archived transactions, oldest first. Reads every
+    /// segment, so it blocks; call it off the runtime.
+    pub fn read(&self, account_id: AccountId) -> io::Result<Vec<Transaction>> {
+        let _moving = self.moving.read().unwrap();
+        let segments = self.segments.lock().unwrap().clone();
+        let mut transactions = Vec::new();
+        // A compaction cut short leaves lines both archived and live.
+        let mut seen = HashSet::new();
+        let mut take = |line: Line| {
+            if line.account != account_id {
+                return;
+            }
+            match line.transaction {
+                Some(transaction) if seen.insert(transaction.id) => transactions.push(transaction),
+                Some(_) => {}
+                None => transactions.clear(),
+            }
+        };
+        for segment in &segments {
+            self.archived(segment)?.into_iter().for_each(&mut take);
+        }
+        self.live(&mut take)?;
+        Ok(transactions)
+    }
+
+    /// What `GET /clientes/:id/arquivo` needs. Segments wholly at or after
+    /// `to` are counted from their checkpoint and those wholly before
+    /// `from` skipped; only the rest, and any holding a purge, are
+    /// decompressed. Blocks.
+    pub fn period(
+        &self,
+        account_id: AccountId,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> io::Result<Period> {
+        let _moving = self.moving.read().unwrap();
+        let segments = self.segments.lock().unwrap().clone();
+        let mut period = Period {
+            transactions: Vec::new(),
+            net_after: 0,
+            newest: None,
+            purged: false,
+            read: Vec::new(),
+            archived_until: segments.iter().map(|segment| segment.cutoff).max(),
+        };
+        // A compaction cut short leaves lines both archived and live.
+        let mut seen = HashSet::new();
+        let mut take = |line: Line, period: &mut Period| {
+            if line.account != account_id {
+                return;
+            }
+            let Some(transaction) = line.transaction else {
+                period.transactions.clear();
+                period.purged = true;
+                return;
+            };
+            if !seen.insert(transaction.id) {
+                return;
+            }
+            if transaction.create_at >= to {
+                if transaction.currency.is_none() {
+                    period.net_after += signed(&transaction);
+                }
+            } else if transaction.create_at >= from {
+                period.transactions.push(transaction);
+            }
+        };
+        for segment in &segments {
+            let Some(aggregate) = segment.accounts.get(&account_id) else {
+                continue;
+            };
+            if segment.first.is_some_and(|first| first >= to) && !aggregate.purged {
+                period.net_after += aggregate.net;
+            } else if segment.last.is_some_and(|last| last >= from) || aggregate.purged {
+                for line in self.archived(segment)? {
+                    take(line, &mut period);
+                }
+                period.read.push(segment.file.clone());
+            }
+        }
+        // What is still live counts too: the period need not be archived yet.
+        let mut live = Vec::new();
+        self.live(&mut |line: Line| {
+            if line.account == account_id {
+                live.push(line);
+            }
+        })?;
+        for line in live {
+            if let Some(transaction) = &line.transaction {
+                period.newest = period.newest.max(Some(transaction.id));
+            }
+            take(line, &mut period);
+        }
+        period
+            .transactions
+            .sort_by_key(|transaction| (transaction.create_at, transaction.seq));
+        Ok(period)
+    }
+
+    /// Moves every live transaction stamped before `cutoff` into a new
+    /// gzip segment and rewrites what stays as one live file. Purges are
+    /// applied on the way: whatever of the account came before one is
+    /// dropped from both, and the purge goes to the segment so it also
+    /// clears older segments on read. `None` when there was nothing to
+    /// archive. Blocks, and holds reads off while it swaps files.
+    pub fn compact(&self, cutoff: OffsetDateTime) -> io::Result<Option<Segment>> {
+        let _moving = self.moving.write().unwrap();
+        self.file.rotate_now()?;
+        let rotated = rotation::rotated(&self.path)?;
+        let Some(newest) = rotated.last().cloned() else {
+            return Ok(None);
+        };
+
+        let (mut archived, mut kept): (Vec<Line>, Vec<Line>) = (Vec::new(), Vec::new());
+        let mut accounts: BTreeMap<AccountId, Aggregate> = BTreeMap::new();
+        let (mut first, mut last) = (None::<OffsetDateTime>, None::<OffsetDateTime>);
+        for path in &rotated {
+            for line in BufReader::new(File::open(path)?).lines() {
+                // A torn last line from a crash is dropped here for good.
+                let Ok(line) = serde_json::from_str::<Line>(&line?) else {
+                    continue;
+                };
+                match &line.transaction {
+                    None => {
+                        archived.retain(|earlier| earlier.account != line.account);
+                        kept.retain(|earlier| earlier.account != line.account);
+                        accounts.insert(
+                            line.account,
+                            Aggregate {
+                                purged: true,
+                                ..Aggregate::default()
+                            },
+                        );
+                        archived.push(line);
+                    }
+                    Some(transaction) if transaction.create_at < cutoff => {
+                        let aggregate = accounts.entry(line.account).or_default();
+                        aggregate.count += 1;
+                        if transaction.currency.is_none() {
+                            aggregate.net += signed(transaction);
+                        }
+                        first = first
+                            .min(Some(transaction.create_at))
+                            .or(Some(transaction.create_at));
+                        last = last.max(Some(transaction.create_at));
+                        archived.push(line);
+                    }
+                    Some(_) => kept.push(line),
+                }
+            }
+        }
+        if archived.is_empty() {
+            return Ok(None);
+        }
+
+        let mut segments = self.segments.lock().unwrap().clone();
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let segment = Segment {
+            file: format!("{name}.arquivo-{:06}.gz", segments.len() + 1),
+            cutoff,
+            first,
+            last,
+            accounts,
+        };
+        let directory = self.path.parent().unwrap_or(Path::new(""));
+        write_atomically(
+            &directory.join(&segment.file),
+            &gzip::encode(&lines(&archived))?,
+        )?;
+        segments.push(segment.clone());
+        write_atomically(
+            &index_path(&self.path),
+            &serde_json::to_vec_pretty(&segments)?,
+        )?;
+        *self.segments.lock().unwrap() = segments;
+
+        // Archived first: a crash from here on leaves lines in both places,
+        // which reads tell apart by id, never lines in neither.
+        write_atomically(&newest, &lines(&kept))?;
+        for path in &rotated[..rotated.len() - 1] {
+            fs::remove_file(path)?;
+        }
+        Ok(Some(segment))
+    }
+
+    fn archived(&self, segment: &Segment) -> io::Result<Vec<Line>> {
+        let directory = self.path.parent().unwrap_or(Path::new(""));
+        let compressed = fs::read(directory.join(&segment.file))?;
+        let text = gzip::decode(&compressed)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", segment.file)))?;
+        Ok(text
+            .split(|&byte| byte == b'\n')
+            .filter_map(|line| serde_json::from_slice(line).ok())
+            .collect())
+    }
+
+    /// Every line not archived yet, rotated files first.
+    fn live(&self, take: &mut impl FnMut(Line)) -> io::Result<()> {
+        for path in rotation::rotated(&self.path)?
+            .into_iter()
+            .chain([self.path.clone()])
+        {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                // A torn last line from a crash is skipped.
+                if let Ok(line) = serde_json::from_str::<Line>(&line) {
+                    take(line);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn index_path(path: &Path) -> PathBuf {
+    let mut index = path.as_os_str().to_owned();
+    index.push(".arquivo.json");
+    PathBuf::from(index)
+}
+
+fn lines(lines: &[Line]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for line in lines {
+        serde_json::to_writer(&mut out, line).unwrap();
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Through a temporary file, so a crash never leaves a torn one.
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    currency::AmountInput,
+    error::{AppError, JsonBody},
+    registry::AccountId,
+    render_transaction,
+    transact::TransactError,
+    ulid::Ulid,
+    Account, AppState, Config, Description, Transaction, TransactionType,
+};
+
+pub const NOT_FOUND: &str = "reserva nao encontrada";
+
+/// Card-style authorization: reserves part of the available balance without
+/// moving it until settled, released or expired.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Hold {
+    pub id: u64,
+    #[serde(rename = "valor")]
+    pub value: i64,
+    #[serde(rename = "descricao")]
+    pub description: Description,
+    #[serde(rename = "criada_em", with = "time::serde::rfc3339")]
+    pub placed_at: OffsetDateTime,
+    #[serde(rename = "expira_em", with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Holds {
+    #[serde(rename = "ultimo_id")]
+    next_id: u64,
+    #[serde(rename = "entradas")]
+    entries: Vec<Hold>,
+}
+
+impl Holds {
+    /// Sum of holds still in force at `now`; expired ones no longer count even
+    /// before `expire` drops them.
+    pub fn outstanding(&self, now: OffsetDateTime) -> i64 {
+        self.entries
+            .iter()
+            .filter(|hold| hold.expires_at > now)
+            .map(|hold| hold.value)
+            .sum()
+    }
+
+    fn expire(&mut self, now: OffsetDateTime) {
+        self.entries.retain(|hold| hold.expires_at > now);
+    }
+
+    fn take(&mut self, id: u64, now: OffsetDateTime) -> Option<Hold> {
+        self.expire(now);
+        let index = self.entries.iter().position(|hold| hold.id == id)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+impl Account {
+    /// Reserves `value` if a debit of that size would be accepted now.
+    fn place_hold(
+        &mut self,
+        value: i64,
+        description: Description,
+        now: OffsetDateTime,
+        config: &Config,
+    ) -> Result<&Hold, &'static str> {
+        self.holds.expire(now);
+        self.check_open()
+            .and_then(|()| self.check_debit(value, now).map(drop))
+            .map_err(TransactError::reason)?;
+        self.holds.next_id += 1;
+        self.holds.entries.push(Hold {
+            id: self.holds.next_id,
+            value,
+            description,
+            placed_at: now,
+            expires_at: now + config.hold_ttl,
+        });
+        Ok(self.holds.entries.last().unwrap())
+    }
+
+    /// What a debit may still take at `now`: balance plus the effective limit
+    /// (just the balance without overdraft), minus what holds have reserved.
+    /// The same figure `check_debit` enforces and statements show as
+    /// `disponivel`.
+    pub fn available(&self, now: OffsetDateTime) -> i64 {
+        // Saturating: a balance near the top of `i64` has room to spare.
+        let spendable = if self.overdraft() {
+            self.effective_limit(now).saturating_add(self.balance)
+        } else {
+            self.balance
+        };
+        spendable.saturating_sub(self.holds.outstanding(now))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HoldRequest {
+    #[serde(rename = "valor")]
+    value: AmountInput,
+    #[serde(rename = "descricao")]
+    description: Description,
+}
+
+fn render(hold: &Hold, config: &Config) -> Value {
+    json!({
+        "id": hold.id,
+        "valor": config.amount(hold.value),
+        "descricao": hold.description,
+        "criada_em": config.timestamp(hold.placed_at),
+        "expira_em": config.timestamp(hold.expires_at),
+    })
+}
+
+pub async fn place(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<HoldRequest>,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    let value = request
+        .value
+        .resolve(state.config.currency)
+        .map_err(AppError::Invalid)?;
+    let description = request
+        .description
+        .normalize(&state.config.description)
+        .map_err(AppError::Invalid)?;
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+
+    let now = state.clock.now();
+    let mut account = entry.account.write().await;
+    let before = account.holds.clone();
+    let hold = account
+        .place_hold(value, description, now, &state.config)
+        .map_err(AppError::Invalid)?;
+    let mut body = render(hold, &state.config);
+    if let Err(error) = state.persist(account_id, &account) {
+        account.holds = before;
+        return Err(error);
+    }
+    body["disponivel"] = state.config.amount(account.available(now));
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+pub async fn list(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let account = entry.account.read().await;
+    let now = state.clock.now();
+    let holds: Vec<Value> = account
+        .holds
+        .entries
+        .iter()
+        .filter(|hold| hold.expires_at > now)
+        .map(|hold| render(hold, &state.config))
+        .collect();
+    Ok(Json(json!({
+        "reservas": holds,
+        "disponivel": state.config.amount(account.available(now)),
+    })))
+}
+
+/// Turns the hold into a debit for its full value. The hold is released
+/// first, so its own reservation does not count against it; if the debit
+/// is still refused the hold is put back untouched.
+pub async fn settle(
+    Path((account_id, hold_id)): Path<(AccountId, u64)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let now = state.clock.now();
+    let mut account = entry.account.write().await;
+    let before = account.clone();
+    let hold = account
+        .holds
+        .take(hold_id, now)
+        .ok_or(AppError::NotFound(NOT_FOUND))?;
+
+    let transaction = Transaction {
+        id: Ulid::NIL,
+        value: hold.value,
+        kind: TransactionType::Debit,
+        description: hold.description.clone(),
+        create_at: now,
+        server_assigned_time: true,
+        seq: 0,
+        transfer: None,
+        reversal: None,
+        currency: None,
+        category: None,
+    };
+    match account.transact(transaction, now) {
+        Ok(result) => {
+            // The hold gone and the debit in, in one record.
+            if let Err(error) = state.persist(account_id, &account) {
+                *account = before;
+                return Err(error);
+            }
+            state.accepted_result(account_id, &result);
+            Ok(Json(json!({
+                "transacao": render_transaction(&result.transaction, &state.config),
+                "saldo": state.config.amount(result.balance),
+                "disponivel": state.config.amount(account.available(now)),
+                "versao": result.version,
+            })))
+        }
+        Err(error) => {
+            account.holds.entries.push(hold);
+            Err(AppError::Transact(error, state.config.formatter()))
+        }
+    }
+}
+
+pub async fn release(
+    Path((account_id, hold_id)): Path<(AccountId, u64)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let now = state.clock.now();
+    let mut account = entry.account.write().await;
+    let before = account.holds.clone();
+    if account.holds.take(hold_id, now).is_none() {
+        return Err(AppError::NotFound(NOT_FOUND));
+    }
+    if let Err(error) = state.persist(account_id, &account) {
+        account.holds = before;
+        return Err(error);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+
+    use crate::{
+        config::Config,
+        testing::{bank, delete, get, post, send},
+    };
+
+    fn hold(value: i64) -> axum::http::Request<axum::body::Body> {
+        post(
+            "/clientes/1/reservas",
+            json!({"valor": value, "descricao": "hotel"}),
+        )
+    }
+
+    async fn available(state: &std::sync::Arc<crate::AppState>) -> Value {
+        let (_, body) = send(state, get("/clientes/1/reservas")).await;
+        body["disponivel"].clone()
+    }
+
+    #[tokio::test]
+    async fn a_hold_reserves_and_settling_debits_it() {
+        let (state, _) = bank(Config::default());
+        let (status, placed) = send(&state, hold(30_000)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(placed["disponivel"], 70_000);
+
+        // The limit check counts the hold as spent.
+        let debit = json!({"valor": 70_001, "tipo": "D", "descricao": "saque"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", debit)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let path = format!("/clientes/1/reservas/{}/liquidar", placed["id"]);
+        let (status, settled) = send(&state, post(&path, json!({}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(settled["saldo"], -30_000);
+        assert_eq!(settled["disponivel"], 70_000);
+        assert_eq!(settled["transacao"]["descricao"], "hotel");
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], -30_000);
+        // Settled once only.
+        let (status, _) = send(&state, post(&path, json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn released_and_expired_holds_free_the_balance() {
+        let (state, clock) = bank(Config {
+            hold_ttl: Duration::from_secs(60 * 60),
+            ..Config::default()
+        });
+        let (_, released) = send(&state, hold(10_000)).await;
+        let path = format!("/clientes/1/reservas/{}", released["id"]);
+        let (status, _) = send(&state, delete(&path)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(available(&state).await, 100_000);
+
+        send(&state, hold(20_000)).await;
+        clock.advance(time::Duration::minutes(59));
+        assert_eq!(available(&state).await, 80_000);
+        clock.advance(time::Duration::minutes(1));
+        assert_eq!(available(&state).await, 100_000);
+        let (_, body) = send(&state, get("/clientes/1/reservas")).await;
+        assert_eq!(body["reservas"], json!([]));
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+    }
+}
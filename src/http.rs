@@ -0,0 +1,234 @@
+pub mod handlers;
+mod views;
+
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post},
+    Router,
+};
+use serde_json::Value;
+
+use crate::{
+    accounts, activity, archival, as_of, auth, batch, business_hours, capabilities, categories,
+    checksum, compare, correlation, deadline, debug, envelope, health, holds, i18n, ledger, limit,
+    live, metrics, openapi, pdf, persistence, projection, rate_limit, rejections, replication,
+    report, reversal, schedule, search, settlement, simulate, storage, transfer, webhook, AppState,
+    Transaction,
+};
+use crate::{config::Config, error::AppError};
+
+pub(crate) fn render_transaction(transaction: &Transaction, config: &Config) -> Value {
+    serde_json::to_value(views::TransactionView::new(transaction, config)).unwrap_or_default()
+}
+
+/// Every route, with the layers each group takes and the ones all share.
+pub fn router(state: Arc<AppState>) -> Router {
+    let writes = Router::new()
+        .route(
+            "/clientes/:id/transacoes",
+            post(handlers::create_transaction),
+        )
+        .route("/clientes/:id/transacoes/lote", post(batch::create))
+        .route("/clientes/:id/transferencias", post(transfer::create))
+        .route(
+            "/clientes/:id/transacoes/:tx_id/estorno",
+            post(reversal::create),
+        )
+        .route("/clientes/:id/agendamentos", post(schedule::create))
+        .route(
+            "/clientes/:id/transacoes/agendadas/:agendamento_id",
+            delete(schedule::cancel),
+        )
+        .route(
+            "/clientes/:id/agendamentos/:agendamento_id",
+            delete(schedule::cancel),
+        )
+        .route("/clientes/:id/reservas", post(holds::place))
+        .route("/clientes/:id/reservas/:reserva_id", delete(holds::release))
+        .route(
+            "/clientes/:id/reservas/:reserva_id/liquidar",
+            post(holds::settle),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            business_hours::enforce,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_without_storage,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_during_maintenance,
+        ));
+
+    let admin = Router::new()
+        .route("/clientes", post(accounts::create))
+        .route(
+            "/clientes/:id",
+            patch(accounts::update).delete(accounts::close),
+        )
+        .route(
+            "/clientes/:id/transacoes",
+            delete(handlers::purge_transactions),
+        )
+        .route("/clientes/:id/limite/transferir", post(limit::transfer))
+        .route("/clientes/:id/export", get(persistence::export))
+        .route("/clientes/:id/import", post(persistence::import))
+        .route("/settlement", post(settlement::apply))
+        .route("/relatorio.csv", get(report::csv))
+        .route("/webhooks", post(webhook::register).get(webhook::list))
+        .route("/webhooks/:webhook_id", delete(webhook::unregister))
+        .route("/webhooks/dlq", get(webhook::list_dead_letters))
+        .route("/webhooks/dlq/replay", post(webhook::replay_dead_letters))
+        .route("/replicacao", get(replication::status))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_without_storage,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_during_maintenance,
+        ))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
+
+    let debug = Router::new()
+        .route("/debug/locks", get(debug::locks))
+        .route("/debug/subscribers", get(debug::subscribers))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            debug::require_debug,
+        ));
+
+    let app = Router::new()
+        .route("/", get(handlers::service_info))
+        .route("/healthz", get(health::live))
+        .route("/readyz", get(health::ready))
+        .route("/capabilities", get(capabilities::describe))
+        .route("/openapi.json", get(openapi::document))
+        .route("/docs", get(openapi::ui))
+        .route("/clientes/:id/extrato", get(handlers::view_extrato))
+        .route("/clientes/:id/extrato.pdf", get(pdf::statement))
+        .route("/extratos", post(handlers::view_extratos))
+        .route("/clientes/compare", get(compare::compare))
+        .route("/clientes/:id/sse", get(handlers::stream_activity))
+        .route("/clientes/:id/extrato/stream", get(live::statement))
+        .route("/clientes/:id/agendamentos", get(schedule::list))
+        .route("/clientes/:id/transacoes/agendadas", get(schedule::list))
+        .route("/clientes/:id/atividade", get(activity::heatmap))
+        .route("/clientes/:id/rejeicoes", get(rejections::list))
+        .route("/clientes/:id/limite/preview", get(limit::preview))
+        .route("/clientes/:id/reservas", get(holds::list))
+        .route("/clientes/:id/simular", post(simulate::run))
+        .route("/clientes/:id/projecao", get(projection::project))
+        .route("/clientes/:id/transacoes/busca", get(search::search))
+        .route("/clientes/:id/ledger", get(ledger::events_of))
+        .route("/clientes/:id/saldo", get(as_of::balance))
+        .route("/clientes/:id/arquivo", get(archival::archived))
+        .route("/clientes/:id/resumo", get(categories::summary))
+        .route("/metrics", get(metrics::render))
+        // Its own token, checked in the handler; not an admin route.
+        .route("/interno/replicacao", post(replication::receive))
+        .merge(writes)
+        .merge(admin)
+        .merge(debug)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::enforce,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            deadline::enforce,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track_requests,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            envelope::wrap,
+        ))
+        .with_state(state.clone());
+    // Outside the router so the check digit is stripped before routing.
+    let router = Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            checksum::verify,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            replication::forward,
+        ));
+    #[cfg(feature = "json-log")]
+    let router = router.layer(crate::trace::requests());
+    router
+        .layer(middleware::from_fn(i18n::negotiate))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            correlation::propagate,
+        ))
+}
+
+pub(crate) const MAINTENANCE: &str = "em manutencao";
+
+/// Reads keep being served while the flag is set, the admin ones included:
+/// only `GET` and `HEAD` pass.
+pub(crate) async fn reject_during_maintenance(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let reading = matches!(*request.method(), Method::GET | Method::HEAD);
+    if !reading && state.maintenance.load(Ordering::SeqCst) {
+        return AppError::Status(StatusCode::SERVICE_UNAVAILABLE, MAINTENANCE).into_response();
+    }
+    next.run(request).await
+}
+
+/// While the database is unreachable nothing new is accepted: it could not
+/// be stored. Reads like the admin export still pass.
+pub(crate) async fn reject_without_storage(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let reading = matches!(*request.method(), Method::GET | Method::HEAD);
+    match &state.storage {
+        Some(storage) if !reading && !storage.is_available() => {
+            AppError::Status(StatusCode::SERVICE_UNAVAILABLE, storage::UNAVAILABLE).into_response()
+        }
+        _ => next.run(request).await,
+    }
+}
+
+/// `X-Admin-Token`, or an API key with the admin scope. Refusals are a
+/// `404`, same as an unknown account, unless `BANK_PRECISE_AUTH_ERRORS` asks
+/// for `403`: otherwise the status tells an unauthorized caller which account
+/// ids exist.
+pub(crate) async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let presented = request
+        .headers()
+        .get("x-admin-token")
+        .and_then(|value| value.to_str().ok());
+    let admin_key = request.extensions().get() == Some(&auth::Scope::Admin);
+    match (&state.config.admin_token, presented) {
+        _ if admin_key => next.run(request).await,
+        (Some(expected), Some(presented)) if expected == presented => next.run(request).await,
+        _ if state.config.precise_auth_errors => StatusCode::FORBIDDEN.into_response(),
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,351 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension, Json,
+};
+use futures_util::{future, stream, Stream};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use crate::{
+    accounting,
+    config::Config,
+    deadline::Deadline,
+    domain::{Account, Transaction, TransactionRequest},
+    error::{self, AppError, JsonBody},
+    http::{
+        render_transaction,
+        views::{self, StatementView, TransactionResponse},
+    },
+    idempotency::{self, Claim},
+    pagination::{self, StatementQuery},
+    precondition,
+    registry::AccountId,
+    schedule,
+    service::{BankService, Options},
+    strict::CheckedJson,
+    transact::TransactError,
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct TransactionQuery {
+    /// Best-effort debit, see `Account::transact_partial`.
+    #[serde(default, rename = "parcial")]
+    pub(crate) partial: bool,
+}
+
+pub(crate) async fn create_transaction(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<TransactionQuery>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    headers: HeaderMap,
+    CheckedJson(request): CheckedJson<TransactionRequest>,
+) -> Result<Response, AppError> {
+    let run_at = request.run_at(state.clock.now());
+    if run_at.is_some() && query.partial {
+        return Err(AppError::Invalid(schedule::PARTIAL));
+    }
+    let service = BankService::new(state.clone());
+    let transaction = service.prepare(request)?;
+
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let key = idempotency::key(&headers).map_err(AppError::BadRequest)?;
+    let expected = precondition::expected_version(&headers).map_err(AppError::BadRequest)?;
+    let ticket = match key {
+        Some(key) => match entry.idempotency.claim(
+            key,
+            &transaction,
+            query.partial,
+            state.clock.now(),
+            state.config.idempotency_ttl,
+        ) {
+            Claim::New(ticket) => Some(ticket),
+            Claim::Replay(outcome) => {
+                let response = render_outcome(outcome, &state.config);
+                return Ok(idempotency::replayed(response));
+            }
+            Claim::InFlight => return Err(AppError::Conflict(idempotency::IN_FLIGHT)),
+            Claim::Mismatch => return Err(AppError::Invalid(idempotency::MISMATCH)),
+        },
+        None => None,
+    };
+
+    // A future `realizada_em` queues it like `POST /clientes/:id/agendamentos`;
+    // limits are checked when it runs, not now.
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    if let Some(run_at) = run_at {
+        let mut account = state.write_account(&entry, deadline).await?;
+        let scheduled = match account.check_version(expected) {
+            Ok(()) => {
+                let before = (*account).clone();
+                let transaction = Transaction {
+                    create_at: run_at,
+                    ..transaction
+                };
+                let body =
+                    schedule::render(account.schedule.add(run_at, transaction), &state.config);
+                account.touch(state.clock.now());
+                if let Err(error) = state.persist(account_id, &account) {
+                    *account = before;
+                    return Err(error);
+                }
+                state.changed(account_id);
+                Ok(body)
+            }
+            // Not a refusal of the transaction: nothing goes to the
+            // rejection ring, as on the immediate path.
+            Err(error) => Err(error),
+        };
+        drop(account);
+        if let Some(ticket) = ticket {
+            ticket.complete(&scheduled, state.clock.now());
+        }
+        return Ok(match scheduled {
+            Ok(body) => (StatusCode::ACCEPTED, Json(body)).into_response(),
+            Err(error) => render_outcome(Err(error), &state.config),
+        });
+    }
+
+    let options = Options {
+        partial: query.partial,
+        expected_version: expected,
+        deadline,
+    };
+    let outcome = service.apply(account_id, transaction, options).await?;
+    let next_credit = match &outcome {
+        Err(TransactError::InsufficientLimit { .. }) => {
+            entry.account.read().await.schedule.next_credit()
+        }
+        _ => None,
+    };
+
+    let response =
+        |result| TransactionResponse::new(account_id, result, query.partial, &state.config);
+    // Only a claimed key needs the body as a `Value`, to replay it later.
+    let Some(ticket) = ticket else {
+        return Ok(match &outcome {
+            Ok(result) => views::json(&response(result)),
+            Err(error) => schedule::refusal(*error, next_credit, &state),
+        });
+    };
+    let outcome = outcome
+        .as_ref()
+        .map(|result| serde_json::to_value(response(result)).unwrap_or_default())
+        .map_err(|error| *error);
+    ticket.complete(&outcome, state.clock.now());
+    Ok(match outcome {
+        Ok(body) => Json(body).into_response(),
+        Err(error) => schedule::refusal(error, next_credit, &state),
+    })
+}
+
+pub(crate) fn render_outcome(outcome: idempotency::Outcome, config: &Config) -> Response {
+    match outcome {
+        Ok(body) => Json(body).into_response(),
+        Err(error) => AppError::Transact(error, config.formatter()).into_response(),
+    }
+}
+
+/// No account locks taken: just the size of the registry.
+pub(crate) async fn service_info(State(state): State<Arc<AppState>>) -> Json<Value> {
+    Json(json!({
+        "service": "bank",
+        "version": env!("CARGO_PKG_VERSION"),
+        "accounts": state.accounts.len(),
+    }))
+}
+
+/// As a `Value`, for callers that edit or re-encode it; `view_extrato`
+/// serializes the same `StatementView` directly when it can.
+pub(crate) fn statement(account_id: AccountId, account: &Account, state: &AppState) -> Value {
+    let mut body =
+        serde_json::to_value(StatementView::new(account_id, account, state)).unwrap_or_default();
+    if stale(state) {
+        body["stale"] = true.into();
+    }
+    body
+}
+
+/// The database is unreachable: memory still has every account, but not
+/// necessarily as stored.
+fn stale(state: &AppState) -> bool {
+    state
+        .storage
+        .as_ref()
+        .is_some_and(|storage| !storage.is_available())
+}
+
+pub(crate) async fn view_extrato(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<StatementQuery>,
+    Query(format): Query<accounting::FormatQuery>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match state.accounts.get(&account_id) {
+        Some(entry) => {
+            if let Some(format) = accounting::Format::requested(&format, &headers)? {
+                return accounting::export(&state, account_id, &entry, format).await;
+            }
+            let account = entry.account.read().await;
+            if !query.is_empty() {
+                let (page, metadata) = query
+                    .page(&account, &state.config)
+                    .ok_or(AppError::Invalid(pagination::INVALID))?;
+                let mut body = statement(account_id, &account, &state);
+                body["ultimas_transacoes"] = page.into();
+                body["paginacao"] = metadata;
+                return Ok(negotiate(&headers, body));
+            }
+            if wants_msgpack(&headers) || stale(&state) {
+                return Ok(negotiate(&headers, statement(account_id, &account, &state)));
+            }
+            if state.config.statement_cache {
+                return Ok(entry
+                    .statement
+                    .serve(account_id, &account, &state, &headers));
+            }
+            Ok(views::json(&StatementView::new(
+                account_id, &account, &state,
+            )))
+        }
+        None => Err(AppError::UnknownAccount),
+    }
+}
+
+/// JSON unless the client asks for MessagePack and the `msgpack` feature is
+/// built in. The MessagePack body is the same document, so timestamps stay
+/// the RFC 3339 strings the JSON carries.
+pub(crate) fn negotiate(headers: &HeaderMap, body: Value) -> Response {
+    if wants_msgpack(headers) {
+        if let Some(response) = msgpack(&body) {
+            return response;
+        }
+    }
+    Json(body).into_response()
+}
+
+pub(crate) fn wants_msgpack(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/msgpack"))
+}
+
+#[cfg(feature = "msgpack")]
+pub(crate) fn msgpack(body: &Value) -> Option<Response> {
+    Some(match rmp_serde::to_vec_named(body) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "application/msgpack")], bytes).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    })
+}
+
+#[cfg(not(feature = "msgpack"))]
+pub(crate) fn msgpack(_body: &Value) -> Option<Response> {
+    None
+}
+
+/// Drops the retained history for privacy requests; balance and limit stay.
+/// The WAL gets the emptied account before the answer, so a restart does not
+/// replay the history back in.
+pub(crate) async fn purge_transactions(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+) -> Result<StatusCode, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    let mut account = state.write_account(&entry, deadline).await?;
+    let removed = account.transactions.len();
+    let before = (*account).clone();
+    account.transactions.clear();
+    account.touch(state.clock.now());
+    // Logged, or WAL replay would bring the history back.
+    if let Err(error) = state.persist(account_id, &account) {
+        *account = before;
+        return Err(error);
+    }
+    if let Some(history) = &state.history {
+        history.purge(account_id);
+    }
+    state.changed(account_id);
+    state.audit.record(json!({
+        "evento": "historico_apagado",
+        "account": account_id,
+        "removidas": removed,
+        "em": state.config.timestamp(state.clock.now()),
+    }));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct BulkStatementRequest {
+    pub(crate) ids: Vec<AccountId>,
+}
+
+/// Unknown ids get an error entry in place instead of failing the whole batch.
+pub(crate) async fn view_extratos(
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<BulkStatementRequest>,
+) -> Json<Vec<Value>> {
+    let statements = request.ids.into_iter().map(|account_id| {
+        let state = &state;
+        async move {
+            match state.accounts.get(&account_id) {
+                Some(entry) => statement(account_id, &*entry.account.read().await, state),
+                None => json!({
+                    "account": account_id,
+                    "status": StatusCode::NOT_FOUND.as_u16(),
+                    "erro": error::UNKNOWN_ACCOUNT,
+                }),
+            }
+        }
+    });
+    Json(future::join_all(statements).await)
+}
+
+pub(crate) async fn stream_activity(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let receiver = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?
+        .events
+        .subscribe();
+
+    // The receiver is dropped together with the stream when the client
+    // disconnects, which unsubscribes it from the channel.
+    let events = stream::unfold((receiver, state), |(mut receiver, state)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(transaction) => {
+                    let event = Event::default()
+                        .json_data(render_transaction(&transaction, &state.config))
+                        .unwrap_or_else(|_| Event::default().comment("serialization error"));
+                    return Some((Ok(event), (receiver, state)));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
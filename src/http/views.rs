@@ -0,0 +1,218 @@
+//! Typed bodies for the hot routes, serialized straight into the response
+//! instead of going through a `serde_json::Value` tree first.
+
+use std::cell::RefCell;
+
+use axum::{
+    body::Bytes,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    config::{Config, Seq, Timestamp},
+    currency::{Amount, Formatter},
+    domain::{Account, Description, Transaction, TransactionType},
+    registry::AccountId,
+    transact::TransactResult,
+    transfer::TransferLink,
+    ulid::Ulid,
+    AppState,
+};
+
+/// A statement with ten transactions is about 2 KiB; most fit first time.
+const BUFFER_CAPACITY: usize = 4 * 1024;
+
+/// Buffers grown past this are dropped rather than kept for the next body.
+const BUFFER_KEPT: usize = 64 * 1024;
+
+thread_local! {
+    static BUFFER: RefCell<Vec<u8>> = RefCell::new(Vec::with_capacity(BUFFER_CAPACITY));
+}
+
+/// `body` as `application/json`, written into this thread's reused buffer
+/// and copied out once, so the body never reallocates as it grows.
+pub(crate) fn json(body: &impl Serialize) -> Response {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.clear();
+        let response = match serde_json::to_writer(&mut *buffer, body) {
+            Ok(()) => (
+                [(header::CONTENT_TYPE, "application/json")],
+                Bytes::copy_from_slice(&buffer),
+            )
+                .into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        if buffer.capacity() > BUFFER_KEPT {
+            *buffer = Vec::with_capacity(BUFFER_CAPACITY);
+        }
+        response
+    })
+}
+
+/// What `render_transaction` returns, field for field.
+#[derive(Serialize)]
+pub(crate) struct TransactionView<'a> {
+    id: Option<Ulid>,
+    valor: Amount,
+    tipo: TransactionType,
+    descricao: &'a Description,
+    realizada_em: Timestamp<'a>,
+    server_assigned_time: bool,
+    seq: Seq,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transferencia: Option<&'a TransferLink>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estorno: Option<Ulid>,
+    moeda: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    categoria: Option<&'a str>,
+}
+
+impl<'a> TransactionView<'a> {
+    pub(crate) fn new(transaction: &'a Transaction, config: &'a Config) -> Self {
+        let currency = transaction.currency.unwrap_or(config.currency);
+        Self {
+            id: (!transaction.id.is_nil()).then_some(transaction.id),
+            valor: config
+                .formatter()
+                .in_currency(currency)
+                .typed(transaction.value),
+            tipo: transaction.kind,
+            descricao: &transaction.description,
+            realizada_em: config.typed_timestamp(transaction.create_at),
+            server_assigned_time: transaction.server_assigned_time,
+            seq: config.seq(transaction.seq),
+            transferencia: transaction.transfer.as_ref(),
+            estorno: transaction.reversal,
+            moeda: currency.code(),
+            categoria: transaction.category.as_deref(),
+        }
+    }
+}
+
+/// `POST /clientes/:id/transacoes` once accepted.
+#[derive(Serialize)]
+pub(crate) struct TransactionResponse<'a> {
+    account: AccountId,
+    id: Ulid,
+    seq: Seq,
+    limite: Amount,
+    saldo: Amount,
+    versao: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moeda: Option<&'static str>,
+    /// Only for `?parcial=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debitado: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tarifa: Option<TransactionView<'a>>,
+}
+
+impl<'a> TransactionResponse<'a> {
+    pub(crate) fn new(
+        account_id: AccountId,
+        result: &'a TransactResult,
+        partial: bool,
+        config: &'a Config,
+    ) -> Self {
+        let transaction = &result.transaction;
+        let currency = transaction.currency.unwrap_or(config.currency);
+        let amount = |minor| config.formatter().in_currency(currency).typed(minor);
+        Self {
+            account: account_id,
+            id: transaction.id,
+            seq: config.seq(transaction.seq),
+            limite: config.formatter().typed(result.limit),
+            saldo: amount(result.balance),
+            versao: result.version,
+            moeda: transaction.currency.map(|currency| currency.code()),
+            debitado: partial.then(|| amount(transaction.value)),
+            tarifa: result
+                .fee
+                .as_ref()
+                .map(|fee| TransactionView::new(fee, config)),
+        }
+    }
+}
+
+/// The `/clientes/:id/extrato` document; see `handlers::statement`.
+#[derive(Serialize)]
+pub(crate) struct StatementView<'a> {
+    account: AccountId,
+    saldo: Summary<'a>,
+    saldos: Balances<'a>,
+    ultimas_transacoes: Transactions<'a>,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    total: Amount,
+    limite: Amount,
+    cheque_especial: bool,
+    tipo: &'a str,
+    encerrada: bool,
+    disponivel: Amount,
+    utilizacao: Option<String>,
+    data_extrato: String,
+    versao: u64,
+}
+
+impl<'a> StatementView<'a> {
+    pub(crate) fn new(account_id: AccountId, account: &'a Account, state: &'a AppState) -> Self {
+        let config = &state.config;
+        let formatter = config.formatter();
+        let now = state.clock.now();
+        Self {
+            account: account_id,
+            saldo: Summary {
+                total: formatter.typed(account.balance),
+                limite: formatter.typed(account.limit),
+                cheque_especial: account.overdraft(),
+                tipo: &account.account_type.name,
+                encerrada: account.closed,
+                disponivel: formatter.typed(account.available(now)),
+                utilizacao: formatter.utilization(account.balance, account.limit),
+                data_extrato: config.coarse_timestamp(now),
+                versao: account.version,
+            },
+            saldos: Balances(account, formatter),
+            ultimas_transacoes: Transactions(account, config),
+        }
+    }
+}
+
+/// `total` per currency held, the base one first, by ISO code.
+struct Balances<'a>(&'a Account, Formatter);
+
+impl Serialize for Balances<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Balances(account, formatter) = self;
+        let mut map = serializer.serialize_map(Some(account.balances.len() + 1))?;
+        map.serialize_entry(formatter.currency.code(), &formatter.typed(account.balance))?;
+        for (currency, balance) in &account.balances {
+            map.serialize_entry(
+                currency.code(),
+                &formatter.in_currency(*currency).typed(*balance),
+            )?;
+        }
+        map.end()
+    }
+}
+
+/// Newest first, as retained.
+struct Transactions<'a>(&'a Account, &'a Config);
+
+impl Serialize for Transactions<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Transactions(account, config) = self;
+        serializer.collect_seq(
+            account
+                .transactions
+                .iter()
+                .map(|transaction| TransactionView::new(transaction, config)),
+        )
+    }
+}
@@ -0,0 +1,474 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    accounting, accounts, activity, archival, as_of, auth, batch,
+    business_hours::CLOSED,
+    categories, checksum,
+    currency::{
+        NOT_INTEGER, OUT_OF_RANGE, REQUIRED as REQUIRED_CURRENCY, UNKNOWN as UNKNOWN_CURRENCY,
+    },
+    deadline, error, holds, idempotency, ledger, limit, pagination, persistence, policy,
+    precondition, projection, rate_limit, replication, reversal, schedule, search, storage,
+    transact, validation, webhook,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Language {
+    PtBr,
+    En,
+}
+
+/// `(code, pt-BR, en)`. The pt-BR text is also the internal rejection reason
+/// used across the crate (webhooks, `agendamentos`), so it doubles as the key
+/// from a reason back to its code.
+const MESSAGES: &[(&str, &str, &str)] = &[
+    (
+        "conta_nao_encontrada",
+        error::UNKNOWN_ACCOUNT,
+        "Account not found",
+    ),
+    (
+        "nao_autenticado",
+        auth::MISSING,
+        "Missing or unknown API key",
+    ),
+    (
+        "sem_acesso",
+        auth::FORBIDDEN,
+        "API key has no access to this account",
+    ),
+    ("json_invalido", error::MALFORMED_JSON, "Invalid JSON body"),
+    ("campo_desconhecido", error::UNKNOWN_FIELD, "Unknown field"),
+    (
+        "contas_iguais",
+        error::SAME_ACCOUNT,
+        "The two accounts must be different",
+    ),
+    ("limite_invalido", error::INVALID_LIMIT, "Invalid limit"),
+    (
+        "limite_acima_do_maximo",
+        error::LIMIT_ABOVE_MAX,
+        "Limit above the maximum",
+    ),
+    (
+        "conta_ja_existe",
+        accounts::EXISTS,
+        "Account already exists",
+    ),
+    ("sem_id_livre", accounts::NO_FREE_ID, "No free account id"),
+    (
+        "saldo_alem_do_limite",
+        accounts::BALANCE_BEYOND_LIMIT,
+        "Balance already beyond the new limit",
+    ),
+    (
+        "nada_a_alterar",
+        accounts::NOTHING_TO_UPDATE,
+        "Give a limit or a type",
+    ),
+    (
+        "tipo_desconhecido",
+        policy::UNKNOWN_TYPE,
+        "Unknown account type",
+    ),
+    (
+        "limite_intransferivel",
+        limit::NOT_TRANSFERABLE,
+        "The source account's limit does not cover the transfer",
+    ),
+    ("reserva_nao_encontrada", holds::NOT_FOUND, "Hold not found"),
+    (
+        "agendamento_nao_encontrado",
+        schedule::NOT_FOUND,
+        "Scheduled transaction not found",
+    ),
+    (
+        "agendamento_no_passado",
+        schedule::PAST,
+        "executar_em must be in the future",
+    ),
+    (
+        "agendamento_parcial",
+        schedule::PARTIAL,
+        "A scheduled transaction cannot be partial",
+    ),
+    (
+        "transacao_nao_encontrada",
+        reversal::NOT_FOUND,
+        "Transaction not found",
+    ),
+    (
+        "paginacao_invalida",
+        pagination::INVALID,
+        "Invalid pagination",
+    ),
+    ("busca_vazia", search::EMPTY_QUERY, "Empty search term"),
+    (
+        "projecao_no_passado",
+        projection::PAST,
+        "Projection date in the past",
+    ),
+    ("tz_invalido", activity::INVALID_TZ, "Invalid tz"),
+    (
+        "digito_verificador_invalido",
+        checksum::INVALID,
+        "Invalid account number: check digit does not match",
+    ),
+    ("prazo_invalido", deadline::INVALID, "Invalid X-Deadline"),
+    (
+        "prazo_expirado",
+        deadline::EXPIRED,
+        "Request deadline expired",
+    ),
+    (
+        "conta_ocupada",
+        crate::LOCK_TIMEOUT,
+        "Account busy, try again",
+    ),
+    (
+        "em_manutencao",
+        crate::http::MAINTENANCE,
+        "Under maintenance",
+    ),
+    (
+        "armazenamento_indisponivel",
+        storage::UNAVAILABLE,
+        "Storage unavailable, try again",
+    ),
+    ("lote_vazio", batch::EMPTY, "Empty batch"),
+    (
+        "falha_no_log",
+        persistence::WAL_FAILED,
+        "Could not write to the log",
+    ),
+    (
+        "lote_grande_demais",
+        batch::TOO_LARGE,
+        "Too many transactions in one batch",
+    ),
+    (
+        "limite_de_requisicoes",
+        rate_limit::LIMITED,
+        "Too many requests, retry later",
+    ),
+    (
+        "webhooks_desativados",
+        webhook::DISABLED,
+        "Webhooks disabled",
+    ),
+    (
+        "webhook_nao_encontrado",
+        webhook::NOT_FOUND,
+        "Webhook not found",
+    ),
+    (
+        "formato_desconhecido",
+        accounting::UNKNOWN,
+        "Unknown statement format",
+    ),
+    (
+        "historico_ilegivel",
+        accounting::UNREADABLE,
+        "The history archive could not be read",
+    ),
+    ("ledger_desativado", ledger::DISABLED, "Ledger not enabled"),
+    (
+        "ledger_paginacao_invalida",
+        ledger::INVALID,
+        "Invalid ledger page: limit must be 1 to 1000",
+    ),
+    (
+        "ledger_ilegivel",
+        ledger::UNREADABLE,
+        "The ledger could not be read",
+    ),
+    (
+        "historico_insuficiente",
+        as_of::BEYOND_HISTORY,
+        "Retained history does not reach back to that instant; enable BANK_LEDGER_FILE",
+    ),
+    (
+        "webhook_nao_suportado",
+        webhook::UNSUPPORTED,
+        "Only http:// webhooks are supported",
+    ),
+    (
+        "replicacao_desativada",
+        replication::DISABLED,
+        "Replication is disabled",
+    ),
+    (
+        "token_de_replicacao_invalido",
+        replication::BAD_TOKEN,
+        "Invalid replication token",
+    ),
+    (
+        "lider_indisponivel",
+        replication::LEADER_UNAVAILABLE,
+        "The leader instance is unavailable",
+    ),
+    (
+        "arquivamento_desativado",
+        archival::DISABLED,
+        "History archival is disabled",
+    ),
+    (
+        "periodo_invalido",
+        archival::INVALID_PERIOD,
+        "Invalid period: use YYYY-MM, YYYY-MM-DD or YYYY-MM-DD/YYYY-MM-DD",
+    ),
+    (
+        "limite_insuficiente",
+        "Limite insuficiente",
+        "Insufficient limit",
+    ),
+    (
+        "saldo_insuficiente",
+        "Saldo insuficiente",
+        "Insufficient balance",
+    ),
+    (
+        "limite_diario_excedido",
+        "limite diario excedido",
+        "Daily debit limit exceeded",
+    ),
+    (
+        "valor_acima_do_maximo",
+        validation::ABOVE_MAX,
+        "Amount above the per-transaction maximum",
+    ),
+    (
+        "limite_janela_excedido",
+        "limite da janela movel excedido",
+        "Rolling debit limit exceeded",
+    ),
+    (
+        "descricao_invalida",
+        "Descrição invalida",
+        "Invalid description",
+    ),
+    (
+        "termo_proibido",
+        "descricao contem termo proibido",
+        "Description contains a forbidden term",
+    ),
+    ("valor_invalido", "valor invalido", "Invalid amount"),
+    ("campo_obrigatorio", validation::REQUIRED, "Required field"),
+    (
+        "valor_nao_positivo",
+        validation::NOT_POSITIVE,
+        "Amount must be greater than zero",
+    ),
+    (
+        "valor_tipo_invalido",
+        validation::AMOUNT_TYPE,
+        "Amount must be an integer in minor units or a decimal string",
+    ),
+    ("tipo_invalido", validation::KIND, "Type must be C or D"),
+    (
+        "descricao_tipo_invalido",
+        validation::DESCRIPTION_TYPE,
+        "Description must be a string",
+    ),
+    (
+        "moeda_tipo_invalido",
+        validation::CURRENCY_TYPE,
+        "Currency must be an ISO code string",
+    ),
+    (
+        "categoria_tipo_invalido",
+        categories::TYPE,
+        "Category must be a string",
+    ),
+    (
+        "categoria_desconhecida",
+        categories::UNKNOWN,
+        "Unknown category",
+    ),
+    (
+        "mes_invalido",
+        categories::INVALID_MONTH,
+        "Invalid month: use YYYY-MM",
+    ),
+    (
+        "realizada_em_invalida",
+        validation::TIMESTAMP,
+        "realizada_em must be an RFC 3339 timestamp",
+    ),
+    (
+        "casas_decimais_invalidas",
+        "valor com casas decimais invalidas para a moeda",
+        "Too many decimal places for the currency",
+    ),
+    (
+        "intervalo_minimo",
+        "intervalo minimo entre transacoes",
+        "Too soon after the previous transaction",
+    ),
+    (
+        "valor_fora_do_intervalo",
+        OUT_OF_RANGE,
+        "Amount out of the supported range (-9223372036854775808 to 9223372036854775807)",
+    ),
+    (
+        "valor_nao_inteiro",
+        NOT_INTEGER,
+        "Amount must be an integer number of minor units",
+    ),
+    (
+        "transacao_duplicada",
+        "transacao duplicada",
+        "Duplicate of the previous transaction",
+    ),
+    (
+        "transacao_ja_estornada",
+        reversal::ALREADY_REVERSED,
+        "Transaction already reversed",
+    ),
+    (
+        "estorno_de_estorno",
+        reversal::OF_REVERSAL,
+        "A reversal cannot be reversed",
+    ),
+    (
+        "estorno_de_transferencia",
+        reversal::OF_TRANSFER,
+        "One side of a transfer cannot be reversed",
+    ),
+    ("conta_encerrada", "conta encerrada", "Account closed"),
+    (
+        "fila_encerrada",
+        "fila da conta encerrada",
+        "Account queue closed",
+    ),
+    (
+        "saldo_fora_do_intervalo",
+        transact::OUT_OF_RANGE,
+        "Amount would take the balance out of the supported range",
+    ),
+    (
+        "moeda_nao_mantida",
+        "moeda nao mantida pela conta",
+        "Account does not hold this currency",
+    ),
+    ("moeda_desconhecida", UNKNOWN_CURRENCY, "Unknown currency"),
+    ("moeda_obrigatoria", REQUIRED_CURRENCY, "moeda is required"),
+    (
+        "versao_desatualizada",
+        "conta alterada desde a versao informada",
+        "Account changed since the given version",
+    ),
+    (
+        "if_match_invalido",
+        precondition::INVALID,
+        "Invalid If-Match: expected an account version",
+    ),
+    ("fora_do_horario", CLOSED, "Outside business hours"),
+    (
+        "chave_idempotencia_invalida",
+        idempotency::INVALID,
+        "Invalid Idempotency-Key",
+    ),
+    (
+        "chave_idempotencia_em_uso",
+        idempotency::IN_FLIGHT,
+        "Idempotency-Key in use by another request",
+    ),
+    (
+        "chave_idempotencia_divergente",
+        idempotency::MISMATCH,
+        "Idempotency-Key reused for a different transaction",
+    ),
+];
+
+tokio::task_local! {
+    static CURRENT: Language;
+}
+
+impl Language {
+    /// The language of the request being served on this task; pt-BR outside
+    /// of one.
+    pub fn current() -> Self {
+        CURRENT
+            .try_with(|language| *language)
+            .unwrap_or(Language::PtBr)
+    }
+
+    /// pt-BR without a header; the first supported tag listed otherwise, and
+    /// English when the client only lists languages we do not carry.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let Some(accept) = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Language::PtBr;
+        };
+
+        for tag in accept.split(',') {
+            let tag = tag.split(';').next().unwrap_or("").trim();
+            let primary = tag.split('-').next().unwrap_or("").to_ascii_lowercase();
+            match primary.as_str() {
+                "pt" | "*" => return Language::PtBr,
+                "en" => return Language::En,
+                _ => {}
+            }
+        }
+        Language::En
+    }
+}
+
+/// Layer just inside `correlation::propagate`: makes the request's language
+/// `Language::current()` for every error rendered while serving it.
+pub async fn negotiate(request: Request, next: Next) -> Response {
+    let language = Language::from_headers(request.headers());
+    CURRENT.scope(language, next.run(request)).await
+}
+
+/// `{code, message}` for a pt-BR reason, the shape of every error body.
+pub fn body(reason: &str, language: Language) -> Value {
+    let (code, message) = match MESSAGES.iter().find(|(_, pt, _)| *pt == reason) {
+        Some((code, pt, en)) => match language {
+            Language::PtBr => (*code, *pt),
+            Language::En => (*code, *en),
+        },
+        None => ("erro", reason),
+    };
+    json!({ "code": code, "message": message })
+}
+
+/// Every code an error body may carry besides the `erro` fallback.
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    MESSAGES.iter().map(|(code, _, _)| *code)
+}
+
+/// The stable code for a pt-BR reason, `erro` for one without an entry.
+pub fn code(reason: &str) -> &'static str {
+    MESSAGES
+        .iter()
+        .find(|(_, pt, _)| *pt == reason)
+        .map_or("erro", |(code, _, _)| code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::MESSAGES;
+
+    #[test]
+    fn codes_and_reasons_are_unique() {
+        let mut codes = HashSet::new();
+        let mut reasons = HashSet::new();
+        for (code, pt, en) in MESSAGES {
+            assert!(codes.insert(code), "code {code} listed twice");
+            assert!(reasons.insert(pt), "reason {pt:?} listed twice");
+            assert!(!en.is_empty(), "{code} has no English message");
+        }
+    }
+}
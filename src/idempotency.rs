@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use axum::{
+    http::{HeaderMap, HeaderName, HeaderValue},
+    response::Response,
+};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::{transact::TransactError, Transaction, TransactionType};
+
+pub const HEADER: &str = "idempotency-key";
+/// Set on a response served from a key instead of a fresh run.
+pub const REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+pub const INVALID: &str = "Idempotency-Key invalida";
+pub const IN_FLIGHT: &str = "Idempotency-Key em uso por outra requisicao";
+pub const MISMATCH: &str = "Idempotency-Key reutilizada com outra transacao";
+
+const MAX_LEN: usize = 255;
+/// Keys remembered per account; past this the oldest answered one goes.
+const KEPT: usize = 1024;
+
+/// Kind, value, description and `parcial`: a key replays only for the same
+/// transaction it was first sent with.
+type Fingerprint = (TransactionType, i64, String, bool);
+
+/// What the key's first request got. Refusals are kept as the error, not
+/// the rendered body, so a replay is answered in its own language.
+pub type Outcome = Result<Value, TransactError>;
+
+enum Slot {
+    InFlight(Fingerprint),
+    Done {
+        fingerprint: Fingerprint,
+        outcome: Outcome,
+        at: OffsetDateTime,
+    },
+}
+
+/// An account's `Idempotency-Key`s for `POST /clientes/:id/transacoes`.
+/// In memory only: a restart forgets them.
+#[derive(Default)]
+pub struct Keys(Mutex<HashMap<String, Slot>>);
+
+pub enum Claim<'a> {
+    /// First use: run the transaction and hand the outcome to the ticket.
+    New(Ticket<'a>),
+    Replay(Outcome),
+    /// Another request holding the key has not finished yet.
+    InFlight,
+    Mismatch,
+}
+
+impl Keys {
+    pub fn claim(
+        &self,
+        key: String,
+        transaction: &Transaction,
+        partial: bool,
+        now: OffsetDateTime,
+        ttl: Duration,
+    ) -> Claim<'_> {
+        let fingerprint = (
+            transaction.kind,
+            transaction.value,
+            transaction.description.0.clone(),
+            partial,
+        );
+        let mut keys = self.0.lock().unwrap();
+        keys.retain(|_, slot| match slot {
+            Slot::InFlight(_) => true,
+            Slot::Done { at, .. } => now - *at < ttl,
+        });
+
+        match keys.get(&key) {
+            Some(Slot::InFlight(_)) => return Claim::InFlight,
+            Some(Slot::Done {
+                fingerprint: first, ..
+            }) if *first != fingerprint => return Claim::Mismatch,
+            Some(Slot::Done { outcome, .. }) => return Claim::Replay(outcome.clone()),
+            None => {}
+        }
+
+        if keys.len() >= KEPT {
+            let oldest = keys
+                .iter()
+                .filter_map(|(key, slot)| match slot {
+                    Slot::Done { at, .. } => Some((*at, key.clone())),
+                    Slot::InFlight(_) => None,
+                })
+                .min();
+            if let Some((_, key)) = oldest {
+                keys.remove(&key);
+            }
+        }
+        keys.insert(key.clone(), Slot::InFlight(fingerprint));
+        Claim::New(Ticket {
+            keys: self,
+            key: Some(key),
+        })
+    }
+}
+
+/// Holds a claimed key. Dropped without `complete` (the handler bailed out
+/// before an outcome, say on a lock timeout) it frees the key for a retry.
+pub struct Ticket<'a> {
+    keys: &'a Keys,
+    key: Option<String>,
+}
+
+impl Ticket<'_> {
+    /// Transient refusals are not remembered: retrying them is the point.
+    pub fn complete(mut self, outcome: &Outcome, now: OffsetDateTime) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let mut keys = self.keys.0.lock().unwrap();
+        if let Err(TransactError::TooSoon | TransactError::QueueClosed) = outcome {
+            keys.remove(&key);
+            return;
+        }
+        if let Some(Slot::InFlight(fingerprint)) = keys.remove(&key) {
+            let outcome = outcome.clone();
+            keys.insert(
+                key,
+                Slot::Done {
+                    fingerprint,
+                    outcome,
+                    at: now,
+                },
+            );
+        }
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.keys.0.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+/// The request's key, if it sent one; `INVALID` when it is empty, longer
+/// than 255 bytes or not visible ASCII.
+pub fn key(headers: &HeaderMap) -> Result<Option<String>, &'static str> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key)
+            if !key.is_empty()
+                && key.len() <= MAX_LEN
+                && key.bytes().all(|byte| byte.is_ascii_graphic()) =>
+        {
+            Ok(Some(key.to_owned()))
+        }
+        _ => Err(INVALID),
+    }
+}
+
+/// Marks `response` as a replay.
+pub fn replayed(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
@@ -0,0 +1,446 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+
+use crate::{
+    config::Config,
+    currency::Currency,
+    domain::{Account, Transaction, TransactionType},
+    error::AppError,
+    observer::{AccountObserver, Outcome},
+    registry::AccountId,
+    rotation::{self, RotatingFile, Rotation},
+    ulid::Ulid,
+};
+
+pub const DISABLED: &str = "ledger desativado";
+pub const INVALID: &str = "paginacao do ledger invalida";
+pub const UNREADABLE: &str = "ledger ilegivel";
+
+const MAX_LIMIT: usize = 1000;
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    /// The balance the account already had when the ledger first saw it.
+    #[serde(rename = "abertura")]
+    Opening,
+    #[serde(rename = "credito")]
+    Credit,
+    #[serde(rename = "debito")]
+    Debit,
+    /// `POST /clientes/:id/import` replacing the balance outright.
+    #[serde(rename = "importacao")]
+    Import,
+}
+
+/// One line of `BANK_LEDGER_FILE`. `saldo` is always the previous event's
+/// `saldo` in the same currency plus `valor`, so any prefix of an account's
+/// events gives its balance at that point.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(rename = "conta")]
+    account: AccountId,
+    /// Per account, from 1, without gaps.
+    seq: u64,
+    #[serde(rename = "evento")]
+    kind: Kind,
+    #[serde(rename = "transacao", default, skip_serializing_if = "Option::is_none")]
+    transaction: Option<Ulid>,
+    /// Signed: debits are negative.
+    valor: i64,
+    /// `None` is the configured currency.
+    #[serde(rename = "moeda", default, skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
+    saldo: i64,
+    #[serde(rename = "em", with = "time::serde::rfc3339")]
+    at: OffsetDateTime,
+}
+
+impl Event {
+    /// Not numbered yet: `Ledger::append` fills in `seq` and `saldo`.
+    fn new(
+        account: AccountId,
+        kind: Kind,
+        valor: i64,
+        currency: Option<Currency>,
+        at: OffsetDateTime,
+    ) -> Self {
+        Self {
+            account,
+            seq: 0,
+            kind,
+            transaction: None,
+            valor,
+            currency,
+            saldo: 0,
+            at,
+        }
+    }
+}
+
+/// Where an account's events stand: the last `seq` and the balances they add
+/// up to.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Head {
+    seq: u64,
+    #[serde(rename = "saldo")]
+    balance: i64,
+    #[serde(rename = "saldos", default)]
+    balances: BTreeMap<Currency, i64>,
+}
+
+impl Head {
+    fn balance_mut(&mut self, currency: Option<Currency>) -> &mut i64 {
+        match currency {
+            Some(currency) => self.balances.entry(currency).or_default(),
+            None => &mut self.balance,
+        }
+    }
+
+    fn advance(&mut self, event: &Event) {
+        if event.seq > self.seq {
+            self.seq = event.seq;
+            *self.balance_mut(event.currency) = event.saldo;
+        }
+    }
+}
+
+/// `<ledger>.checkpoint`: the heads as of the newest rotated segment, so a
+/// restart only reads what came after it.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    #[serde(rename = "segmento")]
+    segment: PathBuf,
+    #[serde(rename = "contas")]
+    heads: Vec<(AccountId, Head)>,
+}
+
+/// `BANK_LEDGER_FILE`: the append-only event stream balances are derived
+/// from. Every accepted transaction is one event; nothing is rewritten or
+/// dropped, purges included, which is why events carry amounts and ids but
+/// no descriptions. On startup the balances it adds up to are the ones the
+/// accounts get.
+pub struct Ledger {
+    path: PathBuf,
+    file: RotatingFile,
+    /// Held across the append too, so `seq` order and file order agree.
+    heads: Mutex<HashMap<AccountId, Head>>,
+}
+
+impl AccountObserver for Ledger {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        if outcome.is_err() {
+            return;
+        }
+        let (kind, valor) = match transaction.kind {
+            TransactionType::Credit => (Kind::Credit, transaction.value),
+            TransactionType::Debit => (Kind::Debit, -transaction.value),
+        };
+        let mut heads = self.heads.lock().unwrap();
+        let head = heads.entry(account_id).or_default();
+        let event = Event {
+            transaction: Some(transaction.id),
+            ..Event::new(
+                account_id,
+                kind,
+                valor,
+                transaction.currency,
+                transaction.create_at,
+            )
+        };
+        self.append(head, event);
+    }
+
+    /// The leader's ledger has it.
+    fn on_replicated(&self, _: AccountId, _: &Transaction) {}
+}
+
+impl Ledger {
+    /// Reads the checkpoint, if any, and the segments after it to find where
+    /// each account's events stand.
+    pub fn open(path: &Path, rotation: Rotation) -> io::Result<Self> {
+        let mut heads = HashMap::new();
+        let mut segments = rotation::rotated(path)?;
+        if let Ok(file) = File::open(checkpoint_path(path)) {
+            let checkpoint: Checkpoint = serde_json::from_reader(file)?;
+            heads.extend(checkpoint.heads);
+            // Reading a covered segment again would be harmless, only slow.
+            if let Some(covered) = segments.iter().position(|s| *s == checkpoint.segment) {
+                segments.drain(..=covered);
+            }
+        }
+        segments.push(path.to_path_buf());
+        for segment in segments.iter().filter(|segment| segment.exists()) {
+            for event in events(segment)? {
+                heads
+                    .entry(event.account)
+                    .or_insert_with(Head::default)
+                    .advance(&event);
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: RotatingFile::open(path, rotation)?,
+            heads: Mutex::new(heads),
+        })
+    }
+
+    /// Before the accounts are shared: an account the ledger has events for
+    /// takes the balances they add up to, whatever the snapshot or WAL said;
+    /// one it has none for is opened in it at its current balances.
+    pub fn restore(&self, accounts: &mut HashMap<AccountId, RwLock<Account>>, now: OffsetDateTime) {
+        let mut heads = self.heads.lock().unwrap();
+        for (account_id, account) in accounts.iter_mut() {
+            let account = account.get_mut();
+            let head = heads.entry(*account_id).or_default();
+            if head.seq > 0 {
+                if head.balance != account.balance || !same_balances(head, account) {
+                    tracing::warn!(
+                        account = account_id.0,
+                        balance = head.balance,
+                        was = account.balance,
+                        "ledger: balance restored from the account's events"
+                    );
+                }
+                account.balance = head.balance;
+                account.balances.extend(&head.balances);
+            }
+            // Whatever the ledger has not seen yet, a new account or a
+            // currency, is opened in it as it stands.
+            self.reconcile_head(*account_id, head, account, Kind::Opening, now);
+        }
+    }
+
+    /// Records whatever moved the account's balances outside a transaction
+    /// (an import), as one event per currency that changed.
+    pub fn reconcile(
+        &self,
+        account_id: AccountId,
+        account: &Account,
+        kind: Kind,
+        now: OffsetDateTime,
+    ) {
+        let mut heads = self.heads.lock().unwrap();
+        let head = heads.entry(account_id).or_default();
+        self.reconcile_head(account_id, head, account, kind, now);
+    }
+
+    fn reconcile_head(
+        &self,
+        account_id: AccountId,
+        head: &mut Head,
+        account: &Account,
+        kind: Kind,
+        now: OffsetDateTime,
+    ) {
+        let balances = [(None, account.balance)].into_iter().chain(
+            account
+                .balances
+                .iter()
+                .map(|(currency, balance)| (Some(*currency), *balance)),
+        );
+        for (currency, balance) in balances {
+            let delta = balance - *head.balance_mut(currency);
+            if delta != 0 {
+                self.append(head, Event::new(account_id, kind, delta, currency, now));
+            }
+        }
+    }
+
+    /// Numbers `event` after `head` and works out its `saldo`.
+    fn append(&self, head: &mut Head, mut event: Event) {
+        event.seq = head.seq + 1;
+        event.saldo = *head.balance_mut(event.currency) + event.valor;
+        let line = format!("{}\n", serde_json::to_string(&event).unwrap());
+        match self.file.append(line.as_bytes(), false) {
+            Ok(()) => head.advance(&event),
+            // Head left as is: the next event takes this `seq` and still adds up.
+            Err(err) => {
+                tracing::error!(error = %err, line = line.trim_end(), "ledger write failed")
+            }
+        }
+    }
+
+    /// For `/readyz`: appends would still land.
+    pub fn check(&self) -> io::Result<()> {
+        self.file.check()
+    }
+
+    /// Rotates and records the heads alongside, so a restart skips every
+    /// segment up to this one. Runs with the snapshot checkpoint.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        let heads = self.heads.lock().unwrap();
+        self.file.rotate_now()?;
+        let Some(segment) = rotation::rotated(&self.path)?.pop() else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint {
+            segment,
+            heads: heads.iter().map(|(id, head)| (*id, head.clone())).collect(),
+        };
+        drop(heads);
+        let path = checkpoint_path(&self.path);
+        let mut temporary = path.clone();
+        temporary.set_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(&checkpoint)?)?;
+        fs::rename(&temporary, path)
+    }
+
+    /// The account's events with `seq` past `after`, up to `limit`, and
+    /// how many it has in all. Reads every segment, so it blocks; call it
+    /// off the runtime.
+    fn read(
+        &self,
+        account_id: AccountId,
+        after: u64,
+        limit: usize,
+    ) -> io::Result<(Vec<Event>, u64)> {
+        let page = self
+            .events_of(account_id)?
+            .into_iter()
+            .filter(|event| event.seq > after)
+            .take(limit)
+            .collect();
+        let total = self
+            .heads
+            .lock()
+            .unwrap()
+            .get(&account_id)
+            .map_or(0, |head| head.seq);
+        Ok((page, total))
+    }
+
+    /// The balance in `currency` as of `at`: the sum of every event stamped
+    /// at or before it, and how many that was. A sum, so the order events
+    /// were written in does not matter, only their `em`. Blocks like `read`.
+    pub fn balance_at(
+        &self,
+        account_id: AccountId,
+        currency: Option<Currency>,
+        at: OffsetDateTime,
+    ) -> io::Result<(i64, usize)> {
+        let folded: Vec<_> = self
+            .events_of(account_id)?
+            .into_iter()
+            .filter(|event| event.currency == currency && event.at <= at)
+            .collect();
+        Ok((folded.iter().map(|event| event.valor).sum(), folded.len()))
+    }
+
+    fn events_of(&self, account_id: AccountId) -> io::Result<Vec<Event>> {
+        let mut segments = rotation::rotated(&self.path)?;
+        segments.push(self.path.clone());
+        let mut found = Vec::new();
+        for segment in segments.iter().filter(|segment| segment.exists()) {
+            found.extend(
+                events(segment)?
+                    .into_iter()
+                    .filter(|event| event.account == account_id),
+            );
+        }
+        Ok(found)
+    }
+}
+
+fn checkpoint_path(path: &Path) -> PathBuf {
+    let mut checkpoint = path.as_os_str().to_owned();
+    checkpoint.push(".checkpoint");
+    PathBuf::from(checkpoint)
+}
+
+fn events(segment: &Path) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(segment)?).lines() {
+        // A torn last line from a crash is skipped.
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+fn same_balances(head: &Head, account: &Account) -> bool {
+    head.balances
+        .iter()
+        .all(|(currency, balance)| account.balances.get(currency) == Some(balance))
+}
+
+#[derive(Deserialize)]
+pub struct LedgerQuery {
+    /// `seq` of the last event already seen.
+    #[serde(default)]
+    cursor: u64,
+    limit: Option<usize>,
+}
+
+/// Events oldest first, in pages of up to 1000. The balance after any one of
+/// them is its `saldo`; what the account holds now is the last page's last.
+pub async fn events_of(
+    UrlPath(account_id): UrlPath<AccountId>,
+    Query(query): Query<LedgerQuery>,
+    State(state): State<Arc<crate::AppState>>,
+) -> Result<Json<Value>, AppError> {
+    state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let ledger = state.ledger.clone().ok_or(AppError::NotFound(DISABLED))?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(AppError::Invalid(INVALID));
+    }
+    let (events, total) =
+        tokio::task::spawn_blocking(move || ledger.read(account_id, query.cursor, limit))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .ok_or(AppError::Status(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                UNREADABLE,
+            ))?;
+    let next = events
+        .last()
+        .map(|event| event.seq)
+        .filter(|seq| *seq < total);
+    Ok(Json(json!({
+        "account": account_id,
+        "eventos": events
+            .iter()
+            .map(|event| render(event, &state.config))
+            .collect::<Vec<_>>(),
+        "paginacao": {
+            "limit": limit,
+            "total": total,
+            "proximo_cursor": next,
+        },
+    })))
+}
+
+fn render(event: &Event, config: &Config) -> Value {
+    let currency = event.currency.unwrap_or(config.currency);
+    let formatter = config.formatter().in_currency(currency);
+    json!({
+        "seq": event.seq,
+        "evento": event.kind,
+        "transacao": event.transaction,
+        "valor": formatter.amount(event.valor),
+        "moeda": currency.code(),
+        "saldo": formatter.amount(event.saldo),
+        "em": config.timestamp(event.at),
+    })
+}
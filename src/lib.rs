@@ -0,0 +1,608 @@
+mod accounting;
+mod accounts;
+mod activity;
+mod actor;
+mod archival;
+mod as_of;
+mod audit;
+mod auth;
+mod batch;
+mod business_hours;
+mod cache;
+mod capabilities;
+mod categories;
+mod checksum;
+pub mod clock;
+mod compare;
+pub mod config;
+mod correlation;
+mod currency;
+mod deadline;
+mod debug;
+pub mod domain;
+mod dynamic_limit;
+mod envelope;
+pub mod error;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod gzip;
+mod health;
+mod history;
+mod holds;
+pub mod http;
+mod i18n;
+mod idempotency;
+mod ledger;
+mod limit;
+mod live;
+mod metrics;
+mod observer;
+mod openapi;
+mod pagination;
+mod pdf;
+mod persistence;
+mod policy;
+mod precondition;
+mod projection;
+mod rate_limit;
+mod registry;
+mod rejections;
+mod replication;
+mod report;
+mod retention;
+mod reversal;
+mod rotation;
+mod savepoint;
+mod schedule;
+mod search;
+pub mod service;
+mod settlement;
+mod simulate;
+pub mod storage;
+mod strict;
+/// The integration tests' helpers, shared with the unit tests; they name
+/// this crate as `rinha2024`, like any dependent.
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod testing;
+#[cfg(test)]
+extern crate self as rinha2024;
+mod tls;
+pub mod trace;
+pub mod transact;
+mod transfer;
+mod ulid;
+mod validation;
+mod velocity;
+mod webhook;
+
+pub use registry::AccountId;
+
+use std::{
+    future::IntoFuture,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+
+use crate::{
+    audit::AuditLog,
+    clock::{Clock, SystemClock},
+    config::Config,
+    deadline::Deadline,
+    error::AppError,
+    metrics::{RequestMetrics, RunStats, TrackedWrite, TransactionCounts},
+    observer::{AccountObserver, Broadcast, Outcome},
+    persistence::Wal,
+    registry::{AccountRegistry, Entry},
+    transact::{TransactError, TransactResult},
+    webhook::Dispatcher,
+};
+
+use domain::{
+    observe_seq, open_account, seed_accounts, Account, Description, RingBuffer, Transaction,
+    TransactionRequest, TransactionType,
+};
+use http::{handlers::statement, render_transaction};
+
+pub struct AppState {
+    pub(crate) accounts: Arc<AccountRegistry>,
+    pub(crate) config: Config,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) maintenance: AtomicBool,
+    pub(crate) audit: AuditLog,
+    pub(crate) webhooks: Option<Arc<Dispatcher>>,
+    /// Told about every transaction outcome, in registration order.
+    pub(crate) observers: Vec<Arc<dyn AccountObserver>>,
+    /// Also an observer; read once, for the shutdown report.
+    pub(crate) run_stats: Arc<RunStats>,
+    pub(crate) requests: RequestMetrics,
+    pub(crate) rate_limits: rate_limit::Limits,
+    /// Written through `log` and `persist` rather than as an observer, so a
+    /// failed write fails the request.
+    pub(crate) wal: Option<Arc<Wal>>,
+    /// Also an observer; read back by statement exports.
+    pub(crate) history: Option<Arc<history::Archive>>,
+    /// Also an observer; read back by `/clientes/:id/ledger`.
+    pub(crate) ledger: Option<Arc<ledger::Ledger>>,
+    /// Also an observer; pinged by `/readyz`.
+    pub(crate) storage: Option<Arc<storage::Writer>>,
+    /// Also an observer, on the leader; see `replication::Replication`.
+    pub(crate) replication: Option<Arc<replication::Replication>>,
+}
+
+impl AppState {
+    /// Honors the optional lock-wait timeout, counting every give-up, and
+    /// records the wait and the in-flight write for `/metrics`. A request
+    /// deadline sooner than the timeout caps the wait; running into it is a
+    /// `408` rather than a lock timeout.
+    pub(crate) async fn write_account<'a>(
+        &'a self,
+        entry: &'a Entry,
+        deadline: Option<Deadline>,
+    ) -> Result<TrackedWrite<'a>, AppError> {
+        let (account, metrics) = (&entry.account, &entry.metrics);
+        let in_flight = metrics.start_write();
+        let started = std::time::Instant::now();
+        let remaining = deadline.map(Deadline::remaining);
+        let capped_by_deadline = match (remaining, self.config.lock_timeout) {
+            (Some(remaining), Some(limit)) => remaining < limit,
+            (remaining, _) => remaining.is_some(),
+        };
+        let guard = match remaining.into_iter().chain(self.config.lock_timeout).min() {
+            None => account.write().await,
+            Some(limit) => match tokio::time::timeout(limit, account.write()).await {
+                Ok(guard) => guard,
+                Err(_) if capped_by_deadline => {
+                    let status = StatusCode::REQUEST_TIMEOUT;
+                    return Err(AppError::Status(status, deadline::EXPIRED));
+                }
+                Err(_) => {
+                    entry.lock_timeouts.fetch_add(1, Ordering::Relaxed);
+                    let status = StatusCode::SERVICE_UNAVAILABLE;
+                    return Err(AppError::Status(status, LOCK_TIMEOUT));
+                }
+            },
+        };
+        metrics.observe_wait(started.elapsed());
+        Ok(TrackedWrite::new(guard, in_flight))
+    }
+
+    /// `write_account` for two accounts, taken in id order so opposite pairs
+    /// cannot deadlock; the guards come back in argument order.
+    pub(crate) async fn write_pair<'a>(
+        &'a self,
+        (first_id, first): (AccountId, &'a Entry),
+        (second_id, second): (AccountId, &'a Entry),
+        deadline: Option<Deadline>,
+    ) -> Result<(TrackedWrite<'a>, TrackedWrite<'a>), AppError> {
+        if first_id < second_id {
+            let first = self.write_account(first, deadline).await?;
+            Ok((first, self.write_account(second, deadline).await?))
+        } else {
+            let second = self.write_account(second, deadline).await?;
+            Ok((self.write_account(first, deadline).await?, second))
+        }
+    }
+
+    /// Writes accepted transactions to the WAL before anyone hears of them.
+    /// Call under the accounts' write locks, then `accepted`; on an error the
+    /// caller puts the accounts back as they were and answers with it.
+    pub(crate) fn log<'a>(
+        &self,
+        recorded: impl IntoIterator<Item = (AccountId, &'a Transaction)>,
+    ) -> Result<(), AppError> {
+        let Some(wal) = &self.wal else {
+            return Ok(());
+        };
+        let recorded: Vec<_> = recorded.into_iter().collect();
+        wal.append(&recorded).map_err(|err| {
+            tracing::error!(error = %err, "wal write failed");
+            persistence::failed()
+        })
+    }
+
+    /// `log` for everything `result` recorded, its fee included.
+    pub(crate) fn log_result(
+        &self,
+        account_id: AccountId,
+        result: &TransactResult,
+    ) -> Result<(), AppError> {
+        self.log(
+            result
+                .recorded()
+                .map(|transaction| (account_id, transaction)),
+        )
+    }
+
+    /// Side effects of an accepted transaction, once `log` has it. Call
+    /// while still holding the account's write lock.
+    pub(crate) fn accepted(&self, account_id: AccountId, transaction: &Transaction) {
+        self.notify(account_id, transaction, &Ok(()));
+    }
+
+    /// `accepted` for a transaction the leader shipped, on the follower.
+    pub(crate) fn replicated(&self, account_id: AccountId, transaction: &Transaction) {
+        for observer in &self.observers {
+            observer.on_replicated(account_id, transaction);
+        }
+    }
+
+    /// `accepted` for everything `result` recorded, its fee included.
+    pub(crate) fn accepted_result(&self, account_id: AccountId, result: &TransactResult) {
+        for transaction in result.recorded() {
+            self.accepted(account_id, transaction);
+        }
+    }
+
+    /// The account changed outside a transaction (settings, history purged
+    /// or pruned, an import): a follower is sent the whole account.
+    pub(crate) fn changed(&self, account_id: AccountId) {
+        if let Some(replication) = &self.replication {
+            replication.changed(account_id);
+        }
+    }
+
+    /// Logs the whole account after a change that is not a transaction (a
+    /// schedule or hold, an admin operation), for WAL replay to restore, and
+    /// queues it as the account's storage row. Call under the account's
+    /// write lock and before answering; on an error the caller puts the
+    /// account back as it was.
+    pub(crate) fn persist(&self, account_id: AccountId, account: &Account) -> Result<(), AppError> {
+        self.persist_all(&[(account_id, account)])
+    }
+
+    /// `persist` for a change spanning accounts: all of them are logged, or
+    /// none.
+    pub(crate) fn persist_all(&self, accounts: &[(AccountId, &Account)]) -> Result<(), AppError> {
+        if let Some(wal) = &self.wal {
+            wal.append_state(accounts).map_err(|err| {
+                tracing::error!(error = %err, "wal write failed");
+                persistence::failed()
+            })?;
+        }
+        if let Some(storage) = &self.storage {
+            for &(account_id, account) in accounts {
+                storage.save(account_id, account);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rejected(
+        &self,
+        account_id: AccountId,
+        transaction: &Transaction,
+        error: TransactError,
+    ) {
+        self.notify(account_id, transaction, &Err(error));
+    }
+
+    pub(crate) fn notify(
+        &self,
+        account_id: AccountId,
+        transaction: &Transaction,
+        outcome: &Outcome,
+    ) {
+        for observer in &self.observers {
+            observer.on_transaction(account_id, transaction, outcome);
+        }
+    }
+}
+
+pub(crate) const LOCK_TIMEOUT: &str = "conta ocupada, tente novamente";
+
+pub async fn run(config: Config) {
+    let mut account_in_memory =
+        seed_accounts(&config.accounts, &config).unwrap_or_else(|err| panic!("{err}"));
+
+    persistence::load(
+        &mut account_in_memory,
+        config.snapshot_file.as_deref(),
+        config.wal_file.as_deref(),
+        &config,
+    )
+    .expect("failed to restore accounts");
+    let storage = storage::open(config.database_url.as_deref())
+        .await
+        .expect("failed to open storage");
+    if let Some(storage) = &storage {
+        storage::load(storage.as_ref(), &mut account_in_memory, &config)
+            .await
+            .expect("failed to load stored transactions");
+    }
+    let wal = config.wal_file.as_deref().map(|path| {
+        Wal::open(path, config.wal_durability, config.log_rotation).expect("failed to open wal")
+    });
+
+    let audit = AuditLog::open(config.audit_log.as_deref(), config.log_rotation)
+        .expect("failed to open audit log");
+    let history = config.history_file.as_deref().map(|path| {
+        Arc::new(
+            history::Archive::open(path, config.log_rotation)
+                .expect("failed to open history archive"),
+        )
+    });
+
+    let ledger = config.ledger_file.as_deref().map(|path| {
+        let ledger =
+            ledger::Ledger::open(path, config.log_rotation).expect("failed to open ledger");
+        ledger.restore(&mut account_in_memory, SystemClock.now());
+        Arc::new(ledger)
+    });
+
+    let accounts = Arc::new(AccountRegistry::new(account_in_memory));
+
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let webhooks = config
+        .webhook
+        .clone()
+        .map(|webhook| Dispatcher::spawn(webhook, config.clone(), clock.clone()));
+
+    let run_stats = Arc::new(RunStats::new());
+    let mut observers: Vec<Arc<dyn AccountObserver>> = Vec::new();
+    let storage = storage.map(storage::Writer::spawn);
+    if let Some(storage) = &storage {
+        observers.push(storage.clone());
+    }
+    if let Some(history) = &history {
+        observers.push(history.clone());
+    }
+    if let Some(ledger) = &ledger {
+        observers.push(ledger.clone());
+    }
+    observers.push(Arc::new(Broadcast(accounts.clone())));
+    if let Some(webhooks) = &webhooks {
+        observers.push(webhooks.clone());
+    }
+    let replication = config
+        .replication
+        .clone()
+        .map(|replication| replication::Replication::start(replication, accounts.clone()));
+    if let Some(replication) = &replication {
+        observers.push(replication.clone());
+    }
+    observers.push(run_stats.clone());
+    observers.push(Arc::new(TransactionCounts(accounts.clone())));
+
+    let rate_limits = rate_limit::Limits {
+        ip: config.rate_limit_ip.map(rate_limit::Limiter::new),
+        account: config.rate_limit_account.map(rate_limit::Limiter::new),
+    };
+    let state = Arc::new(AppState {
+        accounts,
+        config,
+        clock,
+        maintenance: AtomicBool::new(false),
+        audit,
+        webhooks,
+        observers,
+        run_stats,
+        requests: RequestMetrics::default(),
+        rate_limits,
+        wal: wal.clone(),
+        history,
+        ledger,
+        storage: storage.clone(),
+        replication,
+    });
+
+    if let (Some(snapshot), Some(interval)) = (
+        state.config.snapshot_file.clone(),
+        state.config.snapshot_interval,
+    ) {
+        tokio::spawn(persistence::checkpoint_periodically(
+            state.clone(),
+            wal.clone(),
+            snapshot,
+            interval,
+        ));
+    }
+    // On a follower, schedules run, history is pruned and segments are
+    // archived by the leader: what they change is shipped here.
+    if !state.config.is_follower() {
+        tokio::spawn(schedule::run(state.clone()));
+        if state.config.history_retention.is_some() {
+            tokio::spawn(retention::run(state.clone()));
+        }
+        if state.config.archive_after.is_some() {
+            tokio::spawn(archival::run(state.clone()));
+        }
+    }
+    for (account_id, entry) in state.accounts.entries() {
+        if state.config.is_actor(account_id) {
+            actor::start(&state, account_id, &entry);
+        }
+    }
+
+    #[cfg(unix)]
+    tokio::spawn(toggle_maintenance_on_sigusr1(state.clone()));
+
+    if let Some(redirect) = state.config.tls.as_ref().and_then(|tls| tls.redirect) {
+        tokio::spawn(tls::redirect(redirect, state.config.bind.port()));
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(bind) = state.config.grpc_bind {
+        tokio::spawn(grpc::serve(state.clone(), bind));
+    }
+    serve(&state).await;
+    shut_down(&state).await;
+}
+
+/// After the listener stops: drains the storage writer, syncs the WAL,
+/// writes the snapshot and logs the run's report, to
+/// `BANK_SHUTDOWN_REPORT` too when set.
+async fn shut_down(state: &AppState) {
+    if let Some(storage) = &state.storage {
+        let pending = storage.drain(Duration::from_secs(5)).await;
+        if pending > 0 {
+            tracing::warn!(pending, "storage: writes not made before shutdown");
+        }
+    }
+
+    if let Some(wal) = &state.wal {
+        if let Err(err) = wal.flush() {
+            tracing::error!(error = %err, "wal fsync failed");
+        }
+    }
+
+    if let Some(path) = &state.config.snapshot_file {
+        match persistence::write_snapshot(state, path).await {
+            Ok(()) => tracing::info!(path = %path.display(), "snapshot written"),
+            Err(err) => tracing::error!(error = %err, "snapshot write failed"),
+        }
+    }
+
+    let report = state.run_stats.report(state);
+    tracing::info!(report = %report, "shutdown report");
+    if let Some(path) = &state.config.shutdown_report {
+        if let Err(err) = std::fs::write(path, format!("{report}\n")) {
+            tracing::error!(error = %err, "shutdown report write failed");
+        }
+    }
+}
+
+/// Until a shutdown signal, then `shutdown_grace` for requests already
+/// running.
+async fn serve(state: &Arc<AppState>) {
+    let app = http::router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+    let grace = state.config.shutdown_grace;
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &state.config.tls {
+        let bind = state.config.bind;
+        return tls::serve(tls, bind, app, shutdown_signal(), grace)
+            .await
+            .unwrap_or_else(|err| panic!("failed to serve TLS on {bind}: {err}"));
+    }
+
+    let listener = tokio::net::TcpListener::bind(state.config.bind)
+        .await
+        .unwrap_or_else(|err| panic!("failed to bind {}: {err}", state.config.bind));
+    // Once signalled the listener closes and idle connections are dropped;
+    // requests already running get `shutdown_grace` to finish.
+    let (signalled, mut stopping) = tokio::sync::watch::channel(false);
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = signalled.send(true);
+    });
+    let expired = async {
+        let _ = stopping.wait_for(|stopping| *stopping).await;
+        tokio::time::sleep(grace).await;
+    };
+    tokio::select! {
+        served = server.into_future() => served.unwrap(),
+        () = expired => tracing::warn!(
+            grace_ms = grace.as_millis() as u64,
+            "shutdown: requests still open after the grace period, dropping them"
+        ),
+    }
+}
+
+/// Ctrl-C, or SIGTERM from an orchestrator stopping the container.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut signals) => {
+                signals.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+    tracing::info!("shutdown: draining in-flight requests");
+}
+
+#[cfg(unix)]
+async fn toggle_maintenance_on_sigusr1(state: Arc<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::user_defined1()).expect("failed to listen for SIGUSR1");
+    while signals.recv().await.is_some() {
+        let enabled = !state.maintenance.fetch_xor(true, Ordering::SeqCst);
+        tracing::warn!(enabled, "maintenance mode toggled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use crate::{
+        config::Config,
+        domain::TransactionRequest,
+        registry::AccountId,
+        service::{BankService, Options},
+        testing::{bank, get, post, send, START},
+    };
+
+    #[tokio::test]
+    async fn a_transaction_through_the_state() {
+        let (state, clock) = bank(Config::default());
+        assert_eq!(state.accounts.len(), Config::default().accounts.len());
+        assert_eq!(state.clock.now(), START);
+        clock.advance(Duration::minutes(1));
+
+        let request: TransactionRequest = serde_json::from_value(serde_json::json!({
+            "valor": 1_500, "tipo": "D", "descricao": "estado",
+        }))
+        .unwrap();
+        let result = BankService::new(state.clone())
+            .transact(AccountId(1), request, Options::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.balance, -1_500);
+
+        // The registry, the observers and the router all see it.
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let account = entry.account.read().await;
+        assert_eq!(account.balance, -1_500);
+        assert_eq!(account.modified_at, START + Duration::minutes(1));
+        drop(account);
+        assert_eq!(state.run_stats.report(&state)["transacoes"], 1);
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], -1_500);
+        assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "estado");
+    }
+
+    #[tokio::test]
+    async fn shutting_down_writes_the_run_report() {
+        let path = std::env::temp_dir().join(format!("bank-{}-report.json", std::process::id()));
+        let (state, _) = bank(Config {
+            shutdown_report: Some(path.clone()),
+            ..Config::default()
+        });
+        for (valor, tipo) in [(500, "C"), (60_000, "D"), (60_000, "D")] {
+            let body = serde_json::json!({"valor": valor, "tipo": tipo, "descricao": "fim"});
+            send(&state, post("/clientes/1/transacoes", body)).await;
+        }
+
+        super::shut_down(&state).await;
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(report["evento"], "encerramento");
+        assert_eq!(report["contas"], Config::default().accounts.len());
+        assert_eq!(report["transacoes"], 2);
+        assert_eq!(
+            report["rejeicoes"],
+            serde_json::json!({"Limite insuficiente": 1})
+        );
+        assert!(report["uptime_segundos"].as_f64().unwrap() >= 0.0);
+        assert_eq!(report["em"], crate::testing::timestamp(START));
+        std::fs::remove_file(path).unwrap();
+    }
+}
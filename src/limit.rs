@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    deadline::Deadline,
+    error::{self, AppError, JsonBody},
+    registry::AccountId,
+    AppState, TransactionType,
+};
+
+pub const NOT_TRANSFERABLE: &str = "limite da conta de origem nao cobre a transferencia";
+
+#[derive(Deserialize)]
+pub struct LimitTransferRequest {
+    #[serde(rename = "para")]
+    to: AccountId,
+    #[serde(rename = "valor")]
+    value: i64,
+}
+
+/// Moves credit limit, not balance, from `:id` to `para`. Both accounts are
+/// locked in id order so opposite transfers cannot deadlock, and the source
+/// must still cover its balance and holds afterwards. Both are logged in one
+/// WAL write, so a restart never finds the limit on one side only.
+pub async fn transfer(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    JsonBody(request): JsonBody<LimitTransferRequest>,
+) -> Result<Json<Value>, AppError> {
+    if request.to == account_id {
+        return Err(AppError::Invalid(error::SAME_ACCOUNT));
+    }
+    if request.value <= 0 {
+        return Err(AppError::Invalid("valor invalido"));
+    }
+    let (Some(from), Some(to)) = (
+        state.accounts.get(&account_id),
+        state.accounts.get(&request.to),
+    ) else {
+        return Err(AppError::UnknownAccount);
+    };
+
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    let (mut from, mut to) = state
+        .write_pair((account_id, &from), (request.to, &to), deadline)
+        .await?;
+
+    let remaining = from.limit - request.value;
+    if remaining < 0 || to.limit + request.value > state.config.max_limit {
+        return Err(AppError::Invalid(NOT_TRANSFERABLE));
+    }
+    // What `check_debit` would let a debit take with the reduced limit,
+    // holds and the policy's tolerance included, must not go negative.
+    let now = state.clock.now();
+    let current = std::mem::replace(&mut from.limit, remaining);
+    if from.available(now) + from.account_type.policy.tolerance() < 0 {
+        from.limit = current;
+        return Err(AppError::Invalid(NOT_TRANSFERABLE));
+    }
+    let before = ((*from).clone(), (*to).clone());
+    to.limit += request.value;
+    from.touch(now);
+    to.touch(now);
+    if let Err(error) = state.persist_all(&[(account_id, &from), (request.to, &to)]) {
+        (*from, *to) = before;
+        from.limit = current;
+        return Err(error);
+    }
+    state.changed(account_id);
+    state.changed(request.to);
+
+    state.audit.record(json!({
+        "evento": "limite_transferido",
+        "de": account_id,
+        "para": request.to,
+        "valor": request.value,
+        "em": state.config.timestamp(now),
+    }));
+
+    Ok(Json(json!({
+        "de": { "account": account_id, "limite": state.config.amount(from.limit) },
+        "para": { "account": request.to, "limite": state.config.amount(to.limit) },
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    #[serde(rename = "novo")]
+    limit: i64,
+}
+
+/// What `novo` as the limit would change: which retained rejected debits
+/// would now pass, each checked alone against the current balance and holds,
+/// and whether the balance would already sit beyond the new limit.
+pub async fn preview(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<PreviewQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    if query.limit < 0 {
+        return Err(AppError::Invalid(error::INVALID_LIMIT));
+    }
+    if query.limit > state.config.max_limit {
+        return Err(AppError::Invalid(error::LIMIT_ABOVE_MAX));
+    }
+    let mut account = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?
+        .account
+        .read()
+        .await
+        .clone();
+    let current = account.limit;
+    account.limit = query.limit;
+    account.dynamic_limit = None;
+
+    let now = state.clock.now();
+    let formatter = state.config.formatter();
+    let rejections: Vec<Value> = account
+        .rejections
+        .iter()
+        // Other currencies do not draw on the limit.
+        .filter(|rejection| {
+            rejection.transaction.currency.is_none()
+                && matches!(rejection.transaction.kind, TransactionType::Debit)
+        })
+        .map(|rejection| {
+            json!({
+                "valor": formatter.amount(rejection.transaction.value),
+                "motivo": rejection.error.reason(),
+                "em": state.config.timestamp(rejection.at),
+                "passaria": account.check_debit(rejection.transaction.value, now).is_ok(),
+            })
+        })
+        .collect();
+    let passing = rejections
+        .iter()
+        .filter(|rejection| rejection["passaria"] == true)
+        .count();
+
+    Ok(Json(json!({
+        "account": account_id,
+        "limite_atual": formatter.amount(current),
+        "limite_novo": formatter.amount(query.limit),
+        "saldo": formatter.amount(account.balance),
+        "excede_limite": account.balance < -query.limit,
+        "passariam": passing,
+        "rejeicoes": rejections,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::{
+        config::Config,
+        registry::AccountId,
+        testing::{admin, admin_config, bank, get, post, send},
+    };
+
+    #[tokio::test]
+    async fn a_transfer_gives_up_on_a_held_lock() {
+        let (state, _) = bank(Config {
+            lock_timeout: Some(Duration::from_millis(10)),
+            ..admin_config()
+        });
+        let entry = state.accounts.get(&AccountId(2)).unwrap();
+        let held = entry.account.write().await;
+
+        let body = json!({"para": 2, "valor": 100});
+        let request = admin(post("/clientes/1/limite/transferir", body));
+        let (status, _) = send(&state, request).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(entry.lock_timeouts.load(Ordering::Relaxed), 1);
+
+        drop(held);
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(body["saldo"]["limite"], 100_000);
+    }
+}
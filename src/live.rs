@@ -0,0 +1,102 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{stream, Stream};
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    error::AppError,
+    registry::{AccountId, Entry},
+    render_transaction, AppState, Transaction, TransactionType,
+};
+
+/// Where the stream's running balance stands.
+struct Cursor {
+    entry: Arc<Entry>,
+    receiver: broadcast::Receiver<Transaction>,
+    balance: i64,
+    /// A `saldo` event is owed before the next transaction.
+    resync: bool,
+}
+
+impl Cursor {
+    /// Subscribes under the read lock, so no transaction can land between
+    /// the balance read here and the first one received.
+    async fn open(entry: Arc<Entry>) -> Self {
+        let account = entry.account.read().await;
+        let receiver = entry.events.subscribe();
+        let balance = account.balance;
+        drop(account);
+        Self {
+            entry,
+            receiver,
+            balance,
+            resync: true,
+        }
+    }
+}
+
+/// `GET /clientes/:id/extrato/stream`: a `saldo` event with the balance and
+/// limit on connect, then a `transacao` event per accepted transaction with
+/// the balance it left, computed from the previous one. A subscriber that
+/// falls behind the channel gets a fresh `saldo` instead of the transactions
+/// it missed. Limit changes and imports are not transactions and only show
+/// on the next `saldo`.
+pub async fn statement(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let cursor = Cursor::open(entry).await;
+
+    let events = stream::unfold((cursor, state), move |(mut cursor, state)| async move {
+        let formatter = state.config.formatter();
+        if cursor.resync {
+            cursor.resync = false;
+            let limit = cursor.entry.account.read().await.limit;
+            let event = Event::default().event("saldo").json_data(json!({
+                "account": account_id,
+                "total": formatter.amount(cursor.balance),
+                "limite": formatter.amount(limit),
+            }));
+            return Some((Ok(serialized(event)), (cursor, state)));
+        }
+        match cursor.receiver.recv().await {
+            Ok(transaction) => {
+                // The running `saldo` is the base currency's.
+                match (transaction.currency, transaction.kind) {
+                    (Some(_), _) => {}
+                    (None, TransactionType::Credit) => cursor.balance += transaction.value,
+                    (None, TransactionType::Debit) => cursor.balance -= transaction.value,
+                }
+                let event = Event::default()
+                    .event("transacao")
+                    .id(transaction.id.to_string())
+                    .json_data(json!({
+                        "account": account_id,
+                        "transacao": render_transaction(&transaction, &state.config),
+                        "saldo": formatter.amount(cursor.balance),
+                    }));
+                Some((Ok(serialized(event)), (cursor, state)))
+            }
+            Err(RecvError::Lagged(_)) => {
+                let cursor = Cursor::open(cursor.entry).await;
+                Some((Ok(Event::default().comment("resync")), (cursor, state)))
+            }
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn serialized(event: Result<Event, axum::Error>) -> Event {
+    event.unwrap_or_else(|_| Event::default().comment("serialization error"))
+}
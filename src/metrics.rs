@@ -0,0 +1,465 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex, RwLock as StdRwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tokio::sync::RwLockWriteGuard;
+
+use crate::{
+    i18n,
+    observer::{AccountObserver, Outcome},
+    registry::{AccountId, AccountRegistry},
+    Account, AppState, Transaction, TransactionType,
+};
+
+/// Totals for this process run, reported on shutdown.
+pub struct RunStats {
+    started: Instant,
+    accepted: AtomicU64,
+    rejected: Mutex<BTreeMap<String, u64>>,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            accepted: AtomicU64::new(0),
+            rejected: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn report(&self, state: &AppState) -> Value {
+        json!({
+            "evento": "encerramento",
+            "contas": state.accounts.len(),
+            "transacoes": self.accepted.load(Ordering::Relaxed),
+            "rejeicoes": *self.rejected.lock().unwrap(),
+            "uptime_segundos": self.started.elapsed().as_secs_f64(),
+            "em": state.config.timestamp(state.clock.now()),
+        })
+    }
+}
+
+impl AccountObserver for RunStats {
+    fn on_transaction(&self, _: AccountId, _: &Transaction, outcome: &Outcome) {
+        match outcome {
+            Ok(()) => {
+                self.accepted.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(error) => {
+                *self
+                    .rejected
+                    .lock()
+                    .unwrap()
+                    .entry(error.reason().to_string())
+                    .or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Upper bounds, in seconds, of the lock-wait histogram buckets.
+const WAIT_BUCKETS: [f64; 9] = [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per-bucket (not cumulative) counts; the last slot is `+Inf`.
+    buckets: Box<[AtomicU64]>,
+    nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            nanos: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// `_bucket`, `_sum` and `_count` lines; `labels` is the rendered label
+    /// list without braces.
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            cumulative += count.load(Ordering::Relaxed);
+            let bound = match self.bounds.get(index) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(
+            out,
+            "{name}_sum{{{labels}}} {}",
+            self.nanos.load(Ordering::Relaxed) as f64 / 1e9
+        );
+        let _ = writeln!(
+            out,
+            "{name}_count{{{labels}}} {}",
+            self.count.load(Ordering::Relaxed)
+        );
+    }
+}
+
+pub struct AccountMetrics {
+    /// Writes waiting for or holding the account's write lock.
+    in_flight: AtomicI64,
+    lock_wait: Histogram,
+    /// Accepted transactions, credits then debits.
+    transactions: [AtomicU64; 2],
+    /// Refusals by `i18n` code.
+    rejections: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Default for AccountMetrics {
+    fn default() -> Self {
+        Self {
+            in_flight: AtomicI64::new(0),
+            lock_wait: Histogram::new(&WAIT_BUCKETS),
+            transactions: Default::default(),
+            rejections: Mutex::default(),
+        }
+    }
+}
+
+impl AccountMetrics {
+    pub fn start_write(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.in_flight)
+    }
+
+    pub fn observe_wait(&self, wait: Duration) {
+        self.lock_wait.observe(wait);
+    }
+}
+
+/// Counts outcomes per account for `/metrics`.
+pub struct TransactionCounts(pub Arc<AccountRegistry>);
+
+impl AccountObserver for TransactionCounts {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        let Some(entry) = self.0.get(&account_id) else {
+            return;
+        };
+        match outcome {
+            Ok(()) => {
+                let kind = match transaction.kind {
+                    TransactionType::Credit => 0,
+                    TransactionType::Debit => 1,
+                };
+                entry.metrics.transactions[kind].fetch_add(1, Ordering::Relaxed);
+            }
+            Err(error) => {
+                *entry
+                    .metrics
+                    .rejections
+                    .lock()
+                    .unwrap()
+                    .entry(i18n::code(error.reason()))
+                    .or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Latency and status counts per matched route, e.g.
+/// `POST /clientes/:id/transacoes`, so account ids never become labels.
+#[derive(Default)]
+pub struct RequestMetrics {
+    routes: StdRwLock<BTreeMap<(String, String), Arc<RouteMetrics>>>,
+}
+
+struct RouteMetrics {
+    latency: Histogram,
+    statuses: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl RequestMetrics {
+    fn route(&self, method: &str, path: &str) -> Arc<RouteMetrics> {
+        let key = (method.to_string(), path.to_string());
+        if let Some(route) = self.routes.read().unwrap().get(&key) {
+            return route.clone();
+        }
+        self.routes
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(RouteMetrics {
+                    latency: Histogram::new(&LATENCY_BUCKETS),
+                    statuses: Mutex::default(),
+                })
+            })
+            .clone()
+    }
+}
+
+/// Times every routed request. Requests matching no route are not counted.
+pub async fn track_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(path) = request.extensions().get::<MatchedPath>().cloned() else {
+        return next.run(request).await;
+    };
+    let route = state
+        .requests
+        .route(request.method().as_str(), path.as_str());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    route.latency.observe(started.elapsed());
+    *route
+        .statuses
+        .lock()
+        .unwrap()
+        .entry(response.status().as_u16())
+        .or_default() += 1;
+    response
+}
+
+/// Keeps a write counted as in flight until dropped.
+pub struct InFlight<'a>(&'a AtomicI64);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// An account write guard that stays counted in `in_flight` while held.
+pub(crate) struct TrackedWrite<'a> {
+    guard: RwLockWriteGuard<'a, Account>,
+    _in_flight: InFlight<'a>,
+}
+
+impl<'a> TrackedWrite<'a> {
+    pub fn new(guard: RwLockWriteGuard<'a, Account>, in_flight: InFlight<'a>) -> Self {
+        Self {
+            guard,
+            _in_flight: in_flight,
+        }
+    }
+}
+
+impl Deref for TrackedWrite<'_> {
+    type Target = Account;
+
+    fn deref(&self) -> &Account {
+        &self.guard
+    }
+}
+
+impl DerefMut for TrackedWrite<'_> {
+    fn deref_mut(&mut self) -> &mut Account {
+        &mut self.guard
+    }
+}
+
+/// Prometheus text exposition format.
+pub async fn render(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let entries = state.accounts.entries();
+
+    let mut out = String::new();
+    out.push_str("# HELP bank_account_writes_in_flight Writes waiting for or holding the account write lock.\n");
+    out.push_str("# TYPE bank_account_writes_in_flight gauge\n");
+    for (id, entry) in &entries {
+        let metrics = &entry.metrics;
+        let _ = writeln!(
+            out,
+            "bank_account_writes_in_flight{{account=\"{id}\"}} {}",
+            metrics.in_flight.load(Ordering::Relaxed)
+        );
+    }
+
+    out.push_str(
+        "# HELP bank_account_lock_wait_seconds Time spent waiting for the account write lock.\n",
+    );
+    out.push_str("# TYPE bank_account_lock_wait_seconds histogram\n");
+    for (id, entry) in &entries {
+        let labels = format!("account=\"{id}\"");
+        entry
+            .metrics
+            .lock_wait
+            .write(&mut out, "bank_account_lock_wait_seconds", &labels);
+    }
+
+    out.push_str("# HELP bank_account_lock_timeouts_total Write-lock waits that gave up after BANK_LOCK_TIMEOUT_MS.\n");
+    out.push_str("# TYPE bank_account_lock_timeouts_total counter\n");
+    for (id, entry) in &entries {
+        let _ = writeln!(
+            out,
+            "bank_account_lock_timeouts_total{{account=\"{id}\"}} {}",
+            entry.lock_timeouts.load(Ordering::Relaxed)
+        );
+    }
+
+    out.push_str("# HELP bank_transactions_total Accepted transactions.\n");
+    out.push_str("# TYPE bank_transactions_total counter\n");
+    for (id, entry) in &entries {
+        for (index, kind) in ["C", "D"].iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "bank_transactions_total{{account=\"{id}\",tipo=\"{kind}\"}} {}",
+                entry.metrics.transactions[index].load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    out.push_str("# HELP bank_transactions_rejected_total Refused transactions by reason.\n");
+    out.push_str("# TYPE bank_transactions_rejected_total counter\n");
+    for (id, entry) in &entries {
+        for (code, count) in entry.metrics.rejections.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "bank_transactions_rejected_total{{account=\"{id}\",motivo=\"{code}\"}} {count}"
+            );
+        }
+    }
+
+    out.push_str("# HELP bank_account_balance Current balance, in minor units.\n");
+    out.push_str("# TYPE bank_account_balance gauge\n");
+    let mut limits = String::new();
+    for (id, entry) in &entries {
+        let account = entry.account.read().await;
+        let _ = writeln!(
+            out,
+            "bank_account_balance{{account=\"{id}\"}} {}",
+            account.balance
+        );
+        let _ = writeln!(
+            limits,
+            "bank_account_limit{{account=\"{id}\"}} {}",
+            account.limit
+        );
+    }
+    out.push_str("# HELP bank_account_limit Current credit limit, in minor units.\n");
+    out.push_str("# TYPE bank_account_limit gauge\n");
+    out.push_str(&limits);
+
+    let routes = state.requests.routes.read().unwrap();
+    out.push_str("# HELP bank_http_request_duration_seconds Request latency by route.\n");
+    out.push_str("# TYPE bank_http_request_duration_seconds histogram\n");
+    for ((method, path), route) in routes.iter() {
+        let labels = format!("method=\"{method}\",route=\"{path}\"");
+        route
+            .latency
+            .write(&mut out, "bank_http_request_duration_seconds", &labels);
+    }
+    out.push_str("# HELP bank_http_requests_total Requests by route and status.\n");
+    out.push_str("# TYPE bank_http_requests_total counter\n");
+    for ((method, path), route) in routes.iter() {
+        for (status, count) in route.statuses.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "bank_http_requests_total{{method=\"{method}\",route=\"{path}\",status=\"{status}\"}} {count}"
+            );
+        }
+    }
+    drop(routes);
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use serde_json::json;
+
+    use crate::{
+        config::Config,
+        registry::AccountId,
+        testing::{bank, get, post, send, text},
+    };
+
+    #[tokio::test]
+    async fn lock_waits_and_in_flight_writes_are_scraped() {
+        let (state, _) = bank(Config::default());
+        for _ in 0..3 {
+            let credit = json!({"valor": 10, "tipo": "C", "descricao": "metrica"});
+            send(&state, post("/clientes/1/transacoes", credit)).await;
+        }
+
+        let (_, body) = text(&state, get("/metrics")).await;
+        assert!(body.contains("# TYPE bank_account_lock_wait_seconds histogram"));
+        assert!(body.contains("bank_account_lock_wait_seconds_count{account=\"1\"} 3"));
+        assert!(body.contains("bank_account_lock_wait_seconds_bucket{account=\"1\",le=\"+Inf\"} 3"));
+        assert!(body.contains("bank_account_lock_wait_seconds_count{account=\"2\"} 0"));
+        assert!(body.contains("bank_account_writes_in_flight{account=\"1\"} 0"));
+
+        // The scrape reads every balance, so it cannot run while a write
+        // holds the lock; the gauge it renders is checked directly.
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let held = state.write_account(&entry, None).await.unwrap();
+        assert_eq!(entry.metrics.in_flight.load(Ordering::Relaxed), 1);
+        drop(held);
+        assert_eq!(entry.metrics.in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn outcomes_balances_and_routes_are_scraped() {
+        let (state, _) = bank(Config::default());
+        for (valor, tipo) in [(500, "C"), (200, "D"), (100, "D"), (200_000, "D")] {
+            let transaction = json!({"valor": valor, "tipo": tipo, "descricao": "metrica"});
+            send(&state, post("/clientes/1/transacoes", transaction)).await;
+        }
+
+        let (_, body) = text(&state, get("/metrics")).await;
+        assert!(body.contains("bank_transactions_total{account=\"1\",tipo=\"C\"} 1"));
+        assert!(body.contains("bank_transactions_total{account=\"1\",tipo=\"D\"} 2"));
+        assert!(body.contains("bank_transactions_total{account=\"2\",tipo=\"D\"} 0"));
+        assert!(body.contains(
+            "bank_transactions_rejected_total{account=\"1\",motivo=\"limite_insuficiente\"} 1"
+        ));
+        assert!(body.contains("bank_account_balance{account=\"1\"} 200"));
+        assert!(body.contains("bank_account_limit{account=\"1\"} 100000"));
+
+        // Labelled by route, not by the account in the path.
+        let route = "method=\"POST\",route=\"/clientes/:id/transacoes\"";
+        assert!(body.contains(&format!(
+            "bank_http_request_duration_seconds_count{{{route}}} 4"
+        )));
+        assert!(body.contains(&format!(
+            "bank_http_request_duration_seconds_bucket{{{route},le=\"+Inf\"}} 4"
+        )));
+        assert!(body.contains(&format!(
+            "bank_http_requests_total{{{route},status=\"200\"}} 3"
+        )));
+        assert!(body.contains(&format!(
+            "bank_http_requests_total{{{route},status=\"422\"}} 1"
+        )));
+        assert!(!body.contains("route=\"/clientes/1/transacoes\""));
+    }
+}
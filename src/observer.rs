@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use crate::{
+    registry::{AccountId, AccountRegistry},
+    transact::TransactError,
+    Transaction,
+};
+
+/// What `Account::transact` made of a transaction: accepted, or why it was
+/// refused.
+pub type Outcome = Result<(), TransactError>;
+
+/// Side effect of a transaction outcome, registered in `AppState::observers`.
+/// Called while the account's write lock is still held, so implementations
+/// must not block on anything slow; hand work off to a queue instead.
+pub trait AccountObserver: Send + Sync {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        let _ = (account_id, transaction, outcome);
+    }
+
+    /// A transaction the leader accepted, applied here by replication. An
+    /// acceptance like any other, unless the side effect already happened on
+    /// the leader.
+    fn on_replicated(&self, account_id: AccountId, transaction: &Transaction) {
+        self.on_transaction(account_id, transaction, &Ok(()));
+    }
+}
+
+/// Feeds accepted transactions to the account's SSE subscribers.
+pub struct Broadcast(pub Arc<AccountRegistry>);
+
+impl AccountObserver for Broadcast {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        if let (Ok(()), Some(entry)) = (outcome, self.0.get(&account_id)) {
+            // No subscribers is the common case, not an error.
+            let _ = entry.events.send(transaction.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        testing::{bank, post, send},
+    };
+
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<(AccountId, i64, Outcome)>>);
+
+    impl AccountObserver for Recording {
+        fn on_transaction(
+            &self,
+            account_id: AccountId,
+            transaction: &Transaction,
+            outcome: &Outcome,
+        ) {
+            let seen = (account_id, transaction.value, *outcome);
+            self.0.lock().unwrap().push(seen);
+        }
+    }
+
+    /// Relies on the default, which does nothing.
+    struct Silent;
+
+    impl AccountObserver for Silent {}
+
+    #[tokio::test]
+    async fn observers_see_accepted_and_rejected_transactions() {
+        let (mut state, _) = bank(Config::default());
+        let recording = Arc::new(Recording::default());
+        let observers = &mut Arc::get_mut(&mut state).unwrap().observers;
+        observers.push(Arc::new(Silent));
+        observers.push(recording.clone());
+
+        let credit = json!({"valor": 500, "tipo": "C", "descricao": "entra"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::OK);
+        let debit = json!({"valor": 100_501, "tipo": "D", "descricao": "estoura"});
+        let (status, _) = send(&state, post("/clientes/2/transacoes", debit)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let seen = recording.0.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0], (AccountId(1), 500, Ok(())));
+        assert_eq!(seen[1].0, AccountId(2));
+        assert_eq!(seen[1].1, 100_501);
+        assert!(seen[1].2.is_err());
+    }
+}
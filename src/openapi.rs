@@ -0,0 +1,490 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::Html, Json};
+use serde_json::{json, Value};
+
+use crate::{config::Config, currency::AmountFormat, i18n, AppState};
+
+/// `GET /openapi.json`: the public API as an OpenAPI 3.0 document. Written by
+/// hand next to the handlers rather than derived, and built from the running
+/// config, so `valor` and friends carry the wire format actually in use.
+pub async fn document(State(state): State<Arc<AppState>>) -> Json<Value> {
+    Json(spec(&state.config))
+}
+
+/// `GET /docs`: Swagger UI over `/openapi.json`. The UI's own assets come
+/// from a CDN; nothing of it is bundled into the binary.
+pub async fn ui() -> Html<&'static str> {
+    Html(
+        r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>bank API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#ui" });</script>
+</body>
+</html>
+"##,
+    )
+}
+
+fn spec(config: &Config) -> Value {
+    let amount = match config.amount_format {
+        AmountFormat::Minor => json!({
+            "type": "integer",
+            "format": "int64",
+            "description": "Minor units (centavos).",
+        }),
+        AmountFormat::Decimal => json!({
+            "type": "string",
+            "example": "10.00",
+            "description": format!("Decimal string in {}.", config.currency.code()),
+        }),
+    };
+    let account = json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "int64", "minimum": 0 },
+    });
+    let if_match = json!({
+        "name": "If-Match",
+        "in": "header",
+        "schema": { "type": "string" },
+        "description": "`versao` lida antes; 409 se a conta mudou desde entao.",
+    });
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Erro" } } },
+        })
+    };
+    let body = |schema: &str| {
+        json!({
+            "required": true,
+            "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{schema}") } } },
+        })
+    };
+    let ok = |description: &str, schema: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{schema}") } } },
+        })
+    };
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "bank",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Contas, transacoes e extratos. Campos em portugues; \
+                erros sempre como `Erro`, com `message` no idioma do Accept-Language.",
+        },
+        "paths": {
+            "/clientes/{id}/transacoes": {
+                "post": {
+                    "summary": "Credita ou debita a conta",
+                    "parameters": [
+                        account,
+                        { "name": "parcial", "in": "query", "schema": { "type": "boolean" },
+                          "description": "Debita o que couber em vez de recusar." },
+                        { "name": "Idempotency-Key", "in": "header", "schema": { "type": "string", "maxLength": 255 } },
+                        if_match.clone(),
+                    ],
+                    "requestBody": body("TransacaoRequest"),
+                    "responses": {
+                        "200": ok("Transacao aceita", "TransacaoResposta"),
+                        "202": { "description": "`realizada_em` no futuro: agendada, ver `/transacoes/agendadas`" },
+                        "400": error("Corpo malformado ou Idempotency-Key invalida"),
+                        "404": error("Conta inexistente"),
+                        "409": error("Duplicada, Idempotency-Key em uso ou versao desatualizada"),
+                        "422": error("Recusada: campos invalidos, limite, saldo, regras de debito"),
+                        "429": error("Intervalo minimo entre transacoes ou limite de requisicoes"),
+                        "503": error("Instancia seguidora sem acesso ao lider (BANK_REPLICATION_ROLE=follower)"),
+                    },
+                },
+            },
+            "/clientes/{id}/transacoes/lote": {
+                "post": {
+                    "summary": "Aplica uma lista de transacoes, todas ou nenhuma",
+                    "parameters": [account, if_match.clone()],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "array",
+                            "minItems": 1,
+                            "maxItems": 1000,
+                            "items": { "$ref": "#/components/schemas/TransacaoRequest" },
+                        } } },
+                    },
+                    "responses": {
+                        "200": { "description": "Todas aplicadas; as de `realizada_em` futura, agendadas" },
+                        "400": error("Alguma malformada; `indice` diz qual"),
+                        "404": error("Conta inexistente"),
+                        "409": error("Versao desatualizada"),
+                        "422": error("Alguma invalida ou recusada; `indice` diz qual, nada foi aplicado"),
+                    },
+                },
+            },
+            "/clientes/{id}/transacoes/agendadas": {
+                "get": {
+                    "summary": "Transacoes agendadas, pendentes ou que falharam ao executar",
+                    "parameters": [account],
+                    "responses": {
+                        "200": { "description": "`agendamentos`" },
+                        "404": error("Conta inexistente"),
+                    },
+                },
+            },
+            "/clientes/{id}/transacoes/agendadas/{agendamento_id}": {
+                "delete": {
+                    "summary": "Cancela um agendamento pendente",
+                    "parameters": [
+                        account,
+                        { "name": "agendamento_id", "in": "path", "required": true, "schema": { "type": "integer" } },
+                    ],
+                    "responses": {
+                        "204": { "description": "Cancelado" },
+                        "404": error("Conta ou agendamento inexistente"),
+                    },
+                },
+            },
+            "/clientes/{id}/extrato": {
+                "get": {
+                    "summary": "Saldo e ultimas transacoes",
+                    "parameters": [
+                        account,
+                        { "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 1, "maximum": 100 } },
+                        { "name": "offset", "in": "query", "schema": { "type": "integer", "minimum": 0 } },
+                        { "name": "cursor", "in": "query", "schema": { "type": "string" } },
+                        { "name": "tipo", "in": "query", "schema": { "type": "string", "enum": ["C", "D"] } },
+                        { "name": "desde", "in": "query", "schema": { "type": "string", "format": "date-time" } },
+                        { "name": "ate", "in": "query", "schema": { "type": "string", "format": "date-time" } },
+                        { "name": "formato", "in": "query", "schema": { "type": "string", "enum": ["json", "csv", "ofx"] },
+                          "description": "Ou `Accept: text/csv` / `application/x-ofx`. CSV e OFX trazem o historico \
+                            inteiro com BANK_HISTORY_FILE; sem ele, so o retido (`x-historico-parcial`)." },
+                    ],
+                    "responses": {
+                        "200": ok("Extrato", "Extrato"),
+                        "404": error("Conta inexistente"),
+                        "422": error("Paginacao ou formato invalido"),
+                    },
+                },
+            },
+            "/clientes/{id}/extrato/stream": {
+                "get": {
+                    "summary": "Eventos `saldo` e `transacao` (Server-Sent Events)",
+                    "parameters": [account],
+                    "responses": {
+                        "200": { "description": "text/event-stream" },
+                        "404": error("Conta inexistente"),
+                    },
+                },
+            },
+            "/clientes/{id}/saldo": {
+                "get": {
+                    "summary": "Saldo num instante passado, somando as transacoes ate `em`",
+                    "parameters": [
+                        account,
+                        { "name": "em", "in": "query", "schema": { "type": "string", "format": "date-time" },
+                          "description": "Padrao: agora" },
+                        { "name": "moeda", "in": "query", "schema": { "type": "string" },
+                          "description": "Padrao: a moeda configurada" },
+                    ],
+                    "responses": {
+                        "200": { "description": "`saldo`, quantas `transacoes` entraram e a `fonte` (ledger ou historico)" },
+                        "404": error("Conta inexistente"),
+                        "422": error("Moeda desconhecida, ou sem BANK_LEDGER_FILE o historico retido nao alcanca `em`"),
+                    },
+                },
+            },
+            "/clientes/{id}/arquivo": {
+                "get": {
+                    "summary": "Transacoes de um periodo, arquivadas ou nao, com o saldo antes e depois (BANK_ARCHIVE_AFTER_MS)",
+                    "parameters": [
+                        account,
+                        { "name": "periodo", "in": "query", "required": true, "schema": { "type": "string" },
+                          "description": "`AAAA-MM`, `AAAA-MM-DD` ou `AAAA-MM-DD/AAAA-MM-DD`, dias inteiros em UTC" },
+                    ],
+                    "responses": {
+                        "200": { "description": "`transacoes` do periodo, `saldo_inicial` e `saldo_final` (nulos se o historico da conta foi apagado) e os `segmentos` lidos" },
+                        "404": error("Conta inexistente ou arquivamento desativado"),
+                        "422": error("Periodo invalido"),
+                        "500": error("Historico arquivado ilegivel"),
+                    },
+                },
+            },
+            "/clientes/{id}/resumo": {
+                "get": {
+                    "summary": "Totais de um mes por categoria e por tipo, tirados do historico",
+                    "parameters": [
+                        account,
+                        { "name": "mes", "in": "query", "required": true, "schema": { "type": "string" },
+                          "description": "`AAAA-MM`, em UTC" },
+                        { "name": "moeda", "in": "query", "schema": { "type": "string" },
+                          "description": "Padrao: a moeda configurada" },
+                    ],
+                    "responses": {
+                        "200": { "description": "`por_categoria` (`sem_categoria` para as sem uma), `por_tipo` e a `fonte` (arquivo ou historico)" },
+                        "404": error("Conta inexistente"),
+                        "422": error("Mes invalido ou moeda desconhecida"),
+                        "500": error("Historico arquivado ilegivel"),
+                    },
+                },
+            },
+            "/clientes/{id}/ledger": {
+                "get": {
+                    "summary": "Eventos imutaveis do saldo, do mais antigo ao mais novo (BANK_LEDGER_FILE)",
+                    "parameters": [
+                        account,
+                        { "name": "cursor", "in": "query", "schema": { "type": "integer", "minimum": 0 },
+                          "description": "`seq` do ultimo evento ja lido" },
+                        { "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 1, "maximum": 1000 } },
+                    ],
+                    "responses": {
+                        "200": ok("Eventos; `saldo` de cada um e o saldo logo apos ele", "Ledger"),
+                        "404": error("Conta inexistente ou ledger desativado"),
+                        "422": error("Paginacao invalida"),
+                    },
+                },
+            },
+            "/clientes/{id}/transferencias": {
+                "post": {
+                    "summary": "Debita a conta e credita `para` num so passo",
+                    "parameters": [account, if_match.clone()],
+                    "requestBody": body("TransferenciaRequest"),
+                    "responses": {
+                        "200": { "description": "Transferencia feita" },
+                        "404": error("Alguma das contas inexistente"),
+                        "409": error("Versao da conta debitada desatualizada"),
+                        "422": error("Recusada"),
+                    },
+                },
+            },
+            "/clientes/{id}/transacoes/{tx_id}/estorno": {
+                "post": {
+                    "summary": "Estorna uma transacao",
+                    "parameters": [
+                        account,
+                        { "name": "tx_id", "in": "path", "required": true, "schema": { "type": "string" } },
+                        if_match,
+                    ],
+                    "responses": {
+                        "200": ok("Estorno aceito", "TransacaoResposta"),
+                        "404": error("Conta ou transacao inexistente"),
+                        "409": error("Versao desatualizada"),
+                        "422": error("Ja estornada, estorno de estorno ou de transferencia"),
+                    },
+                },
+            },
+            "/capabilities": {
+                "get": {
+                    "summary": "O que esta build e configuracao suportam",
+                    "responses": { "200": { "description": "Flags" } },
+                },
+            },
+            "/healthz": {
+                "get": {
+                    "summary": "Liveness: o processo responde",
+                    "responses": { "200": { "description": "Vivo" } },
+                },
+            },
+            "/readyz": {
+                "get": {
+                    "summary": "Readiness: WAL, historico e banco configurados alcancaveis",
+                    "responses": {
+                        "200": { "description": "Pronto, com o estado de cada dependencia" },
+                        "503": { "description": "Alguma dependencia indisponivel" },
+                    },
+                },
+            },
+        },
+        "security": match config.api_keys.is_empty() {
+            true => json!([]),
+            false => json!([{ "chave": [] }]),
+        },
+        "components": {
+            "securitySchemes": {
+                "chave": { "type": "http", "scheme": "bearer", "description": "BANK_API_KEYS" },
+            },
+            "schemas": {
+                "Valor": amount,
+                "TransacaoRequest": {
+                    "type": "object",
+                    "required": ["valor", "tipo", "descricao"],
+                    "properties": {
+                        "valor": { "$ref": "#/components/schemas/Valor" },
+                        "tipo": { "type": "string", "enum": ["C", "D"] },
+                        "descricao": {
+                            "type": "string",
+                            "minLength": 1,
+                            "maxLength": config.description.max_len,
+                        },
+                        "moeda": {
+                            "type": "string",
+                            "example": "USD",
+                            "description": format!("Codigo ISO 4217; sem ele, {}.", config.currency.code()),
+                        },
+                        "realizada_em": {
+                            "type": "string",
+                            "format": "date-time",
+                            "description": "No futuro, agenda a transacao para esse instante.",
+                        },
+                        "categoria": { "type": "string", "enum": config.categories },
+                    },
+                },
+                "Transacao": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "ULID" },
+                        "seq": { "type": "integer" },
+                        "valor": { "$ref": "#/components/schemas/Valor" },
+                        "tipo": { "type": "string", "enum": ["C", "D"] },
+                        "moeda": { "type": "string" },
+                        "descricao": { "type": "string" },
+                        "categoria": { "type": "string" },
+                        "realizada_em": { "type": "string", "format": "date-time" },
+                        "transferencia": {
+                            "type": "object",
+                            "properties": {
+                                "id": { "type": "string" },
+                                "conta": { "type": "integer" },
+                            },
+                        },
+                        "estorno": { "type": "string", "description": "Id da transacao estornada" },
+                    },
+                },
+                "TransacaoResposta": {
+                    "type": "object",
+                    "properties": {
+                        "account": { "type": "integer" },
+                        "id": { "type": "string" },
+                        "seq": { "type": "integer" },
+                        "limite": { "$ref": "#/components/schemas/Valor" },
+                        "saldo": { "$ref": "#/components/schemas/Valor" },
+                        "debitado": { "$ref": "#/components/schemas/Valor" },
+                        "tarifa": {
+                            "allOf": [{ "$ref": "#/components/schemas/Transacao" }],
+                            "description": "Cobrada pela politica `soft` quando o debito passa do limite; `saldo` ja a desconta",
+                        },
+                        "moeda": { "type": "string", "description": "So fora da moeda base" },
+                        "versao": { "type": "integer", "format": "int64" },
+                    },
+                },
+                "Ledger": {
+                    "type": "object",
+                    "properties": {
+                        "account": { "type": "integer" },
+                        "eventos": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "seq": { "type": "integer", "format": "int64", "description": "Por conta, sem lacunas" },
+                                    "evento": { "type": "string", "enum": ["abertura", "credito", "debito", "importacao"] },
+                                    "transacao": { "type": "string", "nullable": true },
+                                    "valor": { "$ref": "#/components/schemas/Valor" },
+                                    "moeda": { "type": "string" },
+                                    "saldo": { "$ref": "#/components/schemas/Valor" },
+                                    "em": { "type": "string", "format": "date-time" },
+                                },
+                            },
+                        },
+                        "paginacao": {
+                            "type": "object",
+                            "properties": {
+                                "limit": { "type": "integer" },
+                                "total": { "type": "integer" },
+                                "proximo_cursor": { "type": "integer", "nullable": true },
+                            },
+                        },
+                    },
+                },
+                "Extrato": {
+                    "type": "object",
+                    "properties": {
+                        "account": { "type": "integer" },
+                        "saldo": {
+                            "type": "object",
+                            "properties": {
+                                "total": { "$ref": "#/components/schemas/Valor" },
+                                "limite": { "$ref": "#/components/schemas/Valor" },
+                                "disponivel": { "$ref": "#/components/schemas/Valor" },
+                                "cheque_especial": { "type": "boolean" },
+                                "tipo": { "type": "string", "description": "Um de `BANK_ACCOUNT_TYPES`" },
+                                "encerrada": { "type": "boolean" },
+                                "utilizacao": { "type": "string", "nullable": true },
+                                "data_extrato": { "type": "string", "format": "date-time" },
+                                "versao": { "type": "integer", "format": "int64" },
+                            },
+                        },
+                        "saldos": {
+                            "type": "object",
+                            "description": "Saldo por moeda, a base incluida",
+                            "additionalProperties": { "$ref": "#/components/schemas/Valor" },
+                        },
+                        "ultimas_transacoes": {
+                            "type": "array",
+                            "items": { "$ref": "#/components/schemas/Transacao" },
+                        },
+                        "paginacao": {
+                            "type": "object",
+                            "properties": {
+                                "limit": { "type": "integer" },
+                                "offset": { "type": "integer" },
+                                "total": { "type": "integer" },
+                                "proximo_cursor": { "type": "string", "nullable": true },
+                            },
+                        },
+                        "stale": {
+                            "type": "boolean",
+                            "description": "Presente, verdadeiro, enquanto o banco de dados esta inacessivel: \
+                            o extrato vem da memoria e pode incluir o que ainda nao foi gravado.",
+                        },
+                    },
+                },
+                "TransferenciaRequest": {
+                    "type": "object",
+                    "required": ["para", "valor", "descricao"],
+                    "properties": {
+                        "para": { "type": "integer" },
+                        "valor": { "$ref": "#/components/schemas/Valor" },
+                        "descricao": { "type": "string" },
+                    },
+                },
+                "Erro": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "description": "Figures behind a refusal (e.g. `necessario`, `disponivel`) come alongside.",
+                    "properties": {
+                        "code": { "type": "string", "enum": i18n::codes().chain(["erro"]).collect::<Vec<_>>() },
+                        "message": { "type": "string" },
+                        "proximo_credito": {
+                            "type": "string",
+                            "format": "date-time",
+                            "description": "Limite insuficiente com um credito agendado pendente: quando o mais proximo roda, tambem em `Retry-After`.",
+                        },
+                        "campos": {
+                            "type": "array",
+                            "description": "Cada campo recusado do corpo; `code` e `message` acima sao os do primeiro.",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "campo": { "type": "string" },
+                                    "code": { "type": "string" },
+                                    "message": { "type": "string" },
+                                },
+                            },
+                        },
+                    },
+                    "additionalProperties": true,
+                },
+            },
+        },
+    })
+}
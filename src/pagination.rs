@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{config::Config, render_transaction, ulid::Ulid, Account, TransactionType};
+
+pub const INVALID: &str = "paginacao invalida";
+
+/// Largest page `limit` may ask for.
+const MAX_LIMIT: usize = 100;
+
+/// Optional `/clientes/:id/extrato` parameters. With none of them set the
+/// statement is the plain (cacheable) one.
+#[derive(Default, Deserialize)]
+pub struct StatementQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+    /// `id` of the last transaction already seen; the page starts right
+    /// after it. Unlike `offset` it does not shift when new ones arrive.
+    cursor: Option<Ulid>,
+    tipo: Option<TransactionType>,
+    /// Inclusive lower bound on `realizada_em`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    desde: Option<OffsetDateTime>,
+    /// Inclusive upper bound on `realizada_em`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    ate: Option<OffsetDateTime>,
+}
+
+impl StatementQuery {
+    pub fn is_empty(&self) -> bool {
+        self.limit.is_none()
+            && self.offset.is_none()
+            && self.cursor.is_none()
+            && self.tipo.is_none()
+            && self.desde.is_none()
+            && self.ate.is_none()
+    }
+
+    /// A newest-first page over the retained history (`BANK_HISTORY_CAP`
+    /// entries; raise it to keep more to browse), with the filters applied
+    /// before paging. `None` for a `limit` of 0 or over 100, or for `offset`
+    /// and `cursor` together.
+    pub fn page(&self, account: &Account, config: &Config) -> Option<(Vec<Value>, Value)> {
+        let limit = self.limit.unwrap_or(config.history_cap.min(MAX_LIMIT));
+        if limit == 0 || limit > MAX_LIMIT || (self.offset.is_some() && self.cursor.is_some()) {
+            return None;
+        }
+        let matching: Vec<_> = account
+            .transactions
+            .iter()
+            .filter(|transaction| self.tipo.is_none_or(|kind| transaction.kind == kind))
+            .filter(|transaction| self.desde.is_none_or(|from| transaction.create_at >= from))
+            .filter(|transaction| self.ate.is_none_or(|until| transaction.create_at <= until))
+            .collect();
+        // Newest first means ids descend, so "after the cursor" is "older".
+        let offset = match self.cursor {
+            Some(cursor) => matching
+                .iter()
+                .position(|transaction| transaction.id < cursor)
+                .unwrap_or(matching.len()),
+            None => self.offset.unwrap_or(0),
+        };
+
+        let page: Vec<_> = matching.iter().skip(offset).take(limit).collect();
+        let next = match offset + page.len() < matching.len() {
+            true => page.last().map(|transaction| transaction.id),
+            false => None,
+        };
+        let metadata = json!({
+            "limit": limit,
+            "offset": offset,
+            "total": matching.len(),
+            "proximo_cursor": next,
+        });
+        let page = page
+            .into_iter()
+            .map(|transaction| render_transaction(transaction, config))
+            .collect();
+        Some((page, metadata))
+    }
+}
@@ -0,0 +1,229 @@
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+
+use crate::{error::AppError, registry::AccountId, AppState, TransactionType};
+
+const LINES_PER_PAGE: usize = 48;
+
+/// Printable statement. Amounts are always shown in major units, whatever
+/// `BANK_AMOUNT_FORMAT` says, since this is read by people.
+pub async fn statement(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let account = entry.account.read().await;
+    let config = &state.config;
+    let formatter = config.formatter();
+
+    let mut lines = vec![
+        format!("Extrato - conta {account_id}"),
+        format!("Data: {}", config.timestamp(state.clock.now())),
+        format!("Limite: {}", formatter.display(account.limit)),
+        format!("Saldo: {}", formatter.display(account.balance)),
+    ];
+    for (currency, balance) in &account.balances {
+        let balance = formatter.in_currency(*currency).display(*balance);
+        lines.push(format!("Saldo {}: {balance}", currency.code()));
+    }
+    lines.extend([
+        String::new(),
+        format!(
+            "{:<32} {:<4} {:>16}  {}",
+            "Realizada em", "Tipo", "Valor", "Descricao"
+        ),
+    ]);
+    for transaction in account.transactions.iter() {
+        let kind = match transaction.kind {
+            TransactionType::Credit => "C",
+            TransactionType::Debit => "D",
+        };
+        let currency = transaction.currency.unwrap_or(config.currency);
+        let value = format!(
+            "{} {}",
+            formatter.in_currency(currency).display(transaction.value),
+            currency.code()
+        );
+        lines.push(format!(
+            "{:<32} {:<4} {:>16}  {}",
+            config.timestamp(transaction.create_at),
+            kind,
+            value,
+            transaction.description.0,
+        ));
+    }
+    drop(account);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"extrato-{account_id}.pdf\""),
+            ),
+        ],
+        render(&lines),
+    ))
+}
+
+/// Minimal PDF 1.4: monospaced text lines on A4 pages, Courier in
+/// WinAnsiEncoding. Characters outside Latin-1 print as `?`.
+fn render(lines: &[String]) -> Vec<u8> {
+    let pages: Vec<&[String]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // 1: catalog, 2: page tree, 3: font, then a (page, content) pair per page.
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    let mut kids = Vec::new();
+    for page in pages {
+        let page_id = objects.len() + 1;
+        kids.push(format!("{page_id} 0 R"));
+
+        let mut content = String::from("BT /F1 9 Tf 11 TL 40 800 Td\n");
+        for line in page {
+            let _ = writeln!(content, "({}) Tj T*", escape(line));
+        }
+        content.push_str("ET");
+
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        ));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        kids.len()
+    );
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", index + 1).as_bytes());
+    }
+    let xref = out.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    );
+    out.extend_from_slice(trailer.as_bytes());
+    out
+}
+
+/// PDF string literal body; non-ASCII Latin-1 goes out as octal escapes.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(out, "\\{:03o}", c as u32);
+            }
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, http::header};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::{render, LINES_PER_PAGE};
+    use crate::{
+        config::Config,
+        http,
+        testing::{bank, get, post, send},
+    };
+
+    /// Each xref entry must point at the object it numbers.
+    fn assert_xref(pdf: &[u8]) {
+        let text = String::from_utf8_lossy(pdf);
+        let start: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(text[start..].starts_with("xref\n"));
+        let offsets = text[start..]
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "));
+        for (index, line) in offsets.enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj\n", index + 1)));
+        }
+    }
+
+    #[tokio::test]
+    async fn statement_is_a_pdf_attachment() {
+        let (state, _) = bank(Config::default());
+        let credit = json!({"valor": 1_234, "tipo": "C", "descricao": "salário"});
+        send(&state, post("/clientes/1/transacoes", credit)).await;
+
+        let response = http::router(state.clone())
+            .oneshot(get("/clientes/1/extrato.pdf"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"extrato-1.pdf\""
+        );
+        let pdf = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert!(pdf.len() > 500);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("(Extrato - conta 1) Tj"));
+        assert!(text.contains("sal\\341rio"));
+        assert_xref(&pdf);
+    }
+
+    #[test]
+    fn long_statements_span_pages() {
+        let lines: Vec<String> = (0..LINES_PER_PAGE * 2 + 1)
+            .map(|line| format!("linha {line}"))
+            .collect();
+        let pdf = render(&lines);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Count 3 >>"));
+        assert_eq!(text.matches("/Type /Page ").count(), 3);
+        assert_xref(&pdf);
+    }
+}
@@ -0,0 +1,946 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    config::Config,
+    currency::Currency,
+    error::{self, AppError, JsonBody},
+    holds::Holds,
+    ledger, observe_seq, open_account,
+    policy::{self, AccountType},
+    registry::AccountId,
+    rotation::{self, RotatingFile, Rotation},
+    schedule::Schedule,
+    transact::TransactError,
+    ulid::Ulid,
+    Account, AppState, RingBuffer, Transaction,
+};
+
+pub const WAL_FAILED: &str = "falha ao gravar o log";
+
+/// What a request whose change the WAL would not take is answered with.
+pub(crate) fn failed() -> AppError {
+    AppError::Status(StatusCode::SERVICE_UNAVAILABLE, WAL_FAILED)
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AccountSnapshot {
+    pub(crate) id: AccountId,
+    #[serde(rename = "saldo")]
+    balance: i64,
+    #[serde(rename = "limite")]
+    pub(crate) limit: i64,
+    /// Last transaction id folded into this snapshot; the WAL is replayed from
+    /// the entry after it.
+    #[serde(rename = "ultimo_id")]
+    last_id: Ulid,
+    #[serde(rename = "transacoes")]
+    transactions: RingBuffer<Transaction>,
+    /// Absent from snapshots written before accounts could be closed.
+    #[serde(default, rename = "encerrada")]
+    closed: bool,
+    /// Non-base currencies; absent from snapshots that predate them.
+    #[serde(default, rename = "saldos", skip_serializing_if = "BTreeMap::is_empty")]
+    balances: BTreeMap<Currency, i64>,
+    /// So `If-Match` versions read before a restart do not match again.
+    #[serde(default, rename = "versao")]
+    pub(crate) version: u64,
+    /// Absent from snapshots that predate account types; the account keeps
+    /// the one config gave it.
+    #[serde(default, rename = "tipo", skip_serializing_if = "Option::is_none")]
+    account_type: Option<String>,
+    /// Absent from snapshots that predate them; restoring one of those
+    /// leaves the account's pending `agendamentos` and holds alone.
+    #[serde(
+        default,
+        rename = "agendamentos",
+        skip_serializing_if = "Option::is_none"
+    )]
+    schedule: Option<Schedule>,
+    #[serde(default, rename = "reservas", skip_serializing_if = "Option::is_none")]
+    holds: Option<Holds>,
+}
+
+impl AccountSnapshot {
+    pub(crate) fn of(id: AccountId, account: &Account) -> Self {
+        Self {
+            id,
+            balance: account.balance,
+            limit: account.limit,
+            last_id: account.last_id,
+            transactions: account.transactions.clone(),
+            closed: account.closed,
+            balances: account.balances.clone(),
+            version: account.version,
+            account_type: Some(account.account_type.name.clone()),
+            schedule: Some(account.schedule.clone()),
+            holds: Some(account.holds.clone()),
+        }
+    }
+}
+
+/// One line of the WAL: an accepted transaction, or the whole account after
+/// a change that is not one (a schedule or hold, an admin operation).
+#[derive(Serialize, Deserialize)]
+struct WalEntry {
+    account: AccountId,
+    #[serde(default, rename = "transacao", skip_serializing_if = "Option::is_none")]
+    transaction: Option<Transaction>,
+    #[serde(default, rename = "estado", skip_serializing_if = "Option::is_none")]
+    state: Option<Box<AccountExport>>,
+}
+
+/// When WAL appends reach the disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalDurability {
+    /// Leave it to the OS.
+    #[default]
+    None,
+    /// `fsync` once `entries` appends are pending, or every `interval`.
+    Batch { interval: Duration, entries: usize },
+    /// `fsync` every append before the transaction is answered.
+    Strict,
+}
+
+impl WalDurability {
+    /// The `BANK_WAL_DURABILITY` value selecting this level.
+    pub fn name(self) -> &'static str {
+        match self {
+            WalDurability::None => "none",
+            WalDurability::Batch { .. } => "batch",
+            WalDurability::Strict => "strict",
+        }
+    }
+}
+
+/// Append-only JSON-lines log of accepted transactions, and of whole
+/// accounts after changes that are not one.
+pub struct Wal {
+    file: RotatingFile,
+    durability: WalDurability,
+    /// Appends written since the last `fsync`, for `Batch`.
+    pending: AtomicUsize,
+}
+
+impl Wal {
+    /// With `Batch`, also starts the task that syncs on the interval.
+    pub fn open(
+        path: &Path,
+        durability: WalDurability,
+        rotation: Rotation,
+    ) -> io::Result<Arc<Self>> {
+        let wal = Arc::new(Self {
+            file: RotatingFile::open(path, rotation)?,
+            durability,
+            pending: AtomicUsize::new(0),
+        });
+        if let WalDurability::Batch { interval, .. } = durability {
+            tokio::spawn(wal.clone().sync_periodically(interval));
+        }
+        Ok(wal)
+    }
+
+    /// Accepted transactions, in one write so a failure leaves none of them
+    /// half logged. Under `Strict` this blocks on the `fsync`; appends run
+    /// while the accounts are locked, so the response cannot overtake the
+    /// disk.
+    pub fn append(&self, recorded: &[(AccountId, &Transaction)]) -> io::Result<()> {
+        let mut lines = String::new();
+        for &(account_id, transaction) in recorded {
+            let entry = WalEntry {
+                account: account_id,
+                transaction: Some(transaction.clone()),
+                state: None,
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        self.write(lines)
+    }
+
+    /// Whole accounts, which replay restores outright before going on with
+    /// the transactions logged after each. In one write, like `append`, so a
+    /// change spanning accounts is logged for all of them or none.
+    pub fn append_state(&self, accounts: &[(AccountId, &Account)]) -> io::Result<()> {
+        let mut lines = String::new();
+        for &(account_id, account) in accounts {
+            let entry = WalEntry {
+                account: account_id,
+                transaction: None,
+                state: Some(Box::new(AccountExport::of(account_id, account))),
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        self.write(lines)
+    }
+
+    fn write(&self, lines: String) -> io::Result<()> {
+        let sync = match self.durability {
+            WalDurability::None => false,
+            WalDurability::Strict => true,
+            WalDurability::Batch { entries, .. } => {
+                let due = self.pending.fetch_add(1, Ordering::Relaxed) + 1 >= entries;
+                if due {
+                    self.pending.store(0, Ordering::Relaxed);
+                }
+                due
+            }
+        };
+        self.file.append(lines.as_bytes(), sync)
+    }
+
+    /// Starts a fresh segment, so everything logged so far sits in rotated
+    /// ones a later snapshot can make redundant.
+    fn rotate(&self) -> io::Result<()> {
+        self.file.rotate_now()
+    }
+
+    /// For `/readyz`: appends would still land.
+    pub fn check(&self) -> io::Result<()> {
+        self.file.check()
+    }
+
+    /// `fsync`s whatever was appended, whatever the durability, so nothing
+    /// accepted is left in the page cache when the process exits.
+    pub fn flush(&self) -> io::Result<()> {
+        self.pending.store(0, Ordering::Relaxed);
+        self.file.sync()
+    }
+
+    async fn sync_periodically(self: Arc<Self>, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            if self.pending.load(Ordering::Relaxed) == 0 {
+                continue;
+            }
+            self.pending.store(0, Ordering::Relaxed);
+            if let Err(err) = self.file.sync() {
+                tracing::error!(error = %err, "wal fsync failed");
+            }
+        }
+    }
+}
+
+impl Account {
+    /// A `tipo` config no longer has leaves the account's current type.
+    pub(crate) fn restore(&mut self, snapshot: AccountSnapshot, config: &Config) {
+        if let Some(name) = &snapshot.account_type {
+            match config.account_type(name) {
+                Some(account_type) => self.account_type = account_type,
+                None => tracing::warn!(
+                    account = snapshot.id.0,
+                    tipo = %name,
+                    keeping = %self.account_type.name,
+                    "account type not configured"
+                ),
+            }
+        }
+        self.balance = snapshot.balance;
+        self.limit = snapshot.limit;
+        self.last_id = snapshot.last_id;
+        self.closed = snapshot.closed;
+        // Currencies configured since the snapshot stay, at zero.
+        self.balances.extend(snapshot.balances);
+        // Never backwards: an import restores over a live account.
+        self.version = self.version.max(snapshot.version);
+        self.transactions.clear();
+        for transaction in snapshot.transactions.items.into_iter().rev() {
+            observe_seq(transaction.seq);
+            self.transactions.push(transaction);
+        }
+        if let Some(schedule) = snapshot.schedule {
+            self.schedule = schedule;
+        }
+        if let Some(holds) = snapshot.holds {
+            self.holds = holds;
+        }
+    }
+
+    /// Re-applies an already accepted transaction without re-checking limits.
+    /// Returns false when the snapshot already contains it; refuses one that
+    /// would take the balance out of range, which no accepted one can.
+    pub fn replay(&mut self, transaction: Transaction) -> Result<bool, TransactError> {
+        observe_seq(transaction.seq);
+        if transaction.id <= self.last_id {
+            return Ok(false);
+        }
+        let balance = match transaction.currency {
+            Some(currency) => self.balances.entry(currency).or_default(),
+            None => &mut self.balance,
+        };
+        *balance = transaction.kind.apply(*balance, transaction.value)?;
+        self.last_id = transaction.id;
+        self.version += 1;
+        self.record(transaction);
+        Ok(true)
+    }
+}
+
+/// Loads the snapshot (if any) over the seeded accounts, then replays only the
+/// WAL entries newer than each account's snapshotted id.
+pub fn load(
+    accounts: &mut HashMap<AccountId, RwLock<Account>>,
+    snapshot: Option<&Path>,
+    wal: Option<&Path>,
+    config: &Config,
+) -> io::Result<()> {
+    if let Some(path) = snapshot.filter(|path| path.exists()) {
+        let snapshots: Vec<AccountSnapshot> = serde_json::from_reader(File::open(path)?)?;
+        tracing::info!(
+            accounts = snapshots.len(),
+            path = %path.display(),
+            "snapshot loaded"
+        );
+        for snapshot in snapshots {
+            accounts
+                .entry(snapshot.id)
+                .or_insert_with(|| RwLock::new(Account::new(snapshot.limit, config.history_cap)))
+                .get_mut()
+                .restore(snapshot, config);
+        }
+    }
+
+    let Some(path) = wal else {
+        return Ok(());
+    };
+    // Rotated segments hold older entries the snapshot may not cover yet.
+    let mut segments = rotation::rotated(path)?;
+    if path.exists() {
+        segments.push(path.to_path_buf());
+    }
+
+    // Each account's state records split its entries into epochs, kept in
+    // file order: one is written under the account's lock, so everything
+    // logged before it is already in it.
+    let mut epochs: HashMap<AccountId, u64> = HashMap::new();
+    let mut entries = Vec::new();
+    for segment in segments {
+        for line in BufReader::new(File::open(segment)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: WalEntry = serde_json::from_str(&line)?;
+            let epoch = epochs.entry(entry.account).or_default();
+            if entry.state.is_some() {
+                *epoch += 1;
+            }
+            entries.push((*epoch, entry));
+        }
+    }
+    // Within an epoch, appends from concurrent writers may interleave; ids
+    // give the real order. The state record opening one sorts before them.
+    entries.sort_by_key(|(epoch, entry)| {
+        let id = entry.transaction.as_ref().map(|transaction| transaction.id);
+        (entry.account, *epoch, id)
+    });
+
+    let (mut replayed, mut skipped, mut restored) = (0, 0, 0);
+    for (_, entry) in entries {
+        if let Some(export) = entry.state {
+            // Possibly older than the snapshot: what follows it brings the
+            // account forward again.
+            // One opened after startup, as `POST /clientes` did.
+            let account = accounts.entry(entry.account).or_insert_with(|| {
+                let limit = export.state.limit;
+                let opened = open_account(entry.account, limit, config)
+                    .unwrap_or_else(|_| Account::new(limit, config.history_cap));
+                RwLock::new(opened)
+            });
+            export.restore_onto(account.get_mut(), config);
+            restored += 1;
+            continue;
+        }
+        let Some(transaction) = entry.transaction else {
+            continue;
+        };
+        let Some(account) = accounts.get_mut(&entry.account) else {
+            tracing::warn!(
+                account = entry.account.0,
+                "wal entry for an unknown account ignored"
+            );
+            continue;
+        };
+        let id = transaction.id;
+        match account.get_mut().replay(transaction) {
+            Ok(true) => replayed += 1,
+            Ok(false) => skipped += 1,
+            Err(error) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("wal entry {id} for account {}: {error}", entry.account),
+                ))
+            }
+        }
+    }
+    tracing::info!(replayed, skipped, restored, "wal replayed");
+    Ok(())
+}
+
+/// Written to a temporary file first so a crash never leaves a torn snapshot.
+pub async fn write_snapshot(state: &AppState, path: &Path) -> io::Result<()> {
+    let mut snapshots = Vec::with_capacity(state.accounts.len());
+    for (id, entry) in state.accounts.entries() {
+        let account = entry.account.read().await;
+        snapshots.push(AccountSnapshot::of(id, &account));
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.id);
+
+    let mut temporary = PathBuf::from(path);
+    temporary.set_extension("tmp");
+    fs::write(&temporary, serde_json::to_vec(&snapshots)?)?;
+    fs::rename(&temporary, path)
+}
+
+/// Every `interval`, snapshots and drops the WAL segments the snapshot
+/// covers, so startup replays at most one interval's worth of log.
+pub async fn checkpoint_periodically(
+    state: Arc<AppState>,
+    wal: Option<Arc<Wal>>,
+    snapshot: PathBuf,
+    interval: Duration,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        match checkpoint(&state, wal.as_deref(), &snapshot).await {
+            Ok(dropped) => tracing::info!(
+                path = %snapshot.display(),
+                dropped,
+                "checkpoint: snapshot written, wal segments dropped"
+            ),
+            Err(err) => tracing::error!(error = %err, "checkpoint failed"),
+        }
+    }
+}
+
+/// Rotating first is what makes dropping safe: an entry in a rotated
+/// segment was appended under its account's write lock, after the change
+/// it records, so the snapshot read afterwards already contains it.
+/// Segments rotated while the snapshot is written are kept.
+async fn checkpoint(state: &AppState, wal: Option<&Wal>, snapshot: &Path) -> io::Result<usize> {
+    let covered = match (wal, state.config.wal_file.as_deref()) {
+        (Some(wal), Some(path)) => {
+            wal.rotate()?;
+            rotation::rotated(path)?
+        }
+        _ => Vec::new(),
+    };
+    write_snapshot(state, snapshot).await?;
+    if let Some(ledger) = &state.ledger {
+        ledger.checkpoint()?;
+    }
+    for segment in &covered {
+        fs::remove_file(segment)?;
+    }
+    Ok(covered.len())
+}
+
+/// `GET /clientes/:id/export` / `POST /clientes/:id/import` body: the
+/// snapshot plus the per-account settings a snapshot leaves to config.
+#[derive(Serialize, Deserialize)]
+pub struct AccountExport {
+    #[serde(flatten)]
+    state: AccountSnapshot,
+    #[serde(rename = "cheque_especial")]
+    allow_overdraft: bool,
+    #[serde(rename = "limite_diario")]
+    daily_debit_limit: Option<i64>,
+}
+
+impl AccountExport {
+    pub(crate) fn of(account_id: AccountId, account: &Account) -> Self {
+        Self {
+            state: AccountSnapshot::of(account_id, account),
+            allow_overdraft: account.allow_overdraft,
+            daily_debit_limit: account.daily_debit_limit,
+        }
+    }
+
+    pub(crate) fn account_id(&self) -> AccountId {
+        self.state.id
+    }
+
+    pub(crate) fn limit(&self) -> i64 {
+        self.state.limit
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.state.version
+    }
+
+    /// Settings and state both, as a WAL state record is replayed.
+    pub(crate) fn restore_onto(self, account: &mut Account, config: &Config) {
+        account.allow_overdraft = self.allow_overdraft;
+        account.daily_debit_limit = self.daily_debit_limit;
+        account.restore(self.state, config);
+    }
+
+    /// Without a `tipo` the account's current one is kept, so its policy is
+    /// what the balance is checked against.
+    fn validate(
+        &self,
+        account_id: AccountId,
+        current: &AccountType,
+        config: &Config,
+    ) -> Result<(), &'static str> {
+        let state = &self.state;
+        if state.id != account_id {
+            return Err("id da conta diverge do caminho");
+        }
+        if state.limit < 0 || state.limit > config.max_limit {
+            return Err(error::INVALID_LIMIT);
+        }
+        let policy = match &state.account_type {
+            Some(name) => {
+                config
+                    .account_type(name)
+                    .ok_or(policy::UNKNOWN_TYPE)?
+                    .policy
+            }
+            None => current.policy,
+        };
+        if state.balance < policy.floor(state.limit) || (!self.allow_overdraft && state.balance < 0)
+        {
+            return Err("saldo inconsistente com o limite");
+        }
+        if state.balances.values().any(|balance| *balance < 0) {
+            return Err("saldo inconsistente com o limite");
+        }
+        if self.daily_debit_limit.is_some_and(|limit| limit < 0) {
+            return Err("limite diario invalido");
+        }
+        // Newest first: ids must strictly decrease and none may pass `ultimo_id`.
+        let mut newer = None;
+        for transaction in state.transactions.iter() {
+            if transaction.id.is_nil() || transaction.id > state.last_id {
+                return Err("historico inconsistente");
+            }
+            if newer.is_some_and(|newer| transaction.id >= newer) {
+                return Err("historico inconsistente");
+            }
+            newer = Some(transaction.id);
+        }
+        Ok(())
+    }
+}
+
+pub async fn export(
+    UrlPath(account_id): UrlPath<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<AccountExport>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let account = entry.account.read().await;
+    Ok(Json(AccountExport::of(account_id, &account)))
+}
+
+/// Overwrites balance, limit, history and settings, and pending
+/// `agendamentos` and holds when the body has them; otherwise those are kept.
+pub async fn import(
+    UrlPath(account_id): UrlPath<AccountId>,
+    State(state): State<Arc<AppState>>,
+    JsonBody(export): JsonBody<AccountExport>,
+) -> Result<StatusCode, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let mut account = entry.account.write().await;
+    export
+        .validate(account_id, &account.account_type, &state.config)
+        .map_err(AppError::Invalid)?;
+    let replaced = account.transactions.len();
+    let before = account.clone();
+    // Never move the id watermark back, or a later snapshot would let WAL
+    // replay resurrect the transactions this import overwrote.
+    let watermark = account.last_id;
+    account.allow_overdraft = export.allow_overdraft;
+    account.daily_debit_limit = export.daily_debit_limit;
+    account.restore(export.state, &state.config);
+    account.last_id = account.last_id.max(watermark);
+    account.touch(state.clock.now());
+    // Logged as a state record before the answer, so a restart replays the
+    // imported account rather than what it overwrote.
+    if let Err(error) = state.persist(account_id, &account) {
+        *account = before;
+        return Err(error);
+    }
+    state.changed(account_id);
+    if let Some(ledger) = &state.ledger {
+        ledger.reconcile(
+            account_id,
+            &account,
+            ledger::Kind::Import,
+            state.clock.now(),
+        );
+    }
+    state.audit.record(json!({
+        "evento": "conta_importada",
+        "account": account_id,
+        "substituidas": replaced,
+        "importadas": account.transactions.len(),
+        "em": state.config.timestamp(state.clock.now()),
+    }));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use serde_json::json;
+    use time::Duration;
+
+    use super::{load, write_snapshot, Wal, WalDurability, WAL_FAILED};
+    use crate::{
+        domain::{seed_accounts, Transaction},
+        observer::{AccountObserver, Outcome},
+        registry::AccountId,
+        rotation::Rotation,
+        testing::{admin, admin_config, bank, delete, get, post, send, timestamp, START},
+        AppState, Config,
+    };
+
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bank-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// `bank(config)` logging to a strict WAL at `path`.
+    fn logged(path: &Path, config: Config) -> Arc<AppState> {
+        let (mut state, _) = bank(config);
+        let wal = Wal::open(path, WalDurability::Strict, Rotation::default()).unwrap();
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.wal = Some(wal);
+        state
+    }
+
+    /// A bank as it starts over the seeds, `snapshot` and `wal`.
+    async fn restarted(
+        snapshot: Option<&Path>,
+        wal: Option<&Path>,
+        config: Config,
+    ) -> Arc<AppState> {
+        let mut accounts = seed_accounts(&config.accounts, &config).unwrap();
+        load(&mut accounts, snapshot, wal, &config).unwrap();
+        let (restored, _) = bank(config);
+        for (id, account) in accounts {
+            let account = account.into_inner();
+            match restored.accounts.get(&id) {
+                Some(entry) => *entry.account.write().await = account,
+                None => drop(restored.accounts.insert(id, account)),
+            }
+        }
+        restored
+    }
+
+    /// One credit scheduled an hour out and one hold on account 1.
+    async fn schedule_and_hold(state: &Arc<AppState>) {
+        let scheduled = json!({
+            "valor": 700,
+            "tipo": "C",
+            "descricao": "salario",
+            "executar_em": timestamp(START + Duration::hours(1)),
+        });
+        let (status, _) = send(state, post("/clientes/1/agendamentos", scheduled)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let hold = json!({"valor": 300, "descricao": "hotel"});
+        let (status, _) = send(state, post("/clientes/1/reservas", hold)).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    async fn assert_schedule_and_hold(state: &Arc<AppState>) {
+        let (_, schedules) = send(state, get("/clientes/1/agendamentos")).await;
+        assert_eq!(schedules["agendamentos"][0]["transacao"]["valor"], 700);
+        assert_eq!(schedules["agendamentos"][0]["status"], "pendente");
+        let (_, holds) = send(state, get("/clientes/1/reservas")).await;
+        assert_eq!(holds["reservas"][0]["valor"], 300);
+        assert_eq!(holds["disponivel"], 100_000 - 300);
+    }
+
+    #[tokio::test]
+    async fn snapshots_keep_schedules_and_holds() {
+        let path = scratch("snapshot.json");
+        let (state, _) = bank(Config::default());
+        schedule_and_hold(&state).await;
+        write_snapshot(&state, &path).await.unwrap();
+
+        let restored = restarted(Some(&path), None, Config::default()).await;
+        assert_schedule_and_hold(&restored).await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn wal_entries_in_the_snapshot_are_not_applied_twice() {
+        let (snapshot, wal) = (scratch("both.json"), scratch("both.wal"));
+        let state = logged(&wal, Config::default());
+        let credit =
+            |value, description| json!({"valor": value, "tipo": "C", "descricao": description});
+        for value in [1_000, 2_000] {
+            send(
+                &state,
+                post("/clientes/1/transacoes", credit(value, "antes")),
+            )
+            .await;
+        }
+        write_snapshot(&state, &snapshot).await.unwrap();
+        send(
+            &state,
+            post("/clientes/1/transacoes", credit(400, "depois")),
+        )
+        .await;
+
+        let restored = restarted(Some(&snapshot), Some(&wal), Config::default()).await;
+        let (_, statement) = send(&restored, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 3_400);
+        let history = statement["ultimas_transacoes"].as_array().unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0]["descricao"], "depois");
+        std::fs::remove_file(snapshot).unwrap();
+        std::fs::remove_file(wal).unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_restores_an_export_and_survives_a_restart() {
+        let path = scratch("import.wal");
+        let state = logged(&path, admin_config());
+        let credit =
+            |value, description| json!({"valor": value, "tipo": "C", "descricao": description});
+        send(
+            &state,
+            post("/clientes/1/transacoes", credit(1_000, "antes")),
+        )
+        .await;
+        let (status, exported) = send(&state, admin(get("/clientes/1/export"))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        send(
+            &state,
+            post("/clientes/1/transacoes", credit(5_000, "depois")),
+        )
+        .await;
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 6_000);
+
+        let (status, _) = send(&state, admin(post("/clientes/1/import", exported.clone()))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let assert_restored = |statement: serde_json::Value| {
+            assert_eq!(statement["saldo"]["total"], 1_000);
+            let history = statement["ultimas_transacoes"].as_array().unwrap().clone();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0]["descricao"], "antes");
+        };
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_restored(statement);
+        let (_, again) = send(&state, admin(get("/clientes/1/export"))).await;
+        assert_eq!(again["transacoes"], exported["transacoes"]);
+
+        let restored = restarted(None, Some(&path), admin_config()).await;
+        let (_, statement) = send(&restored, get("/clientes/1/extrato")).await;
+        assert_restored(statement);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn state_records_replay_over_the_seeds() {
+        let path = scratch("state.wal");
+        let state = logged(&path, Config::default());
+        schedule_and_hold(&state).await;
+
+        let restored = restarted(None, Some(&path), Config::default()).await;
+        assert_schedule_and_hold(&restored).await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn purged_history_stays_purged_after_replay() {
+        let path = scratch("purge.wal");
+        let state = logged(&path, admin_config());
+        for value in [1_000, 2_000] {
+            let credit = json!({"valor": value, "tipo": "C", "descricao": "deposito"});
+            send(&state, post("/clientes/1/transacoes", credit)).await;
+        }
+        let (status, _) = send(&state, admin(delete("/clientes/1/transacoes"))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let credit = json!({"valor": 500, "tipo": "C", "descricao": "depois"});
+        send(&state, post("/clientes/1/transacoes", credit)).await;
+
+        let restored = restarted(None, Some(&path), admin_config()).await;
+        let (_, statement) = send(&restored, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 3_500);
+        let history = statement["ultimas_transacoes"].as_array().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["descricao"], "depois");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Whether the WAL at its path already held each accepted transaction
+    /// when observers heard of it.
+    struct Durable(PathBuf, Mutex<Vec<bool>>);
+
+    impl AccountObserver for Durable {
+        fn on_transaction(&self, _: AccountId, transaction: &Transaction, _: &Outcome) {
+            let logged = std::fs::read_to_string(&self.0).unwrap();
+            let id = serde_json::to_string(&transaction.id).unwrap();
+            self.1.lock().unwrap().push(logged.contains(&id));
+        }
+    }
+
+    #[tokio::test]
+    async fn strict_transactions_are_logged_before_they_are_answered() {
+        let path = scratch("strict.wal");
+        let mut state = logged(&path, Config::default());
+        let durable = Arc::new(Durable(path.clone(), Mutex::default()));
+        Arc::get_mut(&mut state)
+            .unwrap()
+            .observers
+            .push(durable.clone());
+
+        let credit = json!({"valor": 1_000, "tipo": "C", "descricao": "deposito"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(*durable.1.lock().unwrap(), [true]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_transaction_the_wal_cannot_take_is_refused() {
+        let state = logged(Path::new("/dev/full"), Config::default());
+        let credit = json!({"valor": 1_000, "tipo": "C", "descricao": "deposito"});
+        let (status, body) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["message"], WAL_FAILED);
+
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn account_lifecycle_and_limit_transfers_survive_replay() {
+        let path = scratch("lifecycle.wal");
+        let state = logged(&path, admin_config());
+        let opened = json!({"id": 9, "limite": 5_000});
+        let (status, _) = send(&state, admin(post("/clientes", opened))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let credit = json!({"valor": 700, "tipo": "C", "descricao": "deposito"});
+        send(&state, post("/clientes/9/transacoes", credit)).await;
+        let patch = Request::patch("/clientes/2")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({"limite": 1_234}).to_string()))
+            .unwrap();
+        let (status, _) = send(&state, admin(patch)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, admin(delete("/clientes/3"))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let moved = json!({"para": 9, "valor": 1_000});
+        let (status, _) = send(&state, admin(post("/clientes/1/limite/transferir", moved))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let restored = restarted(None, Some(&path), admin_config()).await;
+        let (_, statement) = send(&restored, get("/clientes/9/extrato")).await;
+        assert_eq!(statement["saldo"]["limite"], 6_000);
+        assert_eq!(statement["saldo"]["total"], 700);
+        let (_, statement) = send(&restored, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["limite"], 99_000);
+        let (_, statement) = send(&restored, get("/clientes/2/extrato")).await;
+        assert_eq!(statement["saldo"]["limite"], 1_234);
+        let (_, statement) = send(&restored, get("/clientes/3/extrato")).await;
+        assert_eq!(statement["saldo"]["encerrada"], true);
+        let debit = json!({"valor": 1, "tipo": "D", "descricao": "depois"});
+        let (status, _) = send(&restored, post("/clientes/3/transacoes", debit)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_future_dated_transaction_is_logged_as_scheduled() {
+        let path = scratch("future.wal");
+        let state = logged(&path, Config::default());
+        let credit = json!({
+            "valor": 700,
+            "tipo": "C",
+            "descricao": "salario",
+            "realizada_em": timestamp(START + Duration::hours(1)),
+        });
+        let (status, body) = send(&state, post("/clientes/1/transacoes", credit.clone())).await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{body}");
+
+        let restored = restarted(None, Some(&path), Config::default()).await;
+        let (_, schedules) = send(&restored, get("/clientes/1/agendamentos")).await;
+        assert_eq!(schedules["agendamentos"][0]["transacao"]["valor"], 700);
+        assert_eq!(schedules["agendamentos"][0]["status"], "pendente");
+        std::fs::remove_file(path).unwrap();
+
+        let state = logged(Path::new("/dev/full"), Config::default());
+        let (status, _) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (_, schedules) = send(&state, get("/clientes/1/agendamentos")).await;
+        assert_eq!(schedules["agendamentos"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn a_purged_history_keeps_its_version_across_a_restart() {
+        let path = scratch("purge.wal");
+        let state = logged(&path, admin_config());
+        let credit = json!({"valor": 700, "tipo": "C", "descricao": "deposito"});
+        send(&state, post("/clientes/1/transacoes", credit)).await;
+        let (status, _) = send(&state, admin(delete("/clientes/1/transacoes"))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, purged) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(purged["saldo"]["versao"], 2);
+
+        let restored = restarted(None, Some(&path), admin_config()).await;
+        let (_, statement) = send(&restored, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["versao"], 2);
+        assert_eq!(statement["saldo"]["total"], 700);
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn an_account_the_wal_cannot_take_is_not_opened() {
+        let state = logged(Path::new("/dev/full"), admin_config());
+        let opened = json!({"id": 9, "limite": 5_000});
+        let (status, _) = send(&state, admin(post("/clientes", opened))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(state.accounts.get(&AccountId(9)).is_none());
+
+        let (status, _) = send(&state, admin(delete("/clientes/3"))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (_, statement) = send(&state, get("/clientes/3/extrato")).await;
+        assert_eq!(statement["saldo"]["encerrada"], false);
+    }
+}
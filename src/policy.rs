@@ -0,0 +1,186 @@
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    config::Config,
+    domain::{next_seq, Account, Description, Transaction, TransactionType},
+    ulid::Ulid,
+};
+
+pub const UNKNOWN_TYPE: &str = "tipo de conta desconhecido";
+
+/// `descricao` of the fee a `Soft` policy charges.
+pub const FEE_DESCRIPTION: &str = "tarifa";
+
+/// How far below zero an account type lets debits take the balance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// Down to the limit and not a cent further; the behaviour before
+    /// account types existed.
+    Hard,
+    /// Up to `tolerance` past the limit, and every debit that ends up there
+    /// is followed by a `fee` debit of its own.
+    Soft { tolerance: i64, fee: i64 },
+    /// Never below zero, whatever the limit.
+    NoOverdraft,
+}
+
+impl LimitPolicy {
+    /// The `BANK_ACCOUNT_TYPES` spelling: `hard`, `none` or
+    /// `soft:<tolerance>:<fee>`, amounts in minor units.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().split(':').collect::<Vec<_>>().as_slice() {
+            ["hard"] => Some(LimitPolicy::Hard),
+            ["none"] => Some(LimitPolicy::NoOverdraft),
+            ["soft", tolerance, fee] => {
+                let tolerance = tolerance.trim().parse().ok().filter(|value| *value >= 0)?;
+                let fee = fee.trim().parse().ok().filter(|value| *value >= 0)?;
+                Some(LimitPolicy::Soft { tolerance, fee })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LimitPolicy::Hard => "hard",
+            LimitPolicy::Soft { .. } => "soft",
+            LimitPolicy::NoOverdraft => "none",
+        }
+    }
+
+    /// How far past the limit a debit may still go.
+    pub fn tolerance(self) -> i64 {
+        match self {
+            LimitPolicy::Soft { tolerance, .. } => tolerance,
+            LimitPolicy::Hard | LimitPolicy::NoOverdraft => 0,
+        }
+    }
+
+    /// Lowest balance the policy allows with `limit`.
+    pub fn floor(self, limit: i64) -> i64 {
+        match self {
+            LimitPolicy::Hard => -limit,
+            LimitPolicy::Soft { tolerance, .. } => -limit - tolerance,
+            LimitPolicy::NoOverdraft => 0,
+        }
+    }
+}
+
+/// A named policy from `BANK_ACCOUNT_TYPES`, picked per account with `tipo`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountType {
+    pub name: String,
+    pub policy: LimitPolicy,
+}
+
+impl Default for AccountType {
+    /// What `Account::new` starts with, before config assigns a type.
+    fn default() -> Self {
+        Self {
+            name: "premium".to_string(),
+            policy: LimitPolicy::Hard,
+        }
+    }
+}
+
+/// `basic` without overdraft, `premium` with the plain limit and `corporate`
+/// with 500.00 of tolerance at 10.00 a debit.
+pub fn defaults() -> Vec<AccountType> {
+    [
+        ("basic", LimitPolicy::NoOverdraft),
+        ("premium", LimitPolicy::Hard),
+        (
+            "corporate",
+            LimitPolicy::Soft {
+                tolerance: 50_000,
+                fee: 1_000,
+            },
+        ),
+    ]
+    .into_iter()
+    .map(|(name, policy)| AccountType {
+        name: name.to_string(),
+        policy,
+    })
+    .collect()
+}
+
+/// `name:policy` pairs separated by `;`, e.g.
+/// `basic:none;gold:soft:20000:500`.
+pub fn parse_types(value: &str) -> Option<Vec<AccountType>> {
+    value
+        .split(';')
+        .map(|pair| {
+            let (name, policy) = pair.split_once(':')?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(AccountType {
+                name: name.to_string(),
+                policy: LimitPolicy::parse(policy)?,
+            })
+        })
+        .collect()
+}
+
+/// The `politica` of account responses.
+pub fn render(policy: LimitPolicy, config: &Config) -> Value {
+    let mut body = json!({ "nome": policy.name() });
+    if let LimitPolicy::Soft { tolerance, fee } = policy {
+        body["excedente"] = config.amount(tolerance);
+        body["tarifa"] = config.amount(fee);
+    }
+    body
+}
+
+impl Account {
+    /// Whether debits may take the base balance below zero at all: the
+    /// account's `BANK_NO_OVERDRAFT_ACCOUNTS` setting and its type both
+    /// have to allow it.
+    pub fn overdraft(&self) -> bool {
+        self.allow_overdraft && self.account_type.policy != LimitPolicy::NoOverdraft
+    }
+
+    /// The fee a `Soft` policy charges once a debit has left the account
+    /// past its limit, recorded right after it with the next id. Not held to
+    /// the tolerance, the daily limit or the velocity rules: it is the price
+    /// of having gone past them.
+    pub(crate) fn charge_overdraft_fee(
+        &mut self,
+        after: &Transaction,
+        now: OffsetDateTime,
+    ) -> Option<Transaction> {
+        let LimitPolicy::Soft { fee, .. } = self.account_type.policy else {
+            return None;
+        };
+        if fee == 0
+            || after.currency.is_some()
+            || after.kind != TransactionType::Debit
+            || self.available(now) >= 0
+        {
+            return None;
+        }
+        self.balance -= fee;
+        self.last_id = Ulid::next_after(self.last_id, now);
+        let transaction = Transaction {
+            id: self.last_id,
+            value: fee,
+            kind: TransactionType::Debit,
+            description: Description(FEE_DESCRIPTION.to_string()),
+            create_at: after.create_at,
+            server_assigned_time: true,
+            seq: next_seq(),
+            transfer: None,
+            reversal: None,
+            currency: None,
+            category: None,
+        };
+        self.record(transaction.clone());
+        // A version step of its own, like any recorded transaction, so a
+        // follower replaying the two one by one lands on the same version.
+        self.touch(now);
+        Some(transaction)
+    }
+}
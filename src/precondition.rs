@@ -0,0 +1,21 @@
+use axum::http::{header, HeaderMap};
+
+pub const INVALID: &str = "If-Match invalido";
+
+/// The account version a write is conditional on: the `versao` from an
+/// earlier response, bare or quoted like an entity tag. `None` when the
+/// header is absent (or `*`): the write goes ahead unconditionally.
+pub fn expected_version(headers: &HeaderMap) -> Result<Option<u64>, &'static str> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let value = value.to_str().map_err(|_| INVALID)?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    let version = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    version.parse().map(Some).map_err(|_| INVALID)
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    error::AppError, registry::AccountId, render_transaction, schedule::ScheduleStatus, AppState,
+};
+
+pub const PAST: &str = "data da projecao no passado";
+
+#[derive(Deserialize)]
+pub struct ProjectionQuery {
+    #[serde(rename = "ate", with = "time::serde::rfc3339")]
+    until: OffsetDateTime,
+}
+
+/// Balance and available funds at `ate`, on a copy of the account: pending
+/// schedules run at their own `executar_em`, so each sees the holds still in
+/// force at that moment, and holds expiring before `ate` stop counting.
+pub async fn project(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<ProjectionQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let mut account = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?
+        .account
+        .read()
+        .await
+        .clone();
+    if query.until < state.clock.now() {
+        return Err(AppError::Invalid(PAST));
+    }
+
+    let mut due: Vec<OffsetDateTime> = account
+        .schedule
+        .entries()
+        .iter()
+        .filter(|entry| entry.status == ScheduleStatus::Pending && entry.run_at <= query.until)
+        .map(|entry| entry.run_at)
+        .collect();
+    due.sort();
+    due.dedup();
+
+    let (mut applied, mut failed) = (Vec::new(), Vec::new());
+    for at in due {
+        for outcome in account.run_due_schedules(at) {
+            match outcome {
+                Ok(transaction) => applied.push(render_transaction(&transaction, &state.config)),
+                Err((transaction, error)) => failed.push(json!({
+                    "motivo": error.reason(),
+                    "transacao": render_transaction(&transaction, &state.config),
+                })),
+            }
+        }
+    }
+
+    let formatter = state.config.formatter();
+    Ok(Json(json!({
+        "account": account_id,
+        "ate": state.config.timestamp(query.until),
+        "saldo": formatter.amount(account.balance),
+        "limite": formatter.amount(account.limit),
+        "reservado": formatter.amount(account.holds.outstanding(query.until)),
+        "disponivel": formatter.amount(account.available(query.until)),
+        "agendamentos_aplicados": applied,
+        "agendamentos_falhos": failed,
+    })))
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{error::AppError, registry::AccountId, trace, AppState};
+
+pub const LIMITED: &str = "limite de requisicoes excedido";
+
+/// Buckets kept before full ones (idle long enough to have refilled) are
+/// dropped, so a scan over many addresses cannot grow the map for good.
+const MAX_BUCKETS: usize = 10_000;
+
+/// `rate` requests per second sustained, up to `burst` at once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rate {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets by key, refilled lazily on each request.
+pub struct Limiter<K> {
+    rate: Rate,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Eq + Hash> Limiter<K> {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Takes a token for `key`, or says how long until one is back.
+    fn take(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let Rate { rate, burst } = self.rate;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst
+            });
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+}
+
+/// The per-IP and per-account limiters, each off unless configured.
+#[derive(Default)]
+pub struct Limits {
+    pub ip: Option<Limiter<IpAddr>>,
+    pub account: Option<Limiter<AccountId>>,
+}
+
+/// `429` with `Retry-After` once the caller's address or the path's account
+/// runs out of tokens. Both buckets are checked, so a busy account is
+/// throttled however many addresses share it. Behind a proxy, see
+/// `BANK_RATE_LIMIT_FORWARDED`.
+pub async fn enforce(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let limits = &state.rate_limits;
+    let now = Instant::now();
+    let ip = limits.ip.as_ref().and_then(|limiter| {
+        let ip = client_ip(&request, state.config.rate_limit_forwarded)?;
+        limiter.take(ip, now).err()
+    });
+    let account = limits.account.as_ref().and_then(|limiter| {
+        let account = trace::account_of(request.uri().path())?;
+        limiter.take(account, now).err()
+    });
+    match ip.into_iter().chain(account).max() {
+        None => next.run(request).await,
+        Some(wait) => {
+            let mut response =
+                AppError::Status(StatusCode::TOO_MANY_REQUESTS, LIMITED).into_response();
+            // Whole seconds, rounded up so a retry right on time succeeds.
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+            response
+        }
+    }
+}
+
+/// The peer address, or with `forwarded` the first `X-Forwarded-For` entry
+/// when there is one.
+fn client_ip(request: &Request, forwarded: bool) -> Option<IpAddr> {
+    let header = forwarded
+        .then(|| request.headers().get("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|first| first.trim().parse().ok());
+    header.or_else(|| {
+        request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| peer.ip())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::Response,
+    };
+    use tower::ServiceExt;
+
+    use super::{Limiter, Rate};
+    use crate::{config::Config, http, testing::bank, AppState};
+
+    const RATE: Rate = Rate {
+        rate: 0.5,
+        burst: 2.0,
+    };
+
+    /// `GET path` as sent from `ip`, through the whole router.
+    async fn get(state: &Arc<AppState>, path: &str, ip: &str) -> Response {
+        let request = Request::get(path)
+            .header("x-forwarded-for", ip)
+            .body(Body::empty())
+            .unwrap();
+        http::router(state.clone()).oneshot(request).await.unwrap()
+    }
+
+    fn retry_after(response: &Response) -> Option<&str> {
+        response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .map(|value| value.to_str().unwrap())
+    }
+
+    #[test]
+    fn a_bucket_refills_at_its_rate_up_to_its_burst() {
+        let limiter = Limiter::new(RATE);
+        let start = Instant::now();
+        assert_eq!(limiter.take('a', start), Ok(()));
+        assert_eq!(limiter.take('a', start), Ok(()));
+        assert_eq!(limiter.take('a', start), Err(Duration::from_secs(2)));
+        // Other keys have buckets of their own.
+        assert_eq!(limiter.take('b', start), Ok(()));
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.take('a', later), Err(Duration::from_secs(1)));
+        // Idle for long, it holds no more than `burst`.
+        let much_later = start + Duration::from_secs(60);
+        assert_eq!(limiter.take('a', much_later), Ok(()));
+        assert_eq!(limiter.take('a', much_later), Ok(()));
+        assert!(limiter.take('a', much_later).is_err());
+    }
+
+    #[tokio::test]
+    async fn an_address_over_its_rate_gets_429_with_retry_after() {
+        let (state, _) = bank(Config {
+            rate_limit_ip: Some(RATE),
+            rate_limit_forwarded: true,
+            ..Config::default()
+        });
+        for account in [1, 2] {
+            let response = get(&state, &format!("/clientes/{account}/extrato"), "10.0.0.1").await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(retry_after(&response), None);
+        }
+        // Whichever account it asks for.
+        let response = get(&state, "/clientes/3/extrato", "10.0.0.1").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after(&response), Some("2"));
+
+        let response = get(&state, "/clientes/1/extrato", "10.0.0.2").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn an_account_over_its_rate_gets_429_from_every_address() {
+        let (state, _) = bank(Config {
+            rate_limit_account: Some(RATE),
+            rate_limit_forwarded: true,
+            ..Config::default()
+        });
+        for ip in ["10.0.0.1", "10.0.0.2"] {
+            let response = get(&state, "/clientes/1/extrato", ip).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = get(&state, "/clientes/1/extrato", "10.0.0.3").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after(&response), Some("2"));
+
+        let response = get(&state, "/clientes/2/extrato", "10.0.0.1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    num::ParseIntError,
+    str::FromStr,
+    sync::{atomic::AtomicU64, Arc, OnceLock, RwLock as StdRwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{actor, cache::CacheSlot, idempotency, metrics::AccountMetrics, Account, Transaction};
+
+/// An account number. A plain integer on the wire (paths, JSON bodies,
+/// snapshots and the WAL), so data written with the old `u8` ids still loads.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct AccountId(pub u64);
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(AccountId)
+    }
+}
+
+/// An account and the bookkeeping kept next to it, outside its lock.
+pub struct Entry {
+    pub account: RwLock<Account>,
+    /// Feeds the account's SSE subscribers.
+    pub events: broadcast::Sender<Transaction>,
+    /// Write-lock acquisitions that gave up after `config.lock_timeout`.
+    pub lock_timeouts: AtomicU64,
+    pub metrics: AccountMetrics,
+    /// Used only with `BANK_STATEMENT_CACHE`.
+    pub statement: CacheSlot,
+    pub idempotency: idempotency::Keys,
+    /// Set for actor accounts: writes are queued here instead of each
+    /// request taking the write lock.
+    pub queue: OnceLock<actor::Queue>,
+}
+
+impl Entry {
+    fn new(account: Account) -> Self {
+        Self {
+            account: RwLock::new(account),
+            events: broadcast::channel(64).0,
+            lock_timeouts: AtomicU64::new(0),
+            metrics: AccountMetrics::default(),
+            statement: CacheSlot::default(),
+            idempotency: idempotency::Keys::default(),
+            queue: OnceLock::new(),
+        }
+    }
+}
+
+/// Independent map locks; ids are spread over them by value, so a lookup
+/// or an account being opened only contends with ids in the same shard.
+const SHARDS: usize = 16;
+
+type Shard = StdRwLock<HashMap<AccountId, Arc<Entry>>>;
+
+/// Every account by id. Accounts are added at runtime but never removed
+/// (closing one only marks it), so a looked-up `Entry` stays valid; a shard
+/// lock is held just long enough to clone an `Arc` or log a new account,
+/// never across an await.
+pub struct AccountRegistry {
+    shards: [Shard; SHARDS],
+}
+
+impl AccountRegistry {
+    pub fn new(accounts: HashMap<AccountId, RwLock<Account>>) -> Self {
+        let registry = Self {
+            shards: Default::default(),
+        };
+        for (id, account) in accounts {
+            registry.insert(id, account.into_inner());
+        }
+        registry
+    }
+
+    fn shard(&self, id: &AccountId) -> &Shard {
+        &self.shards[(id.0 % SHARDS as u64) as usize]
+    }
+
+    pub fn get(&self, id: &AccountId) -> Option<Arc<Entry>> {
+        self.shard(id).read().unwrap().get(id).cloned()
+    }
+
+    /// Sorted.
+    pub fn ids(&self) -> Vec<AccountId> {
+        let mut ids: Vec<AccountId> = self
+            .shards
+            .iter()
+            .flat_map(|shard| shard.read().unwrap().keys().copied().collect::<Vec<_>>())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Sorted by id.
+    pub fn entries(&self) -> Vec<(AccountId, Arc<Entry>)> {
+        let mut entries: Vec<_> = self
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(id, entry)| (*id, entry.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        entries
+    }
+
+    /// The smallest unused id from 1 up; `None` only once `u64` runs out.
+    pub fn free_id(&self) -> Option<AccountId> {
+        let mut next = 1;
+        for AccountId(id) in self.ids() {
+            if id > next {
+                break;
+            }
+            next = next.max(id.checked_add(1)?);
+        }
+        Some(AccountId(next))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    /// Adds `account` under `id` unless the id is taken.
+    pub fn insert(&self, id: AccountId, account: Account) -> Option<Arc<Entry>> {
+        self.insert_logged(id, account, |_| Ok::<_, ()>(()))
+            .ok()
+            .flatten()
+    }
+
+    /// `insert`, once `log` has recorded the account; on its error nothing
+    /// is added. Under the shard lock, so no one else can take the id in
+    /// between.
+    pub(crate) fn insert_logged<E>(
+        &self,
+        id: AccountId,
+        account: Account,
+        log: impl FnOnce(&Account) -> Result<(), E>,
+    ) -> Result<Option<Arc<Entry>>, E> {
+        let mut shard = self.shard(&id).write().unwrap();
+        if shard.contains_key(&id) {
+            return Ok(None);
+        }
+        log(&account)?;
+        let entry = Arc::new(Entry::new(account));
+        shard.insert(id, entry.clone());
+        Ok(Some(entry))
+    }
+}
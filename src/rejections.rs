@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    error::AppError, registry::AccountId, render_transaction, transact::TransactError, AppState,
+    Transaction,
+};
+
+/// Size of each account's rejection ring.
+pub const KEPT: usize = 10;
+
+/// A transaction `Account::transact` turned down, kept for diagnostics only.
+#[derive(Clone)]
+pub struct Rejection {
+    pub transaction: Transaction,
+    pub error: TransactError,
+    pub at: OffsetDateTime,
+}
+
+/// Recently rejected transactions, newest first; independent of the
+/// `ultimas_transacoes` history.
+pub async fn list(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let account = entry.account.read().await;
+    let rejections: Vec<Value> = account
+        .rejections
+        .iter()
+        .map(|rejection| {
+            json!({
+                "motivo": rejection.error.reason(),
+                "em": state.config.timestamp(rejection.at),
+                "transacao": render_transaction(&rejection.transaction, &state.config),
+            })
+        })
+        .collect();
+    Ok(Json(
+        json!({ "account": account_id, "rejeicoes": rejections }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use time::Duration;
+
+    use super::KEPT;
+    use crate::{
+        config::Config,
+        testing::{bank, get, post, send, timestamp, START},
+    };
+
+    #[tokio::test]
+    async fn over_limit_debit_is_listed_with_its_reason() {
+        let (state, _) = bank(Config::default());
+        let debit = json!({"valor": 100_001, "tipo": "D", "descricao": "saque"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", debit)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = send(&state, get("/clientes/1/rejeicoes")).await;
+        assert_eq!(status, StatusCode::OK);
+        let rejection = &body["rejeicoes"][0];
+        assert_eq!(rejection["motivo"], "Limite insuficiente");
+        assert_eq!(rejection["em"], timestamp(START));
+        assert_eq!(rejection["transacao"]["valor"], 100_001);
+        assert_eq!(rejection["transacao"]["tipo"], "D");
+
+        // Not in the history.
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn a_refused_transfer_is_listed_on_the_account_that_refused_it() {
+        let (state, _) = bank(Config::default());
+        let transfer = json!({"para": 2, "valor": 100_001, "descricao": "aluguel"});
+        let (status, _) = send(&state, post("/clientes/1/transferencias", transfer)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, body) = send(&state, get("/clientes/1/rejeicoes")).await;
+        let rejection = &body["rejeicoes"][0];
+        assert_eq!(rejection["motivo"], "Limite insuficiente");
+        assert_eq!(rejection["transacao"]["valor"], 100_001);
+        assert_eq!(rejection["transacao"]["tipo"], "D");
+        // The credit was never tried.
+        let (_, body) = send(&state, get("/clientes/2/rejeicoes")).await;
+        assert_eq!(body["rejeicoes"], json!([]));
+        let (_, statement) = send(&state, get("/clientes/2/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+    }
+
+    #[tokio::test]
+    async fn the_ring_keeps_the_newest() {
+        let (state, _) = bank(Config::default());
+        for value in 0..KEPT as i64 + 3 {
+            let debit = json!({"valor": 200_000 + value, "tipo": "D", "descricao": "saque"});
+            send(&state, post("/clientes/1/transacoes", debit)).await;
+        }
+        let (_, body) = send(&state, get("/clientes/1/rejeicoes")).await;
+        let rejections = body["rejeicoes"].as_array().unwrap();
+        assert_eq!(rejections.len(), KEPT);
+        assert_eq!(
+            rejections[0]["transacao"]["valor"],
+            200_000 + KEPT as i64 + 2
+        );
+    }
+
+    #[tokio::test]
+    async fn a_stale_version_on_a_scheduled_transaction_is_not_listed() {
+        let (state, _) = bank(Config::default());
+        let credit = json!({
+            "valor": 100, "tipo": "C", "descricao": "futuro",
+            "realizada_em": timestamp(START + Duration::hours(1)),
+        });
+        let mut request = post("/clientes/1/transacoes", credit);
+        request
+            .headers_mut()
+            .insert("if-match", "7".parse().unwrap());
+        let (status, body) = send(&state, request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["versao"], 0);
+
+        let (_, body) = send(&state, get("/clientes/1/rejeicoes")).await;
+        assert_eq!(body["rejeicoes"], json!([]));
+    }
+}
@@ -0,0 +1,863 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::to_bytes,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::{broadcast, mpsc},
+    time::timeout,
+};
+
+use crate::{
+    actor,
+    error::{AppError, JsonBody},
+    observer::{AccountObserver, Outcome},
+    open_account,
+    persistence::AccountSnapshot,
+    registry::{AccountId, AccountRegistry},
+    ulid::Ulid,
+    Account, AppState, Transaction,
+};
+
+pub const DISABLED: &str = "replicacao desativada";
+pub const BAD_TOKEN: &str = "token de replicacao invalido";
+pub const LEADER_UNAVAILABLE: &str = "lider indisponivel";
+
+/// Sent by the leader and checked by the follower with `BANK_REPLICATION_TOKEN`.
+const TOKEN_HEADER: &str = "x-replication-token";
+
+/// Events per `POST /interno/replicacao`.
+const BATCH: usize = 256;
+
+/// Same as axum's default body limit, which the leader applies anyway.
+const FORWARDED_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Hop-by-hop, or recomputed for the copy: never passed through.
+const UNFORWARDED: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Leader,
+    Follower,
+}
+
+impl Role {
+    /// The `BANK_REPLICATION_ROLE` value selecting this role.
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Leader => "leader",
+            Role::Follower => "follower",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplicationConfig {
+    pub role: Role,
+    /// The other instance, `http://host:port`: the follower for the leader,
+    /// the leader for the follower.
+    pub peer: String,
+    /// `BANK_REPLICATION_TOKEN`, sent by the leader and expected by the
+    /// follower as `X-Replication-Token` on `/interno/replicacao`.
+    pub token: String,
+    /// How long the follower holds the answer to a forwarded transaction for
+    /// the transaction to come back through replication.
+    pub wait: Duration,
+}
+
+/// What the leader queues, in the order it happened on each account.
+enum Outgoing {
+    Transaction {
+        account: AccountId,
+        previous: Option<Ulid>,
+        transaction: Box<Transaction>,
+    },
+    /// The whole account, read when the batch it is in goes out.
+    State(AccountId),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "tipo")]
+enum Event {
+    #[serde(rename = "transacao")]
+    Transaction {
+        account: AccountId,
+        /// The account's transaction shipped before this one; `None` for the
+        /// first since the leader started, which applies over anything older.
+        #[serde(rename = "anterior")]
+        previous: Option<Ulid>,
+        #[serde(rename = "transacao")]
+        transaction: Transaction,
+    },
+    #[serde(rename = "estado")]
+    State {
+        #[serde(rename = "estado")]
+        snapshot: AccountSnapshot,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Batch {
+    #[serde(rename = "eventos")]
+    events: Vec<Event>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Reply {
+    #[serde(rename = "aplicadas")]
+    applied: u64,
+    #[serde(rename = "duplicadas")]
+    duplicates: u64,
+    /// Accounts whose chain did not continue from what the follower holds;
+    /// the leader answers each with its whole state.
+    #[serde(rename = "conflitos")]
+    conflicts: Vec<AccountId>,
+}
+
+#[derive(Default)]
+struct Counters {
+    /// Queued on the leader and not acknowledged yet.
+    pending: AtomicUsize,
+    sent: AtomicU64,
+    applied: AtomicU64,
+    duplicates: AtomicU64,
+    conflicts: AtomicU64,
+    /// Highest transaction `seq` the follower applied.
+    last_seq: AtomicU64,
+    /// Forwarded transactions answered before they had replicated back.
+    wait_timeouts: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+/// Either end of `BANK_REPLICATION_ROLE`. The leader is the only instance
+/// that takes writes: it ships every accepted transaction to the follower,
+/// in order, and the whole account whenever it changes some other way. The
+/// follower forwards every write it is sent to the leader, applies what the
+/// leader ships and serves reads from that.
+pub struct Replication {
+    config: ReplicationConfig,
+    /// Only on the leader.
+    outbox: Option<mpsc::UnboundedSender<Outgoing>>,
+    /// The id of the last transaction queued per account, chained into the
+    /// next one so the follower can tell when it missed something.
+    shipped: Mutex<HashMap<AccountId, Ulid>>,
+    counters: Counters,
+}
+
+impl AccountObserver for Replication {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        let (Some(outbox), Ok(())) = (&self.outbox, outcome) else {
+            return;
+        };
+        let previous = self
+            .shipped
+            .lock()
+            .unwrap()
+            .insert(account_id, transaction.id);
+        self.counters.pending.fetch_add(1, Ordering::Relaxed);
+        let _ = outbox.send(Outgoing::Transaction {
+            account: account_id,
+            previous,
+            transaction: Box::new(transaction.clone()),
+        });
+    }
+}
+
+impl Replication {
+    /// On the leader, every account is queued whole first: whatever the
+    /// follower held before, it starts from the leader's state.
+    pub fn start(config: ReplicationConfig, accounts: Arc<AccountRegistry>) -> Arc<Self> {
+        let (outbox, inbox) = mpsc::unbounded_channel();
+        let leader = config.role == Role::Leader;
+        let replication = Arc::new(Self {
+            config,
+            outbox: leader.then_some(outbox),
+            shipped: Mutex::new(HashMap::new()),
+            counters: Counters::default(),
+        });
+        if leader {
+            for (account_id, entry) in accounts.entries() {
+                if let Ok(account) = entry.account.try_read() {
+                    replication
+                        .shipped
+                        .lock()
+                        .unwrap()
+                        .insert(account_id, account.last_id);
+                }
+                replication.changed(account_id);
+            }
+            tokio::spawn(replication.clone().ship(inbox, accounts));
+        }
+        replication
+    }
+
+    pub fn role(&self) -> Role {
+        self.config.role
+    }
+
+    /// The account changed outside a transaction; the follower is sent all
+    /// of it. A no-op on the follower.
+    pub(crate) fn changed(&self, account_id: AccountId) {
+        if let Some(outbox) = &self.outbox {
+            self.counters.pending.fetch_add(1, Ordering::Relaxed);
+            let _ = outbox.send(Outgoing::State(account_id));
+        }
+    }
+
+    /// One batch at a time, retried until the follower takes it, so it never
+    /// sees a transaction before the ones queued ahead of it.
+    async fn ship(
+        self: Arc<Self>,
+        mut inbox: mpsc::UnboundedReceiver<Outgoing>,
+        accounts: Arc<AccountRegistry>,
+    ) {
+        let url = format!(
+            "{}/interno/replicacao",
+            self.config.peer.trim_end_matches('/')
+        );
+        let headers = [
+            ("Content-Type".to_string(), "application/json".to_string()),
+            (TOKEN_HEADER.to_string(), self.config.token.clone()),
+        ];
+        let mut queued = Vec::with_capacity(BATCH);
+        while inbox.recv_many(&mut queued, BATCH).await > 0 {
+            let taken = queued.len();
+            let mut events = Vec::with_capacity(taken);
+            for outgoing in queued.drain(..) {
+                events.push(match outgoing {
+                    Outgoing::Transaction {
+                        account,
+                        previous,
+                        transaction,
+                    } => Event::Transaction {
+                        account,
+                        previous,
+                        transaction: *transaction,
+                    },
+                    Outgoing::State(account_id) => match accounts.get(&account_id) {
+                        Some(entry) => Event::State {
+                            snapshot: AccountSnapshot::of(account_id, &*entry.account.read().await),
+                        },
+                        None => continue,
+                    },
+                });
+            }
+            let sent = events.len() as u64;
+            let body = serde_json::to_vec(&Batch { events }).unwrap();
+
+            let mut backoff = Duration::from_millis(100);
+            let reply = loop {
+                match self.send(&url, &headers, &body).await {
+                    Ok(reply) => break reply,
+                    Err(error) => {
+                        *self.counters.last_error.lock().unwrap() = Some(error);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(Duration::from_secs(5));
+                    }
+                }
+            };
+            *self.counters.last_error.lock().unwrap() = None;
+            self.counters.pending.fetch_sub(taken, Ordering::Relaxed);
+            self.counters.sent.fetch_add(sent, Ordering::Relaxed);
+            self.counters
+                .applied
+                .fetch_add(reply.applied, Ordering::Relaxed);
+            self.counters
+                .duplicates
+                .fetch_add(reply.duplicates, Ordering::Relaxed);
+            self.counters
+                .conflicts
+                .fetch_add(reply.conflicts.len() as u64, Ordering::Relaxed);
+            for account_id in reply.conflicts {
+                self.changed(account_id);
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<Reply, String> {
+        let (status, _, body) = exchange(&Method::POST, url, headers, body).await?;
+        if !status.is_success() {
+            return Err(format!("status {status}"));
+        }
+        serde_json::from_slice(&body).map_err(|err| err.to_string())
+    }
+
+    /// The leader's answer, as is. A transaction it accepted is held back
+    /// until it has replicated here, up to `wait`, so a read right after
+    /// the write already sees it.
+    async fn forward(&self, state: &AppState, request: Request) -> Response {
+        let (parts, body) = request.into_parts();
+        let Ok(body) = to_bytes(body, FORWARDED_BODY_LIMIT).await else {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        };
+        let url = format!(
+            "{}{}",
+            self.config.peer.trim_end_matches('/'),
+            parts.uri.path_and_query().map_or("/", |path| path.as_str())
+        );
+        let headers: Vec<_> = parts
+            .headers
+            .iter()
+            .filter(|(name, _)| !UNFORWARDED.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let (status, headers, body) = match exchange(&parts.method, &url, &headers, &body).await {
+            Ok(answer) => answer,
+            Err(error) => {
+                *self.counters.last_error.lock().unwrap() = Some(error);
+                return AppError::Status(StatusCode::SERVICE_UNAVAILABLE, LEADER_UNAVAILABLE)
+                    .into_response();
+            }
+        };
+
+        if status.is_success() {
+            if let Ok(answer) = serde_json::from_slice::<Value>(&body) {
+                let account = serde_json::from_value(answer["account"].clone()).ok();
+                let id = serde_json::from_value(answer["id"].clone()).ok();
+                if let (Some(account_id), Some(id)) = (account, id) {
+                    if !self.caught_up(state, account_id, id).await {
+                        self.counters.wait_timeouts.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+
+        let mut response = (status, body).into_response();
+        for (name, value) in headers {
+            if UNFORWARDED.contains(&name.to_ascii_lowercase().as_str()) {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::try_from(value.as_str()),
+            ) {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+
+    /// Whether transaction `id` of `account_id` is here within `wait`.
+    async fn caught_up(&self, state: &AppState, account_id: AccountId, id: Ulid) -> bool {
+        let Some(entry) = state.accounts.get(&account_id) else {
+            return false;
+        };
+        // Subscribed before looking, so an arrival in between is not missed.
+        let mut arrivals = entry.events.subscribe();
+        if entry.account.read().await.last_id >= id {
+            return true;
+        }
+        timeout(self.config.wait, async {
+            loop {
+                match arrivals.recv().await {
+                    Ok(transaction) if transaction.id >= id => return true,
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        if entry.account.read().await.last_id >= id {
+                            return true;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return false,
+                }
+            }
+        })
+        .await
+        .unwrap_or(false)
+    }
+}
+
+/// On the follower, everything but reads and the replication endpoint
+/// itself goes to the leader. Runs before the account check digit is
+/// stripped, so the leader gets the path exactly as sent.
+pub async fn forward(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(replication) = state
+        .replication
+        .as_ref()
+        .filter(|replication| replication.role() == Role::Follower)
+    else {
+        return next.run(request).await;
+    };
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || request.uri().path().starts_with("/interno/")
+    {
+        return next.run(request).await;
+    }
+    replication.forward(&state, request).await
+}
+
+/// `POST /interno/replicacao`, on the follower. A transaction applies when
+/// it continues the account's chain, is a duplicate when already here, and
+/// otherwise is a conflict: it and everything after it for that account in
+/// the batch are left out until the leader sends the whole account, which
+/// always applies. The leader is right by definition; nothing the follower
+/// holds is merged back.
+pub async fn receive(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonBody(batch): JsonBody<Batch>,
+) -> Result<Json<Reply>, AppError> {
+    let replication = state
+        .replication
+        .as_ref()
+        .filter(|replication| replication.role() == Role::Follower)
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let presented = headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if presented != Some(replication.config.token.as_str()) {
+        return Err(AppError::Status(StatusCode::UNAUTHORIZED, BAD_TOKEN));
+    }
+
+    let mut reply = Reply::default();
+    for event in batch.events {
+        match event {
+            Event::Transaction {
+                account: account_id,
+                previous,
+                transaction,
+            } => {
+                if reply.conflicts.contains(&account_id) {
+                    continue;
+                }
+                let Some(entry) = state.accounts.get(&account_id) else {
+                    reply.conflicts.push(account_id);
+                    continue;
+                };
+                let mut account = entry.account.write().await;
+                if transaction.id <= account.last_id {
+                    reply.duplicates += 1;
+                    continue;
+                }
+                if previous.is_some_and(|previous| previous != account.last_id) {
+                    reply.conflicts.push(account_id);
+                    continue;
+                }
+                let seq = transaction.seq;
+                let before = state.wal.is_some().then(|| (*account).clone());
+                if let Err(error) = account.replay(transaction.clone()) {
+                    tracing::warn!(
+                        account = account_id.0,
+                        id = %transaction.id,
+                        error = %error,
+                        "replication: shipped transaction conflicts"
+                    );
+                    reply.conflicts.push(account_id);
+                    continue;
+                }
+                if state.log([(account_id, &transaction)]).is_err() {
+                    if let Some(before) = before {
+                        *account = before;
+                    }
+                    reply.conflicts.push(account_id);
+                    continue;
+                }
+                account.modified_at = state.clock.now();
+                state.replicated(account_id, &transaction);
+                replication
+                    .counters
+                    .last_seq
+                    .fetch_max(seq, Ordering::Relaxed);
+                reply.applied += 1;
+            }
+            Event::State { snapshot } => {
+                let account_id = snapshot.id;
+                if adopt(&state, snapshot).await {
+                    reply.conflicts.retain(|id| *id != account_id);
+                    reply.applied += 1;
+                } else {
+                    reply.conflicts.push(account_id);
+                }
+            }
+        }
+    }
+    let counters = &replication.counters;
+    counters.applied.fetch_add(reply.applied, Ordering::Relaxed);
+    counters
+        .duplicates
+        .fetch_add(reply.duplicates, Ordering::Relaxed);
+    counters
+        .conflicts
+        .fetch_add(reply.conflicts.len() as u64, Ordering::Relaxed);
+    Ok(Json(reply))
+}
+
+/// The leader's account over the follower's, version included so `If-Match`
+/// values read here hold there; opened first when the follower lacks it.
+async fn adopt(state: &Arc<AppState>, snapshot: AccountSnapshot) -> bool {
+    let (account_id, version) = (snapshot.id, snapshot.version);
+    let now = state.clock.now();
+    if let Some(entry) = state.accounts.get(&account_id) {
+        let mut account = entry.account.write().await;
+        account.restore(snapshot, &state.config);
+        account.version = version;
+        account.modified_at = now;
+        return true;
+    }
+    let mut account = open_account(account_id, snapshot.limit, &state.config)
+        .unwrap_or_else(|_| Account::new(snapshot.limit, state.config.history_cap));
+    account.restore(snapshot, &state.config);
+    account.version = version;
+    account.modified_at = now;
+    let Some(entry) = state.accounts.insert(account_id, account) else {
+        return false;
+    };
+    if state.config.is_actor(account_id) {
+        actor::start(state, account_id, &entry);
+    }
+    true
+}
+
+/// `GET /replicacao`: how far behind the other end is, from this end.
+pub async fn status(State(state): State<Arc<AppState>>) -> Result<Json<Value>, AppError> {
+    let replication = state
+        .replication
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let counters = &replication.counters;
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let last_error = counters.last_error.lock().unwrap().clone();
+    Ok(Json(match replication.role() {
+        Role::Leader => json!({
+            "papel": Role::Leader.name(),
+            "par": replication.config.peer,
+            "pendentes": counters.pending.load(Ordering::Relaxed),
+            "enviados": load(&counters.sent),
+            "aplicados": load(&counters.applied),
+            "duplicados": load(&counters.duplicates),
+            "conflitos": load(&counters.conflicts),
+            "ultimo_erro": last_error,
+        }),
+        Role::Follower => json!({
+            "papel": Role::Follower.name(),
+            "par": replication.config.peer,
+            "aplicados": load(&counters.applied),
+            "duplicados": load(&counters.duplicates),
+            "conflitos": load(&counters.conflicts),
+            "ultima_sequencia": load(&counters.last_seq),
+            "esperas_esgotadas": load(&counters.wait_timeouts),
+            "ultimo_erro": last_error,
+        }),
+    }))
+}
+
+/// Status, headers and the decoded body of an HTTP answer.
+type Answer = (StatusCode, Vec<(String, String)>, Vec<u8>);
+
+/// Minimal HTTP/1.1 over one `Connection: close` exchange, as webhook
+/// delivery does it, but reading the whole answer back.
+async fn exchange(
+    method: &Method,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<Answer, String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or("only http:// peers are supported")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(address).await?;
+        let mut head = format!("{method} {path} HTTP/1.1\r\nHost: {authority}\r\n");
+        for (name, value) in headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ));
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+        let mut answer = Vec::new();
+        stream.read_to_end(&mut answer).await?;
+        Ok::<_, std::io::Error>(answer)
+    };
+    let answer = timeout(Duration::from_secs(5), exchange)
+        .await
+        .map_err(|_| "timeout".to_string())?
+        .map_err(|err| err.to_string())?;
+    parse_answer(&answer).ok_or_else(|| "malformed answer".to_string())
+}
+
+fn parse_answer(answer: &[u8]) -> Option<Answer> {
+    let end = answer.windows(4).position(|window| window == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&answer[..end]).ok()?;
+    let body = &answer[end + 4..];
+    let mut lines = head.split("\r\n");
+    let status = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    let header = |wanted: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.as_str())
+    };
+    let body = if header("transfer-encoding").is_some_and(|value| value.contains("chunked")) {
+        dechunk(body)?
+    } else {
+        match header("content-length").and_then(|length| length.parse::<usize>().ok()) {
+            Some(length) => body.get(..length)?.to_vec(),
+            None => body.to_vec(),
+        }
+    };
+    Some((status, headers, body))
+}
+
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(body.len());
+    loop {
+        let line = body.windows(2).position(|window| window == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use axum::http::{HeaderValue, StatusCode};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::{Batch, Event, Replication, ReplicationConfig, Role, TOKEN_HEADER};
+    use crate::{
+        config::Config,
+        http,
+        ledger::Ledger,
+        persistence::AccountSnapshot,
+        registry::AccountId,
+        testing::{bank, get, post, send, timestamp, START},
+        ulid::Ulid,
+        Account, AppState, Transaction,
+    };
+
+    const TOKEN: &str = "replica";
+
+    /// `bank` as `role`, with `peer` at the other end.
+    fn end(role: Role, peer: String) -> Arc<AppState> {
+        let config = ReplicationConfig {
+            role,
+            peer,
+            token: TOKEN.to_string(),
+            wait: Duration::from_secs(2),
+        };
+        let (mut state, _) = bank(Config {
+            replication: Some(config.clone()),
+            ..Config::default()
+        });
+        let replication = Replication::start(config, state.accounts.clone());
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.observers.push(replication.clone());
+        state_mut.replication = Some(replication);
+        state
+    }
+
+    fn follower() -> Arc<AppState> {
+        // Never reached: these tests send the batches themselves.
+        end(Role::Follower, "http://127.0.0.1:9".to_string())
+    }
+
+    async fn ship(
+        state: &Arc<AppState>,
+        events: Vec<Event>,
+        token: Option<&str>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = post(
+            "/interno/replicacao",
+            serde_json::to_value(Batch { events }).unwrap(),
+        );
+        if let Some(token) = token {
+            let token = HeaderValue::from_str(token).unwrap();
+            request.headers_mut().insert(TOKEN_HEADER, token);
+        }
+        send(state, request).await
+    }
+
+    /// Stamped after `previous`, as the leader would have.
+    fn shipped(value: i64, previous: Ulid) -> Transaction {
+        let mut transaction: Transaction = serde_json::from_value(json!({
+            "valor": value,
+            "tipo": "C",
+            "descricao": "replicada",
+            "realizada_em": timestamp(START),
+        }))
+        .unwrap();
+        transaction.id = Ulid::next_after(previous, START);
+        transaction
+    }
+
+    #[tokio::test]
+    async fn a_write_sent_to_the_follower_is_answered_by_the_leader() {
+        let (leaders, followers) = (
+            TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        );
+        let at = |listener: &TcpListener| format!("http://{}", listener.local_addr().unwrap());
+        let leader = end(Role::Leader, at(&followers));
+        let follower = end(Role::Follower, at(&leaders));
+        for (listener, state) in [(leaders, &leader), (followers, &follower)] {
+            let app = http::router(state.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+        }
+
+        let credit = json!({"valor": 500, "tipo": "C", "descricao": "repassada"});
+        let (status, body) = send(&follower, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["saldo"], 500);
+
+        let (_, statement) = send(&leader, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 500);
+        // Answered once it had come back: readable here right away.
+        let (_, statement) = send(&follower, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 500);
+        assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "repassada");
+        let replication = follower.replication.as_ref().unwrap();
+        assert_eq!(
+            replication.counters.wait_timeouts.load(Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn a_shipped_batch_applies_in_order_and_only_once() {
+        let mut state = follower();
+        let path =
+            std::env::temp_dir().join(format!("bank-{}-follower.ledger", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let ledger = Arc::new(Ledger::open(&path, Default::default()).unwrap());
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.observers.push(ledger.clone());
+        state_mut.ledger = Some(ledger);
+
+        let first = shipped(500, Ulid::NIL);
+        let event = |previous, transaction: &Transaction| Event::Transaction {
+            account: AccountId(1),
+            previous,
+            transaction: transaction.clone(),
+        };
+        let (status, reply) = ship(&state, vec![event(None, &first)], Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            reply,
+            json!({"aplicadas": 1, "duplicadas": 0, "conflitos": []})
+        );
+        let (_, reply) = ship(&state, vec![event(None, &first)], Some(TOKEN)).await;
+        assert_eq!(
+            reply,
+            json!({"aplicadas": 0, "duplicadas": 1, "conflitos": []})
+        );
+        // Chained to something this end never saw.
+        let (_, reply) = ship(
+            &state,
+            vec![event(Some(Ulid::NIL), &shipped(1, first.id))],
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(
+            reply,
+            json!({"aplicadas": 0, "duplicadas": 0, "conflitos": [1]})
+        );
+
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 500);
+        assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "replicada");
+        // The leader's ledger has it, not this one.
+        let (_, events) = send(&state, get("/clientes/1/ledger")).await;
+        assert_eq!(events["eventos"], json!([]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_shipped_account_is_adopted_whole() {
+        let state = follower();
+        let mut account = Account::new(5_000, 10);
+        account.balance = 1_200;
+        account.version = 7;
+        // Account 1 as the leader has it, and one the follower lacks.
+        let events = [AccountId(1), AccountId(9)]
+            .map(|id| Event::State {
+                snapshot: AccountSnapshot::of(id, &account),
+            })
+            .into();
+        let (_, reply) = ship(&state, events, Some(TOKEN)).await;
+        assert_eq!(reply["aplicadas"], 2);
+
+        for id in [1, 9] {
+            let (status, statement) = send(&state, get(&format!("/clientes/{id}/extrato"))).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(statement["saldo"]["total"], 1_200);
+            assert_eq!(statement["saldo"]["limite"], 5_000);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_batch_without_the_token_is_refused() {
+        let state = follower();
+        let credit = || {
+            vec![Event::Transaction {
+                account: AccountId(1),
+                previous: None,
+                transaction: shipped(500, Ulid::NIL),
+            }]
+        };
+        for token in [None, Some("outro")] {
+            let (status, _) = ship(&state, credit(), token).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+    }
+}
@@ -0,0 +1,60 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde_json::Value;
+
+use crate::AppState;
+
+const HEADER: &str = "id,tipo,limite,saldo,disponivel\n";
+
+/// Every account's current figures as CSV, one row per account in id order.
+/// Rows are built as the body is polled, each under its own brief read lock,
+/// so the report is not a single point-in-time view across accounts.
+pub async fn csv(State(state): State<Arc<AppState>>) -> Response {
+    let ids = state.accounts.ids();
+
+    let rows = stream::unfold(
+        (ids.into_iter(), state, true),
+        |(mut ids, state, first)| async move {
+            if first {
+                let chunk = Bytes::from_static(HEADER.as_bytes());
+                return Some((Ok::<_, Infallible>(chunk), (ids, state, false)));
+            }
+            let id = ids.next()?;
+            // Accounts are never removed, so every listed id is still there.
+            let entry = state.accounts.get(&id)?;
+            let row = {
+                let account = entry.account.read().await;
+                let formatter = state.config.formatter();
+                format!(
+                    "{id},{},{},{},{}\n",
+                    account.account_type.name,
+                    cell(formatter.amount(account.limit)),
+                    cell(formatter.amount(account.balance)),
+                    cell(formatter.amount(account.available(state.clock.now()))),
+                )
+            };
+            Some((Ok(Bytes::from(row)), (ids, state, false)))
+        },
+    );
+
+    (
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+        Body::from_stream(rows),
+    )
+        .into_response()
+}
+
+/// Decimal amounts are JSON strings; CSV wants them bare.
+fn cell(amount: Value) -> String {
+    match amount {
+        Value::String(amount) => amount,
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use time::OffsetDateTime;
+
+use crate::AppState;
+
+/// Prunes statement entries older than `BANK_HISTORY_RETENTION_MS` every
+/// `BANK_RETENTION_INTERVAL_MS`. Only the history shrinks: balances, limits
+/// and the id watermark are left as they are.
+pub async fn run(state: Arc<AppState>) {
+    let Some(retention) = state.config.history_retention else {
+        return;
+    };
+    let mut interval = tokio::time::interval(state.config.retention_interval);
+    loop {
+        interval.tick().await;
+        if state.maintenance.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        let now = state.clock.now();
+        let cutoff = now - retention;
+        let pruned = prune(&state, cutoff).await;
+        if pruned > 0 {
+            tracing::info!(
+                pruned,
+                cutoff = %state.config.timestamp(cutoff),
+                "retention: old entries pruned"
+            );
+        }
+    }
+}
+
+/// One pass: drops every account's entries stamped before `cutoff`, and says
+/// how many went.
+async fn prune(state: &AppState, cutoff: OffsetDateTime) -> usize {
+    let now = state.clock.now();
+    let mut pruned = 0;
+    for (account_id, entry) in state.accounts.entries() {
+        let mut account = entry.account.write().await;
+        let removed = account
+            .transactions
+            .prune_oldest(|transaction| transaction.create_at < cutoff);
+        if removed > 0 {
+            account.touch(now);
+            state.changed(account_id);
+            pruned += removed;
+        }
+    }
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use time::Duration;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        testing::{bank, get, post, send},
+    };
+
+    #[tokio::test]
+    async fn a_pass_removes_only_entries_past_the_window() {
+        let (state, clock) = bank(Config::default());
+        let credit =
+            |description: &str| json!({"valor": 100, "tipo": "C", "descricao": description});
+        send(&state, post("/clientes/1/transacoes", credit("velha"))).await;
+        send(&state, post("/clientes/2/transacoes", credit("velha"))).await;
+        clock.advance(Duration::hours(2));
+        send(&state, post("/clientes/1/transacoes", credit("nova"))).await;
+
+        let cutoff = state.clock.now() - Duration::hours(1);
+        assert_eq!(prune(&state, cutoff).await, 2);
+
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        let history = statement["ultimas_transacoes"].as_array().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["descricao"], "nova");
+        // The balance still counts what was pruned.
+        assert_eq!(statement["saldo"]["total"], 200);
+        let (_, statement) = send(&state, get("/clientes/2/extrato")).await;
+        assert_eq!(statement["ultimas_transacoes"], json!([]));
+        assert_eq!(statement["saldo"]["total"], 100);
+
+        assert_eq!(prune(&state, cutoff).await, 0);
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    deadline::Deadline, error::AppError, precondition, registry::AccountId, ulid::Ulid, AppState,
+    Transaction, TransactionType,
+};
+
+pub const NOT_FOUND: &str = "transacao nao encontrada";
+pub const ALREADY_REVERSED: &str = "transacao ja estornada";
+pub const OF_REVERSAL: &str = "estorno nao pode ser estornado";
+pub const OF_TRANSFER: &str = "transferencia nao pode ser estornada por uma das contas";
+
+/// Applies the opposite of transaction `:tx_id` (same value and description)
+/// as a new entry whose `estorno` names the original. Each transaction can be
+/// reversed once, and only while it is still in the retained history; a
+/// reversal is final, and so is either half of a transfer, which would leave
+/// the other account's half standing.
+pub async fn create(
+    Path((account_id, original_id)): Path<(AccountId, Ulid)>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let expected = precondition::expected_version(&headers).map_err(AppError::BadRequest)?;
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let mut account = state
+        .write_account(&entry, deadline.map(|Extension(deadline)| deadline))
+        .await?;
+    account
+        .check_version(expected)
+        .map_err(|error| AppError::Transact(error, state.config.formatter()))?;
+
+    let Some(original) = account
+        .transactions
+        .iter()
+        .find(|transaction| transaction.id == original_id)
+    else {
+        return Err(AppError::NotFound(NOT_FOUND));
+    };
+    let refused = match original {
+        original if original.reversal.is_some() => Some(OF_REVERSAL),
+        original if original.transfer.is_some() => Some(OF_TRANSFER),
+        _ if account
+            .transactions
+            .iter()
+            .any(|transaction| transaction.reversal == Some(original_id)) =>
+        {
+            Some(ALREADY_REVERSED)
+        }
+        _ => None,
+    };
+    if let Some(reason) = refused {
+        return Err(AppError::Invalid(reason));
+    }
+
+    let now = state.clock.now();
+    let reversal = Transaction {
+        id: Ulid::NIL,
+        value: original.value,
+        kind: match original.kind {
+            TransactionType::Credit => TransactionType::Debit,
+            TransactionType::Debit => TransactionType::Credit,
+        },
+        description: original.description.clone(),
+        create_at: now,
+        server_assigned_time: true,
+        seq: 0,
+        transfer: None,
+        reversal: Some(original_id),
+        currency: original.currency,
+        // Undoes the original's share of its category too.
+        category: original.category.clone(),
+    };
+    let submitted = reversal.clone();
+    let savepoint = state.wal.is_some().then(|| account.savepoint(1));
+    let result = match account.transact(reversal, now) {
+        Ok(result) => result,
+        Err(error) => {
+            state.rejected(account_id, &submitted, error);
+            return Err(AppError::Transact(error, state.config.formatter()));
+        }
+    };
+    if let Err(error) = state.log_result(account_id, &result) {
+        if let Some(savepoint) = savepoint {
+            account.roll_back(savepoint);
+        }
+        return Err(error);
+    }
+    state.accepted_result(account_id, &result);
+    state.audit.record(json!({
+        "evento": "estorno",
+        "account": account_id,
+        "id": result.transaction.id,
+        "estorno": original_id,
+        "valor": result.transaction.value,
+        "em": state.config.timestamp(now),
+    }));
+
+    let currency = result.transaction.currency.unwrap_or(state.config.currency);
+    Ok(Json(json!({
+        "account": account_id,
+        "id": result.transaction.id,
+        "seq": state.config.seq(result.transaction.seq),
+        "estorno": original_id,
+        "moeda": currency.code(),
+        "limite": state.config.amount(result.limit),
+        "saldo": state.config.formatter().in_currency(currency).amount(result.balance),
+        "versao": result.version,
+    })))
+}
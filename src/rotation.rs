@@ -0,0 +1,186 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// When an append-only log is moved aside for a fresh one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+struct Active {
+    file: File,
+    written: u64,
+    opened_at: Instant,
+}
+
+/// Append-only file that rotates itself. Every write and rotation happens
+/// under one lock, so a line never straddles two files and concurrent
+/// writers never see a half-renamed one. Rotated files keep the active
+/// name plus `.<unix nanos>`, which sorts oldest first.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    active: Mutex<Active>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, rotation: Rotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            rotation,
+            active: Mutex::new(Active {
+                file,
+                written,
+                opened_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Writes `bytes` whole to the active file, rotating first if they would
+    /// push it past `max_bytes` or it is older than `max_age`. `sync` forces
+    /// the data to disk before returning.
+    pub fn append(&self, bytes: &[u8], sync: bool) -> io::Result<()> {
+        let mut active = self.active.lock().unwrap();
+        let too_big = self
+            .rotation
+            .max_bytes
+            .is_some_and(|max| active.written > 0 && active.written + bytes.len() as u64 > max);
+        let too_old = self
+            .rotation
+            .max_age
+            .is_some_and(|max| active.written > 0 && active.opened_at.elapsed() >= max);
+        if too_big || too_old {
+            self.rotate(&mut active)?;
+        }
+        active.file.write_all(bytes)?;
+        active.written += bytes.len() as u64;
+        if sync {
+            active.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    pub fn sync(&self) -> io::Result<()> {
+        self.active.lock().unwrap().file.sync_data()
+    }
+
+    /// Whether the active file can still be opened for appending where it
+    /// is expected: catches a deleted file, a read-only or unmounted volume.
+    /// Under the lock, so a rotation in progress is not mistaken for one.
+    pub fn check(&self) -> io::Result<()> {
+        let _active = self.active.lock().unwrap();
+        OpenOptions::new().append(true).open(&self.path).map(drop)
+    }
+
+    /// Moves the active file aside now, unless nothing was written to it.
+    pub fn rotate_now(&self) -> io::Result<()> {
+        let mut active = self.active.lock().unwrap();
+        if active.written == 0 {
+            return Ok(());
+        }
+        self.rotate(&mut active)
+    }
+
+    fn rotate(&self, active: &mut Active) -> io::Result<()> {
+        active.file.sync_data()?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(format!(".{nanos}"));
+        fs::rename(&self.path, &rotated)?;
+        *active = Active {
+            file: OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?,
+            written: 0,
+            opened_at: Instant::now(),
+        };
+        Ok(())
+    }
+}
+
+/// Files rotated away from `path`, oldest first.
+pub fn rotated(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let (Some(directory), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let mut files: Vec<(u128, PathBuf)> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let stamp = file_name.strip_prefix(&prefix)?.parse().ok()?;
+            Some((stamp, entry.path()))
+        })
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc, thread};
+
+    use super::{rotated, RotatingFile, Rotation};
+
+    #[test]
+    fn writes_past_max_bytes_rotate_and_keep_every_line_whole() {
+        let directory = std::env::temp_dir().join(format!("bank-{}-rotation", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir(&directory).unwrap();
+        let path = directory.join("audit.log");
+        let rotation = Rotation {
+            max_bytes: Some(100),
+            max_age: None,
+        };
+        let file = Arc::new(RotatingFile::open(&path, rotation).unwrap());
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    for line in 0..10 {
+                        let line = format!("{writer:02}-{line:02}-{}\n", "x".repeat(20));
+                        file.append(line.as_bytes(), false).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let rotated = rotated(&path).unwrap();
+        assert!(!rotated.is_empty());
+        assert!(path.exists(), "a fresh active file beside the rotated ones");
+        let mut lines = Vec::new();
+        for file in rotated.iter().chain([&path]) {
+            let contents = fs::read_to_string(file).unwrap();
+            assert!(
+                contents.len() <= 100,
+                "{} is past max_bytes",
+                file.display()
+            );
+            lines.extend(contents.lines().map(str::to_string));
+        }
+        assert_eq!(lines.len(), 40);
+        assert!(lines.iter().all(|line| line.len() == 26));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
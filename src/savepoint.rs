@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use time::{Date, OffsetDateTime};
+
+use crate::{
+    currency::Currency,
+    domain::{Account, Transaction},
+    ulid::Ulid,
+};
+
+/// What `Account::transact` can change, kept before transactions whose WAL
+/// write may still fail so they can be taken back without a copy of the
+/// whole account. Refusals are not taken back: they were answered as such.
+pub(crate) struct Savepoint {
+    balance: i64,
+    balances: BTreeMap<Currency, i64>,
+    daily_debits: (Date, i64),
+    velocity: u64,
+    last_id: Ulid,
+    version: u64,
+    modified_at: OffsetDateTime,
+    history: usize,
+    /// The newest entry, which a coalesced credit changes in place; only
+    /// with a coalescing window.
+    head: Option<Transaction>,
+    /// Oldest first, the entries new ones could push out of a full ring.
+    oldest: Vec<Transaction>,
+}
+
+impl Account {
+    /// Before up to `pending` transactions, each of which may record itself
+    /// and an overdraft fee.
+    pub(crate) fn savepoint(&self, pending: usize) -> Savepoint {
+        let items = &self.transactions.items;
+        let room = self.transactions.capacity - items.len();
+        let pushed_out = (2 * pending).saturating_sub(room).min(items.len());
+        Savepoint {
+            balance: self.balance,
+            balances: self.balances.clone(),
+            daily_debits: self.daily_debits,
+            velocity: self.velocity.recorded(),
+            last_id: self.last_id,
+            version: self.version,
+            modified_at: self.modified_at,
+            history: items.len(),
+            head: self
+                .coalesce_window
+                .and_then(|_| self.transactions.latest().cloned()),
+            oldest: items.iter().rev().take(pushed_out).cloned().collect(),
+        }
+    }
+
+    /// Undoes every transaction accepted since `savepoint`. New entries are
+    /// the ones with ids past its `last_id`, a coalesced head included.
+    pub(crate) fn roll_back(&mut self, savepoint: Savepoint) {
+        let items = &mut self.transactions.items;
+        while items
+            .front()
+            .is_some_and(|newest| newest.id > savepoint.last_id)
+        {
+            items.pop_front();
+        }
+        if let Some(head) = savepoint.head {
+            if items.front().map(|newest| newest.id) != Some(head.id) {
+                items.push_front(head);
+            }
+        }
+        let missing = savepoint.history.saturating_sub(items.len());
+        let mut oldest = savepoint.oldest;
+        oldest.truncate(missing);
+        items.extend(oldest.into_iter().rev());
+
+        self.balance = savepoint.balance;
+        self.balances = savepoint.balances;
+        self.daily_debits = savepoint.daily_debits;
+        self.velocity.forget_since(savepoint.velocity);
+        self.last_id = savepoint.last_id;
+        self.version = savepoint.version;
+        self.modified_at = savepoint.modified_at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use time::{macros::datetime, OffsetDateTime};
+
+    use crate::{
+        domain::{Account, Transaction},
+        velocity::{Rule, Rules},
+    };
+
+    const NOW: OffsetDateTime = datetime!(2024-03-01 12:00 UTC);
+
+    fn transaction(value: i64, kind: &str) -> Transaction {
+        serde_json::from_value(json!({"valor": value, "tipo": kind, "descricao": "teste"})).unwrap()
+    }
+
+    /// What the statement and later checks can see of an account.
+    fn visible(account: &Account) -> String {
+        let history: Vec<_> = account
+            .transactions
+            .iter()
+            .map(|transaction| (transaction.id.to_string(), transaction.value))
+            .collect();
+        format!(
+            "{} {:?} {} {} {:?}",
+            account.balance, account.daily_debits, account.version, account.last_id, history
+        )
+    }
+
+    #[test]
+    fn a_rollback_restores_what_a_full_ring_pushed_out() {
+        let mut account = Account::new(100_000, 3);
+        account.velocity = Rules::new(vec![Rule::Rolling {
+            window: Duration::from_secs(60),
+            limit: 1_000,
+        }]);
+        for value in [1, 2, 3] {
+            account.transact(transaction(value, "C"), NOW).unwrap();
+        }
+        let before = visible(&account);
+
+        let savepoint = account.savepoint(2);
+        account.transact(transaction(500, "D"), NOW).unwrap();
+        account.transact(transaction(500, "D"), NOW).unwrap();
+        account.roll_back(savepoint);
+
+        assert_eq!(visible(&account), before);
+        // The rolled-back debits no longer count against the window.
+        assert!(account.transact(transaction(1_000, "D"), NOW).is_ok());
+    }
+
+    #[test]
+    fn a_rollback_restores_a_coalesced_head() {
+        let mut account = Account::new(100_000, 10);
+        account.coalesce_window = Some(Duration::from_secs(5));
+        account.transact(transaction(100, "C"), NOW).unwrap();
+        let before = visible(&account);
+
+        let savepoint = account.savepoint(1);
+        account.transact(transaction(50, "C"), NOW).unwrap();
+        assert_eq!(account.transactions.len(), 1);
+        account.roll_back(savepoint);
+
+        assert_eq!(visible(&account), before);
+    }
+}
@@ -0,0 +1,369 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    error::{AppError, JsonBody},
+    registry::AccountId,
+    render_transaction,
+    transact::TransactError,
+    Account, AppState, Config, Transaction, TransactionRequest, TransactionType,
+};
+
+pub const PAST: &str = "executar_em precisa estar no futuro";
+pub const NOT_FOUND: &str = "agendamento nao encontrado";
+pub const PARTIAL: &str = "transacao agendada nao pode ser parcial";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleStatus {
+    #[serde(rename = "pendente")]
+    Pending,
+    /// The debit did not fit the limit when its time came; kept so the client can see it.
+    #[serde(rename = "falhou")]
+    Failed(TransactError),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduledTransaction {
+    pub id: u64,
+    #[serde(rename = "executar_em", with = "time::serde::rfc3339")]
+    pub run_at: OffsetDateTime,
+    #[serde(rename = "transacao")]
+    pub transaction: Transaction,
+    pub status: ScheduleStatus,
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleRequest {
+    #[serde(flatten)]
+    transaction: TransactionRequest,
+
+    #[serde(rename = "executar_em", with = "time::serde::rfc3339")]
+    run_at: OffsetDateTime,
+}
+
+/// Per-account list of future-dated transactions. Executed entries leave the
+/// schedule and show up in the statement instead. Snapshotted with the
+/// account, so pending entries survive a restart.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(rename = "ultimo_id")]
+    next_id: u64,
+    #[serde(rename = "entradas")]
+    entries: Vec<ScheduledTransaction>,
+}
+
+impl Schedule {
+    pub fn add(
+        &mut self,
+        run_at: OffsetDateTime,
+        transaction: Transaction,
+    ) -> &ScheduledTransaction {
+        self.next_id += 1;
+        self.entries.push(ScheduledTransaction {
+            id: self.next_id,
+            run_at,
+            transaction,
+            status: ScheduleStatus::Pending,
+        });
+        self.entries.last().unwrap()
+    }
+
+    /// Only pending entries can be cancelled.
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|entry| entry.id != id || entry.status != ScheduleStatus::Pending);
+        self.entries.len() != before
+    }
+
+    fn has_due(&self, now: OffsetDateTime) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.status == ScheduleStatus::Pending && entry.run_at <= now)
+    }
+
+    /// When the earliest pending credit runs: the soonest a refused debit
+    /// may fit, as far as the account knows.
+    pub fn next_credit(&self) -> Option<OffsetDateTime> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == ScheduleStatus::Pending)
+            .filter(|entry| entry.transaction.kind == TransactionType::Credit)
+            .map(|entry| entry.run_at)
+            .min()
+    }
+
+    pub fn entries(&self) -> &[ScheduledTransaction] {
+        &self.entries
+    }
+}
+
+impl Account {
+    /// Applies every pending entry due at `now`, oldest first, and returns each
+    /// outcome: the recorded transaction, or the rejected one with its reason.
+    pub fn run_due_schedules(
+        &mut self,
+        now: OffsetDateTime,
+    ) -> Vec<Result<Transaction, (Transaction, TransactError)>> {
+        let (mut due, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.schedule.entries)
+            .into_iter()
+            .partition(|entry| entry.status == ScheduleStatus::Pending && entry.run_at <= now);
+        self.schedule.entries = rest;
+        due.sort_by_key(|entry| (entry.run_at, entry.id));
+
+        let mut outcomes = Vec::new();
+        for mut entry in due {
+            match self.transact(entry.transaction.clone(), now) {
+                Ok(result) => outcomes.extend(result.recorded().cloned().map(Ok)),
+                Err(error) => {
+                    outcomes.push(Err((entry.transaction.clone(), error)));
+                    entry.status = ScheduleStatus::Failed(error);
+                    self.schedule.entries.push(entry);
+                }
+            }
+        }
+        outcomes
+    }
+}
+
+pub async fn run(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.config.schedule_interval);
+    loop {
+        interval.tick().await;
+        // Maintenance mode promises no writes, scheduled ones included.
+        if state.maintenance.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        pass(&state).await;
+    }
+}
+
+/// Runs whatever is due on every account. Each account's new state is
+/// logged before anyone hears of the outcomes; when that fails the account
+/// is put back and its entries stay due for the next pass, so a restart
+/// never runs one twice.
+pub(crate) async fn pass(state: &AppState) {
+    let now = state.clock.now();
+    for (id, entry) in state.accounts.entries() {
+        if !entry.account.read().await.schedule.has_due(now) {
+            continue;
+        }
+        let mut account = entry.account.write().await;
+        let before = account.clone();
+        let outcomes = account.run_due_schedules(now);
+        if state.persist(id, &account).is_err() {
+            *account = before;
+            continue;
+        }
+        for outcome in outcomes {
+            match outcome {
+                Ok(transaction) => state.accepted(id, &transaction),
+                Err((transaction, error)) => state.rejected(id, &transaction, error),
+            }
+        }
+    }
+}
+
+/// `error` as the transaction endpoint answers it. A debit refused for lack
+/// of limit while a scheduled credit is pending also gets that credit's time,
+/// as `proximo_credito` and as `Retry-After` in whole seconds; with none
+/// pending neither is sent, as there is nothing to go by.
+pub fn refusal(
+    error: TransactError,
+    next_credit: Option<OffsetDateTime>,
+    state: &AppState,
+) -> Response {
+    let refused = AppError::Transact(error, state.config.formatter());
+    let next_credit =
+        next_credit.filter(|_| matches!(error, TransactError::InsufficientLimit { .. }));
+    let Some(next_credit) = next_credit else {
+        return refused.into_response();
+    };
+    let mut body = refused.body();
+    body["proximo_credito"] = json!(state.config.timestamp(next_credit));
+    let wait = next_credit - state.clock.now();
+    // Whole seconds, rounded up, like the rate limiter's.
+    let seconds = wait.whole_seconds() + i64::from(wait.subsec_nanoseconds() > 0);
+    let mut response = (refused.status(), Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
+
+pub fn render(entry: &ScheduledTransaction, config: &Config) -> Value {
+    let (status, reason) = match entry.status {
+        ScheduleStatus::Pending => ("pendente", None),
+        ScheduleStatus::Failed(error) => ("falhou", Some(error.reason())),
+    };
+    json!({
+        "id": entry.id,
+        "executar_em": config.timestamp(entry.run_at),
+        "status": status,
+        "motivo": reason,
+        "transacao": render_transaction(&entry.transaction, config),
+    })
+}
+
+pub async fn create(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<ScheduleRequest>,
+) -> impl IntoResponse {
+    // Shown with the intended time until execution stamps the real one.
+    let transaction = request
+        .transaction
+        .into_transaction(&state.config, request.run_at)
+        .map_err(AppError::Invalid)?;
+    if request.run_at <= state.clock.now() {
+        return Err(AppError::Invalid(PAST));
+    }
+
+    match state.accounts.get(&account_id) {
+        Some(entry) => {
+            let mut account = entry.account.write().await;
+            let before = account.schedule.clone();
+            let body = render(
+                account.schedule.add(request.run_at, transaction),
+                &state.config,
+            );
+            if let Err(error) = state.persist(account_id, &account) {
+                account.schedule = before;
+                return Err(error);
+            }
+            Ok((StatusCode::CREATED, Json(body)))
+        }
+        None => Err(AppError::UnknownAccount),
+    }
+}
+
+pub async fn list(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.accounts.get(&account_id) {
+        Some(entry) => {
+            let account = entry.account.read().await;
+            let entries: Vec<Value> = account
+                .schedule
+                .entries()
+                .iter()
+                .map(|entry| render(entry, &state.config))
+                .collect();
+            Ok(Json(json!({ "agendamentos": entries })))
+        }
+        None => Err(AppError::UnknownAccount),
+    }
+}
+
+pub async fn cancel(
+    Path((account_id, schedule_id)): Path<(AccountId, u64)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, AppError> {
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let mut account = entry.account.write().await;
+    let before = account.schedule.clone();
+    if !account.schedule.cancel(schedule_id) {
+        return Err(AppError::NotFound(NOT_FOUND));
+    }
+    if let Err(error) = state.persist(account_id, &account) {
+        account.schedule = before;
+        return Err(error);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use time::Duration;
+
+    use super::pass;
+    use crate::{
+        testing::{bank, delete, get, post, send, timestamp, START},
+        Config,
+    };
+
+    fn scheduled(valor: i64, tipo: &str, at: time::OffsetDateTime) -> serde_json::Value {
+        json!({"valor": valor, "tipo": tipo, "descricao": "agendada", "executar_em": timestamp(at)})
+    }
+
+    #[tokio::test]
+    async fn runs_once_the_clock_reaches_it() {
+        let (state, clock) = bank(Config::default());
+        let at = START + Duration::hours(1);
+        let (status, _) = send(
+            &state,
+            post("/clientes/1/agendamentos", scheduled(500, "C", at)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        pass(&state).await;
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+
+        clock.advance(Duration::hours(2));
+        pass(&state).await;
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 500);
+        assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "agendada");
+        let (_, list) = send(&state, get("/clientes/1/agendamentos")).await;
+        assert_eq!(list["agendamentos"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn a_debit_past_the_limit_is_kept_as_failed() {
+        let (state, clock) = bank(Config::default());
+        let at = START + Duration::minutes(5);
+        send(
+            &state,
+            post("/clientes/1/agendamentos", scheduled(100_001, "D", at)),
+        )
+        .await;
+
+        clock.advance(Duration::minutes(10));
+        pass(&state).await;
+        let (_, list) = send(&state, get("/clientes/1/agendamentos")).await;
+        assert_eq!(list["agendamentos"][0]["status"], "falhou");
+        assert_eq!(list["agendamentos"][0]["motivo"], "Limite insuficiente");
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_entries_never_run() {
+        let (state, clock) = bank(Config::default());
+        let at = START + Duration::hours(1);
+        let (_, created) = send(
+            &state,
+            post("/clientes/1/agendamentos", scheduled(500, "C", at)),
+        )
+        .await;
+        let path = format!("/clientes/1/agendamentos/{}", created["id"]);
+
+        let (status, _) = send(&state, delete(&path)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&state, delete(&path)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        clock.advance(Duration::hours(2));
+        pass(&state).await;
+        let (_, statement) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(statement["saldo"]["total"], 0);
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{config::fold, error::AppError, registry::AccountId, render_transaction, AppState};
+
+pub const EMPTY_QUERY: &str = "termo de busca vazio";
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    /// Inclusive lower bound on `realizada_em`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    de: Option<OffsetDateTime>,
+    /// Inclusive upper bound on `realizada_em`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    ate: Option<OffsetDateTime>,
+}
+
+/// Retained transactions whose `descricao` contains `q`, ignoring case and
+/// accents, newest first like the statement.
+pub async fn search(
+    Path(account_id): Path<AccountId>,
+    Query(query): Query<SearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let needle = fold(query.q.trim());
+    if needle.is_empty() {
+        return Err(AppError::Invalid(EMPTY_QUERY));
+    }
+    let entry = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?;
+    let account = entry.account.read().await;
+
+    let matches: Vec<Value> = account
+        .transactions
+        .iter()
+        .filter(|transaction| query.de.is_none_or(|from| transaction.create_at >= from))
+        .filter(|transaction| query.ate.is_none_or(|until| transaction.create_at <= until))
+        .filter(|transaction| fold(&transaction.description.0).contains(&needle))
+        .map(|transaction| render_transaction(transaction, &state.config))
+        .collect();
+
+    Ok(Json(json!({
+        "account": account_id,
+        "q": query.q,
+        "transacoes": matches,
+    })))
+}
@@ -0,0 +1,259 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::Router;
+use serde_json::Value;
+use tracing::Instrument;
+
+use crate::{
+    actor,
+    audit::AuditLog,
+    clock::{Clock, SystemClock},
+    config::Config,
+    correlation,
+    deadline::Deadline,
+    domain::{seed_accounts, Transaction, TransactionRequest, TransactionType},
+    error::AppError,
+    http::{self, handlers},
+    metrics::{RequestMetrics, RunStats, TransactionCounts},
+    observer::{AccountObserver, Broadcast},
+    rate_limit,
+    registry::{AccountId, AccountRegistry},
+    transact::{TransactError, TransactResult},
+    AppState,
+};
+
+/// How a transaction is applied, besides the transaction itself.
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    /// Best-effort debit, see `Account::transact_partial`.
+    pub partial: bool,
+    /// `If-Match`: refused with `VersionMismatch` unless still current.
+    pub expected_version: Option<u64>,
+    pub deadline: Option<Deadline>,
+}
+
+/// The bank without HTTP around it: what the handlers call, and what tests
+/// drive directly. Cheap to build from the shared state, one per request.
+#[derive(Clone)]
+pub struct BankService {
+    state: Arc<AppState>,
+}
+
+impl BankService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    /// The accounts `config` seeds, with nothing persisted and no webhooks:
+    /// only the observers that feed statements and metrics.
+    pub fn in_memory(config: Config) -> Result<Self, String> {
+        Self::in_memory_with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// `in_memory`, telling time by `clock`.
+    pub fn in_memory_with_clock(config: Config, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let accounts = Arc::new(AccountRegistry::new(seed_accounts(
+            &config.accounts,
+            &config,
+        )?));
+        let run_stats = Arc::new(RunStats::new());
+        let observers: Vec<Arc<dyn AccountObserver>> = vec![
+            Arc::new(Broadcast(accounts.clone())),
+            run_stats.clone(),
+            Arc::new(TransactionCounts(accounts.clone())),
+        ];
+        let rate_limits = rate_limit::Limits {
+            ip: config.rate_limit_ip.map(rate_limit::Limiter::new),
+            account: config.rate_limit_account.map(rate_limit::Limiter::new),
+        };
+        let audit = AuditLog::open(config.audit_log.as_deref(), config.log_rotation)
+            .map_err(|err| err.to_string())?;
+        let state = Arc::new(AppState {
+            accounts,
+            config,
+            clock,
+            maintenance: AtomicBool::new(false),
+            audit,
+            webhooks: None,
+            observers,
+            run_stats,
+            requests: RequestMetrics::default(),
+            rate_limits,
+            wal: None,
+            history: None,
+            ledger: None,
+            storage: None,
+            replication: None,
+        });
+        for (account_id, entry) in state.accounts.entries() {
+            if state.config.is_actor(account_id) {
+                actor::start(&state, account_id, &entry);
+            }
+        }
+        Ok(Self::new(state))
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    /// Maintenance mode, as `SIGUSR1` toggles it: writes answer `503` while
+    /// it is on, reads keep being served.
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.state.maintenance.store(enabled, Ordering::SeqCst);
+    }
+
+    /// The whole HTTP API over this service's state.
+    pub fn router(&self) -> Router {
+        http::router(self.state.clone())
+    }
+
+    /// Checks `valor`, `descricao` and `moeda` against the config, refusing
+    /// with every field at fault. A `valor` that is not an i64 at all is
+    /// malformed (`400`), anything else is refused (`422`).
+    pub fn prepare(&self, request: TransactionRequest) -> Result<Transaction, AppError> {
+        request
+            .validate(&self.state.config, self.state.clock.now())
+            .map_err(AppError::Fields)
+    }
+
+    /// Applies `transaction` to the account, through its actor queue when it
+    /// has one, and tells the observers either way. The outer error is for a
+    /// transaction never tried (unknown account, lock timeout, deadline);
+    /// the inner one is the account refusing it.
+    pub async fn apply(
+        &self,
+        account_id: AccountId,
+        transaction: Transaction,
+        options: Options,
+    ) -> Result<Result<TransactResult, TransactError>, AppError> {
+        let state = &self.state;
+        let entry = state
+            .accounts
+            .get(&account_id)
+            .ok_or(AppError::UnknownAccount)?;
+        // Rejections consume the transaction, so keep a copy for the observers.
+        let submitted = transaction.clone();
+        let kind = match transaction.kind {
+            TransactionType::Credit => "C",
+            TransactionType::Debit => "D",
+        };
+
+        // Lock wait (or actor queueing) included: that is what the slow log is for.
+        let started = Instant::now();
+        let outcome = match entry.queue.get() {
+            Some(queue) => {
+                actor::submit(
+                    queue,
+                    transaction,
+                    options.partial,
+                    options.expected_version,
+                    options.deadline,
+                )
+                .await?
+            }
+            None => {
+                let span = tracing::debug_span!("transact", account = account_id.0, kind);
+                let mut account = state
+                    .write_account(&entry, options.deadline)
+                    .instrument(span.clone())
+                    .await?;
+                span.in_scope(|| {
+                    let wait_us = started.elapsed().as_micros() as u64;
+                    tracing::debug!(wait_us, "write lock acquired");
+                    let now = state.clock.now();
+                    // Put back if the WAL will not take it.
+                    let savepoint = state.wal.is_some().then(|| account.savepoint(1));
+                    let outcome = account
+                        .check_version(options.expected_version)
+                        .and_then(|()| {
+                            if options.partial {
+                                account.transact_partial(transaction, now)
+                            } else {
+                                account.transact(transaction, now)
+                            }
+                        });
+                    if let Ok(result) = &outcome {
+                        if let Err(error) = state.log_result(account_id, result) {
+                            if let Some(savepoint) = savepoint {
+                                account.roll_back(savepoint);
+                            }
+                            return Err(error);
+                        }
+                        state.accepted_result(account_id, result);
+                    }
+                    Ok(outcome)
+                })?
+            }
+        };
+        let elapsed = started.elapsed();
+        if is_slow(&state.config, elapsed) {
+            tracing::warn!(
+                account = account_id.0,
+                kind,
+                duration_ms = elapsed.as_millis() as u64,
+                correlation = correlation::current().as_deref(),
+                "slow transaction"
+            );
+        }
+
+        if let Err(error) = outcome {
+            state.rejected(account_id, &submitted, error);
+        }
+        Ok(outcome)
+    }
+
+    /// `prepare` then `apply`.
+    pub async fn transact(
+        &self,
+        account_id: AccountId,
+        request: TransactionRequest,
+        options: Options,
+    ) -> Result<Result<TransactResult, TransactError>, AppError> {
+        let transaction = self.prepare(request)?;
+        self.apply(account_id, transaction, options).await
+    }
+
+    /// The `/clientes/:id/extrato` document.
+    pub async fn statement(&self, account_id: AccountId) -> Result<Value, AppError> {
+        let entry = self
+            .state
+            .accounts
+            .get(&account_id)
+            .ok_or(AppError::UnknownAccount)?;
+        let account = entry.account.read().await;
+        Ok(handlers::statement(account_id, &account, &self.state))
+    }
+}
+
+/// Whether a transaction that took `elapsed` reached the
+/// `BANK_SLOW_TRANSACTION_MS` threshold and gets a warning.
+fn is_slow(config: &Config, elapsed: Duration) -> bool {
+    config
+        .slow_transaction
+        .is_some_and(|threshold| elapsed >= threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transactions_over_the_threshold_are_logged() {
+        let config = Config {
+            slow_transaction: Some(Duration::from_millis(50)),
+            ..Config::default()
+        };
+        assert!(is_slow(&config, Duration::from_millis(80)));
+        assert!(is_slow(&config, Duration::from_millis(50)));
+        assert!(!is_slow(&config, Duration::from_millis(49)));
+        let disabled = Config::default();
+        assert!(!is_slow(&disabled, Duration::from_secs(60)));
+    }
+}
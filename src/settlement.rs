@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    deadline::Deadline, error::JsonBody, registry::AccountId, ulid::Ulid, validation, AppState,
+    Description, Transaction, TransactionType,
+};
+
+const DESCRIPTION: &str = "liquidacao";
+
+#[derive(Deserialize)]
+pub struct Adjustment {
+    id: AccountId,
+    /// Signed: positive credits the account, negative debits it.
+    #[serde(rename = "valor")]
+    value: i64,
+}
+
+/// End-of-day settlement: every adjustment is applied or none is. All
+/// referenced accounts are locked in id order, the batch runs against
+/// copies, and the copies replace the originals only if nothing failed.
+/// Each adjustment is held to `max_transaction_value` like any transaction.
+pub async fn apply(
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    JsonBody(adjustments): JsonBody<Vec<Adjustment>>,
+) -> Result<Json<Value>, Response> {
+    let mut failures = Vec::new();
+    let mut locks = BTreeMap::new();
+    for (index, adjustment) in adjustments.iter().enumerate() {
+        match state.accounts.get(&adjustment.id) {
+            Some(entry) => {
+                locks.entry(adjustment.id).or_insert(entry);
+            }
+            None => failures.push(failure(index, adjustment, "conta inexistente")),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(rejected(failures));
+    }
+
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    let mut guards = BTreeMap::new();
+    for (id, entry) in &locks {
+        guards.insert(*id, state.write_account(entry, deadline).await?);
+    }
+    let mut scratch: BTreeMap<_, _> = guards
+        .iter()
+        .map(|(id, account)| (*id, (**account).clone()))
+        .collect();
+
+    let now = state.clock.now();
+    let mut applied = Vec::with_capacity(adjustments.len());
+    for (index, adjustment) in adjustments.iter().enumerate() {
+        let value = match adjustment.value.unsigned_abs() {
+            0 => {
+                failures.push(failure(index, adjustment, "valor invalido"));
+                continue;
+            }
+            value if value > state.config.max_transaction_value.unsigned_abs() => {
+                failures.push(failure(index, adjustment, validation::ABOVE_MAX));
+                continue;
+            }
+            value => value as i64,
+        };
+        let transaction = Transaction {
+            id: Ulid::NIL,
+            value,
+            kind: if adjustment.value > 0 {
+                TransactionType::Credit
+            } else {
+                TransactionType::Debit
+            },
+            description: Description(DESCRIPTION.to_string()),
+            create_at: now,
+            server_assigned_time: true,
+            seq: 0,
+            transfer: None,
+            reversal: None,
+            currency: None,
+            category: None,
+        };
+        match scratch
+            .get_mut(&adjustment.id)
+            .unwrap()
+            .transact(transaction, now)
+        {
+            Ok(result) => applied.extend(
+                result
+                    .recorded()
+                    .map(|transaction| (adjustment.id, transaction.clone())),
+            ),
+            Err(error) => failures.push(failure(index, adjustment, error.reason())),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(rejected(failures));
+    }
+
+    state.log(applied.iter().map(|(id, transaction)| (*id, transaction)))?;
+    for (id, account) in scratch {
+        **guards.get_mut(&id).unwrap() = account;
+    }
+    let results: Vec<Value> = applied
+        .iter()
+        .map(|(id, transaction)| {
+            state.accepted(*id, transaction);
+            json!({
+                "account": id,
+                "id": transaction.id,
+                "seq": state.config.seq(transaction.seq),
+            })
+        })
+        .collect();
+    let balances: Vec<Value> = guards
+        .iter()
+        .map(|(id, account)| {
+            json!({
+                "account": id,
+                "limite": state.config.amount(account.limit),
+                "saldo": state.config.amount(account.balance),
+            })
+        })
+        .collect();
+
+    state.audit.record(json!({
+        "evento": "liquidacao_aplicada",
+        "ajustes": applied.len(),
+        "contas": guards.keys().collect::<Vec<_>>(),
+        "em": state.config.timestamp(now),
+    }));
+
+    Ok(Json(json!({ "aplicados": results, "saldos": balances })))
+}
+
+fn failure(index: usize, adjustment: &Adjustment, reason: &str) -> Value {
+    json!({
+        "indice": index,
+        "id": adjustment.id,
+        "valor": adjustment.value,
+        "motivo": reason,
+    })
+}
+
+fn rejected(failures: Vec<Value>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({ "falhas": failures })),
+    )
+        .into_response()
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    error::{AppError, JsonBody},
+    registry::AccountId,
+    transact::TransactError,
+    AppState, TransactionRequest,
+};
+
+/// Runs `transacoes` in order against a copy of the account, so each one sees
+/// the balance the previous ones left. Nothing is recorded, published or
+/// counted as a rejection on the real account.
+pub async fn run(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    JsonBody(requests): JsonBody<Vec<TransactionRequest>>,
+) -> Result<Json<Value>, AppError> {
+    let mut account = state
+        .accounts
+        .get(&account_id)
+        .ok_or(AppError::UnknownAccount)?
+        .account
+        .read()
+        .await
+        .clone();
+
+    let formatter = state.config.formatter();
+    let now = state.clock.now();
+    let results: Vec<Value> = requests
+        .into_iter()
+        .enumerate()
+        .map(|(index, request)| {
+            let outcome = request
+                .into_transaction(&state.config, now)
+                .and_then(|transaction| {
+                    account
+                        .transact(transaction, now)
+                        .map_err(TransactError::reason)
+                });
+            match outcome {
+                Ok(_) => json!({
+                    "indice": index,
+                    "aceita": true,
+                    "saldo": formatter.amount(account.balance),
+                }),
+                Err(reason) => json!({
+                    "indice": index,
+                    "aceita": false,
+                    "motivo": reason,
+                }),
+            }
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "account": account_id,
+        "saldo": formatter.amount(account.balance),
+        "limite": formatter.amount(account.limit),
+        "resultados": results,
+    })))
+}
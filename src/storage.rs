@@ -0,0 +1,604 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::async_trait;
+use tokio::sync::{mpsc, RwLock};
+
+use crate::{
+    observer::{AccountObserver, Outcome},
+    open_account,
+    persistence::AccountExport,
+    registry::AccountId,
+    Account, Config, Transaction,
+};
+
+/// Durable home for accounts and their accepted transactions. Accounts still
+/// live in memory for the request path; a backend records what they accept,
+/// and each account as a row after changes that are not a transaction, and
+/// hands both back on startup, where they are replayed like the WAL. Without
+/// `BANK_DATABASE_URL` there is no backend and state is in memory only, as
+/// before.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Every stored transaction, ordered by account and then id.
+    async fn load(&self) -> Result<Vec<(AccountId, Transaction)>, String>;
+    /// Every stored account row, in id order.
+    async fn load_accounts(&self) -> Result<Vec<AccountExport>, String>;
+    /// Idempotent: a transaction already stored is left as is.
+    async fn append(&self, account_id: AccountId, transaction: &Transaction) -> Result<(), String>;
+    /// Replaces the account's row, unless the stored one has a later
+    /// version.
+    async fn put_account(&self, account: &AccountExport) -> Result<(), String>;
+    /// A round trip to the backend, for `/readyz`.
+    async fn ping(&self) -> Result<(), String>;
+}
+
+pub const UNAVAILABLE: &str = "armazenamento indisponivel";
+
+/// The backend `BANK_DATABASE_URL` selects, if any.
+pub async fn open(database_url: Option<&str>) -> Result<Option<Arc<dyn Storage>>, String> {
+    let Some(url) = database_url else {
+        return Ok(None);
+    };
+    #[cfg(feature = "postgres")]
+    {
+        let storage: Arc<dyn Storage> = Arc::new(Postgres::connect(url).await?);
+        Ok(Some(storage))
+    }
+    #[cfg(not(feature = "postgres"))]
+    {
+        let _ = url;
+        Err("BANK_DATABASE_URL requer o recurso postgres".to_string())
+    }
+}
+
+/// Restores stored account rows over the seeded (and snapshot/WAL restored)
+/// accounts, opening the ones created after startup, then replays stored
+/// transactions; ones already applied are skipped by id. A row older than
+/// what the snapshot or WAL already gave the account is left out.
+pub async fn load(
+    storage: &dyn Storage,
+    accounts: &mut HashMap<AccountId, RwLock<Account>>,
+    config: &Config,
+) -> Result<(), String> {
+    let mut restored = 0;
+    for export in storage.load_accounts().await? {
+        let (account_id, limit) = (export.account_id(), export.limit());
+        let account = accounts.entry(account_id).or_insert_with(|| {
+            let opened = open_account(account_id, limit, config)
+                .unwrap_or_else(|_| Account::new(limit, config.history_cap));
+            RwLock::new(opened)
+        });
+        if export.version() >= account.get_mut().version {
+            export.restore_onto(account.get_mut(), config);
+            restored += 1;
+        }
+    }
+    let (mut replayed, mut skipped) = (0, 0);
+    for (account_id, transaction) in storage.load().await? {
+        let Some(account) = accounts.get_mut(&account_id) else {
+            tracing::warn!(
+                account = account_id.0,
+                "stored transaction for an unknown account ignored"
+            );
+            continue;
+        };
+        let id = transaction.id;
+        match account.get_mut().replay(transaction) {
+            Ok(true) => replayed += 1,
+            Ok(false) => skipped += 1,
+            Err(error) => {
+                return Err(format!(
+                    "stored transaction {id} for account {account_id}: {error}"
+                ))
+            }
+        }
+    }
+    tracing::info!(
+        restored,
+        replayed,
+        skipped,
+        "storage: accounts restored and transactions replayed"
+    );
+    Ok(())
+}
+
+/// Longest wait between retries of a write the backend refused.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// How often the backend is pinged to tell whether it is reachable.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What `Writer` hands the backend, in the order it was accepted.
+enum Write {
+    Transaction(AccountId, Transaction),
+    Account(Box<AccountExport>),
+}
+
+/// Observer that hands accepted transactions, and account rows through
+/// `save`, to a `Storage` from a background task, in acceptance order, so no
+/// account lock is held across a database round trip. Whatever is still
+/// queued when the process dies is lost, the same trade-off as
+/// `WalDurability::None`.
+///
+/// A write the backend refuses is never dropped: it is retried, with
+/// backoff, ahead of everything queued after it, and until it lands the
+/// writer counts as unavailable. While the backend is unreachable, as the
+/// last write or ping found it, writes are refused with `503` and
+/// statements are served from memory marked `stale`.
+pub struct Writer {
+    storage: Arc<dyn Storage>,
+    queue: mpsc::UnboundedSender<Write>,
+    pending: Arc<AtomicUsize>,
+    available: Arc<AtomicBool>,
+    /// Why the write at the head of the queue is being retried.
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl AccountObserver for Writer {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        if outcome.is_ok() {
+            self.enqueue(Write::Transaction(account_id, transaction.clone()));
+        }
+    }
+}
+
+impl Writer {
+    pub fn spawn(storage: Arc<dyn Storage>) -> Arc<Self> {
+        let (queue, inbox) = mpsc::unbounded_channel();
+        let writer = Arc::new(Self {
+            storage,
+            queue,
+            pending: Arc::new(AtomicUsize::new(0)),
+            available: Arc::new(AtomicBool::new(true)),
+            failure: Arc::default(),
+        });
+        tokio::spawn(write(
+            writer.storage.clone(),
+            inbox,
+            writer.pending.clone(),
+            writer.available.clone(),
+            writer.failure.clone(),
+        ));
+        tokio::spawn(probe_periodically(Arc::downgrade(&writer)));
+        writer
+    }
+
+    /// The whole account, after a change that is not a transaction.
+    pub(crate) fn save(&self, account_id: AccountId, account: &Account) {
+        self.enqueue(Write::Account(Box::new(AccountExport::of(
+            account_id, account,
+        ))));
+    }
+
+    fn enqueue(&self, write: Write) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if self.queue.send(write).is_err() {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// A round trip to the backend; a write still being retried fails it
+    /// too, with the backend's reason.
+    pub async fn ping(&self) -> Result<(), String> {
+        let reached = self
+            .storage
+            .ping()
+            .await
+            .and_then(|()| match self.failure() {
+                Some(err) => Err(format!("escrita pendente: {err}")),
+                None => Ok(()),
+            });
+        self.available.store(reached.is_ok(), Ordering::SeqCst);
+        reached
+    }
+
+    /// Whether the backend took the last write and answered the last ping.
+    pub fn is_available(&self) -> bool {
+        self.available.load(Ordering::SeqCst) && self.failure().is_none()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.lock().unwrap().clone()
+    }
+
+    /// Appends queued but not yet written.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Waits up to `timeout` for queued appends to land, for graceful
+    /// shutdown. Returns how many were still pending.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.pending.load(Ordering::SeqCst) > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.pending.load(Ordering::SeqCst)
+    }
+}
+
+/// Until the writer is dropped.
+async fn probe_periodically(writer: std::sync::Weak<Writer>) {
+    let mut interval = tokio::time::interval(PROBE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(writer) = writer.upgrade() else {
+            return;
+        };
+        let _ = writer.ping().await;
+    }
+}
+
+async fn write(
+    storage: Arc<dyn Storage>,
+    mut inbox: mpsc::UnboundedReceiver<Write>,
+    pending: Arc<AtomicUsize>,
+    available: Arc<AtomicBool>,
+    failure: Arc<Mutex<Option<String>>>,
+) {
+    while let Some(write) = inbox.recv().await {
+        let mut backoff = Duration::from_millis(100);
+        loop {
+            let written = match &write {
+                Write::Transaction(account_id, transaction) => {
+                    storage.append(*account_id, transaction).await
+                }
+                Write::Account(export) => storage.put_account(export).await,
+            };
+            match written {
+                Ok(()) => {
+                    *failure.lock().unwrap() = None;
+                    available.store(true, Ordering::SeqCst);
+                    break;
+                }
+                Err(err) => {
+                    if failure.lock().unwrap().is_none() {
+                        let what = match &write {
+                            Write::Transaction(account_id, transaction) => {
+                                format!("account {account_id}, transaction {}", transaction.id)
+                            }
+                            Write::Account(export) => format!("account {}", export.account_id()),
+                        };
+                        tracing::warn!(write = %what, error = %err, "storage write failed, retrying");
+                    }
+                    *failure.lock().unwrap() = Some(err);
+                    available.store(false, Ordering::SeqCst);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// One row per accepted transaction; the whole transaction is kept as JSON in
+/// the same shape the WAL and snapshots use.
+#[cfg(feature = "postgres")]
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS transacoes (
+    conta BIGINT NOT NULL,
+    id TEXT NOT NULL,
+    seq BIGINT NOT NULL,
+    dados JSONB NOT NULL,
+    PRIMARY KEY (conta, id)
+)";
+
+/// One row per account, the whole account as JSON in the shape of an
+/// export, written after changes that are not a transaction.
+#[cfg(feature = "postgres")]
+const ACCOUNTS: &str = "CREATE TABLE IF NOT EXISTS contas (
+    conta BIGINT PRIMARY KEY,
+    versao BIGINT NOT NULL,
+    dados JSONB NOT NULL
+)";
+
+/// Tables created while ids were `u8` have a `SMALLINT` column.
+#[cfg(feature = "postgres")]
+const WIDEN_ACCOUNT: &str = "ALTER TABLE transacoes ALTER COLUMN conta TYPE BIGINT";
+
+#[cfg(feature = "postgres")]
+pub struct Postgres {
+    pool: sqlx::PgPool,
+}
+
+#[cfg(feature = "postgres")]
+impl Postgres {
+    /// Connects and creates the table if it does not exist yet.
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(5)
+            .connect(url)
+            .await
+            .map_err(|err| err.to_string())?;
+        for statement in [SCHEMA, WIDEN_ACCOUNT, ACCOUNTS] {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(Self { pool })
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[async_trait]
+impl Storage for Postgres {
+    async fn load(&self) -> Result<Vec<(AccountId, Transaction)>, String> {
+        // Ids are ULIDs, so text order is acceptance order.
+        let rows: Vec<(i64, serde_json::Value)> =
+            sqlx::query_as("SELECT conta, dados FROM transacoes ORDER BY conta, id")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|err| err.to_string())?;
+        rows.into_iter()
+            .map(|(account_id, data)| {
+                let account_id =
+                    AccountId(u64::try_from(account_id).map_err(|err| err.to_string())?);
+                let transaction = serde_json::from_value(data).map_err(|err| err.to_string())?;
+                Ok((account_id, transaction))
+            })
+            .collect()
+    }
+
+    async fn load_accounts(&self) -> Result<Vec<AccountExport>, String> {
+        let rows: Vec<(serde_json::Value,)> =
+            sqlx::query_as("SELECT dados FROM contas ORDER BY conta")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|err| err.to_string())?;
+        rows.into_iter()
+            .map(|(data,)| serde_json::from_value(data).map_err(|err| err.to_string()))
+            .collect()
+    }
+
+    async fn append(&self, account_id: AccountId, transaction: &Transaction) -> Result<(), String> {
+        let data = serde_json::to_value(transaction).map_err(|err| err.to_string())?;
+        sqlx::query(
+            "INSERT INTO transacoes (conta, id, seq, dados) VALUES ($1, $2, $3, $4)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(i64::try_from(account_id.0).map_err(|err| err.to_string())?)
+        .bind(transaction.id.to_string())
+        .bind(transaction.seq as i64)
+        .bind(data)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    async fn put_account(&self, account: &AccountExport) -> Result<(), String> {
+        let data = serde_json::to_value(account).map_err(|err| err.to_string())?;
+        sqlx::query(
+            "INSERT INTO contas (conta, versao, dados) VALUES ($1, $2, $3)
+             ON CONFLICT (conta) DO UPDATE SET versao = EXCLUDED.versao, dados = EXCLUDED.dados
+             WHERE contas.versao <= EXCLUDED.versao",
+        )
+        .bind(i64::try_from(account.account_id().0).map_err(|err| err.to_string())?)
+        .bind(account.version() as i64)
+        .bind(data)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(drop)
+            .map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use axum::{async_trait, http::StatusCode};
+    use serde_json::json;
+
+    use super::{load, Storage, Writer};
+    use crate::{
+        domain::seed_accounts,
+        persistence::AccountExport,
+        registry::AccountId,
+        testing::{bank, get, post, send, START},
+        Account, AppState, Config, Transaction,
+    };
+
+    /// Keeps what it is given in memory, or fails everything while `down`.
+    #[derive(Default)]
+    struct Flaky {
+        down: AtomicBool,
+        stored: Mutex<Vec<(AccountId, Transaction)>>,
+        accounts: Mutex<BTreeMap<AccountId, AccountExport>>,
+    }
+
+    impl Flaky {
+        fn reach(&self) -> Result<(), String> {
+            match self.down.load(Ordering::SeqCst) {
+                true => Err("connection refused".to_string()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Storage for Flaky {
+        async fn load(&self) -> Result<Vec<(AccountId, Transaction)>, String> {
+            self.reach()?;
+            Ok(self.stored.lock().unwrap().clone())
+        }
+
+        async fn load_accounts(&self) -> Result<Vec<AccountExport>, String> {
+            self.reach()?;
+            let accounts = self.accounts.lock().unwrap();
+            // Through JSON, as a backend would hand them back.
+            accounts
+                .values()
+                .map(|export| {
+                    serde_json::from_value(serde_json::to_value(export).unwrap())
+                        .map_err(|err| err.to_string())
+                })
+                .collect()
+        }
+
+        async fn append(
+            &self,
+            account_id: AccountId,
+            transaction: &Transaction,
+        ) -> Result<(), String> {
+            self.reach()?;
+            self.stored
+                .lock()
+                .unwrap()
+                .push((account_id, transaction.clone()));
+            Ok(())
+        }
+
+        async fn put_account(&self, account: &AccountExport) -> Result<(), String> {
+            self.reach()?;
+            let row = serde_json::from_value(serde_json::to_value(account).unwrap()).unwrap();
+            self.accounts
+                .lock()
+                .unwrap()
+                .insert(account.account_id(), row);
+            Ok(())
+        }
+
+        async fn ping(&self) -> Result<(), String> {
+            self.reach()
+        }
+    }
+
+    fn stored(backend: Arc<Flaky>) -> (Arc<AppState>, Arc<Writer>) {
+        let (mut state, _) = bank(Config::default());
+        let writer = Writer::spawn(backend);
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.observers.push(writer.clone());
+        state_mut.storage = Some(writer.clone());
+        (state, writer)
+    }
+
+    fn credit(value: i64) -> axum::http::Request<axum::body::Body> {
+        post(
+            "/clientes/1/transacoes",
+            json!({"valor": value, "tipo": "C", "descricao": "deposito"}),
+        )
+    }
+
+    #[tokio::test]
+    async fn outage_serves_stale_reads_and_refuses_writes() {
+        let backend = Arc::new(Flaky::default());
+        let (state, writer) = stored(backend.clone());
+        let (status, _) = send(&state, credit(700)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(writer.drain(Duration::from_secs(1)).await, 0);
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert!(body.get("stale").is_none());
+
+        backend.down.store(true, Ordering::SeqCst);
+        assert!(writer.ping().await.is_err());
+        let (status, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["stale"], true);
+        assert_eq!(body["saldo"]["total"], 700);
+        assert_eq!(body["ultimas_transacoes"][0]["valor"], 700);
+
+        let (status, body) = send(&state, credit(300)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "armazenamento_indisponivel");
+        let (status, _) = send(&state, get("/readyz")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        backend.down.store(false, Ordering::SeqCst);
+        assert!(writer.ping().await.is_ok());
+        let (status, _) = send(&state, credit(300)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = send(&state, get("/clientes/1/extrato")).await;
+        assert!(body.get("stale").is_none());
+        assert_eq!(body["saldo"]["total"], 1_000);
+        writer.drain(Duration::from_secs(1)).await;
+        assert_eq!(backend.stored.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_refused_write_is_retried_until_it_lands() {
+        let backend = Arc::new(Flaky::default());
+        let (state, writer) = stored(backend.clone());
+        backend.down.store(true, Ordering::SeqCst);
+        // Accepted before anything noticed the outage; it must not be lost.
+        let (status, _) = send(&state, credit(700)).await;
+        assert_eq!(status, StatusCode::OK);
+        while writer.is_available() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let (status, body) = send(&state, get("/readyz")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let report = &body["dependencias"]["armazenamento"];
+        assert_eq!(report["status"], "falha");
+        assert_eq!(report["pendentes"], 1);
+        let (status, _) = send(&state, credit(300)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        // Well past what a bounded number of retries would have waited.
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(writer.pending(), 1);
+        backend.down.store(false, Ordering::SeqCst);
+        assert_eq!(writer.drain(Duration::from_secs(5)).await, 0);
+        assert!(writer.is_available());
+        let stored = backend.stored.lock().unwrap().clone();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].1.value, 700);
+        let (status, body) = send(&state, get("/readyz")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+
+    #[tokio::test]
+    async fn account_rows_and_transactions_survive_a_restart() {
+        let backend = Arc::new(Flaky::default());
+        let (state, writer) = stored(backend.clone());
+        send(&state, credit(700)).await;
+        let hold = json!({"valor": 300, "descricao": "hotel"});
+        let (status, _) = send(&state, post("/clientes/1/reservas", hold)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        send(&state, credit(50)).await;
+        assert_eq!(writer.drain(Duration::from_secs(1)).await, 0);
+        // An account opened after startup, with only its row stored.
+        let opened = AccountExport::of(AccountId(9), &Account::new(5_000, 10));
+        backend
+            .accounts
+            .lock()
+            .unwrap()
+            .insert(AccountId(9), opened);
+
+        let config = Config::default();
+        let mut accounts = seed_accounts(&config.accounts, &config).unwrap();
+        load(backend.as_ref(), &mut accounts, &config)
+            .await
+            .unwrap();
+        let account = accounts.remove(&AccountId(1)).unwrap().into_inner();
+        assert_eq!(account.balance, 750);
+        assert_eq!(account.holds.outstanding(START), 300);
+        assert_eq!(account.transactions.iter().count(), 2);
+        let opened = accounts.remove(&AccountId(9)).unwrap().into_inner();
+        assert_eq!(opened.limit, 5_000);
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, StatusCode},
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{config::Config, error::AppError, validation::FieldError, AppState};
+
+/// Top-level field names a request body may carry.
+pub trait KnownFields {
+    const FIELDS: &'static [&'static str];
+
+    /// What is wrong with each field of a `body` that did not deserialize;
+    /// empty falls back to serde's one message.
+    fn field_errors(_body: &Value, _config: &Config) -> Vec<FieldError> {
+        Vec::new()
+    }
+}
+
+/// `Json<T>` that, with `BANK_STRICT_JSON`, answers `400` naming the first
+/// field `T` does not know instead of letting serde drop it, and that
+/// answers a body of the wrong shape with every field at fault rather than
+/// serde's first complaint. A body that is not JSON at all is still `400`.
+pub struct CheckedJson<T>(pub T);
+
+#[async_trait]
+impl<T> FromRequest<Arc<AppState>> for CheckedJson<T>
+where
+    T: DeserializeOwned + KnownFields,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &Arc<AppState>) -> Result<Self, AppError> {
+        if !json_content_type(request.headers()) {
+            return Err(AppError::MalformedJson {
+                status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                detail: "Expected request with `Content-Type: application/json`".to_string(),
+            });
+        }
+        let bytes = Bytes::from_request(request, state)
+            .await
+            .map_err(|rejection| AppError::MalformedJson {
+                status: rejection.status(),
+                detail: rejection.body_text(),
+            })?;
+
+        if state.config.strict_json {
+            if let Ok(body) = serde_json::from_slice::<Value>(&bytes) {
+                unknown_field::<T>(&body)?;
+            }
+        }
+        // The whole body in one go first: a valid one never pays for the
+        // field-by-field pass.
+        let err = match serde_json::from_slice::<T>(&bytes) {
+            Ok(value) => return Ok(Self(value)),
+            Err(err) => err,
+        };
+        let malformed = |status| AppError::MalformedJson {
+            status,
+            detail: format!("Failed to deserialize the JSON body into the target type: {err}"),
+        };
+        if err.is_syntax() || err.is_eof() {
+            return Err(AppError::MalformedJson {
+                status: StatusCode::BAD_REQUEST,
+                detail: format!("Failed to parse the request body as JSON: {err}"),
+            });
+        }
+        let errors = serde_json::from_slice::<Value>(&bytes)
+            .ok()
+            .filter(Value::is_object)
+            .map(|body| T::field_errors(&body, &state.config))
+            .unwrap_or_default();
+        match errors.is_empty() {
+            true => Err(malformed(StatusCode::UNPROCESSABLE_ENTITY)),
+            false => Err(AppError::Fields(errors)),
+        }
+    }
+}
+
+/// `CheckedJson` for a value already parsed, such as one entry of a batch
+/// body: the same strict-mode check and the same per-field errors.
+pub(crate) fn from_value<T>(value: &Value, config: &Config) -> Result<T, AppError>
+where
+    T: DeserializeOwned + KnownFields,
+{
+    if config.strict_json {
+        unknown_field::<T>(value)?;
+    }
+    let err = match T::deserialize(value) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+    let errors = match value.is_object() {
+        true => T::field_errors(value, config),
+        false => Vec::new(),
+    };
+    match errors.is_empty() {
+        true => Err(AppError::MalformedJson {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            detail: format!("Failed to deserialize the JSON body into the target type: {err}"),
+        }),
+        false => Err(AppError::Fields(errors)),
+    }
+}
+
+/// The first top-level field of `body` that `T` does not know.
+fn unknown_field<T: KnownFields>(body: &Value) -> Result<(), AppError> {
+    match body
+        .as_object()
+        .and_then(|fields| fields.keys().find(|key| !T::FIELDS.contains(&key.as_str())))
+    {
+        Some(field) => Err(AppError::UnknownField(field.clone())),
+        None => Ok(()),
+    }
+}
+
+/// `application/json` or any `application/*+json`, as `Json` accepts.
+fn json_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    let essence = essence.to_ascii_lowercase();
+    essence == "application/json"
+        || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
@@ -0,0 +1,75 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use axum::{
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+
+/// `BANK_TLS_CERT` and `BANK_TLS_KEY`, PEM files read once at startup, for
+/// running without a reverse proxy in front. Needs the `tls` feature.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// `BANK_TLS_REDIRECT_BIND`: a plain HTTP listener sending everything
+    /// to the `https://` equivalent.
+    pub redirect: Option<SocketAddr>,
+}
+
+/// Serves `app` over TLS at `bind` until `shutdown` resolves, then gives
+/// requests already running `grace` to finish. ALPN offers `h2` before
+/// `http/1.1`, so clients that can get HTTP/2.
+#[cfg(feature = "tls")]
+pub async fn serve(
+    tls: &TlsConfig,
+    bind: SocketAddr,
+    app: axum::extract::connect_info::IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+    grace: std::time::Duration,
+) -> std::io::Result<()> {
+    let rustls = axum_server::tls_rustls::RustlsConfig::from_pem_file(&tls.cert, &tls.key).await?;
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(Some(grace));
+        }
+    });
+    axum_server::bind_rustls(bind, rustls)
+        .handle(handle)
+        .serve(app)
+        .await
+}
+
+/// The `BANK_TLS_REDIRECT_BIND` listener: `308` to the same path on
+/// `https://`, at `port` unless that is the default. A `308` rather than a
+/// `301` so a `POST` stays a `POST`.
+pub async fn redirect(bind: SocketAddr, port: u16) {
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .unwrap_or_else(|err| panic!("failed to bind {bind}: {err}"));
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        to_https(&headers, &uri, port)
+    });
+    if let Err(err) = axum::serve(listener, app).await {
+        tracing::error!(error = %err, "tls redirect listener failed");
+    }
+}
+
+fn to_https(headers: &HeaderMap, uri: &Uri, port: u16) -> Response {
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let location = match port {
+        443 => format!("https://{}{path}", host.host()),
+        port => format!("https://{}:{port}{path}", host.host()),
+    };
+    Redirect::permanent(&location).into_response()
+}
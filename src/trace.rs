@@ -0,0 +1,184 @@
+#[cfg(feature = "json-log")]
+use axum::extract::Request;
+#[cfg(feature = "json-log")]
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing::Level;
+#[cfg(feature = "json-log")]
+use tracing::Span;
+
+#[cfg(feature = "json-log")]
+use crate::correlation;
+use crate::registry::AccountId;
+
+/// Installs tracing-subscriber's JSON formatter on stderr at
+/// `BANK_LOG_LEVEL`, or at `WARN` when it is unset so warnings and errors
+/// still get out while request spans stay disabled at their callsites. Only
+/// this crate's events and `tower_http`'s are kept. Without the `json-log`
+/// feature nothing is installed and every event is dropped.
+#[cfg(feature = "json-log")]
+pub fn init(level: Option<Level>) {
+    use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+    let level = level.unwrap_or(Level::WARN);
+    let targets = Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_target("tower_http", level);
+    let json = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(std::io::stderr);
+    let _ = tracing_subscriber::registry()
+        .with(json)
+        .with(targets)
+        .try_init();
+}
+
+#[cfg(not(feature = "json-log"))]
+pub fn init(_level: Option<Level>) {}
+
+/// `TraceLayer` with the `request` span of `request_span`.
+#[cfg(feature = "json-log")]
+pub type RequestTrace = TraceLayer<SharedClassifier<ServerErrorsAsFailures>, fn(&Request) -> Span>;
+
+/// Runs each request inside a `request` span, so whatever it logs carries
+/// those fields, and closes it with one `INFO` event giving the status and
+/// the latency in microseconds.
+#[cfg(feature = "json-log")]
+pub fn requests() -> RequestTrace {
+    TraceLayer::new_for_http()
+        .make_span_with(request_span as fn(&Request) -> Span)
+        .on_response(
+            DefaultOnResponse::new()
+                .level(Level::INFO)
+                .latency_unit(LatencyUnit::Micros),
+        )
+}
+
+/// Method, path, account id and correlation id; inside
+/// `correlation::propagate`, so the id is already known.
+#[cfg(feature = "json-log")]
+pub fn request_span(request: &Request) -> Span {
+    let path = request.uri().path();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %path,
+        account = account_of(path).map(|id| id.0),
+        correlation = correlation::current().as_deref(),
+    )
+}
+
+/// The `:id` of a `/clientes/:id/...` path (after `checksum::verify`).
+pub fn account_of(path: &str) -> Option<AccountId> {
+    path.strip_prefix("/clientes/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(all(test, feature = "json-log"))]
+mod tests {
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use axum::{body::Body, http::Request};
+    use serde_json::{Map, Value};
+    use tower::ServiceExt;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    use crate::{correlation::HEADER, http, testing::bank, Config};
+
+    /// Collects the fields a span opened with.
+    struct Fields<'a>(&'a mut Map<String, Value>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().into(), format!("{value:?}").into());
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name().into(), value.into());
+        }
+    }
+
+    /// Keeps the fields each `request` span opened with.
+    #[derive(Default)]
+    struct Requests {
+        next_id: AtomicU64,
+        opened: Arc<Mutex<Vec<Map<String, Value>>>>,
+    }
+
+    impl Subscriber for Requests {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            if attributes.metadata().name() == "request" {
+                let mut fields = Map::new();
+                attributes.record(&mut Fields(&mut fields));
+                self.opened.lock().unwrap().push(fields);
+            }
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn the_correlation_id_is_echoed_and_on_the_request_span() {
+        let requests = Requests::default();
+        let opened = requests.opened.clone();
+        let _default = tracing::subscriber::set_default(requests);
+        let (state, _) = bank(Config::default());
+
+        let request = Request::get("/clientes/1/extrato")
+            .header(HEADER, "pedido-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = http::router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[HEADER], "pedido-42");
+
+        // Without one a fresh id is minted, and the span has that one.
+        let request = Request::get("/clientes/1/extrato")
+            .body(Body::empty())
+            .unwrap();
+        let response = http::router(state).oneshot(request).await.unwrap();
+        let minted = response.headers()[HEADER].to_str().unwrap().to_string();
+        assert_eq!(minted.len(), 26);
+
+        let opened = opened.lock().unwrap();
+        assert_eq!(opened.len(), 2);
+        assert_eq!(opened[0]["correlation"], "pedido-42");
+        assert_eq!(opened[0]["account"], 1);
+        assert_eq!(opened[1]["correlation"], minted.as_str());
+    }
+}
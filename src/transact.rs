@@ -0,0 +1,145 @@
+use std::fmt;
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    currency::{Currency, Formatter},
+    ulid::Ulid,
+    Transaction,
+};
+
+pub const OUT_OF_RANGE: &str = "valor fora do intervalo suportado";
+
+/// What `Account::transact` recorded: the transaction with its assigned
+/// `id`, `seq` and `create_at`, and the account right after it. `balance`
+/// is in the transaction's currency.
+pub struct TransactResult {
+    pub transaction: Transaction,
+    pub balance: i64,
+    pub limit: i64,
+    /// The account's `versao` after it, for a later `If-Match`.
+    pub version: u64,
+    /// Charged by a `Soft` limit policy when the debit went past the limit;
+    /// `balance` is after it.
+    pub fee: Option<Transaction>,
+}
+
+impl TransactResult {
+    /// What the account recorded, in order: the transaction, then its fee.
+    pub fn recorded(&self) -> impl Iterator<Item = &Transaction> {
+        std::iter::once(&self.transaction).chain(&self.fee)
+    }
+}
+
+/// Why `Account::transact` refused a transaction. Serialized only inside
+/// snapshots, with a failed schedule entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactError {
+    /// Overdraft disabled and the balance net of holds does not cover it.
+    InsufficientBalance { needed: i64, available: i64 },
+    /// The debit would take the balance past the (effective) limit.
+    InsufficientLimit { needed: i64, available: i64 },
+    /// The day's debits would exceed `daily_debit_limit`.
+    DailyLimitExceeded { limit: i64, debited: i64 },
+    /// A single debit above the account's `velocity::Rule::MaxDebit`.
+    DebitTooLarge { max: i64 },
+    /// The debits of the trailing window would exceed its rolling limit.
+    RollingLimitExceeded { limit: i64, debited: i64 },
+    /// Inside `min_interval` of the previous transaction.
+    TooSoon,
+    /// Repeats `previous` within `dedup_window`.
+    Duplicate { previous: Ulid },
+    /// Closed through `DELETE /clientes/:id`.
+    AccountClosed,
+    /// The account's actor stopped before answering.
+    QueueClosed,
+    /// `moeda` names a currency the account does not hold.
+    CurrencyNotHeld { currency: Currency },
+    /// A debit in a non-base currency beyond that currency's balance.
+    InsufficientCurrencyBalance {
+        currency: Currency,
+        needed: i64,
+        available: i64,
+    },
+    /// `If-Match` named a version the account has since moved past.
+    VersionMismatch { current: u64 },
+    /// The balance would leave the range of `i64`.
+    OutOfRange,
+}
+
+impl TransactError {
+    /// Portuguese reason, also the key `i18n` translates from and what the
+    /// rejection ring, schedules and webhooks report.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::InsufficientBalance { .. } | Self::InsufficientCurrencyBalance { .. } => {
+                "Saldo insuficiente"
+            }
+            Self::CurrencyNotHeld { .. } => "moeda nao mantida pela conta",
+            Self::InsufficientLimit { .. } => "Limite insuficiente",
+            Self::DailyLimitExceeded { .. } => "limite diario excedido",
+            Self::DebitTooLarge { .. } => "valor acima do maximo por transacao",
+            Self::RollingLimitExceeded { .. } => "limite da janela movel excedido",
+            Self::TooSoon => "intervalo minimo entre transacoes",
+            Self::Duplicate { .. } => "transacao duplicada",
+            Self::AccountClosed => "conta encerrada",
+            Self::QueueClosed => "fila da conta encerrada",
+            Self::VersionMismatch { .. } => "conta alterada desde a versao informada",
+            Self::OutOfRange => OUT_OF_RANGE,
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::TooSoon => StatusCode::TOO_MANY_REQUESTS,
+            Self::Duplicate { .. } | Self::VersionMismatch { .. } => StatusCode::CONFLICT,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    /// Figures behind the refusal, merged into the error body.
+    pub fn details(self, formatter: Formatter) -> Value {
+        match self {
+            Self::InsufficientBalance { needed, available }
+            | Self::InsufficientLimit { needed, available } => json!({
+                "necessario": formatter.amount(needed),
+                "disponivel": formatter.amount(available),
+            }),
+            Self::DailyLimitExceeded { limit, debited } => json!({
+                "limite_diario": formatter.amount(limit),
+                "debitado_hoje": formatter.amount(debited),
+            }),
+            Self::DebitTooLarge { max } => json!({ "maximo": formatter.amount(max) }),
+            Self::RollingLimitExceeded { limit, debited } => json!({
+                "limite_janela": formatter.amount(limit),
+                "debitado_na_janela": formatter.amount(debited),
+            }),
+            Self::InsufficientCurrencyBalance {
+                currency,
+                needed,
+                available,
+            } => {
+                let formatter = formatter.in_currency(currency);
+                json!({
+                    "moeda": currency.code(),
+                    "necessario": formatter.amount(needed),
+                    "disponivel": formatter.amount(available),
+                })
+            }
+            Self::CurrencyNotHeld { currency } => json!({ "moeda": currency.code() }),
+            Self::Duplicate { previous } => json!({ "transacao_anterior": previous }),
+            Self::VersionMismatch { current } => json!({ "versao": current }),
+            Self::TooSoon | Self::AccountClosed | Self::QueueClosed | Self::OutOfRange => {
+                json!({})
+            }
+        }
+    }
+}
+
+impl fmt::Display for TransactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reason())
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    currency::AmountInput,
+    deadline::Deadline,
+    error::{self, AppError, JsonBody},
+    precondition,
+    registry::AccountId,
+    ulid::Ulid,
+    AppState, Description, TransactionRequest, TransactionType,
+};
+
+/// Ties the debit and the credit of one transfer together: both carry the
+/// same `id`, each naming the other side's account.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TransferLink {
+    pub id: Ulid,
+    #[serde(rename = "conta")]
+    pub counterpart: AccountId,
+}
+
+#[derive(Deserialize)]
+pub struct TransferRequest {
+    #[serde(rename = "para")]
+    to: AccountId,
+    #[serde(rename = "valor")]
+    value: AmountInput,
+    #[serde(rename = "descricao")]
+    description: Description,
+}
+
+/// Debits `:id` and credits `para` as one step. Both accounts are locked in
+/// id order so opposite transfers cannot deadlock. Both halves are checked
+/// before either is applied, so a refused credit never leaves the debit
+/// behind; the refusal is recorded on the account that refused it, with
+/// nothing applied to either.
+pub async fn create(
+    Path(account_id): Path<AccountId>,
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    headers: HeaderMap,
+    JsonBody(request): JsonBody<TransferRequest>,
+) -> Result<Json<Value>, AppError> {
+    // Conditions the debited account only; the credited one is not the
+    // caller's to have read.
+    let expected = precondition::expected_version(&headers).map_err(AppError::BadRequest)?;
+    if request.to == account_id {
+        return Err(AppError::Invalid(error::SAME_ACCOUNT));
+    }
+    let now = state.clock.now();
+    let mut debit = TransactionRequest {
+        value: request.value,
+        kind: TransactionType::Debit,
+        description: request.description,
+        currency: None,
+        scheduled_for: None,
+        category: None,
+    }
+    .into_transaction(&state.config, now)
+    .map_err(AppError::Invalid)?;
+    let (Some(from), Some(to)) = (
+        state.accounts.get(&account_id),
+        state.accounts.get(&request.to),
+    ) else {
+        return Err(AppError::UnknownAccount);
+    };
+
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    let (mut from, mut to) = state
+        .write_pair((account_id, &from), (request.to, &to), deadline)
+        .await?;
+
+    let id = Ulid::next_after(Ulid::NIL, now);
+    let mut credit = debit.clone();
+    credit.kind = TransactionType::Credit;
+    debit.transfer = Some(TransferLink {
+        id,
+        counterpart: request.to,
+    });
+    credit.transfer = Some(TransferLink {
+        id,
+        counterpart: account_id,
+    });
+
+    let refused = |error| AppError::Transact(error, state.config.formatter());
+    from.check_version(expected).map_err(refused)?;
+    for (account, account_id, transaction) in [
+        (&mut from, account_id, &debit),
+        (&mut to, request.to, &credit),
+    ] {
+        if let Err(error) = account.check(transaction, now) {
+            account.reject(transaction.clone(), error, now);
+            state.rejected(account_id, transaction, error);
+            return Err(refused(error));
+        }
+    }
+    let savepoints = state
+        .wal
+        .is_some()
+        .then(|| (from.savepoint(1), to.savepoint(1)));
+    let debited = from.transact(debit, now).map_err(refused)?;
+    let credited = to.transact(credit, now).map_err(refused)?;
+    let logged = state.log(
+        debited
+            .recorded()
+            .map(|transaction| (account_id, transaction))
+            .chain([(request.to, &credited.transaction)]),
+    );
+    if let Err(error) = logged {
+        if let Some((debited, credited)) = savepoints {
+            from.roll_back(debited);
+            to.roll_back(credited);
+        }
+        return Err(error);
+    }
+
+    state.accepted_result(account_id, &debited);
+    state.accepted(request.to, &credited.transaction);
+    state.audit.record(json!({
+        "evento": "transferencia",
+        "id": id,
+        "de": account_id,
+        "para": request.to,
+        "valor": debited.transaction.value,
+        "em": state.config.timestamp(now),
+    }));
+
+    let formatter = state.config.formatter();
+    Ok(Json(json!({
+        "id": id,
+        "valor": formatter.amount(debited.transaction.value),
+        "de": {
+            "account": account_id,
+            "id": debited.transaction.id,
+            "saldo": formatter.amount(debited.balance),
+            "limite": formatter.amount(debited.limit),
+            "versao": debited.version,
+        },
+        "para": {
+            "account": request.to,
+            "id": credited.transaction.id,
+            "saldo": formatter.amount(credited.balance),
+            "limite": formatter.amount(credited.limit),
+            "versao": credited.version,
+        },
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::{
+        config::Config,
+        registry::AccountId,
+        testing::{bank, get, post, send},
+    };
+
+    #[tokio::test]
+    async fn a_transfer_gives_up_on_a_held_lock() {
+        let (state, _) = bank(Config {
+            lock_timeout: Some(Duration::from_millis(10)),
+            ..Config::default()
+        });
+        let entry = state.accounts.get(&AccountId(1)).unwrap();
+        let held = entry.account.write().await;
+
+        let body = json!({"para": 1, "valor": 100, "descricao": "bloqueio"});
+        let (status, _) = send(&state, post("/clientes/2/transferencias", body)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(entry.lock_timeouts.load(Ordering::Relaxed), 1);
+
+        drop(held);
+        let (_, body) = send(&state, get("/clientes/2/extrato")).await;
+        assert_eq!(body["saldo"]["total"], 0);
+    }
+}
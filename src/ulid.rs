@@ -0,0 +1,137 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use time::OffsetDateTime;
+
+/// Crockford's base32, as used by the ULID spec.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RANDOM_BITS: u32 = 80;
+
+/// 48-bit millisecond timestamp followed by 80 random bits, rendered as 26
+/// base32 characters, so string order matches numeric and creation order.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Ulid(u128);
+
+impl Ulid {
+    /// Placeholder for transactions not yet accepted.
+    pub const NIL: Ulid = Ulid(0);
+
+    pub fn is_nil(self) -> bool {
+        self == Self::NIL
+    }
+
+    fn timestamp_ms(self) -> u64 {
+        (self.0 >> RANDOM_BITS) as u64
+    }
+
+    /// A fresh id for `now`, strictly greater than `previous` even when the
+    /// clock stands still or steps back: those cases increment `previous`.
+    pub fn next_after(previous: Ulid, now: OffsetDateTime) -> Ulid {
+        let ms = (now.unix_timestamp_nanos() / 1_000_000).clamp(0, (1 << 48) - 1) as u64;
+        if !previous.is_nil() && ms <= previous.timestamp_ms() {
+            return Ulid(previous.0 + 1);
+        }
+        Ulid((ms as u128) << RANDOM_BITS | random_bits())
+    }
+}
+
+/// 80 bits from std's per-process random hasher keys; ids need to be
+/// unpredictable enough to avoid collisions, not cryptographically secure.
+fn random_bits() -> u128 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let draw = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.finish() as u128
+    };
+    (draw() << 64 | draw()) & ((1 << RANDOM_BITS) - 1)
+}
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = [0u8; 26];
+        for (index, byte) in out.iter_mut().enumerate() {
+            let shift = 5 * (25 - index);
+            *byte = ALPHABET[(self.0 >> shift) as usize & 0x1f];
+        }
+        f.write_str(std::str::from_utf8(&out).unwrap())
+    }
+}
+
+impl FromStr for Ulid {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 26 {
+            return Err("ulid invalido");
+        }
+        let mut value: u128 = 0;
+        for (index, byte) in s.bytes().enumerate() {
+            let digit = ALPHABET
+                .iter()
+                .position(|c| *c == byte.to_ascii_uppercase())
+                .ok_or("ulid invalido")?;
+            // 26 * 5 = 130 bits: the leading character may only carry 3.
+            if index == 0 && digit > 7 {
+                return Err("ulid invalido");
+            }
+            value = value << 5 | digit as u128;
+        }
+        Ok(Ulid(value))
+    }
+}
+
+impl Serialize for Ulid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ulid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use super::Ulid;
+
+    #[test]
+    fn ids_increase_within_a_millisecond_and_when_the_clock_steps_back() {
+        let now = datetime!(2024-03-01 12:00 UTC);
+        let first = Ulid::next_after(Ulid::NIL, now);
+        let same_ms = Ulid::next_after(first, now);
+        let earlier = Ulid::next_after(same_ms, now - Duration::seconds(5));
+        assert!(first < same_ms && same_ms < earlier);
+        assert_eq!(earlier.0, first.0 + 2);
+
+        let later = Ulid::next_after(earlier, now + Duration::milliseconds(1));
+        assert!(later > earlier);
+        assert_eq!(later.timestamp_ms(), first.timestamp_ms() + 1);
+        // String order is id order.
+        assert!(first.to_string() < same_ms.to_string());
+        assert!(earlier.to_string() < later.to_string());
+    }
+
+    #[test]
+    fn text_form_round_trips() {
+        let id = Ulid::next_after(Ulid::NIL, datetime!(2024-03-01 12:00 UTC));
+        let text = id.to_string();
+        assert_eq!(text.len(), 26);
+        assert_eq!(text.parse::<Ulid>(), Ok(id));
+        assert_eq!(text.to_ascii_lowercase().parse::<Ulid>(), Ok(id));
+        assert!("8ZZZZZZZZZZZZZZZZZZZZZZZZZ".parse::<Ulid>().is_err());
+        assert!("0123".parse::<Ulid>().is_err());
+        assert!("0000000000000000000000000U".parse::<Ulid>().is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use axum::http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    currency,
+    i18n::{self, Language},
+};
+
+pub const REQUIRED: &str = "campo obrigatorio";
+pub const NOT_POSITIVE: &str = "valor deve ser maior que zero";
+pub const ABOVE_MAX: &str = "valor acima do maximo por transacao";
+pub const AMOUNT_TYPE: &str = "valor deve ser um inteiro em centavos ou um decimal em texto";
+pub const KIND: &str = "tipo deve ser C ou D";
+pub const DESCRIPTION_TYPE: &str = "descricao deve ser texto";
+pub const CURRENCY_TYPE: &str = "moeda deve ser um codigo ISO em texto";
+pub const TIMESTAMP: &str = "realizada_em deve ser uma data RFC 3339";
+
+/// One field of a request body refused, and why.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub reason: &'static str,
+}
+
+/// `400` when any amount is not representable at all, like `prepare` has
+/// always answered for one; `422` for everything else.
+pub fn status(errors: &[FieldError]) -> StatusCode {
+    match errors
+        .iter()
+        .any(|error| matches!(error.reason, currency::OUT_OF_RANGE | currency::NOT_INTEGER))
+    {
+        true => StatusCode::BAD_REQUEST,
+        false => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+/// The `campos` of the error body: each field with its own code and message.
+pub fn render(errors: &[FieldError], language: Language) -> Value {
+    errors
+        .iter()
+        .map(|error| {
+            let mut entry = i18n::body(error.reason, language);
+            entry["campo"] = error.field.into();
+            entry
+        })
+        .collect()
+}
+
+/// Reads `name` out of `body` on its own, so one bad field does not hide the
+/// others. `None` (with the error pushed) when it is missing but `required`,
+/// or is not a `T`; `Some(None)` when it is absent and optional.
+pub fn field<T: DeserializeOwned>(
+    body: &Value,
+    name: &'static str,
+    required: bool,
+    wrong: &'static str,
+    errors: &mut Vec<FieldError>,
+) -> Option<Option<T>> {
+    match body.get(name) {
+        None | Some(Value::Null) if required => {
+            errors.push(FieldError {
+                field: name,
+                reason: REQUIRED,
+            });
+            None
+        }
+        None | Some(Value::Null) => Some(None),
+        Some(value) => match T::deserialize(value) {
+            Ok(value) => Some(Some(value)),
+            Err(_) => {
+                errors.push(FieldError {
+                    field: name,
+                    reason: wrong,
+                });
+                None
+            }
+        },
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::VecDeque, time::Duration};
+
+use time::OffsetDateTime;
+
+use crate::transact::TransactError;
+
+/// A per-account check on debits, on top of the limit and the calendar-day
+/// `daily_debit_limit`.
+#[derive(Clone, Copy, Debug)]
+pub enum Rule {
+    /// No single debit above this.
+    MaxDebit(i64),
+    /// Debits inside any trailing `window` may not sum past `limit`.
+    Rolling { window: Duration, limit: i64 },
+}
+
+/// The account's rules, evaluated in order by `Account::check_debit`, with
+/// the recent debits the rolling ones need. The debits are in memory only,
+/// so a restart forgets them, like the daily total.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+    /// Accepted debits, oldest first, trimmed to the longest window.
+    debits: VecDeque<(OffsetDateTime, i64)>,
+    /// Debits ever pushed onto `debits`; see `forget_since`.
+    recorded: u64,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            debits: VecDeque::new(),
+            recorded: 0,
+        }
+    }
+
+    pub fn check(&self, value: i64, now: OffsetDateTime) -> Result<(), TransactError> {
+        for rule in &self.rules {
+            match *rule {
+                Rule::MaxDebit(max) if value > max => {
+                    return Err(TransactError::DebitTooLarge { max });
+                }
+                Rule::Rolling { window, limit } => {
+                    let debited = self.debited_within(window, now);
+                    if debited + value > limit {
+                        return Err(TransactError::RollingLimitExceeded { limit, debited });
+                    }
+                }
+                Rule::MaxDebit(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Largest debit every rule would let through at `now`.
+    pub fn affordable(&self, now: OffsetDateTime) -> i64 {
+        self.rules
+            .iter()
+            .map(|rule| match *rule {
+                Rule::MaxDebit(max) => max,
+                Rule::Rolling { window, limit } => limit - self.debited_within(window, now),
+            })
+            .min()
+            .unwrap_or(i64::MAX)
+    }
+
+    pub fn record(&mut self, value: i64, now: OffsetDateTime) {
+        let Some(longest) = self
+            .rules
+            .iter()
+            .filter_map(|rule| match rule {
+                Rule::Rolling { window, .. } => Some(*window),
+                Rule::MaxDebit(_) => None,
+            })
+            .max()
+        else {
+            return;
+        };
+        while self
+            .debits
+            .front()
+            .is_some_and(|(at, _)| now - *at >= longest)
+        {
+            self.debits.pop_front();
+        }
+        self.debits.push_back((now, value));
+        self.recorded += 1;
+    }
+
+    /// A mark for `forget_since`.
+    pub fn recorded(&self) -> u64 {
+        self.recorded
+    }
+
+    /// Takes back the debits recorded since `recorded` returned `mark`.
+    /// Debits trimmed in the meantime stay gone: they were already outside
+    /// every window.
+    pub fn forget_since(&mut self, mark: u64) {
+        for _ in mark..self.recorded {
+            self.debits.pop_back();
+        }
+        self.recorded = mark;
+    }
+
+    fn debited_within(&self, window: Duration, now: OffsetDateTime) -> i64 {
+        self.debits
+            .iter()
+            .filter(|(at, _)| now - *at < window)
+            .map(|(_, value)| value)
+            .sum()
+    }
+}
@@ -0,0 +1,511 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use futures_util::future;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use time::format_description::well_known::Rfc3339;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+    time::{timeout, Instant},
+};
+
+use crate::{
+    clock::Clock,
+    correlation,
+    error::{AppError, JsonBody},
+    observer::{AccountObserver, Outcome},
+    registry::AccountId,
+    render_transaction, AppState, Config, Transaction,
+};
+
+pub const DISABLED: &str = "webhooks desativados";
+pub const NOT_FOUND: &str = "webhook nao encontrado";
+pub const UNSUPPORTED: &str = "apenas webhooks http:// sao suportados";
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    /// Registered at startup for every account; more come from
+    /// `POST /webhooks`. Only plain `http://` endpoints are supported.
+    pub url: Option<String>,
+    pub batch_size: usize,
+    pub batch_window: Duration,
+    pub max_retries: u32,
+    /// Dead letters are also appended here as JSON lines when set.
+    pub dlq_file: Option<PathBuf>,
+}
+
+/// A callback for every outcome on `account`, or on all of them.
+#[derive(Clone, Serialize)]
+pub struct Subscription {
+    id: u64,
+    url: String,
+    #[serde(rename = "conta", skip_serializing_if = "Option::is_none")]
+    account: Option<AccountId>,
+}
+
+/// In memory only: registrations other than `BANK_WEBHOOK_URL` do not
+/// survive a restart.
+#[derive(Default)]
+struct Subscriptions {
+    next_id: u64,
+    entries: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    fn add(&mut self, url: String, account: Option<AccountId>) -> Subscription {
+        self.next_id += 1;
+        let subscription = Subscription {
+            id: self.next_id,
+            url,
+            account,
+        };
+        self.entries.push(subscription.clone());
+        subscription
+    }
+
+    fn targets(&self, account_id: AccountId) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.account.is_none_or(|id| id == account_id))
+            .map(|entry| entry.url.as_str())
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeadLetter {
+    url: String,
+    #[serde(rename = "eventos")]
+    events: Vec<Value>,
+    #[serde(rename = "erro")]
+    error: String,
+    #[serde(rename = "em")]
+    failed_at: String,
+}
+
+/// Batches transaction outcomes and posts them off the request path, one
+/// batch per subscribed URL; batches that exhaust their retries land in the
+/// dead-letter queue.
+pub struct Dispatcher {
+    config: WebhookConfig,
+    /// Amount and timestamp formats for the rendered transactions.
+    rendering: Config,
+    subscriptions: Mutex<Subscriptions>,
+    /// Each event paired with the URL it goes to, resolved when queued.
+    queue: mpsc::UnboundedSender<(String, Value)>,
+    dead_letters: Mutex<Vec<DeadLetter>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AccountObserver for Dispatcher {
+    fn on_transaction(&self, account_id: AccountId, transaction: &Transaction, outcome: &Outcome) {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut targets = subscriptions.targets(account_id).peekable();
+        if targets.peek().is_none() {
+            return;
+        }
+        let mut event = json!({
+            "account": account_id,
+            "resultado": "aceita",
+            "transacao": render_transaction(transaction, &self.rendering),
+        });
+        if let Some(id) = correlation::current() {
+            event["correlacao"] = id.as_ref().into();
+        }
+        if let Err(error) = outcome {
+            event["resultado"] = "rejeitada".into();
+            event["motivo"] = error.reason().into();
+            event["detalhes"] = error.details(self.rendering.formatter());
+        }
+        for url in targets {
+            let _ = self.queue.send((url.to_string(), event.clone()));
+        }
+    }
+
+    /// The leader delivered it.
+    fn on_replicated(&self, _: AccountId, _: &Transaction) {}
+}
+
+impl Dispatcher {
+    pub fn spawn(config: WebhookConfig, rendering: Config, clock: Arc<dyn Clock>) -> Arc<Self> {
+        let (queue, inbox) = mpsc::unbounded_channel();
+        let mut subscriptions = Subscriptions::default();
+        if let Some(url) = &config.url {
+            subscriptions.add(url.clone(), None);
+        }
+        let dispatcher = Arc::new(Self {
+            config,
+            rendering,
+            subscriptions: Mutex::new(subscriptions),
+            queue,
+            dead_letters: Mutex::new(Vec::new()),
+            clock,
+        });
+        tokio::spawn(dispatcher.clone().run(inbox));
+        dispatcher
+    }
+
+    async fn run(self: Arc<Self>, mut inbox: mpsc::UnboundedReceiver<(String, Value)>) {
+        let mut batch = Vec::with_capacity(self.config.batch_size);
+        while inbox.recv_many(&mut batch, self.config.batch_size).await > 0 {
+            let deadline = Instant::now() + self.config.batch_window;
+            while batch.len() < self.config.batch_size {
+                match tokio::time::timeout_at(deadline, inbox.recv()).await {
+                    Ok(Some(event)) => batch.push(event),
+                    _ => break,
+                }
+            }
+            let mut by_url: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+            for (url, event) in batch.drain(..) {
+                by_url.entry(url).or_default().push(event);
+            }
+            // Endpoints retry side by side; the next batch waits for the
+            // slowest of them, which keeps each URL's events in order.
+            future::join_all(
+                by_url
+                    .into_iter()
+                    .map(|(url, events)| self.deliver(url, events)),
+            )
+            .await;
+        }
+    }
+
+    async fn deliver(&self, url: String, events: Vec<Value>) {
+        let body = Value::from(events.clone()).to_string();
+        // A batch can span requests: the header lists each distinct id once.
+        let mut ids: Vec<&str> = events
+            .iter()
+            .filter_map(|event| event["correlacao"].as_str())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let correlation = ids.join(", ");
+        let mut backoff = Duration::from_millis(100);
+        let mut attempt = 0;
+        loop {
+            match post(&url, body.as_bytes(), &correlation).await {
+                Ok(()) => return,
+                Err(error) if attempt >= self.config.max_retries => {
+                    self.dead_letter(url, events, error);
+                    return;
+                }
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+
+    fn dead_letter(&self, url: String, events: Vec<Value>, error: String) {
+        let letter = DeadLetter {
+            url,
+            events,
+            error,
+            failed_at: self.clock.now().format(&Rfc3339).unwrap(),
+        };
+        if let Some(path) = &self.config.dlq_file {
+            let line = format!("{}\n", serde_json::to_string(&letter).unwrap());
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()));
+            if let Err(err) = written {
+                tracing::error!(error = %err, line = line.trim_end(), "dead letter write failed");
+            }
+        }
+        self.dead_letters.lock().unwrap().push(letter);
+    }
+}
+
+/// Minimal HTTP/1.1 POST; any 2xx status counts as delivered.
+async fn post(url: &str, body: &[u8], correlation: &str) -> Result<(), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or("only http:// webhooks are supported")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(address).await?;
+        let correlation = if correlation.is_empty() {
+            String::new()
+        } else {
+            format!("X-Correlation-Id: {correlation}\r\n")
+        };
+        let head = format!(
+            "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{correlation}Connection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+
+        let mut status = [0u8; 12];
+        stream.read_exact(&mut status).await?;
+        Ok::<_, std::io::Error>(status)
+    };
+
+    let status = timeout(Duration::from_secs(5), exchange)
+        .await
+        .map_err(|_| "timeout".to_string())?
+        .map_err(|err| err.to_string())?;
+    match &status[9..10] {
+        b"2" => Ok(()),
+        _ => Err(format!("status {}", String::from_utf8_lossy(&status[9..]))),
+    }
+}
+
+pub async fn list_dead_letters(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<DeadLetter>>, AppError> {
+    let dispatcher = state
+        .webhooks
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let letters = dispatcher.dead_letters.lock().unwrap().clone();
+    Ok(Json(letters))
+}
+
+/// Re-enqueues every dead-lettered event for the URL it failed at, whether
+/// or not that is still subscribed; failures land back in the queue.
+pub async fn replay_dead_letters(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, AppError> {
+    let dispatcher = state
+        .webhooks
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let letters = std::mem::take(&mut *dispatcher.dead_letters.lock().unwrap());
+    let mut replayed = 0;
+    for letter in letters {
+        for event in letter.events {
+            let _ = dispatcher.queue.send((letter.url.clone(), event));
+            replayed += 1;
+        }
+    }
+    Ok(Json(json!({ "reenviados": replayed })))
+}
+
+#[derive(Deserialize)]
+pub struct Registration {
+    url: String,
+    /// Every account when absent.
+    #[serde(default, rename = "conta")]
+    account: Option<AccountId>,
+}
+
+/// `POST /webhooks`: from now on, outcomes on `conta` (or on every account)
+/// are posted to `url` too.
+pub async fn register(
+    State(state): State<Arc<AppState>>,
+    JsonBody(registration): JsonBody<Registration>,
+) -> Result<(StatusCode, Json<Subscription>), AppError> {
+    let dispatcher = state
+        .webhooks
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    if !registration.url.starts_with("http://") {
+        return Err(AppError::Invalid(UNSUPPORTED));
+    }
+    if let Some(id) = registration.account {
+        state.accounts.get(&id).ok_or(AppError::UnknownAccount)?;
+    }
+    let subscription = dispatcher
+        .subscriptions
+        .lock()
+        .unwrap()
+        .add(registration.url, registration.account);
+    state.audit.record(json!({
+        "evento": "webhook_registrado",
+        "webhook": subscription.id,
+        "url": subscription.url,
+        "account": subscription.account,
+        "em": state.config.timestamp(state.clock.now()),
+    }));
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+pub async fn list(State(state): State<Arc<AppState>>) -> Result<Json<Value>, AppError> {
+    let dispatcher = state
+        .webhooks
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let subscriptions = dispatcher.subscriptions.lock().unwrap().entries.clone();
+    Ok(Json(json!({ "webhooks": subscriptions })))
+}
+
+/// Events already queued for the URL are still delivered.
+pub async fn unregister(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, AppError> {
+    let dispatcher = state
+        .webhooks
+        .as_ref()
+        .ok_or(AppError::NotFound(DISABLED))?;
+    let mut subscriptions = dispatcher.subscriptions.lock().unwrap();
+    let index = subscriptions
+        .entries
+        .iter()
+        .position(|entry| entry.id == id)
+        .ok_or(AppError::NotFound(NOT_FOUND))?;
+    subscriptions.entries.remove(index);
+    drop(subscriptions);
+    state.audit.record(json!({
+        "evento": "webhook_removido",
+        "webhook": id,
+        "em": state.config.timestamp(state.clock.now()),
+    }));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{Dispatcher, WebhookConfig};
+    use crate::{
+        testing::{admin, admin_config, bank, get, post, send},
+        AppState,
+    };
+
+    /// An endpoint answering `500` until `up` is set, recording the bodies
+    /// it accepted.
+    struct Endpoint {
+        url: String,
+        up: Arc<AtomicBool>,
+        received: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    async fn endpoint() -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let up = Arc::new(AtomicBool::new(false));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (flag, bodies) = (up.clone(), received.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                let body = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    if body.len() >= length || read == 0 {
+                        break body.to_string();
+                    }
+                };
+                let status = if flag.load(Ordering::SeqCst) {
+                    bodies
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::from_str(&body).unwrap());
+                    "200 OK"
+                } else {
+                    "500 Internal Server Error"
+                };
+                let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        Endpoint { url, up, received }
+    }
+
+    fn with_webhooks(mut state: Arc<AppState>, url: &str) -> Arc<AppState> {
+        let config = WebhookConfig {
+            url: Some(url.to_string()),
+            batch_size: 10,
+            batch_window: std::time::Duration::from_millis(10),
+            max_retries: 1,
+            dlq_file: None,
+        };
+        let dispatcher = Dispatcher::spawn(config, state.config.clone(), state.clock.clone());
+        let state_mut = Arc::get_mut(&mut state).unwrap();
+        state_mut.observers.push(dispatcher.clone());
+        state_mut.webhooks = Some(dispatcher);
+        state
+    }
+
+    async fn eventually(mut done: impl FnMut() -> bool) {
+        for _ in 0..200 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("timed out");
+    }
+
+    #[tokio::test]
+    async fn failed_batches_land_in_the_dlq_and_replay() {
+        let endpoint = endpoint().await;
+        let (state, _) = bank(admin_config());
+        let state = with_webhooks(state, &endpoint.url);
+
+        let credit = json!({"valor": 100, "tipo": "C", "descricao": "webhook"});
+        let (status, _) = send(&state, post("/clientes/1/transacoes", credit)).await;
+        assert_eq!(status, StatusCode::OK);
+        let dispatcher = state.webhooks.clone().unwrap();
+        eventually(|| !dispatcher.dead_letters.lock().unwrap().is_empty()).await;
+
+        let (status, letters) = send(&state, admin(get("/webhooks/dlq"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(letters[0]["url"], endpoint.url);
+        assert_eq!(
+            letters[0]["eventos"][0]["transacao"]["descricao"],
+            "webhook"
+        );
+        assert!(endpoint.received.lock().unwrap().is_empty());
+
+        endpoint.up.store(true, Ordering::SeqCst);
+        let (_, replayed) = send(&state, admin(post("/webhooks/dlq/replay", json!({})))).await;
+        assert_eq!(replayed["reenviados"], 1);
+        eventually(|| !endpoint.received.lock().unwrap().is_empty()).await;
+        let received = endpoint.received.lock().unwrap();
+        assert_eq!(received[0][0]["resultado"], "aceita");
+        assert_eq!(received[0][0]["transacao"]["valor"], 100);
+        assert!(dispatcher.dead_letters.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+//! Accounts whose writes go through their actor queue instead of the lock.
+
+use futures_util::future;
+use rinha2024::{
+    config::Config,
+    domain::TransactionRequest,
+    service::{BankService, Options},
+    AccountId,
+};
+use serde_json::json;
+
+fn request(valor: i64, tipo: &str) -> TransactionRequest {
+    serde_json::from_value(json!({"valor": valor, "tipo": tipo, "descricao": "fila"})).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn queued_writes_preserve_the_balance() {
+    let service = BankService::in_memory(Config {
+        actor_accounts: vec![AccountId(1)],
+        ..Config::default()
+    })
+    .unwrap();
+
+    // 300 credits of 7 and 200 debits of 5, interleaved across tasks.
+    let writes = (0..500).map(|index| {
+        let service = service.clone();
+        tokio::spawn(async move {
+            let request = match index % 5 {
+                0 | 1 => request(5, "D"),
+                _ => request(7, "C"),
+            };
+            service
+                .transact(AccountId(1), request, Options::default())
+                .await
+                .unwrap()
+                .unwrap();
+        })
+    });
+    for write in future::join_all(writes).await {
+        write.unwrap();
+    }
+
+    let statement = service.statement(AccountId(1)).await.unwrap();
+    assert_eq!(statement["saldo"]["total"], 300 * 7 - 200 * 5);
+
+    // The lock path agrees on the same sequence.
+    let locked = BankService::in_memory(Config::default()).unwrap();
+    for index in 0..500 {
+        let request = match index % 5 {
+            0 | 1 => request(5, "D"),
+            _ => request(7, "C"),
+        };
+        locked
+            .transact(AccountId(1), request, Options::default())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let statement = locked.statement(AccountId(1)).await.unwrap();
+    assert_eq!(statement["saldo"]["total"], 300 * 7 - 200 * 5);
+}
@@ -0,0 +1,43 @@
+//! Whether a write is let through at all, before any account looks at it.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, admin_config, get, post, send, statement, transaction};
+use rinha2024::{config::Config, service::BankService};
+use serde_json::json;
+
+#[tokio::test]
+async fn maintenance_blocks_writes_but_not_reads() {
+    let service = BankService::in_memory(Config::default()).unwrap();
+    let app = service.router();
+    let (status, _) = send(&app, transaction(1, 100, "C", "antes")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    service.set_maintenance(true);
+    let (status, body) = send(&app, transaction(1, 100, "C", "durante")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["message"], "em manutencao");
+    let (status, body) = send(&app, statement(1)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"]["total"], 100);
+
+    service.set_maintenance(false);
+    let (status, body) = send(&app, transaction(1, 100, "C", "depois")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"], 200);
+}
+
+#[tokio::test]
+async fn maintenance_still_serves_admin_reads() {
+    let service = BankService::in_memory(admin_config()).unwrap();
+    let app = service.router();
+    service.set_maintenance(true);
+    for path in ["/relatorio.csv", "/clientes/1/export"] {
+        let (status, _) = send(&app, admin(get(path))).await;
+        assert_eq!(status, StatusCode::OK, "{path}");
+    }
+    let limit = json!({"para": 2, "valor": 1_000});
+    let (status, _) = send(&app, admin(post("/clientes/1/limite/transferir", limit))).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
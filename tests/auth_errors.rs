@@ -0,0 +1,42 @@
+//! `BANK_PRECISE_AUTH_ERRORS`: whether an unauthorized admin request is told
+//! `403`, or the same `404` an unknown account gets.
+
+mod common;
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use common::{admin, admin_config, app};
+use rinha2024::config::Config;
+use serde_json::json;
+
+fn update(account: u64) -> Request<Body> {
+    Request::patch(format!("/clientes/{account}"))
+        .header("content-type", "application/json")
+        .body(Body::from(json!({"limite": 1_000}).to_string()))
+        .unwrap()
+}
+
+async fn status(app: &axum::Router, request: Request<Body>) -> StatusCode {
+    common::send(app, request).await.0
+}
+
+#[tokio::test]
+async fn by_default_unauthorized_looks_like_unknown() {
+    let app = app(admin_config());
+    // Existing or not, the caller cannot tell.
+    assert_eq!(status(&app, update(1)).await, StatusCode::NOT_FOUND);
+    assert_eq!(status(&app, update(99)).await, StatusCode::NOT_FOUND);
+    assert_eq!(status(&app, admin(update(99))).await, StatusCode::NOT_FOUND);
+    assert_eq!(status(&app, admin(update(1))).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn precise_errors_tell_forbidden_from_unknown() {
+    let app = app(Config {
+        precise_auth_errors: true,
+        ..admin_config()
+    });
+    assert_eq!(status(&app, update(1)).await, StatusCode::FORBIDDEN);
+    assert_eq!(status(&app, update(99)).await, StatusCode::FORBIDDEN);
+    assert_eq!(status(&app, admin(update(99))).await, StatusCode::NOT_FOUND);
+    assert_eq!(status(&app, admin(update(1))).await, StatusCode::OK);
+}
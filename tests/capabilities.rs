@@ -0,0 +1,35 @@
+//! `GET /capabilities`: the enabled features, read off the config.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use common::{admin_config, app, get, send};
+use rinha2024::config::Config;
+use serde_json::json;
+
+#[tokio::test]
+async fn the_document_follows_the_config() {
+    let (status, defaults) = send(&app(Config::default()), get("/capabilities")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(defaults["autenticacao"]["admin"], false);
+    assert_eq!(defaults["limites"]["deduplicacao_ms"], json!(null));
+    assert_eq!(defaults["persistencia"]["wal"], false);
+    assert_eq!(defaults["persistencia"]["durabilidade"], json!(null));
+    assert_eq!(defaults["moeda"], "BRL");
+
+    let config = Config {
+        history_cap: 25,
+        dedup_window: Some(Duration::from_millis(1_500)),
+        precise_auth_errors: true,
+        ..admin_config()
+    };
+    let (_, body) = send(&app(config), get("/capabilities")).await;
+    assert_eq!(body["autenticacao"]["admin"], true);
+    assert_eq!(body["autenticacao"]["erros_precisos"], true);
+    assert_eq!(body["historico"]["capacidade"], 25);
+    assert_eq!(body["limites"]["deduplicacao_ms"], 1_500);
+    // Only flags: the token itself is nowhere in it.
+    assert!(!body.to_string().contains(common::ADMIN_TOKEN));
+}
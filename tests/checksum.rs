@@ -0,0 +1,60 @@
+//! `BANK_ACCOUNT_CHECKSUM`: account numbers in the path carry a trailing
+//! Luhn check digit.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, get, send};
+use rinha2024::config::Config;
+use serde_json::json;
+
+fn checked() -> Config {
+    Config {
+        account_checksum: true,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn a_valid_check_digit_reaches_the_account() {
+    let app = app(checked());
+    let (status, body) = send(
+        &app,
+        common::post(
+            "/clientes/18/transacoes",
+            json!({"valor": 100, "tipo": "C", "descricao": "luhn"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // The query string survives the rewrite.
+    let (status, body) = send(&app, get("/clientes/18/extrato?tipo=D")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["saldo"]["total"], 100);
+    assert_eq!(body["ultimas_transacoes"], json!([]));
+    let (status, _) = send(&app, get("/clientes/26/extrato")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_wrong_or_missing_check_digit_is_a_bad_request() {
+    let app = app(checked());
+    for path in [
+        "/clientes/17/extrato",
+        "/clientes/1/extrato",
+        "/clientes/27/extrato",
+    ] {
+        let (status, body) = send(&app, get(path)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{path}: {body}");
+    }
+}
+
+#[tokio::test]
+async fn plain_ids_are_used_as_they_are_when_disabled() {
+    let app = app(Config::default());
+    let (status, _) = send(&app, get("/clientes/1/extrato")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, get("/clientes/18/extrato")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,126 @@
+//! Shared by the integration tests, and by the library's own unit tests as
+//! `crate::testing`: an in-memory service on a clock the test moves, its
+//! router, and a few request builders. Only public API, so both can use it.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rinha2024::{clock::ManualClock, config::Config, http, service::BankService, AppState};
+use serde_json::Value;
+use time::{macros::datetime, OffsetDateTime};
+use tower::ServiceExt;
+
+pub const ADMIN_TOKEN: &str = "segredo";
+
+pub fn app(config: Config) -> Router {
+    BankService::in_memory(config).unwrap().router()
+}
+
+/// Where the clock of `app_at` starts.
+pub const START: OffsetDateTime = datetime!(2024-03-01 12:00 UTC);
+
+/// `app` telling time by a clock the test moves.
+pub fn app_at(config: Config) -> (Router, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::new(START));
+    let service = BankService::in_memory_with_clock(config, clock.clone()).unwrap();
+    (service.router(), clock)
+}
+
+/// `app_at` for tests that also reach into the state behind the router.
+pub fn bank(config: Config) -> (Arc<AppState>, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::new(START));
+    let service = BankService::in_memory_with_clock(config, clock.clone()).unwrap();
+    (service.state().clone(), clock)
+}
+
+/// What `send` can send to: a router, or the state one is built over.
+pub trait Target {
+    fn router(&self) -> Router;
+}
+
+impl Target for Router {
+    fn router(&self) -> Router {
+        self.clone()
+    }
+}
+
+impl Target for Arc<AppState> {
+    fn router(&self) -> Router {
+        http::router(self.clone())
+    }
+}
+
+/// `Config::default()` with `BANK_ADMIN_TOKEN` set to `ADMIN_TOKEN`.
+pub fn admin_config() -> Config {
+    Config {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..Config::default()
+    }
+}
+
+pub async fn send(app: &impl Target, request: Request<Body>) -> (StatusCode, Value) {
+    let (status, _, body) = send_raw(app, request).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// `send` for bodies that are not JSON, like `/metrics`.
+pub async fn text(app: &impl Target, request: Request<Body>) -> (StatusCode, String) {
+    let (status, _, body) = send_raw(app, request).await;
+    (status, String::from_utf8(body).unwrap())
+}
+
+pub async fn send_raw(
+    app: &impl Target,
+    request: Request<Body>,
+) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+    let response = app.router().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, body.to_vec())
+}
+
+pub fn get(path: &str) -> Request<Body> {
+    Request::get(path).body(Body::empty()).unwrap()
+}
+
+pub fn post(path: &str, body: Value) -> Request<Body> {
+    Request::post(path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+pub fn delete(path: &str) -> Request<Body> {
+    Request::delete(path).body(Body::empty()).unwrap()
+}
+
+/// `request` carrying `X-Admin-Token: ADMIN_TOKEN`.
+pub fn admin(mut request: Request<Body>) -> Request<Body> {
+    request
+        .headers_mut()
+        .insert("x-admin-token", ADMIN_TOKEN.parse().unwrap());
+    request
+}
+
+pub fn transaction(account: u64, valor: i64, tipo: &str, descricao: &str) -> Request<Body> {
+    post(
+        &format!("/clientes/{account}/transacoes"),
+        serde_json::json!({"valor": valor, "tipo": tipo, "descricao": descricao}),
+    )
+}
+
+pub fn statement(account: u64) -> Request<Body> {
+    get(&format!("/clientes/{account}/extrato"))
+}
+
+/// RFC 3339, as responses render times by default.
+pub fn timestamp(at: OffsetDateTime) -> String {
+    at.format(&time::format_description::well_known::Rfc3339)
+        .unwrap()
+}
@@ -0,0 +1,70 @@
+//! `GET /clientes/compare`: two statements side by side, with the entries
+//! that are two legs of one movement between them.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, get, post, send, transaction};
+use rinha2024::config::Config;
+use serde_json::json;
+
+#[tokio::test]
+async fn a_transfer_is_linked_on_both_sides() {
+    let app = app(Config::default());
+    let (status, transfer) = send(
+        &app,
+        post(
+            "/clientes/1/transferencias",
+            json!({"para": 2, "valor": 500, "descricao": "aluguel"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{transfer}");
+    // Unrelated to each other and to the transfer.
+    send(&app, transaction(1, 500, "C", "deposito")).await;
+    send(&app, transaction(2, 700, "D", "aluguel")).await;
+
+    let (status, body) = send(&app, get("/clientes/compare?a=1&b=2")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(
+        body["vinculadas"],
+        json!([{
+            "a": transfer["de"]["id"],
+            "b": transfer["para"]["id"],
+            "transferencia": transfer["id"],
+        }])
+    );
+    assert_eq!(body["a"]["saldo"]["total"], 0);
+    assert_eq!(body["b"]["saldo"]["total"], -200);
+    let legs = body["a"]["ultimas_transacoes"].as_array().unwrap();
+    assert_eq!(legs.len(), 2);
+
+    // The same pair whichever side is asked about first.
+    let (_, swapped) = send(&app, get("/clientes/compare?a=2&b=1")).await;
+    assert_eq!(swapped["vinculadas"][0]["a"], transfer["para"]["id"]);
+    assert_eq!(swapped["vinculadas"][0]["b"], transfer["de"]["id"]);
+}
+
+#[tokio::test]
+async fn untagged_legs_are_matched_on_value_and_description() {
+    let app = app(Config::default());
+    let (_, debit) = send(&app, transaction(1, 300, "D", "acerto")).await;
+    let (_, credit) = send(&app, transaction(3, 300, "C", "acerto")).await;
+    send(&app, transaction(3, 301, "C", "acerto")).await;
+
+    let (_, body) = send(&app, get("/clientes/compare?a=1&b=3")).await;
+    let links = body["vinculadas"].as_array().unwrap();
+    assert_eq!(links.len(), 1, "{body}");
+    assert_eq!(links[0]["a"], debit["id"]);
+    assert_eq!(links[0]["b"], credit["id"]);
+    assert!(links[0].get("transferencia").is_none());
+}
+
+#[tokio::test]
+async fn the_same_account_twice_is_refused() {
+    let app = app(Config::default());
+    let (status, _) = send(&app, get("/clientes/compare?a=1&b=1")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = send(&app, get("/clientes/compare?a=1&b=99")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,93 @@
+//! `Config::load` reading `BANK_*` settings. The environment is shared by
+//! every test here, so each one holds `ENV` while it sets and reads it.
+
+mod common;
+
+use std::sync::Mutex;
+
+use axum::http::StatusCode;
+use common::{admin, app, post, send, statement, transaction, ADMIN_TOKEN};
+use rinha2024::{config::Config, service::BankService};
+use serde_json::json;
+
+static ENV: Mutex<()> = Mutex::new(());
+
+/// `Config::load` with `vars` set, cleared again afterwards.
+fn load_with(vars: &[(&str, &str)]) -> Result<Config, String> {
+    let _guard = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (name, value) in vars {
+        std::env::set_var(name, value);
+    }
+    let config = Config::load();
+    for (name, _) in vars {
+        std::env::remove_var(name);
+    }
+    config
+}
+
+#[tokio::test]
+async fn history_cap_applies_to_new_accounts() {
+    let config = load_with(&[
+        ("BANK_HISTORY_CAP", "25"),
+        ("BANK_ADMIN_TOKEN", ADMIN_TOKEN),
+    ])
+    .unwrap();
+    assert_eq!(config.history_cap, 25);
+    let app = app(config);
+
+    let (status, _) = send(
+        &app,
+        admin(post("/clientes", json!({"id": 42, "limite": 1_000}))),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    for account in [1, 42] {
+        for value in 1..=30 {
+            send(&app, transaction(account, value, "C", "deposito")).await;
+        }
+        let (_, body) = send(&app, statement(account)).await;
+        let history = body["ultimas_transacoes"].as_array().unwrap();
+        assert_eq!(history.len(), 25);
+        // The newest kept, the oldest five gone.
+        assert_eq!(history[0]["valor"], 30);
+        assert_eq!(history[24]["valor"], 6);
+    }
+}
+
+#[tokio::test]
+async fn blocklist_file_rejects_listed_terms_in_any_case_or_accent() {
+    let path = std::env::temp_dir().join(format!("bank-{}-blocklist.txt", std::process::id()));
+    std::fs::write(&path, "# compliance\ngolpe\n\n  Pirâmide  \n").unwrap();
+    let config = load_with(&[("BANK_DESCRIPTION_BLOCKLIST", path.to_str().unwrap())]).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(config.description.blocklist, ["golpe", "piramide"]);
+    let app = app(config);
+
+    let (status, _) = send(&app, transaction(1, 100, "C", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+    for description in ["GoLpE pix", "piramide", "PIRÂMIDE"] {
+        let (status, body) = send(&app, transaction(1, 100, "C", description)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{description}");
+        assert_eq!(body["campo"], "descricao");
+        assert_eq!(body["message"], "descricao contem termo proibido");
+    }
+}
+
+#[test]
+fn duplicate_account_ids_refuse_to_start() {
+    let config = load_with(&[("BANK_ACCOUNTS", "1:100000,2:80000,1:5")]).unwrap();
+    let err = BankService::in_memory(config).err().unwrap();
+    assert_eq!(err, "ids de conta duplicados: 1");
+}
+
+#[test]
+fn replication_refuses_to_start_without_a_token() {
+    let role = [
+        ("BANK_REPLICATION_ROLE", "follower"),
+        ("BANK_REPLICATION_PEER", "http://lider:3000"),
+    ];
+    let err = load_with(&role).err().unwrap();
+    assert_eq!(err, "BANK_REPLICATION_ROLE requer BANK_REPLICATION_TOKEN");
+    let config = load_with(&[role[0], role[1], ("BANK_REPLICATION_TOKEN", "segredo")]).unwrap();
+    assert_eq!(config.replication.unwrap().token, "segredo");
+}
@@ -0,0 +1,57 @@
+//! `BANK_DAILY_DEBIT_LIMITS`: debits per calendar day, in
+//! `BANK_DAILY_DEBIT_TIMEZONE`.
+
+mod common;
+
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use common::{app_at, send, transaction, START};
+use rinha2024::{config::Config, AccountId};
+use time::{macros::offset, Duration};
+
+fn capped() -> Config {
+    Config {
+        daily_debit_limits: HashMap::from([(AccountId(1), 1_000)]),
+        daily_debit_timezone: offset!(-3),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn debits_up_to_the_cap_pass_and_past_it_are_refused() {
+    let (app, _) = app_at(capped());
+
+    let (status, _) = send(&app, transaction(1, 600, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, transaction(1, 400, "D", "farmacia")).await;
+    assert_eq!(status, StatusCode::OK, "exactly the cap");
+
+    let (status, body) = send(&app, transaction(1, 1, "D", "padaria")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["message"], "limite diario excedido");
+    // Credits are not capped, and other accounts have no cap.
+    let (status, _) = send(&app, transaction(1, 5_000, "C", "salario")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, transaction(2, 5_000, "D", "aluguel")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_cap_resets_at_local_midnight() {
+    let (app, clock) = app_at(capped());
+    let (status, _) = send(&app, transaction(1, 1_000, "D", "aluguel")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 09:00 at -03:00; midnight there is 03:00 UTC, which is still the same
+    // local day one minute before.
+    clock.set(START + Duration::hours(15) - Duration::minutes(1));
+    let (status, _) = send(&app, transaction(1, 1, "D", "padaria")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    clock.advance(Duration::minutes(1));
+    let (status, _) = send(&app, transaction(1, 1_000, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, transaction(1, 1, "D", "padaria")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,57 @@
+//! `BANK_DEDUP_WINDOW_MS`: an identical follow-up is a double submit.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use common::{app_at, send, statement, transaction};
+use rinha2024::config::Config;
+
+fn deduplicating() -> Config {
+    Config {
+        dedup_window: Some(Duration::from_secs(2)),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn an_identical_follow_up_is_refused_with_the_earlier_id() {
+    let (app, clock) = app_at(deduplicating());
+    let (status, _) = send(&app, transaction(1, 500, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, statement(1)).await;
+    let first = body["ultimas_transacoes"][0]["id"].clone();
+
+    clock.advance(time::Duration::seconds(1));
+    let (status, body) = send(&app, transaction(1, 500, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["message"], "transacao duplicada");
+    assert_eq!(body["transacao_anterior"], first);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], -500);
+    assert_eq!(body["ultimas_transacoes"].as_array().unwrap().len(), 1);
+
+    // Past the window it is a new one.
+    clock.advance(time::Duration::seconds(2));
+    let (status, _) = send(&app, transaction(1, 500, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_differing_follow_up_is_accepted() {
+    let (app, _) = app_at(deduplicating());
+    let (status, _) = send(&app, transaction(1, 500, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+    for (valor, tipo, descricao) in [
+        (501, "D", "mercado"),
+        (501, "C", "mercado"),
+        (501, "C", "feira"),
+    ] {
+        let (status, _) = send(&app, transaction(1, valor, tipo, descricao)).await;
+        assert_eq!(status, StatusCode::OK, "{valor} {tipo} {descricao}");
+    }
+    // Other accounts keep their own latest.
+    let (status, _) = send(&app, transaction(2, 500, "D", "mercado")).await;
+    assert_eq!(status, StatusCode::OK);
+}
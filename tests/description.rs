@@ -0,0 +1,64 @@
+//! `descricao` as `DescriptionPolicy` measures and filters it.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, send, transaction};
+use rinha2024::config::{Config, DescriptionPolicy, LengthUnit};
+
+fn policy(policy: DescriptionPolicy) -> Config {
+    Config {
+        description: policy,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn multibyte_description_fits_chars_but_not_bytes() {
+    // 8 characters, 12 bytes in UTF-8.
+    let description = "açãoação";
+
+    let chars = app(policy(DescriptionPolicy::default()));
+    let (status, _) = send(&chars, transaction(1, 100, "C", description)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let bytes = app(policy(DescriptionPolicy {
+        unit: LengthUnit::Bytes,
+        ..DescriptionPolicy::default()
+    }));
+    let (status, body) = send(&bytes, transaction(1, 100, "C", description)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["campo"], "descricao");
+
+    // Within 10 bytes as well.
+    let (status, _) = send(&bytes, transaction(1, 100, "C", "ação")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn surrounding_whitespace_is_trimmed_only_when_configured() {
+    // Eleven characters with the spaces, three without.
+    let padded = "    pix    ";
+
+    let strict = app(Config::default());
+    let (status, body) = send(&strict, transaction(1, 100, "C", padded)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["campo"], "descricao");
+    // Within the limit untrimmed, stored as sent.
+    let (status, _) = send(&strict, transaction(1, 100, "C", "  pix ")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, statement) = send(&strict, common::statement(1)).await;
+    assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "  pix ");
+
+    let trimming = app(policy(DescriptionPolicy {
+        trim: true,
+        ..DescriptionPolicy::default()
+    }));
+    let (status, _) = send(&trimming, transaction(1, 100, "C", padded)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, statement) = send(&trimming, common::statement(1)).await;
+    assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "pix");
+    // Nothing left once trimmed.
+    let (status, _) = send(&trimming, transaction(1, 100, "C", "   ")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
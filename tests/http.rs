@@ -0,0 +1,289 @@
+//! The router over an in-memory service, one request at a time.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rinha2024::{config::Config, service::BankService, AccountId};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    BankService::in_memory(Config::default()).unwrap().router()
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn post_transaction(account: u64, body: Value) -> Request<Body> {
+    Request::post(format!("/clientes/{account}/transacoes"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn get_statement(account: u64) -> Request<Body> {
+    Request::get(format!("/clientes/{account}/extrato"))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn transaction_then_statement() {
+    let app = app();
+
+    let (status, body) = send(
+        &app,
+        post_transaction(
+            1,
+            json!({"valor": 500, "tipo": "C", "descricao": "deposito"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"], 500);
+    assert_eq!(body["limite"], 100_000);
+
+    let (status, body) = send(&app, get_statement(1)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"]["total"], 500);
+    assert_eq!(body["ultimas_transacoes"][0]["descricao"], "deposito");
+    let realizada_em = body["ultimas_transacoes"][0]["realizada_em"]
+        .as_str()
+        .unwrap();
+    assert!(time::OffsetDateTime::parse(
+        realizada_em,
+        &time::format_description::well_known::Rfc3339
+    )
+    .is_ok());
+}
+
+#[tokio::test]
+async fn debit_past_the_limit_is_unprocessable() {
+    let app = app();
+    let debit = |valor: i64| {
+        post_transaction(
+            2,
+            json!({"valor": valor, "tipo": "D", "descricao": "saque"}),
+        )
+    };
+    let (status, body) = send(&app, debit(80_001)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "limite_insuficiente");
+
+    // Down to the limit exactly, then not a cent more.
+    let (status, body) = send(&app, debit(80_000)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"], -80_000);
+    let (status, _) = send(&app, debit(1)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (_, body) = send(&app, get_statement(2)).await;
+    assert_eq!(body["saldo"]["total"], -80_000);
+    assert_eq!(body["ultimas_transacoes"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn credit_past_the_largest_balance_is_unprocessable() {
+    let app = BankService::in_memory(Config {
+        max_transaction_value: i64::MAX,
+        ..Config::default()
+    })
+    .unwrap()
+    .router();
+    let credit =
+        |valor: i64| post_transaction(1, json!({"valor": valor, "tipo": "C", "descricao": "teto"}));
+    let (status, _) = send(&app, credit(i64::MAX)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, credit(1)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "saldo_fora_do_intervalo");
+    assert_eq!(body["message"], "valor fora do intervalo suportado");
+    let (_, body) = send(&app, get_statement(1)).await;
+    assert_eq!(body["saldo"]["total"], i64::MAX);
+}
+
+#[tokio::test]
+async fn invalid_description_is_unprocessable() {
+    let app = app();
+    for descricao in ["", "onze letras"] {
+        let (status, body) = send(
+            &app,
+            post_transaction(1, json!({"valor": 1, "tipo": "C", "descricao": descricao})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{descricao:?}");
+        assert_eq!(body["message"], "Descrição invalida");
+    }
+    let (_, body) = send(&app, get_statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"], json!([]));
+}
+
+#[tokio::test]
+async fn malformed_body_lists_every_field() {
+    let (status, body) = send(
+        &app(),
+        post_transaction(1, json!({"valor": 0, "tipo": "X", "descricao": 7})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "valor_nao_positivo");
+    let fields: Vec<_> = body["campos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| (field["campo"].clone(), field["code"].clone()))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            (json!("valor"), json!("valor_nao_positivo")),
+            (json!("tipo"), json!("tipo_invalido")),
+            (json!("descricao"), json!("descricao_tipo_invalido")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unknown_account_is_not_found() {
+    let app = app();
+    let (status, _) = send(
+        &app,
+        post_transaction(99, json!({"valor": 1, "tipo": "C", "descricao": "x"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, get_statement(99)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn probes_answer_without_dependencies() {
+    let app = app();
+    for path in ["/healthz", "/readyz"] {
+        let (status, body) = send(&app, Request::get(path).body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK, "{path}");
+        assert_eq!(body["status"], "ok", "{path}");
+    }
+}
+
+#[tokio::test]
+async fn root_describes_the_service() {
+    let get_root = || Request::get("/").body(Body::empty()).unwrap();
+    let (status, body) = send(&app(), get_root()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["service"], "bank");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(body["accounts"], 5);
+
+    let config = Config {
+        accounts: vec![(AccountId(7), 1_000), (AccountId(8), 2_000)],
+        ..Config::default()
+    };
+    let two = BankService::in_memory(config).unwrap().router();
+    let (_, body) = send(&two, get_root()).await;
+    assert_eq!(body["accounts"], 2);
+}
+
+#[tokio::test]
+async fn ledger_is_not_found_unless_enabled() {
+    let (status, body) = send(
+        &app(),
+        Request::get("/clientes/1/ledger")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "ledger_desativado");
+}
+
+#[tokio::test]
+async fn balance_as_of_unwinds_retained_history() {
+    let app = app();
+    send(
+        &app,
+        post_transaction(
+            1,
+            json!({"valor": 100, "tipo": "C", "descricao": "deposito"}),
+        ),
+    )
+    .await;
+    let between = time::OffsetDateTime::now_utc();
+    send(
+        &app,
+        post_transaction(1, json!({"valor": 30, "tipo": "D", "descricao": "saque"})),
+    )
+    .await;
+
+    let at = |instant: time::OffsetDateTime| {
+        let instant = instant
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap();
+        Request::get(format!("/clientes/1/saldo?em={instant}"))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let (status, body) = send(&app, at(between)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"], 100);
+    assert_eq!(body["transacoes"], 1);
+
+    let (_, body) = send(&app, at(time::OffsetDateTime::now_utc())).await;
+    assert_eq!(body["saldo"], 70);
+
+    // Before anything retained, with the account changed since: unknowable.
+    let (status, body) = send(&app, at(between - time::Duration::hours(1))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "historico_insuficiente");
+}
+
+#[tokio::test]
+async fn monthly_summary_totals_by_category() {
+    let app = app();
+    for body in [
+        json!({"valor": 1_000, "tipo": "C", "descricao": "salario"}),
+        json!({"valor": 300, "tipo": "D", "descricao": "mercado", "categoria": "alimentacao"}),
+        json!({"valor": 200, "tipo": "D", "descricao": "feira", "categoria": "Alimentacao"}),
+    ] {
+        let (status, _) = send(&app, post_transaction(1, body)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, body) = send(
+        &app,
+        post_transaction(
+            1,
+            json!({"valor": 1, "tipo": "D", "descricao": "x", "categoria": "viagem"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["campos"][0]["campo"], "categoria");
+
+    let (_, statement) = send(&app, get_statement(1)).await;
+    assert_eq!(
+        statement["ultimas_transacoes"][0]["categoria"],
+        "alimentacao"
+    );
+    let month = &statement["ultimas_transacoes"][0]["realizada_em"]
+        .as_str()
+        .unwrap()[..7];
+    let (status, body) = send(
+        &app,
+        Request::get(format!("/clientes/1/resumo?mes={month}"))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["por_categoria"]["alimentacao"]["debitos"], 500);
+    assert_eq!(body["por_categoria"]["alimentacao"]["transacoes"], 2);
+    assert_eq!(body["por_categoria"]["sem_categoria"]["creditos"], 1_000);
+    assert_eq!(body["por_tipo"]["D"]["total"], 500);
+    assert_eq!(body["por_tipo"]["C"]["transacoes"], 1);
+}
@@ -0,0 +1,52 @@
+//! `Accept-Language` picks the `message` of an error; `code` never changes.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use common::{app, send, transaction};
+use rinha2024::config::Config;
+
+fn in_language(
+    mut request: axum::http::Request<axum::body::Body>,
+    language: &str,
+) -> axum::http::Request<axum::body::Body> {
+    request
+        .headers_mut()
+        .insert(header::ACCEPT_LANGUAGE, language.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn english_messages_keep_the_codes() {
+    let app = app(Config::default());
+    let over_limit = || transaction(1, 1_000_000, "D", "saque");
+    let invalid = || transaction(1, 100, "C", "");
+
+    let (status, pt) = send(&app, over_limit()).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(pt["code"], "limite_insuficiente");
+    assert_eq!(pt["message"], "Limite insuficiente");
+    let (status, en) = send(&app, in_language(over_limit(), "en-US,en;q=0.9")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(en["code"], pt["code"]);
+    assert_eq!(en["message"], "Insufficient limit");
+
+    let (_, pt) = send(&app, invalid()).await;
+    let (_, en) = send(&app, in_language(invalid(), "en")).await;
+    assert_eq!(pt["code"], "descricao_invalida");
+    assert_eq!(en["code"], pt["code"]);
+    assert_eq!(pt["message"], "Descrição invalida");
+    assert_eq!(en["message"], "Invalid description");
+}
+
+#[tokio::test]
+async fn portuguese_is_preferred_when_listed_first() {
+    let app = app(Config::default());
+    let request = in_language(transaction(1, 1_000_000, "D", "saque"), "pt-BR, en;q=0.5");
+    let (_, body) = send(&app, request).await;
+    assert_eq!(body["message"], "Limite insuficiente");
+    // Nothing we carry: English.
+    let request = in_language(transaction(1, 1_000_000, "D", "saque"), "fr-FR");
+    let (_, body) = send(&app, request).await;
+    assert_eq!(body["message"], "Insufficient limit");
+}
@@ -0,0 +1,40 @@
+//! Transaction ids stay exact for clients that read JSON numbers as doubles.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, send, statement, transaction};
+use rinha2024::config::Config;
+
+#[tokio::test]
+async fn ids_render_as_strings_beyond_double_precision() {
+    let app = app(Config::default());
+
+    let (status, created) = send(&app, transaction(1, 100, "C", "id")).await;
+    assert_eq!(status, StatusCode::OK);
+    // A ULID, 128 bits: no JSON number could carry it to JavaScript intact.
+    let id = created["id"].as_str().unwrap();
+    assert_eq!(id.len(), 26);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"][0]["id"], id);
+
+    // The sequence number stays a number by default.
+    assert!(created["seq"].is_u64());
+    assert_eq!(body["ultimas_transacoes"][0]["seq"], created["seq"]);
+}
+
+#[tokio::test]
+async fn sequence_numbers_render_as_strings_on_request() {
+    let config = Config {
+        ids_as_strings: true,
+        ..Config::default()
+    };
+    let app = app(config);
+
+    let (status, created) = send(&app, transaction(1, 100, "C", "id")).await;
+    assert_eq!(status, StatusCode::OK);
+    let seq = created["seq"].as_str().unwrap();
+    assert!(seq.parse::<u64>().unwrap() > 0);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"][0]["seq"], seq);
+}
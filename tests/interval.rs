@@ -0,0 +1,34 @@
+//! `BANK_MIN_TRANSACTION_INTERVAL_MS`: a per-account gap between
+//! transactions, on the service clock.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app_at, send, statement, transaction};
+use rinha2024::config::Config;
+use time::Duration;
+
+#[tokio::test]
+async fn transactions_inside_the_gap_are_throttled() {
+    let (app, clock) = app_at(Config {
+        min_transaction_interval: Some(std::time::Duration::from_secs(2)),
+        ..Config::default()
+    });
+
+    let (status, _) = send(&app, transaction(1, 100, "C", "primeira")).await;
+    assert_eq!(status, StatusCode::OK);
+    clock.advance(Duration::milliseconds(1_999));
+    let (status, body) = send(&app, transaction(1, 100, "C", "cedo")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "intervalo_minimo");
+    // Another account has its own gap.
+    let (status, _) = send(&app, transaction(2, 100, "C", "outra")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The refused one did not restart the gap.
+    clock.advance(Duration::milliseconds(1));
+    let (status, _) = send(&app, transaction(1, 100, "D", "segunda")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"].as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,181 @@
+//! `POST /clientes/:id/limite/transferir`: moving limit between accounts.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, admin_config, app, get, post, send, statement, transaction};
+use futures_util::future;
+use serde_json::json;
+
+fn transfer(from: u64, to: u64, value: i64) -> axum::http::Request<axum::body::Body> {
+    admin(post(
+        &format!("/clientes/{from}/limite/transferir"),
+        json!({"para": to, "valor": value}),
+    ))
+}
+
+#[tokio::test]
+async fn holds_count_against_the_limit_given_away() {
+    let app = app(admin_config());
+    let (status, _) = send(
+        &app,
+        post(
+            "/clientes/1/reservas",
+            json!({"valor": 60_000, "descricao": "hotel"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    // The balance alone would cover it; the hold would not.
+    let (status, body) = send(&app, transfer(1, 2, 50_000)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["limite"], 100_000);
+
+    let (status, _) = send(&app, transfer(1, 2, 40_000)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["limite"], 60_000);
+    assert_eq!(body["saldo"]["disponivel"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_transfers_and_debits_conserve_the_limit() {
+    let app = app(admin_config());
+    let accounts = [1, 2, 3];
+    let total = |bodies: &[serde_json::Value]| {
+        bodies
+            .iter()
+            .map(|body| body["saldo"]["limite"].as_i64().unwrap())
+            .sum::<i64>()
+    };
+    let before: Vec<_> = future::join_all(accounts.map(|id| send(&app, statement(id))))
+        .await
+        .into_iter()
+        .map(|(_, body)| body)
+        .collect();
+
+    // Transfers both ways between every pair while debits draw each account
+    // down, so some transfers find their limit already spent.
+    let writes = (0..300u64).map(|index| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let from = accounts[index as usize % 3];
+            let to = accounts[(index as usize + 1 + index as usize / 3 % 2) % 3];
+            let (status, body) = if index % 4 == 0 {
+                send(&app, transaction(from, 1_500, "D", "saque")).await
+            } else {
+                send(&app, transfer(from, to, 7_000)).await
+            };
+            assert!(
+                status == StatusCode::OK || status == StatusCode::UNPROCESSABLE_ENTITY,
+                "{status} {body}"
+            );
+        })
+    });
+    for write in future::join_all(writes).await {
+        write.unwrap();
+    }
+
+    let after: Vec<_> = future::join_all(accounts.map(|id| send(&app, statement(id))))
+        .await
+        .into_iter()
+        .map(|(_, body)| body)
+        .collect();
+    assert_eq!(total(&after), total(&before));
+    for body in &after {
+        let (balance, limit) = (&body["saldo"]["total"], &body["saldo"]["limite"]);
+        assert!(
+            balance.as_i64().unwrap() >= -limit.as_i64().unwrap(),
+            "{body}"
+        );
+        assert!(body["saldo"]["disponivel"].as_i64().unwrap() >= 0, "{body}");
+    }
+}
+
+#[tokio::test]
+async fn accounts_are_created_up_to_the_maximum_limit() {
+    let app = app(rinha2024::config::Config {
+        // The largest seed.
+        max_limit: 10_000_000,
+        ..admin_config()
+    });
+    let create = |id: u64, limit: i64| admin(post("/clientes", json!({"id": id, "limite": limit})));
+
+    let (status, _) = send(&app, create(10, 500_000)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&app, create(11, 10_000_000)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, body) = send(&app, create(12, 10_000_001)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "limite_acima_do_maximo");
+    let (status, _) = send(&app, statement(12)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn partial_debits_take_what_the_limit_allows() {
+    let app = app(admin_config());
+    let partial = |value: i64| {
+        post(
+            "/clientes/1/transacoes?parcial=true",
+            json!({"valor": value, "tipo": "D", "descricao": "varredura"}),
+        )
+    };
+
+    // Fits: taken whole.
+    let (status, body) = send(&app, partial(40_000)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["debitado"], 40_000);
+    assert_eq!(body["saldo"], -40_000);
+
+    // 60_000 left of the limit.
+    let (status, body) = send(&app, partial(100_000)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["debitado"], 60_000);
+    assert_eq!(body["saldo"], -100_000);
+    let (_, statement) = send(&app, statement(1)).await;
+    assert_eq!(statement["ultimas_transacoes"][0]["valor"], 60_000);
+
+    // Nothing left: still a rejection.
+    let (status, body) = send(&app, partial(1)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "limite_insuficiente");
+
+    // Without the flag, no prorating.
+    let (status, body) = send(&app, transaction(2, 100_000, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body.get("debitado").is_none());
+}
+
+#[tokio::test]
+async fn preview_shows_a_rejected_debit_passing_under_a_higher_limit() {
+    let app = app(admin_config());
+    let (status, _) = send(&app, transaction(1, 100_001, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = send(&app, get("/clientes/1/limite/preview?novo=150000")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["limite_atual"], 100_000);
+    assert_eq!(body["limite_novo"], 150_000);
+    assert_eq!(body["passariam"], 1);
+    assert_eq!(body["excede_limite"], false);
+    assert_eq!(body["rejeicoes"][0]["valor"], 100_001);
+    assert_eq!(body["rejeicoes"][0]["motivo"], "Limite insuficiente");
+    assert_eq!(body["rejeicoes"][0]["passaria"], true);
+
+    // Nothing changed: the limit is still the old one.
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["limite"], 100_000);
+
+    let (status, _) = send(&app, transaction(1, 90_000, "D", "aluguel")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, get("/clientes/1/limite/preview?novo=50000")).await;
+    assert_eq!(body["passariam"], 0);
+    assert_eq!(body["excede_limite"], true);
+    assert_eq!(body["rejeicoes"][0]["passaria"], false);
+
+    let (status, _) = send(&app, get("/clientes/1/limite/preview?novo=-1")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,42 @@
+//! The per-account streams: SSE activity and the statement stream.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use common::{app, get, send, transaction};
+use futures_util::StreamExt;
+use rinha2024::config::Config;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn sse_emits_the_posted_transaction() {
+    let app = app(Config::default());
+
+    let response = app.clone().oneshot(get("/clientes/1/sse")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body().into_data_stream();
+
+    let (status, _) = send(&app, transaction(1, 250, "C", "pix")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut received = String::new();
+    while !received.contains("\n\n") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("no event within 5s")
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let data = received
+        .lines()
+        .find_map(|line| line.strip_prefix("data:"))
+        .unwrap();
+    let event: serde_json::Value = serde_json::from_str(data.trim()).unwrap();
+    assert_eq!(event["valor"], 250);
+    assert_eq!(event["tipo"], "C");
+    assert_eq!(event["descricao"], "pix");
+}
@@ -0,0 +1,47 @@
+//! `Accept: application/msgpack` on the statement.
+
+mod common;
+
+use axum::http::{header, StatusCode};
+use common::{app_at, send, send_raw, statement, transaction};
+use rinha2024::config::Config;
+
+fn msgpack(account: u64) -> axum::http::Request<axum::body::Body> {
+    let mut request = statement(account);
+    request
+        .headers_mut()
+        .insert(header::ACCEPT, "application/msgpack".parse().unwrap());
+    request
+}
+
+#[cfg(feature = "msgpack")]
+#[tokio::test]
+async fn statement_round_trips_through_msgpack() {
+    let (app, _) = app_at(Config::default());
+    send(&app, transaction(1, 1_000, "C", "salario")).await;
+    send(&app, transaction(1, 250, "D", "mercado")).await;
+
+    let (status, headers, bytes) = send_raw(&app, msgpack(1)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/msgpack");
+    let decoded: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+
+    let (_, json) = send(&app, statement(1)).await;
+    assert_eq!(decoded, json);
+    // Timestamps as the same RFC 3339 strings.
+    assert!(decoded["ultimas_transacoes"][0]["realizada_em"].is_string());
+    assert!(bytes.len() < json.to_string().len());
+}
+
+#[cfg(not(feature = "msgpack"))]
+#[tokio::test]
+async fn statement_stays_json_without_the_feature() {
+    let (app, _) = app_at(Config::default());
+    send(&app, transaction(1, 1_000, "C", "salario")).await;
+
+    let (status, headers, bytes) = send_raw(&app, msgpack(1)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["saldo"]["total"], 1_000);
+}
@@ -0,0 +1,80 @@
+//! The order transactions are accepted in, as ids, timestamps and `seq`
+//! record it, under concurrent writers.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, send, statement, transaction};
+use futures_util::future;
+use rinha2024::config::Config;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Newest first, as the statement lists them.
+async fn concurrent_history(writes: usize) -> Vec<serde_json::Value> {
+    let app = app(Config {
+        history_cap: writes,
+        ..Config::default()
+    });
+    let requests = (0..writes).map(|index| {
+        let app = app.clone();
+        let kind = if index % 3 == 0 { "D" } else { "C" };
+        tokio::spawn(async move { send(&app, transaction(1, 10, kind, "corrida")).await })
+    });
+    for response in future::join_all(requests).await {
+        assert_eq!(response.unwrap().0, StatusCode::OK);
+    }
+    let (_, body) = send(&app, statement(1)).await;
+    body["ultimas_transacoes"].as_array().unwrap().clone()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn timestamps_never_go_back_in_application_order() {
+    let history = concurrent_history(200).await;
+    assert_eq!(history.len(), 200);
+    let stamps: Vec<OffsetDateTime> = history
+        .iter()
+        .map(|transaction| {
+            OffsetDateTime::parse(transaction["realizada_em"].as_str().unwrap(), &Rfc3339).unwrap()
+        })
+        .collect();
+    assert!(stamps.windows(2).all(|pair| pair[0] >= pair[1]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn seq_orders_transactions_across_accounts() {
+    let app = app(Config {
+        history_cap: 100,
+        ..Config::default()
+    });
+    let requests = (0..200).map(|index| {
+        let app = app.clone();
+        let account = 1 + index % 2;
+        tokio::spawn(async move { send(&app, transaction(account, 10, "C", "corrida")).await })
+    });
+    let mut created: Vec<u64> = Vec::new();
+    for response in future::join_all(requests).await {
+        let (status, body) = response.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        created.push(body["seq"].as_u64().unwrap());
+    }
+
+    let mut listed = Vec::new();
+    for account in [1, 2] {
+        let (_, body) = send(&app, statement(account)).await;
+        let seqs: Vec<u64> = body["ultimas_transacoes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|transaction| transaction["seq"].as_u64().unwrap())
+            .collect();
+        assert_eq!(seqs.len(), 100);
+        // Newest first within each account.
+        assert!(seqs.windows(2).all(|pair| pair[0] > pair[1]));
+        listed.extend(seqs);
+    }
+    // One sequence shared by both accounts: no number handed out twice.
+    listed.sort_unstable();
+    created.sort_unstable();
+    listed.dedup();
+    assert_eq!(listed, created);
+}
@@ -0,0 +1,36 @@
+//! `BANK_NO_OVERDRAFT_ACCOUNTS`: accounts whose `limite` never lets the
+//! balance go below zero.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, send, statement, transaction};
+use rinha2024::{config::Config, AccountId};
+
+#[tokio::test]
+async fn debit_within_the_limit_is_refused_without_overdraft() {
+    let app = app(Config {
+        no_overdraft_accounts: vec![AccountId(1)],
+        ..Config::default()
+    });
+
+    let (status, body) = send(&app, transaction(1, 1, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["cheque_especial"], false);
+    assert_eq!(body["saldo"]["total"], 0);
+    // The limit is not there to spend.
+    assert_eq!(body["saldo"]["disponivel"], 0);
+
+    // Down to zero is still fine.
+    send(&app, transaction(1, 500, "C", "deposito")).await;
+    let (status, _) = send(&app, transaction(1, 500, "D", "saque")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Other accounts keep their overdraft.
+    let (status, _) = send(&app, transaction(2, 1, "D", "saque")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, statement(2)).await;
+    assert_eq!(body["saldo"]["cheque_especial"], true);
+    assert_eq!(body["saldo"]["total"], -1);
+}
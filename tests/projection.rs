@@ -0,0 +1,73 @@
+//! `GET /clientes/:id/projecao`: the balance at a future time, with pending
+//! schedules applied and expired holds released, on a copy of the account.
+
+mod common;
+
+use std::time::Duration as StdDuration;
+
+use axum::http::StatusCode;
+use common::{app_at, get, post, send, statement, START};
+use rinha2024::config::Config;
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, Duration};
+
+fn at(offset: Duration) -> String {
+    (START + offset).format(&Rfc3339).unwrap()
+}
+
+fn projection(offset: Duration) -> axum::http::Request<axum::body::Body> {
+    get(&format!("/clientes/1/projecao?ate={}", at(offset)))
+}
+
+#[tokio::test]
+async fn schedules_and_expiring_holds_shape_the_projection() {
+    let (app, _clock) = app_at(Config {
+        hold_ttl: StdDuration::from_secs(60 * 60),
+        ..Config::default()
+    });
+    let hold = json!({"valor": 30_000, "descricao": "hotel"});
+    let (status, _) = send(&app, post("/clientes/1/reservas", hold)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let schedule = |valor: i64, tipo: &str, offset: Duration| {
+        post(
+            "/clientes/1/agendamentos",
+            json!({"valor": valor, "tipo": tipo, "descricao": "previsto", "executar_em": at(offset)}),
+        )
+    };
+    // Due while the hold still counts: 70_000 available, so refused.
+    let (status, _) = send(&app, schedule(80_000, "D", Duration::minutes(30))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = send(&app, schedule(5_000, "C", Duration::hours(2))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = send(&app, projection(Duration::minutes(20))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["saldo"], 0);
+    assert_eq!(body["reservado"], 30_000);
+    assert_eq!(body["disponivel"], 70_000);
+    assert_eq!(body["agendamentos_aplicados"], json!([]));
+
+    let (_, body) = send(&app, projection(Duration::hours(3))).await;
+    assert_eq!(body["saldo"], 5_000);
+    assert_eq!(body["reservado"], 0);
+    assert_eq!(body["disponivel"], 105_000);
+    assert_eq!(body["agendamentos_aplicados"].as_array().unwrap().len(), 1);
+    assert_eq!(body["agendamentos_aplicados"][0]["valor"], 5_000);
+    let failed = body["agendamentos_falhos"].as_array().unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["transacao"]["valor"], 80_000);
+
+    // Nothing of it happened.
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], 0);
+    assert_eq!(body["ultimas_transacoes"], json!([]));
+    let (_, body) = send(&app, projection(Duration::hours(3))).await;
+    assert_eq!(body["saldo"], 5_000);
+}
+
+#[tokio::test]
+async fn a_date_in_the_past_is_refused() {
+    let (app, _clock) = app_at(Config::default());
+    let (status, body) = send(&app, projection(-Duration::hours(1))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+}
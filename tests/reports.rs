@@ -0,0 +1,66 @@
+//! Views computed over an account's history rather than its balance.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, admin_config, app_at, get, send, send_raw, transaction};
+use rinha2024::config::Config;
+use time::Duration;
+
+#[tokio::test]
+async fn heatmap_counts_transactions_per_hour() {
+    // 12:00 UTC: two at 12h, one at 15h, one the next day at 09h.
+    let (app, clock) = app_at(Config::default());
+    send(&app, transaction(1, 10, "C", "a")).await;
+    clock.advance(Duration::minutes(30));
+    send(&app, transaction(1, 10, "C", "b")).await;
+    clock.advance(Duration::hours(3));
+    send(&app, transaction(1, 10, "C", "c")).await;
+    clock.advance(Duration::hours(18));
+    send(&app, transaction(1, 10, "C", "d")).await;
+
+    let (_, body) = send(&app, get("/clientes/1/atividade")).await;
+    let hours = body["horas"].as_array().unwrap();
+    assert_eq!(hours.len(), 24);
+    assert_eq!(hours[12], 2);
+    assert_eq!(hours[15], 1);
+    assert_eq!(hours[9], 1);
+    assert_eq!(
+        hours.iter().filter_map(|count| count.as_u64()).sum::<u64>(),
+        4
+    );
+
+    // The same four at -03:00.
+    let (_, body) = send(&app, get("/clientes/1/atividade?tz=-03:00")).await;
+    assert_eq!(body["horas"][9], 2);
+    assert_eq!(body["horas"][12], 1);
+    assert_eq!(body["horas"][6], 1);
+}
+
+#[tokio::test]
+async fn the_csv_report_has_a_header_and_a_row_per_account() {
+    let config = admin_config();
+    let (app, _) = app_at(config.clone());
+    send(&app, transaction(1, 2_500, "D", "aluguel")).await;
+
+    let (status, _, _) = send_raw(&app, get("/relatorio.csv")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "admin only");
+    let (status, headers, body) = send_raw(&app, admin(get("/relatorio.csv"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "text/csv; charset=utf-8");
+
+    let body = String::from_utf8(body).unwrap();
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some("id,tipo,limite,saldo,disponivel"));
+    let rows: Vec<_> = lines.collect();
+    assert_eq!(rows.len(), config.accounts.len());
+    for (row, (id, limit)) in rows.iter().zip(&config.accounts) {
+        let balance = if id.0 == 1 { -2_500 } else { 0 };
+        let expected = format!(
+            "{id},{},{limit},{balance},{}",
+            config.default_account_type,
+            limit + balance
+        );
+        assert_eq!(*row, expected);
+    }
+}
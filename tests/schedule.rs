@@ -0,0 +1,39 @@
+//! Scheduled transactions, and what they tell a refused debit.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, post, send_raw, transaction};
+use rinha2024::config::Config;
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+#[tokio::test]
+async fn limit_refusal_names_the_next_scheduled_credit() {
+    let app = app(Config::default());
+
+    // Nothing scheduled: no hint to give.
+    let (status, headers, body) = send_raw(&app, transaction(1, 100_001, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(headers.get("retry-after").is_none());
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert!(body.get("proximo_credito").is_none());
+
+    let run_at = OffsetDateTime::now_utc() + Duration::hours(1);
+    let scheduled = json!({
+        "valor": 5_000,
+        "tipo": "C",
+        "descricao": "salario",
+        "executar_em": run_at.format(&Rfc3339).unwrap(),
+    });
+    let (status, _, _) = send_raw(&app, post("/clientes/1/agendamentos", scheduled)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, headers, body) = send_raw(&app, transaction(1, 100_001, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    let next = OffsetDateTime::parse(body["proximo_credito"].as_str().unwrap(), &Rfc3339).unwrap();
+    assert_eq!(next.unix_timestamp(), run_at.unix_timestamp());
+    let retry_after: i64 = headers["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((3_590..=3_600).contains(&retry_after), "{retry_after}");
+}
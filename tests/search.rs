@@ -0,0 +1,52 @@
+//! `GET /clientes/:id/transacoes/busca`: retained transactions by a piece of
+//! their `descricao`.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app_at, get, send, transaction, START};
+use rinha2024::config::Config;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, Duration};
+
+fn descriptions(body: &Value) -> Vec<&str> {
+    body["transacoes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction["descricao"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn matches_ignore_case_and_accents() {
+    let (app, clock) = app_at(Config::default());
+    send(&app, transaction(1, 100, "C", "açaí")).await;
+    send(&app, transaction(1, 100, "C", "pão")).await;
+    clock.advance(Duration::hours(1));
+    send(&app, transaction(1, 100, "D", "ACAI bowl")).await;
+
+    let (status, body) = send(&app, get("/clientes/1/transacoes/busca?q=acai")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(descriptions(&body), ["ACAI bowl", "açaí"]);
+    // The query may carry accents too.
+    let (_, body) = send(&app, get("/clientes/1/transacoes/busca?q=P%C3%83O")).await;
+    assert_eq!(descriptions(&body), ["pão"]);
+
+    let from = (START + Duration::minutes(30)).format(&Rfc3339).unwrap();
+    let path = format!("/clientes/1/transacoes/busca?q=acai&de={from}");
+    let (_, body) = send(&app, get(&path)).await;
+    assert_eq!(descriptions(&body), ["ACAI bowl"]);
+    let path = format!("/clientes/1/transacoes/busca?q=acai&ate={from}");
+    let (_, body) = send(&app, get(&path)).await;
+    assert_eq!(descriptions(&body), ["açaí"]);
+}
+
+#[tokio::test]
+async fn a_blank_query_is_refused() {
+    let (app, _) = app_at(Config::default());
+    let (status, _) = send(&app, get("/clientes/1/transacoes/busca?q=%20")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = send(&app, get("/clientes/99/transacoes/busca?q=x")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,203 @@
+//! `BankService` driven directly, without HTTP in between.
+
+use rinha2024::{
+    config::Config,
+    domain::TransactionRequest,
+    error::AppError,
+    service::{BankService, Options},
+    transact::TransactError,
+    AccountId,
+};
+use serde_json::json;
+
+fn service() -> BankService {
+    BankService::in_memory(Config::default()).unwrap()
+}
+
+fn request(valor: i64, tipo: &str, descricao: &str) -> TransactionRequest {
+    serde_json::from_value(json!({"valor": valor, "tipo": tipo, "descricao": descricao})).unwrap()
+}
+
+#[tokio::test]
+async fn credit_and_debit_move_the_balance() {
+    let service = service();
+    let account = AccountId(1);
+
+    let credit = service
+        .transact(account, request(1_000, "C", "deposito"), Options::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(credit.balance, 1_000);
+    assert_eq!(credit.limit, 100_000);
+
+    let debit = service
+        .transact(account, request(400, "D", "saque"), Options::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(debit.balance, 600);
+    assert!(debit.version > credit.version);
+}
+
+#[tokio::test]
+async fn debit_past_the_limit_is_refused() {
+    let service = service();
+    let account = AccountId(2);
+
+    let outcome = service
+        .transact(account, request(80_001, "D", "saque"), Options::default())
+        .await
+        .unwrap();
+    assert!(matches!(
+        outcome,
+        Err(TransactError::InsufficientLimit { .. })
+    ));
+
+    // Exactly down to the limit still goes through.
+    let accepted = service
+        .transact(account, request(80_000, "D", "saque"), Options::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(accepted.balance, -80_000);
+}
+
+#[tokio::test]
+async fn partial_debit_stops_at_the_limit() {
+    let service = service();
+    let options = Options {
+        partial: true,
+        ..Options::default()
+    };
+
+    let result = service
+        .transact(AccountId(2), request(100_000, "D", "saque"), options)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.balance, -80_000);
+}
+
+#[tokio::test]
+async fn descriptions_are_validated() {
+    let service = service();
+
+    for descricao in ["", "descricao longa demais"] {
+        let refused = service
+            .transact(AccountId(1), request(1, "C", descricao), Options::default())
+            .await;
+        assert!(
+            matches!(&refused, Err(AppError::Fields(errors)) if errors[0].field == "descricao"),
+            "{descricao:?} was accepted"
+        );
+    }
+}
+
+#[tokio::test]
+async fn unknown_account_is_not_tried() {
+    let refused = service()
+        .transact(
+            AccountId(99),
+            request(1, "C", "deposito"),
+            Options::default(),
+        )
+        .await;
+    assert!(matches!(refused, Err(AppError::UnknownAccount)));
+}
+
+#[tokio::test]
+async fn statement_lists_accepted_transactions_newest_first() {
+    let service = service();
+    let account = AccountId(3);
+    for (valor, descricao) in [(10, "primeira"), (20, "segunda")] {
+        service
+            .transact(account, request(valor, "C", descricao), Options::default())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let statement = service.statement(account).await.unwrap();
+    assert_eq!(statement["saldo"]["total"], 30);
+    assert_eq!(statement["saldo"]["limite"], 1_000_000);
+    let descriptions: Vec<_> = statement["ultimas_transacoes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction["descricao"].as_str().unwrap())
+        .collect();
+    assert_eq!(descriptions, ["segunda", "primeira"]);
+}
+
+fn service_of_type(account_type: &str) -> BankService {
+    BankService::in_memory(Config {
+        default_account_type: account_type.to_string(),
+        ..Config::default()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn soft_policy_charges_a_fee_past_the_limit() {
+    // corporate: 500.00 past the limit, 10.00 for each debit that goes there.
+    let service = service_of_type("corporate");
+    let account = AccountId(2);
+
+    let within = service
+        .transact(account, request(80_000, "D", "saque"), Options::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(within.fee.is_none());
+
+    let past = service
+        .transact(account, request(30_000, "D", "saque"), Options::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(past.fee.is_some());
+    assert_eq!(past.balance, -111_000);
+
+    let statement = service.statement(account).await.unwrap();
+    assert_eq!(statement["saldo"]["tipo"], "corporate");
+    assert_eq!(statement["ultimas_transacoes"][0]["descricao"], "tarifa");
+
+    let outcome = service
+        .transact(account, request(20_000, "D", "saque"), Options::default())
+        .await
+        .unwrap();
+    assert!(matches!(
+        outcome,
+        Err(TransactError::InsufficientLimit { .. })
+    ));
+}
+
+#[tokio::test]
+async fn no_overdraft_policy_ignores_the_limit() {
+    let service = service_of_type("basic");
+    let account = AccountId(1);
+
+    let outcome = service
+        .transact(account, request(1, "D", "saque"), Options::default())
+        .await
+        .unwrap();
+    assert!(matches!(
+        outcome,
+        Err(TransactError::InsufficientBalance { .. })
+    ));
+}
+
+#[tokio::test]
+async fn every_refused_field_is_reported() {
+    let service = service();
+
+    let refused = service
+        .transact(AccountId(1), request(-5, "C", ""), Options::default())
+        .await;
+    let Err(AppError::Fields(errors)) = refused else {
+        panic!("a negative valor and an empty descricao were accepted");
+    };
+    let fields: Vec<_> = errors.iter().map(|error| error.field).collect();
+    assert_eq!(fields, ["valor", "descricao"]);
+}
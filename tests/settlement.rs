@@ -0,0 +1,108 @@
+//! `POST /settlement`: end-of-day adjustments, all applied or none.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, admin_config, app, post, send, statement};
+use serde_json::{json, Value};
+
+fn settle(adjustments: Value) -> axum::http::Request<axum::body::Body> {
+    admin(post("/settlement", adjustments))
+}
+
+async fn balance(app: &axum::Router, account: u64) -> Value {
+    let (_, body) = send(app, statement(account)).await;
+    body["saldo"]["total"].clone()
+}
+
+#[tokio::test]
+async fn a_valid_batch_is_applied_in_order() {
+    let app = app(admin_config());
+    let (status, body) = send(
+        &app,
+        settle(json!([
+            {"id": 1, "valor": 50_000},
+            {"id": 2, "valor": -30_000},
+            // Only fits because of the credit before it: 150_000 of room.
+            {"id": 1, "valor": -140_000},
+        ])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["aplicados"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        body["saldos"][0],
+        json!({"account": 1, "limite": 100_000, "saldo": -90_000})
+    );
+    assert_eq!(balance(&app, 1).await, -90_000);
+    assert_eq!(balance(&app, 2).await, -30_000);
+
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"][0]["descricao"], "liquidacao");
+}
+
+#[tokio::test]
+async fn one_failure_rolls_back_the_whole_batch() {
+    let app = app(admin_config());
+    let (status, body) = send(
+        &app,
+        settle(json!([
+            {"id": 2, "valor": 10_000},
+            {"id": 1, "valor": -100_001},
+            {"id": 3, "valor": 0},
+        ])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let failures = body["falhas"].as_array().unwrap();
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0]["indice"], 1);
+    assert_eq!(failures[0]["id"], 1);
+    assert_eq!(failures[1]["indice"], 2);
+    assert_eq!(failures[1]["motivo"], "valor invalido");
+
+    for account in [1, 2, 3] {
+        assert_eq!(balance(&app, account).await, 0, "account {account}");
+        let (_, body) = send(&app, statement(account)).await;
+        assert_eq!(body["ultimas_transacoes"], json!([]));
+    }
+}
+
+#[tokio::test]
+async fn unknown_accounts_fail_before_anything_is_locked() {
+    let app = app(admin_config());
+    let (status, body) = send(
+        &app,
+        settle(json!([{"id": 1, "valor": 10}, {"id": 99, "valor": 10}])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["falhas"],
+        json!([{"indice": 1, "id": 99, "valor": 10, "motivo": "conta inexistente"}])
+    );
+    assert_eq!(balance(&app, 1).await, 0);
+}
+
+#[tokio::test]
+async fn adjustments_are_held_to_the_transaction_maximum() {
+    let app = app(admin_config());
+    let (status, body) = send(
+        &app,
+        settle(json!([
+            {"id": 1, "valor": 1_000_000_000_001_i64},
+            // No positive i64 is its absolute value.
+            {"id": 2, "valor": i64::MIN},
+            {"id": 3, "valor": 10},
+        ])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    let failures = body["falhas"].as_array().unwrap();
+    assert_eq!(failures.len(), 2);
+    for (failure, index) in failures.iter().zip([0, 1]) {
+        assert_eq!(failure["indice"], index);
+        assert_eq!(failure["motivo"], "valor acima do maximo por transacao");
+    }
+    assert_eq!(balance(&app, 3).await, 0);
+}
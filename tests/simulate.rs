@@ -0,0 +1,58 @@
+//! `POST /clientes/:id/simular`: a sequence of transactions tried against a
+//! copy of the account.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, get, post, send, statement, transaction};
+use rinha2024::config::Config;
+use serde_json::json;
+
+#[tokio::test]
+async fn each_step_sees_the_previous_ones_and_nothing_is_kept() {
+    let app = app(Config::default());
+    send(&app, transaction(1, 1_000, "C", "antes")).await;
+
+    let (status, body) = send(
+        &app,
+        post(
+            "/clientes/1/simular",
+            json!([
+                {"valor": 100_000, "tipo": "D", "descricao": "grande"},
+                // Past the limit only because of the debit before it.
+                {"valor": 1_001, "tipo": "D", "descricao": "estoura"},
+                {"valor": 1, "tipo": "C", "descricao": ""},
+                {"valor": 500, "tipo": "C", "descricao": "volta"},
+            ]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let results = body["resultados"].as_array().unwrap();
+    assert_eq!(
+        results[0],
+        json!({"indice": 0, "aceita": true, "saldo": -99_000})
+    );
+    assert_eq!(results[1]["aceita"], false);
+    assert!(results[1]["motivo"].is_string());
+    assert_eq!(results[2]["aceita"], false);
+    assert_eq!(
+        results[3],
+        json!({"indice": 3, "aceita": true, "saldo": -98_500})
+    );
+    assert_eq!(body["saldo"], -98_500);
+    assert_eq!(body["limite"], 100_000);
+
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], 1_000);
+    assert_eq!(body["ultimas_transacoes"].as_array().unwrap().len(), 1);
+    let (_, rejections) = send(&app, get("/clientes/1/rejeicoes")).await;
+    assert_eq!(rejections["rejeicoes"], json!([]));
+}
+
+#[tokio::test]
+async fn unknown_accounts_are_not_found() {
+    let app = app(Config::default());
+    let (status, _) = send(&app, post("/clientes/99/simular", json!([]))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,142 @@
+//! What `GET /clientes/:id/extrato` and its neighbours answer.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{admin, admin_config, app, app_at, delete, post, send, statement, transaction};
+use rinha2024::{config::Config, AccountId};
+use serde_json::json;
+use time::Duration;
+
+#[tokio::test]
+async fn bulk_statements_answer_per_id() {
+    let app = app(Config::default());
+    send(&app, transaction(2, 300, "C", "deposito")).await;
+
+    let (status, body) = send(&app, post("/extratos", json!({"ids": [2, 99, 1]}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let results = body.as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["account"], 2);
+    assert_eq!(results[0]["saldo"]["total"], 300);
+    assert_eq!(results[1]["account"], 99);
+    assert_eq!(results[1]["status"], 404);
+    assert!(results[1].get("saldo").is_none());
+    assert_eq!(results[2]["account"], 1);
+    assert_eq!(results[2]["saldo"]["total"], 0);
+
+    let (_, single) = send(&app, statement(2)).await;
+    assert_eq!(results[0]["saldo"]["total"], single["saldo"]["total"]);
+}
+
+#[tokio::test]
+async fn utilization_of_unused_half_used_and_zero_limits() {
+    let mut config = Config::default();
+    config.accounts.push((AccountId(6), 0));
+    let app = app(config);
+
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["utilizacao"], "0.00");
+
+    send(&app, transaction(1, 50_000, "D", "metade")).await;
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["utilizacao"], "50.00");
+
+    // Nothing to use: no percentage rather than a division by zero.
+    let (status, body) = send(&app, statement(6)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["saldo"]["utilizacao"], json!(null));
+}
+
+#[tokio::test]
+async fn purge_empties_the_history_but_keeps_the_balance() {
+    let app = app(admin_config());
+    send(&app, transaction(1, 1_000, "C", "deposito")).await;
+    send(&app, transaction(1, 400, "D", "saque")).await;
+
+    let (status, _) = send(&app, admin(delete("/clientes/1/transacoes"))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["ultimas_transacoes"], json!([]));
+    assert_eq!(body["saldo"]["total"], 600);
+}
+
+#[tokio::test]
+async fn credits_within_the_window_share_one_entry() {
+    let (app, clock) = app_at(Config {
+        coalesce_credits: Some(std::time::Duration::from_secs(1)),
+        ..Config::default()
+    });
+    for (value, description) in [(100, "pix 1"), (200, "pix 2"), (300, "pix 3")] {
+        let (status, _) = send(&app, transaction(1, value, "C", description)).await;
+        assert_eq!(status, StatusCode::OK);
+        clock.advance(Duration::milliseconds(400));
+    }
+
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], 600);
+    let history = body["ultimas_transacoes"].as_array().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["valor"], 600);
+    assert_eq!(history[0]["descricao"], "pix 1");
+
+    // A debit is never folded in, and a credit past it starts a new entry.
+    send(&app, transaction(1, 50, "D", "tarifa")).await;
+    send(&app, transaction(1, 10, "C", "pix 4")).await;
+    // Past the window of that one.
+    clock.advance(Duration::seconds(2));
+    send(&app, transaction(1, 20, "C", "pix 5")).await;
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], 580);
+    let values: Vec<_> = body["ultimas_transacoes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction["valor"].as_i64().unwrap())
+        .collect();
+    assert_eq!(values, [20, 10, 50, 600]);
+}
+
+#[tokio::test]
+async fn brand_new_account_lists_an_empty_array() {
+    let app = app(admin_config());
+    send(
+        &app,
+        admin(post("/clientes", json!({"id": 7, "limite": 500}))),
+    )
+    .await;
+
+    for account in [1, 7] {
+        let (status, headers, body) = common::send_raw(&app, statement(account)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-type"], "application/json");
+        let text = String::from_utf8(body).unwrap();
+        assert!(text.contains("\"ultimas_transacoes\":[]"), "{text}");
+    }
+}
+
+#[tokio::test]
+async fn available_matches_what_a_debit_may_take() {
+    let app = app(Config::default());
+    let available = |app: axum::Router| async move {
+        let (_, body) = send(&app, statement(1)).await;
+        body["saldo"]["disponivel"].as_i64().unwrap()
+    };
+
+    send(&app, transaction(1, 500, "C", "deposito")).await;
+    assert_eq!(available(app.clone()).await, 100_500);
+    send(&app, transaction(1, 40_500, "D", "saque")).await;
+    assert_eq!(available(app.clone()).await, 60_000);
+
+    // One centavo past it is refused, all of it is not.
+    let (status, _) = send(&app, transaction(1, 60_001, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = send(&app, transaction(1, 60_000, "D", "saque")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(available(app.clone()).await, 0);
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], -100_000);
+    let (status, _) = send(&app, transaction(1, 1, "D", "saque")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,49 @@
+//! `BANK_STRICT_JSON`: top-level fields a body may not carry, like a
+//! typo'd `valorr`.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{app, post, send, statement};
+use rinha2024::config::Config;
+use serde_json::json;
+
+fn typo() -> axum::http::Request<axum::body::Body> {
+    post(
+        "/clientes/1/transacoes",
+        json!({"valor": 100, "valorr": 1_000, "tipo": "C", "descricao": "erro"}),
+    )
+}
+
+#[tokio::test]
+async fn lenient_by_default_the_extra_field_is_ignored() {
+    let app = app(Config::default());
+    let (status, body) = send(&app, typo()).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, body) = send(&app, statement(1)).await;
+    assert_eq!(body["saldo"]["total"], 100);
+}
+
+#[tokio::test]
+async fn strict_mode_names_the_unknown_field() {
+    let app = app(Config {
+        strict_json: true,
+        ..Config::default()
+    });
+    let (status, body) = send(&app, typo()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["campo"], "valorr");
+    let (_, statement_body) = send(&app, statement(1)).await;
+    assert_eq!(statement_body["saldo"]["total"], 0);
+
+    // Known fields alone still go through.
+    let (status, _) = send(
+        &app,
+        post(
+            "/clientes/1/transacoes",
+            json!({"valor": 100, "tipo": "C", "descricao": "certo"}),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}